@@ -0,0 +1,22 @@
+-- Threads anchored to a parent message. Thread replies are regular messages
+-- in the parent's channel with thread_id set, so they are excluded from the
+-- main channel timeline but share reactions, previews and attachments.
+CREATE TABLE threads (
+    id UUID PRIMARY KEY,
+    channel_id UUID NOT NULL REFERENCES channels(id) ON DELETE CASCADE,
+    parent_message_id UUID NOT NULL UNIQUE REFERENCES messages(id) ON DELETE CASCADE,
+    name TEXT NOT NULL,
+    created_by UUID REFERENCES users(id),
+    reply_count INTEGER NOT NULL DEFAULT 0,
+    last_activity_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    archived BOOLEAN NOT NULL DEFAULT FALSE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_threads_channel_activity ON threads(channel_id, last_activity_at DESC);
+CREATE INDEX idx_threads_active_activity ON threads(last_activity_at) WHERE NOT archived;
+
+ALTER TABLE messages ADD COLUMN thread_id UUID REFERENCES threads(id) ON DELETE CASCADE;
+
+CREATE INDEX idx_messages_thread_created ON messages(thread_id, created_at DESC)
+    WHERE thread_id IS NOT NULL;
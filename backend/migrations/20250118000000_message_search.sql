@@ -0,0 +1,6 @@
+-- Full-text search over message content. The generated column keeps the
+-- tsvector in sync with edits without triggers.
+ALTER TABLE messages ADD COLUMN search_vector tsvector
+    GENERATED ALWAYS AS (to_tsvector('english', content)) STORED;
+
+CREATE INDEX idx_messages_search_vector ON messages USING GIN (search_vector);
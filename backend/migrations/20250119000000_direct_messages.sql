@@ -0,0 +1,17 @@
+-- Direct messages: 1:1 ('dm') and small-group ('group_dm') channels that are
+-- only visible to their members.
+ALTER TABLE channels DROP CONSTRAINT IF EXISTS channels_channel_type_check;
+ALTER TABLE channels ADD CONSTRAINT channels_channel_type_check
+    CHECK (channel_type IN ('text', 'voice', 'dm', 'group_dm'));
+
+-- Sorted "<user_a>:<user_b>" pair for 1:1 DMs so each pair has exactly one channel.
+ALTER TABLE channels ADD COLUMN dm_key TEXT UNIQUE;
+
+CREATE TABLE channel_members (
+    channel_id UUID NOT NULL REFERENCES channels(id) ON DELETE CASCADE,
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    joined_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (channel_id, user_id)
+);
+
+CREATE INDEX idx_channel_members_user_id ON channel_members(user_id);
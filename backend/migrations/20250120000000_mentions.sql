@@ -0,0 +1,14 @@
+-- Users addressed by a message, resolved from @username, @role and @here at
+-- send/edit time. @everyone is stored as a flag instead of one row per user.
+CREATE TABLE message_mentions (
+    message_id UUID NOT NULL REFERENCES messages(id) ON DELETE CASCADE,
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    PRIMARY KEY (message_id, user_id)
+);
+
+CREATE INDEX idx_message_mentions_user_id ON message_mentions(user_id);
+
+ALTER TABLE messages ADD COLUMN mention_everyone BOOLEAN NOT NULL DEFAULT FALSE;
+
+INSERT INTO server_settings (key, value) VALUES ('mention_everyone_min_role', 'moderator')
+    ON CONFLICT (key) DO NOTHING;
@@ -0,0 +1,9 @@
+-- Cross-node event payloads too large for a NOTIFY message (8000 bytes).
+-- The notification carries only the row id; rows are short-lived.
+CREATE TABLE event_bus_payloads (
+    id BIGSERIAL PRIMARY KEY,
+    payload TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_event_bus_payloads_created_at ON event_bus_payloads(created_at);
@@ -0,0 +1,11 @@
+-- User-selected presence status and custom status, restored on reconnect.
+-- Auto-idle is reported by the client and is not persisted.
+ALTER TABLE users
+    ADD COLUMN status TEXT NOT NULL DEFAULT 'online'
+        CHECK (status IN ('online', 'idle', 'dnd', 'invisible')),
+    ADD COLUMN custom_status_text TEXT,
+    ADD COLUMN custom_status_emoji TEXT,
+    ADD COLUMN custom_status_expires_at TIMESTAMPTZ;
+
+CREATE INDEX idx_users_custom_status_expires_at ON users (custom_status_expires_at)
+    WHERE custom_status_expires_at IS NOT NULL;
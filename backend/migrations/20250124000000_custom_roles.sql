@@ -0,0 +1,63 @@
+-- Admin-defined roles with a permission bitfield, replacing the fixed
+-- owner/admin/moderator/member ladder. Permission bits are defined by
+-- permissions::Permissions. Every user implicitly holds the default role;
+-- other roles are granted through user_roles. Higher positions outrank
+-- lower ones.
+CREATE TABLE roles (
+    id UUID PRIMARY KEY,
+    name TEXT NOT NULL,
+    color INTEGER CHECK (color BETWEEN 0 AND 16777215),
+    position INTEGER NOT NULL,
+    permissions BIGINT NOT NULL DEFAULT 0,
+    is_default BOOLEAN NOT NULL DEFAULT FALSE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE UNIQUE INDEX idx_roles_single_default ON roles (is_default) WHERE is_default;
+
+CREATE TABLE user_roles (
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    role_id UUID NOT NULL REFERENCES roles(id) ON DELETE CASCADE,
+    PRIMARY KEY (user_id, role_id)
+);
+
+CREATE INDEX idx_user_roles_role_id ON user_roles(role_id);
+
+-- Map the old ladder onto default roles. Members keep attachments and the
+-- soundboard; moderators get the moderation tools; admins additionally
+-- manage the server, roles and channels. The mention_everyone_min_role
+-- setting becomes the mention_everyone bit on the roles that met it.
+WITH setting AS (
+    SELECT COALESCE(
+        (SELECT value FROM server_settings WHERE key = 'mention_everyone_min_role'),
+        'moderator'
+    ) AS min_role
+)
+INSERT INTO roles (id, name, color, position, permissions, is_default)
+SELECT gen_random_uuid(), 'Member', NULL, 0,
+       12288 | CASE WHEN min_role = 'member' THEN 16384 ELSE 0 END, TRUE
+FROM setting
+UNION ALL
+SELECT gen_random_uuid(), 'Moderator', 3447003, 1,
+       4080 | CASE WHEN min_role IN ('member', 'moderator') THEN 16384 ELSE 0 END, FALSE
+FROM setting
+UNION ALL
+SELECT gen_random_uuid(), 'Admin', 15105570, 2,
+       4094 | CASE WHEN min_role <> 'owner' THEN 16384 ELSE 0 END, FALSE
+FROM setting;
+
+INSERT INTO user_roles (user_id, role_id)
+SELECT u.id, r.id FROM users u JOIN roles r ON r.name = 'Moderator' AND NOT r.is_default
+WHERE u.role = 'moderator';
+
+INSERT INTO user_roles (user_id, role_id)
+SELECT u.id, r.id FROM users u JOIN roles r ON r.name = 'Admin' AND NOT r.is_default
+WHERE u.role = 'admin';
+
+-- Ownership is no longer a role: the owner has every permission and cannot
+-- be moderated.
+ALTER TABLE users ADD COLUMN is_owner BOOLEAN NOT NULL DEFAULT FALSE;
+UPDATE users SET is_owner = TRUE WHERE role = 'owner';
+ALTER TABLE users DROP COLUMN role;
+
+DELETE FROM server_settings WHERE key = 'mention_everyone_min_role';
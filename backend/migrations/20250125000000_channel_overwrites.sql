@@ -0,0 +1,21 @@
+-- Per-channel allow/deny overwrites for a role or a single user. Only the
+-- channel permission bits (view, send, react, attach, connect, speak,
+-- stream) may be set. A channel that some members cannot view is private.
+CREATE TABLE channel_overwrites (
+    id UUID PRIMARY KEY,
+    channel_id UUID NOT NULL REFERENCES channels(id) ON DELETE CASCADE,
+    role_id UUID REFERENCES roles(id) ON DELETE CASCADE,
+    user_id UUID REFERENCES users(id) ON DELETE CASCADE,
+    allow BIGINT NOT NULL DEFAULT 0,
+    deny BIGINT NOT NULL DEFAULT 0,
+    CHECK ((role_id IS NULL) <> (user_id IS NULL))
+);
+
+CREATE UNIQUE INDEX idx_channel_overwrites_role
+    ON channel_overwrites (channel_id, role_id) WHERE role_id IS NOT NULL;
+CREATE UNIQUE INDEX idx_channel_overwrites_user
+    ON channel_overwrites (channel_id, user_id) WHERE user_id IS NOT NULL;
+
+-- New channel permission bits: view channel, send messages, add reactions,
+-- connect, speak and stream. Everyone keeps them through the default role.
+UPDATE roles SET permissions = permissions | 2064384 WHERE is_default;
@@ -0,0 +1,23 @@
+-- Collapsible channel categories and explicit ordering. Categories are
+-- ordered by position; channels by position within their category, with
+-- uncategorized channels listed first.
+CREATE TABLE channel_categories (
+    id UUID PRIMARY KEY,
+    name TEXT NOT NULL,
+    position INTEGER NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+ALTER TABLE channels
+    ADD COLUMN category_id UUID REFERENCES channel_categories(id) ON DELETE SET NULL,
+    ADD COLUMN position INTEGER NOT NULL DEFAULT 0;
+
+-- Keep the existing order: text channels before voice, then by creation
+WITH ordered AS (
+    SELECT id, ROW_NUMBER() OVER (ORDER BY channel_type, id) - 1 AS position
+    FROM channels
+    WHERE channel_type IN ('text', 'voice')
+)
+UPDATE channels c SET position = o.position FROM ordered o WHERE c.id = o.id;
+
+CREATE INDEX idx_channels_category ON channels (category_id, position);
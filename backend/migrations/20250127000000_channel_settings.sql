@@ -0,0 +1,13 @@
+-- Channel topic, per-user slowmode interval and announcement mode. In an
+-- announcement channel only members holding one of the listed roles may
+-- post; moderators are exempt from both restrictions.
+ALTER TABLE channels
+    ADD COLUMN topic TEXT,
+    ADD COLUMN slowmode_secs INTEGER NOT NULL DEFAULT 0,
+    ADD COLUMN announcement BOOLEAN NOT NULL DEFAULT FALSE;
+
+CREATE TABLE channel_announcement_roles (
+    channel_id UUID NOT NULL REFERENCES channels(id) ON DELETE CASCADE,
+    role_id UUID NOT NULL REFERENCES roles(id) ON DELETE CASCADE,
+    PRIMARY KEY (channel_id, role_id)
+);
@@ -0,0 +1,13 @@
+-- Pinned messages, and a message kind for system messages. A pin notice is
+-- a system message whose reply_to_id points at the pinned message.
+ALTER TABLE messages
+    ADD COLUMN kind TEXT NOT NULL DEFAULT 'default',
+    ADD COLUMN pinned_at TIMESTAMPTZ,
+    ADD COLUMN pinned_by UUID REFERENCES users(id) ON DELETE SET NULL;
+
+CREATE INDEX idx_messages_pinned
+    ON messages (channel_id, pinned_at DESC) WHERE pinned_at IS NOT NULL;
+
+-- New pin messages permission, granted to every role that can manage
+-- messages.
+UPDATE roles SET permissions = permissions | 2097152 WHERE permissions & 64 <> 0;
@@ -0,0 +1,17 @@
+-- Prior contents of edited messages. Each row is the content a message had
+-- from `created_at` (when it was posted or last edited) until `replaced_at`.
+CREATE TABLE message_revisions (
+    id UUID PRIMARY KEY,
+    message_id UUID NOT NULL REFERENCES messages(id) ON DELETE CASCADE,
+    content TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL,
+    replaced_at TIMESTAMPTZ NOT NULL
+);
+
+CREATE INDEX idx_message_revisions_message ON message_revisions (message_id, replaced_at);
+CREATE INDEX idx_message_revisions_replaced_at ON message_revisions (replaced_at);
+
+-- Days to keep revisions after they are replaced. 0 keeps them forever.
+INSERT INTO server_settings (key, value, updated_at)
+VALUES ('revision_retention_days', '0', NOW())
+ON CONFLICT (key) DO NOTHING;
@@ -0,0 +1,10 @@
+-- Deleted messages are kept as tombstones until the purge job removes them.
+ALTER TABLE messages ADD COLUMN deleted_at TIMESTAMPTZ;
+ALTER TABLE messages ADD COLUMN deleted_by UUID REFERENCES users(id) ON DELETE SET NULL;
+
+CREATE INDEX idx_messages_deleted_at ON messages (deleted_at) WHERE deleted_at IS NOT NULL;
+
+-- Days to keep deleted messages before they and their attachments are purged.
+INSERT INTO server_settings (key, value, updated_at)
+VALUES ('deleted_message_retention_days', '30', NOW())
+ON CONFLICT (key) DO NOTHING;
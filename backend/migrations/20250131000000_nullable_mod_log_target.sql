@@ -0,0 +1,2 @@
+-- Bulk actions such as purges may not target a single user.
+ALTER TABLE moderation_log ALTER COLUMN target_user_id DROP NOT NULL;
@@ -0,0 +1,34 @@
+-- Polls attached to messages. When a poll closes on schedule the vote
+-- counts are frozen into `final_votes`/`final_voters`.
+CREATE TABLE polls (
+    message_id UUID PRIMARY KEY REFERENCES messages(id) ON DELETE CASCADE,
+    question TEXT NOT NULL,
+    allow_multiple BOOLEAN NOT NULL DEFAULT FALSE,
+    anonymous BOOLEAN NOT NULL DEFAULT FALSE,
+    expires_at TIMESTAMPTZ,
+    closed_at TIMESTAMPTZ,
+    final_voters INTEGER
+);
+
+CREATE INDEX idx_polls_open_expiry ON polls (expires_at)
+    WHERE closed_at IS NULL AND expires_at IS NOT NULL;
+
+CREATE TABLE poll_options (
+    id UUID PRIMARY KEY,
+    message_id UUID NOT NULL REFERENCES polls(message_id) ON DELETE CASCADE,
+    position INTEGER NOT NULL,
+    text TEXT NOT NULL,
+    final_votes INTEGER
+);
+
+CREATE INDEX idx_poll_options_message ON poll_options (message_id, position);
+
+CREATE TABLE poll_votes (
+    option_id UUID NOT NULL REFERENCES poll_options(id) ON DELETE CASCADE,
+    message_id UUID NOT NULL REFERENCES polls(message_id) ON DELETE CASCADE,
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (option_id, user_id)
+);
+
+CREATE INDEX idx_poll_votes_message_user ON poll_votes (message_id, user_id);
@@ -0,0 +1,19 @@
+-- Messages queued to post at `send_at`. A node claims a due row by setting
+-- `claimed_at` and deletes it once delivered. Rows that cannot be delivered
+-- keep a `failure` reason so the author can see why and reschedule.
+CREATE TABLE scheduled_messages (
+    id UUID PRIMARY KEY,
+    author_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    channel_id UUID NOT NULL REFERENCES channels(id) ON DELETE CASCADE,
+    content TEXT,
+    reply_to_id UUID REFERENCES messages(id) ON DELETE SET NULL,
+    attachment_ids UUID[] NOT NULL DEFAULT '{}',
+    poll JSONB,
+    send_at TIMESTAMPTZ NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    claimed_at TIMESTAMPTZ,
+    failure TEXT
+);
+
+CREATE INDEX idx_scheduled_messages_due ON scheduled_messages (send_at) WHERE failure IS NULL;
+CREATE INDEX idx_scheduled_messages_author ON scheduled_messages (author_id, send_at);
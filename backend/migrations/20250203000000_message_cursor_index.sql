@@ -0,0 +1,8 @@
+-- History pages are ordered by (created_at, id) so messages sharing a
+-- timestamp keep a stable order across page boundaries.
+CREATE INDEX idx_messages_channel_created_id ON messages(channel_id, created_at DESC, id DESC);
+DROP INDEX idx_messages_channel_created;
+
+CREATE INDEX idx_messages_thread_created_id ON messages(thread_id, created_at DESC, id DESC)
+    WHERE thread_id IS NOT NULL;
+DROP INDEX idx_messages_thread_created;
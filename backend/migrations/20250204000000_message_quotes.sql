@@ -0,0 +1,7 @@
+-- Permalinks to messages on this server are previewed as quotes of the
+-- linked message. Deleting the message removes the quotes.
+ALTER TABLE link_previews
+    ADD COLUMN quoted_message_id UUID REFERENCES messages(id) ON DELETE CASCADE;
+
+CREATE INDEX idx_link_previews_quoted_message ON link_previews (quoted_message_id)
+    WHERE quoted_message_id IS NOT NULL;
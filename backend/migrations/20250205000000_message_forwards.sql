@@ -0,0 +1,10 @@
+-- Attribution for forwarded messages. The forward keeps its own copy of the
+-- content and attachments; these columns record where it was first posted
+-- and survive the original being deleted.
+CREATE TABLE message_forwards (
+    message_id UUID PRIMARY KEY REFERENCES messages(id) ON DELETE CASCADE,
+    source_message_id UUID REFERENCES messages(id) ON DELETE SET NULL,
+    source_channel_id UUID REFERENCES channels(id) ON DELETE SET NULL,
+    source_author_id UUID REFERENCES users(id) ON DELETE SET NULL,
+    source_created_at TIMESTAMPTZ NOT NULL
+);
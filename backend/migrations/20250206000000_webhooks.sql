@@ -0,0 +1,23 @@
+-- Incoming webhooks post into a channel as a named author without a user
+-- account. Only a hash of the secret token is kept.
+CREATE TABLE webhooks (
+    id UUID PRIMARY KEY,
+    channel_id UUID NOT NULL REFERENCES channels(id) ON DELETE CASCADE,
+    name TEXT NOT NULL,
+    avatar_url TEXT,
+    token_hash TEXT NOT NULL,
+    created_by UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_webhooks_channel ON webhooks (channel_id);
+
+-- Messages posted through a webhook. `author_id` on the message is the
+-- webhook's creator; this records the name and avatar it was posted as.
+CREATE TABLE webhook_messages (
+    message_id UUID PRIMARY KEY REFERENCES messages(id) ON DELETE CASCADE,
+    webhook_id UUID REFERENCES webhooks(id) ON DELETE SET NULL,
+    name TEXT NOT NULL,
+    avatar_url TEXT,
+    embeds JSONB NOT NULL DEFAULT '[]'
+);
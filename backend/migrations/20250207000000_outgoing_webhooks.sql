@@ -0,0 +1,34 @@
+-- Admin-configured endpoints that receive signed copies of gateway events.
+-- `secret` signs each delivery, so it is kept rather than hashed.
+CREATE TABLE outgoing_webhooks (
+    id UUID PRIMARY KEY,
+    name TEXT NOT NULL,
+    url TEXT NOT NULL,
+    secret TEXT NOT NULL,
+    event_types TEXT[] NOT NULL,
+    enabled BOOLEAN NOT NULL DEFAULT TRUE,
+    created_by UUID REFERENCES users(id) ON DELETE SET NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+-- One row per event sent to a webhook, kept as its delivery log. Pending rows
+-- are retried at `next_attempt_at`; a node claims a row by setting
+-- `claimed_at`. `payload` is the exact request body, so retries are
+-- byte-for-byte identical.
+CREATE TABLE outgoing_webhook_deliveries (
+    id UUID PRIMARY KEY,
+    webhook_id UUID NOT NULL REFERENCES outgoing_webhooks(id) ON DELETE CASCADE,
+    event_type TEXT NOT NULL,
+    payload TEXT NOT NULL,
+    status TEXT NOT NULL DEFAULT 'pending',
+    attempts INT NOT NULL DEFAULT 0,
+    status_code INT,
+    error TEXT,
+    next_attempt_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    claimed_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    completed_at TIMESTAMPTZ
+);
+
+CREATE INDEX idx_outgoing_webhook_deliveries_due ON outgoing_webhook_deliveries (next_attempt_at) WHERE status = 'pending';
+CREATE INDEX idx_outgoing_webhook_deliveries_webhook ON outgoing_webhook_deliveries (webhook_id, created_at DESC);
@@ -10,7 +10,7 @@ use uuid::Uuid;
 use crate::auth::AuthUser;
 use crate::database;
 use crate::models::{AppState, CreateInviteRequest, Invite};
-use crate::permissions::{self, Role};
+use crate::permissions::Permissions;
 use crate::shared::validation;
 use crate::shared::{AppError, AppResult};
 
@@ -28,8 +28,8 @@ pub async fn create_invite(
     Json(payload): Json<CreateInviteRequest>,
 ) -> AppResult<Json<Invite>> {
     let actor_id = auth_user.user_id();
-    let actor_role = database::get_user_role(&state.db, actor_id).await?;
-    permissions::require_role(actor_role, Role::Moderator)?;
+    let actor = database::get_member_permissions(&state.db, actor_id).await?;
+    actor.require(Permissions::MANAGE_INVITES)?;
 
     validation::validate_positive_duration(payload.expires_in_hours, "expires_in_hours")?;
     if let Some(max) = payload.max_uses
@@ -63,8 +63,8 @@ pub async fn list_invites(
     State(state): State<Arc<AppState>>,
     auth_user: AuthUser,
 ) -> AppResult<Json<Vec<Invite>>> {
-    let actor_role = database::get_user_role(&state.db, auth_user.user_id()).await?;
-    permissions::require_role(actor_role, Role::Moderator)?;
+    let actor = database::get_member_permissions(&state.db, auth_user.user_id()).await?;
+    actor.require(Permissions::MANAGE_INVITES)?;
 
     let invites = database::get_all_invites(&state.db).await?;
     Ok(Json(invites))
@@ -75,8 +75,8 @@ pub async fn revoke_invite(
     auth_user: AuthUser,
     Path(invite_id): Path<Uuid>,
 ) -> AppResult<()> {
-    let actor_role = database::get_user_role(&state.db, auth_user.user_id()).await?;
-    permissions::require_role(actor_role, Role::Moderator)?;
+    let actor = database::get_member_permissions(&state.db, auth_user.user_id()).await?;
+    actor.require(Permissions::MANAGE_INVITES)?;
 
     database::revoke_invite(&state.db, invite_id).await?;
 
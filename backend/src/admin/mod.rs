@@ -1,11 +1,15 @@
 mod invites;
 mod moderation;
 mod modlog;
+mod outgoing_webhooks;
+mod roles;
 mod settings;
 mod users;
 
 pub use invites::*;
 pub use moderation::*;
 pub use modlog::*;
+pub use outgoing_webhooks::*;
+pub use roles::*;
 pub use settings::*;
 pub use users::*;
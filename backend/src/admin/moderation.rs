@@ -8,10 +8,11 @@ use uuid::Uuid;
 
 use crate::auth::AuthUser;
 use crate::database;
+use crate::gateway::GatewayEvent;
 use crate::models::{
     AppState, Ban, BanRequest, KickRequest, ModAction, ModLogEntry, Mute, MuteRequest,
 };
-use crate::permissions::{self, Role};
+use crate::permissions::{self, Permissions};
 use crate::shared::AppResult;
 use crate::shared::validation;
 
@@ -21,33 +22,30 @@ pub async fn kick_user(
     Json(payload): Json<KickRequest>,
 ) -> AppResult<()> {
     let actor_id = auth_user.user_id();
-    let actor_role = database::get_user_role(&state.db, actor_id).await?;
-    permissions::require_role(actor_role, Role::Moderator)?;
+    let actor = database::get_member_permissions(&state.db, actor_id).await?;
+    actor.require(Permissions::KICK_MEMBERS)?;
 
     validation::validate_reason(&payload.reason)?;
 
-    let target_role = database::get_user_role(&state.db, payload.user_id).await?;
-    permissions::require_higher_role(actor_role, target_role)?;
+    let target = database::get_member_permissions(&state.db, payload.user_id).await?;
+    permissions::require_higher_rank(&actor, &target)?;
 
     database::create_mod_log_entry(
         &state.db,
         &ModLogEntry::new(
             ModAction::Kick,
             actor_id,
-            payload.user_id,
+            Some(payload.user_id),
             payload.reason.clone(),
             None,
         ),
     )
     .await?;
 
-    state.broadcast_global(
-        "user_kicked",
-        serde_json::json!({
-            "user_id": payload.user_id,
-            "reason": payload.reason,
-        }),
-    );
+    state.broadcast_global(GatewayEvent::UserKicked {
+        user_id: payload.user_id,
+        reason: payload.reason,
+    });
 
     Ok(())
 }
@@ -58,14 +56,14 @@ pub async fn ban_user(
     Json(payload): Json<BanRequest>,
 ) -> AppResult<()> {
     let actor_id = auth_user.user_id();
-    let actor_role = database::get_user_role(&state.db, actor_id).await?;
-    permissions::require_role(actor_role, Role::Moderator)?;
+    let actor = database::get_member_permissions(&state.db, actor_id).await?;
+    actor.require(Permissions::BAN_MEMBERS)?;
 
     validation::validate_reason(&payload.reason)?;
     validation::validate_positive_duration(payload.duration_hours, "duration_hours")?;
 
-    let target_role = database::get_user_role(&state.db, payload.user_id).await?;
-    permissions::require_higher_role(actor_role, target_role)?;
+    let target = database::get_member_permissions(&state.db, payload.user_id).await?;
+    permissions::require_higher_rank(&actor, &target)?;
 
     let expires_at = payload
         .duration_hours
@@ -91,7 +89,7 @@ pub async fn ban_user(
         &ModLogEntry::new(
             ModAction::Ban,
             actor_id,
-            payload.user_id,
+            Some(payload.user_id),
             payload.reason.clone(),
             payload
                 .duration_hours
@@ -100,13 +98,10 @@ pub async fn ban_user(
     )
     .await?;
 
-    state.broadcast_global(
-        "user_banned",
-        serde_json::json!({
-            "user_id": payload.user_id,
-            "reason": payload.reason,
-        }),
-    );
+    state.broadcast_global(GatewayEvent::UserBanned {
+        user_id: payload.user_id,
+        reason: payload.reason,
+    });
 
     Ok(())
 }
@@ -117,8 +112,8 @@ pub async fn unban_user(
     Path(target_user_id): Path<Uuid>,
 ) -> AppResult<()> {
     let actor_id = auth_user.user_id();
-    let actor_role = database::get_user_role(&state.db, actor_id).await?;
-    permissions::require_role(actor_role, Role::Moderator)?;
+    let actor = database::get_member_permissions(&state.db, actor_id).await?;
+    actor.require(Permissions::BAN_MEMBERS)?;
 
     database::remove_ban(&state.db, target_user_id).await?;
 
@@ -126,14 +121,13 @@ pub async fn unban_user(
 
     database::create_mod_log_entry(
         &state.db,
-        &ModLogEntry::new(ModAction::Unban, actor_id, target_user_id, None, None),
+        &ModLogEntry::new(ModAction::Unban, actor_id, Some(target_user_id), None, None),
     )
     .await?;
 
-    state.broadcast_global(
-        "user_unbanned",
-        serde_json::json!({ "user_id": target_user_id }),
-    );
+    state.broadcast_global(GatewayEvent::UserUnbanned {
+        user_id: target_user_id,
+    });
 
     Ok(())
 }
@@ -142,8 +136,8 @@ pub async fn list_bans(
     State(state): State<Arc<AppState>>,
     auth_user: AuthUser,
 ) -> AppResult<Json<Vec<Ban>>> {
-    let actor_role = database::get_user_role(&state.db, auth_user.user_id()).await?;
-    permissions::require_role(actor_role, Role::Moderator)?;
+    let actor = database::get_member_permissions(&state.db, auth_user.user_id()).await?;
+    actor.require(Permissions::BAN_MEMBERS)?;
 
     let bans = database::get_all_bans(&state.db).await?;
     Ok(Json(bans))
@@ -155,14 +149,14 @@ pub async fn mute_user(
     Json(payload): Json<MuteRequest>,
 ) -> AppResult<()> {
     let actor_id = auth_user.user_id();
-    let actor_role = database::get_user_role(&state.db, actor_id).await?;
-    permissions::require_role(actor_role, Role::Moderator)?;
+    let actor = database::get_member_permissions(&state.db, actor_id).await?;
+    actor.require(Permissions::MUTE_MEMBERS)?;
 
     validation::validate_reason(&payload.reason)?;
     validation::validate_positive_duration(payload.duration_hours, "duration_hours")?;
 
-    let target_role = database::get_user_role(&state.db, payload.user_id).await?;
-    permissions::require_higher_role(actor_role, target_role)?;
+    let target = database::get_member_permissions(&state.db, payload.user_id).await?;
+    permissions::require_higher_rank(&actor, &target)?;
 
     let expires_at = payload
         .duration_hours
@@ -187,7 +181,7 @@ pub async fn mute_user(
         &ModLogEntry::new(
             ModAction::Mute,
             actor_id,
-            payload.user_id,
+            Some(payload.user_id),
             payload.reason.clone(),
             payload
                 .duration_hours
@@ -196,14 +190,11 @@ pub async fn mute_user(
     )
     .await?;
 
-    state.broadcast_global(
-        "user_muted",
-        serde_json::json!({
-            "user_id": payload.user_id,
-            "reason": payload.reason,
-            "expires_at": expires_at,
-        }),
-    );
+    state.broadcast_global(GatewayEvent::UserMuted {
+        user_id: payload.user_id,
+        reason: payload.reason,
+        expires_at,
+    });
 
     Ok(())
 }
@@ -214,8 +205,8 @@ pub async fn unmute_user(
     Path(target_user_id): Path<Uuid>,
 ) -> AppResult<()> {
     let actor_id = auth_user.user_id();
-    let actor_role = database::get_user_role(&state.db, actor_id).await?;
-    permissions::require_role(actor_role, Role::Moderator)?;
+    let actor = database::get_member_permissions(&state.db, actor_id).await?;
+    actor.require(Permissions::MUTE_MEMBERS)?;
 
     database::remove_mute(&state.db, target_user_id).await?;
 
@@ -223,14 +214,19 @@ pub async fn unmute_user(
 
     database::create_mod_log_entry(
         &state.db,
-        &ModLogEntry::new(ModAction::Unmute, actor_id, target_user_id, None, None),
+        &ModLogEntry::new(
+            ModAction::Unmute,
+            actor_id,
+            Some(target_user_id),
+            None,
+            None,
+        ),
     )
     .await?;
 
-    state.broadcast_global(
-        "user_unmuted",
-        serde_json::json!({ "user_id": target_user_id }),
-    );
+    state.broadcast_global(GatewayEvent::UserUnmuted {
+        user_id: target_user_id,
+    });
 
     Ok(())
 }
@@ -239,8 +235,8 @@ pub async fn list_mutes(
     State(state): State<Arc<AppState>>,
     auth_user: AuthUser,
 ) -> AppResult<Json<Vec<Mute>>> {
-    let actor_role = database::get_user_role(&state.db, auth_user.user_id()).await?;
-    permissions::require_role(actor_role, Role::Moderator)?;
+    let actor = database::get_member_permissions(&state.db, auth_user.user_id()).await?;
+    actor.require(Permissions::MUTE_MEMBERS)?;
 
     let mutes = database::get_all_mutes(&state.db).await?;
     Ok(Json(mutes))
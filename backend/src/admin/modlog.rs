@@ -8,7 +8,7 @@ use std::sync::Arc;
 use crate::auth::AuthUser;
 use crate::database;
 use crate::models::{AppState, ModLogEntry};
-use crate::permissions::{self, Role};
+use crate::permissions::Permissions;
 use crate::shared::AppResult;
 
 #[derive(Debug, Deserialize)]
@@ -21,8 +21,8 @@ pub async fn get_moderation_log(
     auth_user: AuthUser,
     Query(query): Query<ModLogQuery>,
 ) -> AppResult<Json<Vec<ModLogEntry>>> {
-    let actor_role = database::get_user_role(&state.db, auth_user.user_id()).await?;
-    permissions::require_role(actor_role, Role::Moderator)?;
+    let actor = database::get_member_permissions(&state.db, auth_user.user_id()).await?;
+    actor.require(Permissions::VIEW_MOD_LOG)?;
 
     let limit = query.limit.unwrap_or(100).clamp(1, 500);
     let log = database::get_mod_log(&state.db, limit).await?;
@@ -0,0 +1,188 @@
+use axum::{
+    extract::{Path, Query, State},
+    response::Json,
+};
+use chrono::Utc;
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::auth::AuthUser;
+use crate::database;
+use crate::models::{
+    AppState, CreateOutgoingWebhookRequest, DeliveryLogQuery, OutgoingWebhook,
+    OutgoingWebhookDelivery, UpdateOutgoingWebhookRequest,
+};
+use crate::permissions::Permissions;
+use crate::services::outgoing_webhook::{generate_secret, is_allowed_url};
+use crate::shared::validation::{
+    MAX_DELIVERY_LOG_PAGE_SIZE, MAX_OUTGOING_WEBHOOKS, validate_event_types, validate_http_url,
+    validate_webhook_name,
+};
+use crate::shared::{AppError, AppResult};
+
+async fn require_manage_server(state: &AppState, user_id: Uuid) -> AppResult<()> {
+    database::get_member_permissions(&state.db, user_id)
+        .await?
+        .require(Permissions::MANAGE_SERVER)
+}
+
+/// Reject URLs deliveries could never be sent to, so a typo or an internal
+/// address is caught when the webhook is saved rather than on every event.
+async fn validate_delivery_url(url: &str) -> AppResult<String> {
+    let url = url.trim();
+    validate_http_url(url, "Webhook URL")?;
+    if !is_allowed_url(url).await {
+        return Err(AppError::bad_request(
+            "Webhook URL must resolve to a public address or an allowlisted host",
+        ));
+    }
+    Ok(url.to_string())
+}
+
+async fn get_webhook_or_404(state: &AppState, webhook_id: Uuid) -> AppResult<OutgoingWebhook> {
+    database::get_outgoing_webhook(&state.db, webhook_id)
+        .await?
+        .ok_or_else(|| AppError::not_found("Webhook not found"))
+}
+
+/// Refresh every node's cached subscriptions for the webhook.
+fn cache_subscriptions(state: &AppState, webhook: &OutgoingWebhook) {
+    state.cache_outgoing_webhook(
+        webhook.id,
+        webhook.enabled.then(|| webhook.event_types.clone()),
+    );
+}
+
+pub async fn list_outgoing_webhooks(
+    State(state): State<Arc<AppState>>,
+    auth_user: AuthUser,
+) -> AppResult<Json<Vec<OutgoingWebhook>>> {
+    require_manage_server(&state, auth_user.user_id()).await?;
+    let webhooks = database::get_outgoing_webhooks(&state.db).await?;
+    Ok(Json(webhooks))
+}
+
+/// Create a webhook. The response carries the signing secret, which is not
+/// shown again.
+pub async fn create_outgoing_webhook(
+    State(state): State<Arc<AppState>>,
+    auth_user: AuthUser,
+    Json(payload): Json<CreateOutgoingWebhookRequest>,
+) -> AppResult<Json<OutgoingWebhook>> {
+    let actor_id = auth_user.user_id();
+    require_manage_server(&state, actor_id).await?;
+
+    let name = validate_webhook_name(&payload.name)?;
+    let url = validate_delivery_url(&payload.url).await?;
+    let event_types = validate_event_types(&payload.event_types)?;
+
+    if database::count_outgoing_webhooks(&state.db).await? >= MAX_OUTGOING_WEBHOOKS {
+        return Err(AppError::bad_request(format!(
+            "Maximum of {MAX_OUTGOING_WEBHOOKS} outgoing webhooks"
+        )));
+    }
+
+    let secret = generate_secret();
+    let mut webhook = OutgoingWebhook {
+        id: Uuid::now_v7(),
+        name,
+        url,
+        event_types,
+        enabled: true,
+        created_by: Some(actor_id),
+        created_at: Utc::now(),
+        secret: None,
+    };
+    database::create_outgoing_webhook(&state.db, &webhook, &secret).await?;
+    cache_subscriptions(&state, &webhook);
+
+    webhook.secret = Some(secret);
+    Ok(Json(webhook))
+}
+
+pub async fn update_outgoing_webhook(
+    State(state): State<Arc<AppState>>,
+    auth_user: AuthUser,
+    Path(webhook_id): Path<Uuid>,
+    Json(payload): Json<UpdateOutgoingWebhookRequest>,
+) -> AppResult<Json<OutgoingWebhook>> {
+    require_manage_server(&state, auth_user.user_id()).await?;
+    let mut webhook = get_webhook_or_404(&state, webhook_id).await?;
+
+    if let Some(ref name) = payload.name {
+        webhook.name = validate_webhook_name(name)?;
+    }
+    if let Some(ref url) = payload.url {
+        webhook.url = validate_delivery_url(url).await?;
+    }
+    if let Some(ref event_types) = payload.event_types {
+        webhook.event_types = validate_event_types(event_types)?;
+    }
+    if let Some(enabled) = payload.enabled {
+        webhook.enabled = enabled;
+    }
+
+    database::update_outgoing_webhook(&state.db, &webhook).await?;
+    cache_subscriptions(&state, &webhook);
+
+    Ok(Json(webhook))
+}
+
+/// Replace a webhook's signing secret. Deliveries still queued are signed
+/// with the new one.
+pub async fn rotate_outgoing_webhook_secret(
+    State(state): State<Arc<AppState>>,
+    auth_user: AuthUser,
+    Path(webhook_id): Path<Uuid>,
+) -> AppResult<Json<OutgoingWebhook>> {
+    require_manage_server(&state, auth_user.user_id()).await?;
+    let mut webhook = get_webhook_or_404(&state, webhook_id).await?;
+
+    let secret = generate_secret();
+    database::update_outgoing_webhook_secret(&state.db, webhook_id, &secret).await?;
+
+    webhook.secret = Some(secret);
+    Ok(Json(webhook))
+}
+
+pub async fn delete_outgoing_webhook(
+    State(state): State<Arc<AppState>>,
+    auth_user: AuthUser,
+    Path(webhook_id): Path<Uuid>,
+) -> AppResult<()> {
+    require_manage_server(&state, auth_user.user_id()).await?;
+    database::delete_outgoing_webhook(&state.db, webhook_id).await?;
+    state.cache_outgoing_webhook(webhook_id, None);
+    Ok(())
+}
+
+pub async fn list_outgoing_webhook_deliveries(
+    State(state): State<Arc<AppState>>,
+    auth_user: AuthUser,
+    Path(webhook_id): Path<Uuid>,
+    Query(query): Query<DeliveryLogQuery>,
+) -> AppResult<Json<Vec<OutgoingWebhookDelivery>>> {
+    require_manage_server(&state, auth_user.user_id()).await?;
+    get_webhook_or_404(&state, webhook_id).await?;
+
+    let limit = query
+        .limit
+        .unwrap_or(MAX_DELIVERY_LOG_PAGE_SIZE)
+        .clamp(1, MAX_DELIVERY_LOG_PAGE_SIZE);
+    let deliveries =
+        database::get_outgoing_webhook_deliveries(&state.db, webhook_id, query.before, limit)
+            .await?;
+    Ok(Json(deliveries))
+}
+
+/// Queue a finished delivery to be sent again with a fresh set of retries.
+pub async fn retry_outgoing_webhook_delivery(
+    State(state): State<Arc<AppState>>,
+    auth_user: AuthUser,
+    Path((webhook_id, delivery_id)): Path<(Uuid, Uuid)>,
+) -> AppResult<Json<OutgoingWebhookDelivery>> {
+    require_manage_server(&state, auth_user.user_id()).await?;
+    let delivery =
+        database::retry_outgoing_webhook_delivery(&state.db, webhook_id, delivery_id).await?;
+    Ok(Json(delivery))
+}
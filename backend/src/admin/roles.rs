@@ -0,0 +1,173 @@
+use axum::{
+    extract::{Path, State},
+    response::Json,
+};
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::auth::AuthUser;
+use crate::database;
+use crate::gateway::GatewayEvent;
+use crate::models::{AppState, ReorderRolesRequest, Role, RoleRequest};
+use crate::permissions::{self, Permissions};
+use crate::services::channel_access;
+use crate::shared::validation::{self, MAX_ROLES};
+use crate::shared::{AppError, AppResult};
+
+pub async fn list_roles(
+    State(state): State<Arc<AppState>>,
+    _auth_user: AuthUser,
+) -> AppResult<Json<Vec<Role>>> {
+    let roles = database::get_roles(&state.db).await?;
+    Ok(Json(roles))
+}
+
+pub async fn create_role(
+    State(state): State<Arc<AppState>>,
+    auth_user: AuthUser,
+    Json(payload): Json<RoleRequest>,
+) -> AppResult<Json<Role>> {
+    let actor = database::get_member_permissions(&state.db, auth_user.user_id()).await?;
+    actor.require(Permissions::MANAGE_ROLES)?;
+
+    let name = validation::validate_role_name(&payload.name)?;
+    validation::validate_role_color(payload.color)?;
+    // New roles are inserted just above the default role
+    permissions::require_role_manageable(&actor, 1, payload.permissions)?;
+
+    if database::get_roles(&state.db).await?.len() >= MAX_ROLES {
+        return Err(AppError::bad_request(format!(
+            "Maximum of {MAX_ROLES} roles reached"
+        )));
+    }
+
+    let role = database::create_role(&state.db, &name, payload.color, payload.permissions).await?;
+
+    // Existing roles shifted up by one
+    let roles = database::get_roles(&state.db).await?;
+    state.broadcast_global(GatewayEvent::RoleCreated(role.clone()));
+    state.broadcast_global(GatewayEvent::RolesReordered { roles });
+
+    Ok(Json(role))
+}
+
+pub async fn update_role(
+    State(state): State<Arc<AppState>>,
+    auth_user: AuthUser,
+    Path(role_id): Path<Uuid>,
+    Json(payload): Json<RoleRequest>,
+) -> AppResult<Json<Role>> {
+    let actor = database::get_member_permissions(&state.db, auth_user.user_id()).await?;
+    actor.require(Permissions::MANAGE_ROLES)?;
+
+    let existing = database::get_role_by_id(&state.db, role_id)
+        .await?
+        .ok_or_else(|| AppError::not_found("Role not found"))?;
+
+    let name = validation::validate_role_name(&payload.name)?;
+    validation::validate_role_color(payload.color)?;
+    if existing.is_default {
+        // Anyone who can manage roles may edit the default role's
+        // permissions, but not beyond their own.
+        if !actor.permissions.contains(payload.permissions) {
+            return Err(AppError::forbidden(
+                "Cannot grant permissions you do not have",
+            ));
+        }
+    } else {
+        permissions::require_role_manageable(&actor, existing.position, payload.permissions)?;
+    }
+
+    let role = database::update_role(
+        &state.db,
+        role_id,
+        &name,
+        payload.color,
+        payload.permissions,
+    )
+    .await?;
+
+    state.broadcast_global(GatewayEvent::RoleUpdated(role.clone()));
+    channel_access::refresh_channel_access(&state).await?;
+
+    Ok(Json(role))
+}
+
+pub async fn delete_role(
+    State(state): State<Arc<AppState>>,
+    auth_user: AuthUser,
+    Path(role_id): Path<Uuid>,
+) -> AppResult<()> {
+    let actor = database::get_member_permissions(&state.db, auth_user.user_id()).await?;
+    actor.require(Permissions::MANAGE_ROLES)?;
+
+    let role = database::get_role_by_id(&state.db, role_id)
+        .await?
+        .ok_or_else(|| AppError::not_found("Role not found"))?;
+    if role.is_default {
+        return Err(AppError::bad_request("The default role cannot be deleted"));
+    }
+    if !actor.can_manage_role(role.position) {
+        return Err(AppError::forbidden(
+            "Cannot manage a role equal to or above your own",
+        ));
+    }
+
+    database::delete_role(&state.db, role_id).await?;
+
+    state.broadcast_global(GatewayEvent::RoleDeleted { id: role_id });
+    channel_access::refresh_channel_access(&state).await?;
+    let roles = database::get_roles(&state.db).await?;
+    state.broadcast_global(GatewayEvent::RolesReordered { roles });
+
+    Ok(())
+}
+
+/// Reorder all non-default roles. `role_ids` lists them from lowest to
+/// highest. Roles that change position must stay below the actor's highest
+/// role.
+pub async fn reorder_roles(
+    State(state): State<Arc<AppState>>,
+    auth_user: AuthUser,
+    Json(payload): Json<ReorderRolesRequest>,
+) -> AppResult<Json<Vec<Role>>> {
+    let actor = database::get_member_permissions(&state.db, auth_user.user_id()).await?;
+    actor.require(Permissions::MANAGE_ROLES)?;
+
+    let current = database::get_roles(&state.db).await?;
+    let custom: Vec<&Role> = current.iter().filter(|r| !r.is_default).collect();
+
+    let mut requested = payload.role_ids.clone();
+    requested.sort();
+    requested.dedup();
+    if requested.len() != payload.role_ids.len()
+        || requested.len() != custom.len()
+        || custom.iter().any(|r| !requested.contains(&r.id))
+    {
+        return Err(AppError::bad_request(
+            "role_ids must list every non-default role exactly once",
+        ));
+    }
+
+    for (index, role_id) in payload.role_ids.iter().enumerate() {
+        let new_position = index as i32 + 1;
+        let Some(role) = custom.iter().find(|r| r.id == *role_id) else {
+            continue;
+        };
+        if role.position != new_position
+            && !(actor.can_manage_role(role.position) && actor.can_manage_role(new_position))
+        {
+            return Err(AppError::forbidden(
+                "Cannot move roles equal to or above your own",
+            ));
+        }
+    }
+
+    let roles = database::reorder_roles(&state.db, &payload.role_ids).await?;
+
+    state.broadcast_global(GatewayEvent::RolesReordered {
+        roles: roles.clone(),
+    });
+
+    Ok(Json(roles))
+}
@@ -3,16 +3,17 @@ use std::sync::Arc;
 
 use crate::auth::AuthUser;
 use crate::database;
+use crate::gateway::GatewayEvent;
 use crate::models::{AppState, ServerSettingUpdate};
-use crate::permissions::{self, Role};
+use crate::permissions::Permissions;
 use crate::shared::{AppError, AppResult};
 
 pub async fn get_settings(
     State(state): State<Arc<AppState>>,
     auth_user: AuthUser,
 ) -> AppResult<Json<std::collections::HashMap<String, String>>> {
-    let actor_role = database::get_user_role(&state.db, auth_user.user_id()).await?;
-    permissions::require_role(actor_role, Role::Admin)?;
+    let actor = database::get_member_permissions(&state.db, auth_user.user_id()).await?;
+    actor.require(Permissions::MANAGE_SERVER)?;
 
     let settings = database::get_all_server_settings(&state.db).await?;
     Ok(Json(settings))
@@ -23,8 +24,8 @@ pub async fn update_setting(
     auth_user: AuthUser,
     Json(payload): Json<ServerSettingUpdate>,
 ) -> AppResult<()> {
-    let actor_role = database::get_user_role(&state.db, auth_user.user_id()).await?;
-    permissions::require_role(actor_role, Role::Admin)?;
+    let actor = database::get_member_permissions(&state.db, auth_user.user_id()).await?;
+    actor.require(Permissions::MANAGE_SERVER)?;
 
     // Validate known settings
     match payload.key.as_str() {
@@ -47,6 +48,22 @@ pub async fn update_setting(
                 )));
             }
         }
+        "revision_retention_days" => {
+            let max = crate::shared::validation::MAX_REVISION_RETENTION_DAYS;
+            if !matches!(payload.value.parse::<i64>(), Ok(days) if (0..=max).contains(&days)) {
+                return Err(AppError::bad_request(format!(
+                    "revision_retention_days must be a number of days from 0 to {max}"
+                )));
+            }
+        }
+        "deleted_message_retention_days" => {
+            let max = crate::shared::validation::MAX_DELETED_MESSAGE_RETENTION_DAYS;
+            if !matches!(payload.value.parse::<i64>(), Ok(days) if (1..=max).contains(&days)) {
+                return Err(AppError::bad_request(format!(
+                    "deleted_message_retention_days must be a number of days from 1 to {max}"
+                )));
+            }
+        }
         _ => {
             return Err(AppError::bad_request(format!(
                 "Unknown setting: {}",
@@ -57,13 +74,10 @@ pub async fn update_setting(
 
     database::set_server_setting(&state.db, &payload.key, &payload.value).await?;
 
-    state.broadcast_global(
-        "settings_updated",
-        serde_json::json!({
-            "key": payload.key,
-            "value": payload.value,
-        }),
-    );
+    state.broadcast_global(GatewayEvent::SettingsUpdated {
+        key: payload.key,
+        value: payload.value,
+    });
 
     Ok(())
 }
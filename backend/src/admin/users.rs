@@ -7,53 +7,88 @@ use uuid::Uuid;
 
 use crate::auth::AuthUser;
 use crate::database;
-use crate::models::{AppState, ModAction, ModLogEntry, RoleChangeRequest, UserSummary};
-use crate::permissions::{self, Role};
+use crate::gateway::GatewayEvent;
+use crate::models::{AppState, ModAction, ModLogEntry, Role, SetUserRolesRequest, UserSummary};
+use crate::permissions::{self, Permissions};
+use crate::services::channel_access;
 use crate::shared::{AppError, AppResult};
 
 pub async fn get_all_users(
     State(state): State<Arc<AppState>>,
     auth_user: AuthUser,
 ) -> AppResult<Json<Vec<UserSummary>>> {
-    let actor_role = database::get_user_role(&state.db, auth_user.user_id()).await?;
-    permissions::require_role(actor_role, Role::Moderator)?;
+    let actor = database::get_member_permissions(&state.db, auth_user.user_id()).await?;
+    if !actor.permissions.intersects(Permissions::MODERATION) {
+        return Err(AppError::forbidden("Insufficient permissions"));
+    }
 
     let users = database::get_all_users(&state.db).await?;
     Ok(Json(users))
 }
 
-pub async fn change_user_role(
+pub async fn set_user_roles(
     State(state): State<Arc<AppState>>,
     auth_user: AuthUser,
     Path(target_user_id): Path<Uuid>,
-    Json(payload): Json<RoleChangeRequest>,
+    Json(payload): Json<SetUserRolesRequest>,
 ) -> AppResult<()> {
     let actor_id = auth_user.user_id();
-    let actor_role = database::get_user_role(&state.db, actor_id).await?;
-    permissions::require_role(actor_role, Role::Admin)?;
+    let actor = database::get_member_permissions(&state.db, actor_id).await?;
+    actor.require(Permissions::MANAGE_ROLES)?;
 
-    let target_role = database::get_user_role(&state.db, target_user_id).await?;
+    let target = database::get_member_permissions(&state.db, target_user_id).await?;
+    if target.is_owner {
+        return Err(AppError::forbidden("Cannot change the owner's roles"));
+    }
+    if target_user_id != actor_id {
+        permissions::require_higher_rank(&actor, &target)?;
+    }
 
-    if target_role == Role::Owner {
-        return Err(AppError::forbidden("Cannot change the owner's role"));
+    let roles = database::get_roles(&state.db).await?;
+    let mut role_ids = payload.role_ids;
+    role_ids.sort();
+    role_ids.dedup();
+    for role_id in &role_ids {
+        if !roles.iter().any(|r| r.id == *role_id && !r.is_default) {
+            return Err(AppError::bad_request(format!("Unknown role: {role_id}")));
+        }
     }
 
-    permissions::can_assign_role(actor_role, payload.role)?;
-    permissions::require_higher_role(actor_role, target_role)?;
+    let current = database::get_user_role_ids(&state.db, target_user_id).await?;
+    let added: Vec<&Role> = roles
+        .iter()
+        .filter(|r| role_ids.contains(&r.id) && !current.contains(&r.id))
+        .collect();
+    let removed: Vec<&Role> = roles
+        .iter()
+        .filter(|r| current.contains(&r.id) && !role_ids.contains(&r.id))
+        .collect();
+    if added.is_empty() && removed.is_empty() {
+        return Ok(());
+    }
+    if added
+        .iter()
+        .chain(removed.iter())
+        .any(|r| !actor.can_manage_role(r.position))
+    {
+        return Err(AppError::forbidden(
+            "Cannot assign or remove a role equal to or above your own",
+        ));
+    }
 
-    database::set_user_role(&state.db, target_user_id, payload.role).await?;
+    database::set_user_roles(&state.db, target_user_id, &role_ids).await?;
 
     database::create_mod_log_entry(
         &state.db,
         &ModLogEntry::new(
             ModAction::RoleChange,
             actor_id,
-            target_user_id,
+            Some(target_user_id),
             None,
             Some(
                 serde_json::json!({
-                    "from": target_role,
-                    "to": payload.role,
+                    "added": added.iter().map(|r| &r.name).collect::<Vec<_>>(),
+                    "removed": removed.iter().map(|r| &r.name).collect::<Vec<_>>(),
                 })
                 .to_string(),
             ),
@@ -61,13 +96,11 @@ pub async fn change_user_role(
     )
     .await?;
 
-    state.broadcast_global(
-        "user_role_changed",
-        serde_json::json!({
-            "user_id": target_user_id,
-            "new_role": payload.role,
-        }),
-    );
+    state.broadcast_global(GatewayEvent::UserRolesUpdated {
+        user_id: target_user_id,
+        role_ids,
+    });
+    channel_access::refresh_channel_access(&state).await?;
 
     Ok(())
 }
@@ -78,30 +111,37 @@ pub async fn delete_user(
     Path(target_user_id): Path<Uuid>,
 ) -> AppResult<()> {
     let actor_id = auth_user.user_id();
-    let actor_role = database::get_user_role(&state.db, actor_id).await?;
-    permissions::require_role(actor_role, Role::Owner)?;
+    let actor = database::get_member_permissions(&state.db, actor_id).await?;
+    if !actor.is_owner {
+        return Err(AppError::forbidden("Only the owner can delete users"));
+    }
 
     if target_user_id == actor_id {
         return Err(AppError::bad_request("Cannot delete your own account"));
     }
 
-    let target_role = database::get_user_role(&state.db, target_user_id).await?;
-    if target_role == Role::Owner {
+    let target = database::get_member_permissions(&state.db, target_user_id).await?;
+    if target.is_owner {
         return Err(AppError::forbidden("Cannot delete another owner"));
     }
 
     database::create_mod_log_entry(
         &state.db,
-        &ModLogEntry::new(ModAction::DeleteUser, actor_id, target_user_id, None, None),
+        &ModLogEntry::new(
+            ModAction::DeleteUser,
+            actor_id,
+            Some(target_user_id),
+            None,
+            None,
+        ),
     )
     .await?;
 
     database::delete_user(&state.db, target_user_id).await?;
 
-    state.broadcast_global(
-        "user_deleted",
-        serde_json::json!({ "user_id": target_user_id }),
-    );
+    state.broadcast_global(GatewayEvent::UserDeleted {
+        user_id: target_user_id,
+    });
 
     Ok(())
 }
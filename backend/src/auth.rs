@@ -4,7 +4,6 @@ use jsonwebtoken::{DecodingKey, EncodingKey, Header, Validation, decode, encode}
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
-use crate::permissions::Role;
 use crate::shared::AppError;
 
 use std::sync::OnceLock;
@@ -36,7 +35,6 @@ pub fn hmac_secret() -> &'static str {
 pub struct Claims {
     pub sub: Uuid,
     pub username: String,
-    pub role: Role,
     pub exp: i64,
 }
 
@@ -46,7 +44,7 @@ pub struct User {
     pub username: String,
     pub email: String,
     pub password_hash: String,
-    pub role: Role,
+    pub is_owner: bool,
     pub created_at: chrono::DateTime<Utc>,
     pub avatar_path: Option<String>,
     pub display_name: Option<String>,
@@ -88,7 +86,7 @@ pub struct UserInfo {
     pub id: Uuid,
     pub username: String,
     pub email: String,
-    pub role: Role,
+    pub is_owner: bool,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub avatar_url: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -103,7 +101,8 @@ pub struct PublicProfile {
     pub display_name: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub avatar_url: Option<String>,
-    pub role: Role,
+    pub is_owner: bool,
+    pub role_ids: Vec<Uuid>,
     pub created_at: chrono::DateTime<Utc>,
 }
 
@@ -143,7 +142,7 @@ where
     }
 }
 
-pub fn create_jwt(user_id: Uuid, username: &str, role: Role) -> Result<String, AppError> {
+pub fn create_jwt(user_id: Uuid, username: &str) -> Result<String, AppError> {
     let expiration = Utc::now()
         .checked_add_signed(TimeDelta::days(7))
         .ok_or_else(|| AppError::internal("Failed to compute token expiration"))?
@@ -152,7 +151,6 @@ pub fn create_jwt(user_id: Uuid, username: &str, role: Role) -> Result<String, A
     let claims = Claims {
         sub: user_id,
         username: username.to_string(),
-        role,
         exp: expiration,
     };
 
@@ -13,8 +13,10 @@ use crate::auth::{
     UpdateProfileRequest, UserInfo, create_jwt,
 };
 use crate::database;
-use crate::models::{AppState, avatar_url_from_path};
-use crate::permissions::{self, Role};
+use crate::gateway::GatewayEvent;
+use crate::models::{AppState, MemberInfo, UpdatePresenceRequest, avatar_url_from_path};
+use crate::permissions;
+use crate::services;
 use crate::shared::password;
 use crate::shared::validation;
 use crate::shared::{AppError, AppResult};
@@ -24,7 +26,7 @@ fn user_info_from_db(user: &crate::auth::User) -> UserInfo {
         id: user.id,
         username: user.username.clone(),
         email: user.email.clone(),
-        role: user.role,
+        is_owner: user.is_owner,
         avatar_url: avatar_url_from_path(user.id, &user.avatar_path),
         display_name: user.display_name.clone(),
     }
@@ -57,20 +59,15 @@ pub async fn register(
 
     let password_hash = password::hash_password(&payload.password)?;
 
-    // First user becomes owner, rest are members
+    // First user becomes owner, rest only hold the default role
     let user_count = database::get_user_count(&state.db).await?;
-    let role = if user_count == 0 {
-        Role::Owner
-    } else {
-        Role::Member
-    };
 
     let user = crate::auth::User {
         id: Uuid::now_v7(),
         username,
         email,
         password_hash,
-        role,
+        is_owner: user_count == 0,
         created_at: chrono::Utc::now(),
         avatar_path: None,
         display_name: None,
@@ -79,8 +76,19 @@ pub async fn register(
     // Relies on DB unique constraints -- create_user maps constraint violations
     // to specific conflict errors (username taken, email in use)
     database::create_user(&state.db, &user).await?;
+    // Restricted channels that allow the default role now include this user
+    services::channel_access::refresh_channel_access(&state).await?;
 
-    let token = create_jwt(user.id, &user.username, user.role)?;
+    state.broadcast_global(GatewayEvent::MemberJoined(MemberInfo {
+        id: user.id,
+        username: user.username.clone(),
+        display_name: None,
+        is_owner: user.is_owner,
+        role_ids: Vec::new(),
+        avatar_url: None,
+    }));
+
+    let token = create_jwt(user.id, &user.username)?;
 
     Ok(Json(AuthResponse {
         token,
@@ -107,7 +115,7 @@ pub async fn login(
 
     permissions::check_not_banned(&state.db, user.id).await?;
 
-    let token = create_jwt(user.id, &user.username, user.role)?;
+    let token = create_jwt(user.id, &user.username)?;
 
     Ok(Json(AuthResponse {
         token,
@@ -146,17 +154,8 @@ pub async fn update_profile(
         };
         database::update_user_display_name(&state.db, user.id, validated.as_deref()).await?;
 
-        // Update in-memory online presence
-        if let Some(mut presence) = state.online_users.get_mut(&user.id) {
-            presence.display_name = validated.clone();
-        }
-
-        // Update in-memory voice states
-        for channel_users in state.voice_states.iter() {
-            if let Some(mut vs) = channel_users.get_mut(&user.id) {
-                vs.display_name = validated.clone();
-            }
-        }
+        // Update in-memory online presence and voice states
+        state.set_cached_display_name(user.id, validated);
 
         changed = true;
     }
@@ -168,20 +167,17 @@ pub async fn update_profile(
             .ok_or_else(|| AppError::not_found("User not found"))?;
         let info = user_info_from_db(&updated_user);
 
-        state.broadcast_global(
-            "user_profile_updated",
-            serde_json::json!({
-                "user_id": user.id,
-                "username": info.username,
-                "display_name": info.display_name,
-                "avatar_url": info.avatar_url,
-            }),
-        );
-
-        let token = create_jwt(user.id, &current_username, user.role)?;
+        state.broadcast_global(GatewayEvent::UserProfileUpdated {
+            user_id: user.id,
+            username: info.username.clone(),
+            display_name: info.display_name.clone(),
+            avatar_url: info.avatar_url.clone(),
+        });
+
+        let token = create_jwt(user.id, &current_username)?;
         Ok(Json(AuthResponse { token, user: info }))
     } else {
-        let token = create_jwt(user.id, &current_username, user.role)?;
+        let token = create_jwt(user.id, &current_username)?;
         Ok(Json(AuthResponse {
             token,
             user: user_info_from_db(&user),
@@ -269,26 +265,14 @@ pub async fn upload_avatar(
 
     let avatar_url = avatar_url_from_path(user_id, &Some(storage_path));
 
-    // Update in-memory presence
-    if let Some(mut presence) = state.online_users.get_mut(&user_id) {
-        presence.avatar_url = avatar_url.clone();
-    }
-
-    // Update in-memory voice states
-    for channel_users in state.voice_states.iter() {
-        if let Some(mut vs) = channel_users.get_mut(&user_id) {
-            vs.avatar_url = avatar_url.clone();
-        }
-    }
+    // Update in-memory presence and voice states
+    state.set_cached_avatar_url(user_id, avatar_url.clone());
 
     // Broadcast avatar update
-    state.broadcast_global(
-        "user_avatar_updated",
-        serde_json::json!({
-            "user_id": user_id,
-            "avatar_url": avatar_url,
-        }),
-    );
+    state.broadcast_global(GatewayEvent::UserAvatarUpdated {
+        user_id,
+        avatar_url: avatar_url.clone(),
+    });
 
     let updated_user = database::get_user_by_id(&state.db, user_id)
         .await?
@@ -316,26 +300,14 @@ pub async fn delete_avatar(
 
     database::update_user_avatar(&state.db, user_id, None).await?;
 
-    // Update in-memory presence
-    if let Some(mut presence) = state.online_users.get_mut(&user_id) {
-        presence.avatar_url = None;
-    }
-
-    // Update in-memory voice states
-    for channel_users in state.voice_states.iter() {
-        if let Some(mut vs) = channel_users.get_mut(&user_id) {
-            vs.avatar_url = None;
-        }
-    }
+    // Update in-memory presence and voice states
+    state.set_cached_avatar_url(user_id, None);
 
     // Broadcast avatar removal
-    state.broadcast_global(
-        "user_avatar_updated",
-        serde_json::json!({
-            "user_id": user_id,
-            "avatar_url": null,
-        }),
-    );
+    state.broadcast_global(GatewayEvent::UserAvatarUpdated {
+        user_id,
+        avatar_url: None,
+    });
 
     let updated_user = database::get_user_by_id(&state.db, user_id)
         .await?
@@ -362,6 +334,15 @@ pub async fn change_password(
     Ok(StatusCode::NO_CONTENT)
 }
 
+pub async fn update_presence(
+    State(state): State<Arc<AppState>>,
+    auth_user: AuthUser,
+    Json(payload): Json<UpdatePresenceRequest>,
+) -> AppResult<StatusCode> {
+    crate::services::presence::update_presence(&state, auth_user.user_id(), payload).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
 pub async fn get_user_profile(
     State(state): State<Arc<AppState>>,
     _auth_user: AuthUser,
@@ -370,13 +351,15 @@ pub async fn get_user_profile(
     let user = database::get_user_by_id(&state.db, user_id)
         .await?
         .ok_or_else(|| AppError::not_found("User not found"))?;
+    let role_ids = database::get_user_role_ids(&state.db, user.id).await?;
 
     Ok(Json(PublicProfile {
         id: user.id,
         username: user.username,
         display_name: user.display_name,
         avatar_url: avatar_url_from_path(user.id, &user.avatar_path),
-        role: user.role,
+        is_owner: user.is_owner,
+        role_ids,
         created_at: user.created_at,
     }))
 }
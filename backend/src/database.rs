@@ -6,13 +6,18 @@ use uuid::Uuid;
 use crate::auth::User;
 use crate::link_preview::LinkPreviewData;
 use crate::models::{
-    Attachment, Ban, Channel, ChannelType, Invite, LinkPreview, MemberInfo, Message, ModLogEntry,
-    Mute, Reaction, ReplyPreview, UserSummary,
+    Attachment, Ban, CategoryPosition, Channel, ChannelCategory, ChannelPosition, ChannelReadState,
+    ChannelRules, ChannelType, CustomStatus, DmChannel, Embed, ForwardedFrom, Invite, LinkPreview,
+    MemberInfo, Message, MessageCursor, MessageKind, MessagePage, MessageRevision, ModLogEntry,
+    Mute, OutgoingWebhook, OutgoingWebhookDelivery, OverwriteTargetType, PendingDelivery,
+    PermissionOverwrite, Poll, PollOption, PresenceSettings, PresenceStatus, PurgeMessagesRequest,
+    Reaction, ReplyPreview, Role, ScheduledMessage, SearchQuery, SearchResult, Thread, UserSummary,
+    Webhook, WebhookAuthor,
 };
-use crate::permissions::Role;
+use crate::permissions::{MemberPermissions, Overwrite, Permissions};
 use crate::shared::AppError;
 use crate::shared::truncate_string;
-use crate::shared::validation::REPLY_PREVIEW_LENGTH;
+use crate::shared::validation::{MAX_UNREAD_COUNT, REPLY_PREVIEW_LENGTH};
 
 // --- Query row types (only for queries that don't map directly to model structs) ---
 
@@ -27,6 +32,89 @@ struct MessageRow {
     created_at: DateTime<Utc>,
     edited_at: Option<DateTime<Utc>>,
     reply_to_id: Option<Uuid>,
+    thread_id: Option<Uuid>,
+    mention_everyone: bool,
+    kind: MessageKind,
+    pinned_at: Option<DateTime<Utc>>,
+    pinned_by: Option<Uuid>,
+    deleted_at: Option<DateTime<Utc>>,
+    deleted_by: Option<Uuid>,
+}
+
+#[derive(FromRow)]
+struct ChannelMemberRow {
+    channel_id: Uuid,
+    id: Uuid,
+    username: String,
+    display_name: Option<String>,
+    is_owner: bool,
+    role_ids: Vec<Uuid>,
+    avatar_path: Option<String>,
+}
+
+impl From<ChannelMemberRow> for MemberInfo {
+    fn from(row: ChannelMemberRow) -> Self {
+        MemberInfo {
+            avatar_url: crate::models::avatar_url_from_path(row.id, &row.avatar_path),
+            id: row.id,
+            username: row.username,
+            display_name: row.display_name,
+            is_owner: row.is_owner,
+            role_ids: row.role_ids,
+        }
+    }
+}
+
+#[derive(FromRow)]
+struct MemberRow {
+    id: Uuid,
+    username: String,
+    email: String,
+    display_name: Option<String>,
+    is_owner: bool,
+    role_ids: Vec<Uuid>,
+    created_at: DateTime<Utc>,
+    avatar_path: Option<String>,
+}
+
+#[derive(FromRow)]
+struct MemberPermissionsRow {
+    is_owner: bool,
+    permissions: i64,
+    top_position: i32,
+}
+
+/// Roles held by `u.id` besides the default role.
+const USER_ROLE_IDS: &str =
+    "ARRAY(SELECT ur.role_id FROM user_roles ur WHERE ur.user_id = u.id) AS role_ids";
+
+/// Server channel columns selected from `channels c`.
+const CHANNEL_COLUMNS: &str = "c.id, c.name, c.channel_type, c.category_id, c.position, c.topic,
+     c.slowmode_secs, c.announcement,
+     ARRAY(SELECT ar.role_id FROM channel_announcement_roles ar WHERE ar.channel_id = c.id)
+         AS announcement_role_ids";
+
+const SCHEDULED_MESSAGE_COLUMNS: &str = "id, author_id, channel_id, content, reply_to_id,
+     attachment_ids, poll, send_at, created_at, failure";
+
+const WEBHOOK_COLUMNS: &str = "id, channel_id, name, avatar_url, created_by, created_at";
+const OUTGOING_WEBHOOK_COLUMNS: &str =
+    "id, name, url, event_types, enabled, created_by, created_at";
+const DELIVERY_COLUMNS: &str = "id, webhook_id, event_type, status, attempts, status_code, error, next_attempt_at, created_at, completed_at";
+
+#[derive(FromRow)]
+struct DmChannelRow {
+    id: Uuid,
+    name: String,
+    channel_type: ChannelType,
+    created_at: DateTime<Utc>,
+}
+
+#[derive(FromRow)]
+struct SearchRow {
+    #[sqlx(flatten)]
+    message: MessageRow,
+    highlight: Option<String>,
 }
 
 impl From<MessageRow> for Message {
@@ -45,10 +133,44 @@ impl From<MessageRow> for Message {
             reactions: None,
             link_previews: None,
             attachments: None,
+            thread_id: row.thread_id,
+            thread: None,
+            mentions: None,
+            mention_everyone: row.mention_everyone,
+            kind: row.kind,
+            pinned_at: row.pinned_at,
+            pinned_by: row.pinned_by,
+            deleted_at: row.deleted_at,
+            deleted_by: row.deleted_by,
+            poll: None,
+            forwarded_from: None,
+            webhook: None,
+            embeds: None,
         }
     }
 }
 
+#[derive(FromRow)]
+struct ForwardRow {
+    message_id: Uuid,
+    source_message_id: Option<Uuid>,
+    source_channel_id: Option<Uuid>,
+    source_author_id: Option<Uuid>,
+    username: Option<String>,
+    display_name: Option<String>,
+    source_created_at: DateTime<Utc>,
+}
+
+#[derive(FromRow)]
+struct WebhookMessageRow {
+    message_id: Uuid,
+    webhook_id: Option<Uuid>,
+    name: String,
+    avatar_url: Option<String>,
+    #[sqlx(json)]
+    embeds: Vec<Embed>,
+}
+
 #[derive(FromRow)]
 struct ReplyPreviewRow {
     id: Uuid,
@@ -66,6 +188,7 @@ struct LinkPreviewJoinRow {
     description: Option<String>,
     image_url: Option<String>,
     site_name: Option<String>,
+    quoted_message_id: Option<Uuid>,
 }
 
 fn require_rows_affected(
@@ -124,7 +247,7 @@ pub async fn seed_data(pool: &PgPool) -> Result<(), AppError> {
     .await?;
 
     // Ensure at least one owner exists (promote oldest user if none)
-    let owner_count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM users WHERE role = 'owner'")
+    let owner_count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM users WHERE is_owner")
         .fetch_one(pool)
         .await?;
 
@@ -135,12 +258,12 @@ pub async fn seed_data(pool: &PgPool) -> Result<(), AppError> {
 
         if user_count > 0 {
             sqlx::query(
-                "UPDATE users SET role = 'owner' WHERE id = (SELECT id FROM users ORDER BY created_at ASC LIMIT 1)",
+                "UPDATE users SET is_owner = TRUE WHERE id = (SELECT id FROM users ORDER BY created_at ASC LIMIT 1)",
             )
             .execute(pool)
             .await?;
 
-            tracing::info!("Promoted oldest user to owner role");
+            tracing::info!("Promoted oldest user to owner");
         }
     }
 
@@ -150,9 +273,24 @@ pub async fn seed_data(pool: &PgPool) -> Result<(), AppError> {
 // --- Channels ---
 
 pub async fn get_channels(pool: &PgPool) -> Result<Vec<Channel>, AppError> {
-    let channels: Vec<Channel> = sqlx::query_as("SELECT id, name, channel_type FROM channels")
-        .fetch_all(pool)
-        .await?;
+    let mut channels: Vec<Channel> = sqlx::query_as(&format!(
+        "SELECT {CHANNEL_COLUMNS} FROM channels c
+         WHERE c.channel_type IN ('text', 'voice')
+         ORDER BY c.position, c.id"
+    ))
+    .fetch_all(pool)
+    .await?;
+
+    let mut overwrites: HashMap<Uuid, Vec<PermissionOverwrite>> = HashMap::new();
+    for overwrite in get_all_channel_overwrites(pool).await? {
+        overwrites
+            .entry(overwrite.channel_id)
+            .or_default()
+            .push(overwrite);
+    }
+    for channel in &mut channels {
+        channel.overwrites = overwrites.remove(&channel.id).unwrap_or_default();
+    }
 
     Ok(channels)
 }
@@ -161,13 +299,18 @@ pub async fn get_channel_by_id(
     pool: &PgPool,
     channel_id: Uuid,
 ) -> Result<Option<Channel>, AppError> {
-    let channel: Option<Channel> =
-        sqlx::query_as("SELECT id, name, channel_type FROM channels WHERE id = $1")
-            .bind(channel_id)
-            .fetch_optional(pool)
-            .await?;
+    let channel: Option<Channel> = sqlx::query_as(&format!(
+        "SELECT {CHANNEL_COLUMNS} FROM channels c WHERE c.id = $1"
+    ))
+    .bind(channel_id)
+    .fetch_optional(pool)
+    .await?;
 
-    Ok(channel)
+    let Some(mut channel) = channel else {
+        return Ok(None);
+    };
+    channel.overwrites = get_channel_overwrites(pool, channel_id).await?;
+    Ok(Some(channel))
 }
 
 pub async fn get_channel_type(pool: &PgPool, channel_id: Uuid) -> Result<ChannelType, AppError> {
@@ -184,38 +327,90 @@ pub async fn create_channel(
     channel: &Channel,
     created_by: Uuid,
 ) -> Result<(), AppError> {
+    // New channels go to the end of their category
     sqlx::query(
-        "INSERT INTO channels (id, name, channel_type, created_by, created_at)
-         VALUES ($1, $2, $3, $4, $5)",
+        "INSERT INTO channels (id, name, channel_type, created_by, created_at, category_id, position)
+         VALUES ($1, $2, $3, $4, $5, $6, (
+             SELECT COALESCE(MAX(position) + 1, 0) FROM channels
+             WHERE category_id IS NOT DISTINCT FROM $6 AND channel_type IN ('text', 'voice')
+         ))",
     )
     .bind(channel.id)
     .bind(&channel.name)
     .bind(&channel.channel_type)
     .bind(created_by)
     .bind(Utc::now())
+    .bind(channel.category_id)
     .execute(pool)
     .await?;
 
     Ok(())
 }
 
-pub async fn update_channel(pool: &PgPool, channel_id: Uuid, name: &str) -> Result<(), AppError> {
-    let result = sqlx::query("UPDATE channels SET name = $1 WHERE id = $2")
-        .bind(name)
+pub async fn update_channel(
+    pool: &PgPool,
+    channel_id: Uuid,
+    name: &str,
+    topic: Option<&str>,
+    slowmode_secs: i32,
+    announcement: bool,
+    announcement_role_ids: &[Uuid],
+) -> Result<(), AppError> {
+    let mut tx = pool.begin().await?;
+
+    let result = sqlx::query(
+        "UPDATE channels SET name = $1, topic = $2, slowmode_secs = $3, announcement = $4
+         WHERE id = $5",
+    )
+    .bind(name)
+    .bind(topic)
+    .bind(slowmode_secs)
+    .bind(announcement)
+    .bind(channel_id)
+    .execute(&mut *tx)
+    .await?;
+    require_rows_affected(result, "Channel not found")?;
+
+    sqlx::query("DELETE FROM channel_announcement_roles WHERE channel_id = $1")
         .bind(channel_id)
-        .execute(pool)
+        .execute(&mut *tx)
         .await?;
+    sqlx::query(
+        "INSERT INTO channel_announcement_roles (channel_id, role_id)
+         SELECT $1, UNNEST($2::uuid[])",
+    )
+    .bind(channel_id)
+    .bind(announcement_role_ids)
+    .execute(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+    Ok(())
+}
 
-    require_rows_affected(result, "Channel not found")
+/// Slowmode and announcement settings checked before a member posts.
+pub async fn get_channel_rules(
+    pool: &PgPool,
+    channel_id: Uuid,
+) -> Result<Option<ChannelRules>, AppError> {
+    let rules = sqlx::query_as(
+        "SELECT c.slowmode_secs, c.announcement,
+             ARRAY(SELECT ar.role_id FROM channel_announcement_roles ar WHERE ar.channel_id = c.id)
+                 AS announcement_role_ids
+         FROM channels c WHERE c.id = $1",
+    )
+    .bind(channel_id)
+    .fetch_optional(pool)
+    .await?;
+    Ok(rules)
 }
 
-pub async fn delete_channel(pool: &PgPool, channel_id: Uuid) -> Result<(), AppError> {
+/// Delete a channel and its messages. Returns the storage paths of their
+/// attachments to remove.
+pub async fn delete_channel(pool: &PgPool, channel_id: Uuid) -> Result<Vec<String>, AppError> {
     let mut tx = pool.begin().await?;
 
-    sqlx::query("DELETE FROM messages WHERE channel_id = $1")
-        .bind(channel_id)
-        .execute(&mut *tx)
-        .await?;
+    let storage_paths = delete_channel_messages(&mut tx, &[channel_id]).await?;
 
     let result = sqlx::query("DELETE FROM channels WHERE id = $1")
         .bind(channel_id)
@@ -224,316 +419,2473 @@ pub async fn delete_channel(pool: &PgPool, channel_id: Uuid) -> Result<(), AppEr
 
     require_rows_affected(result, "Channel not found")?;
     tx.commit().await?;
-    Ok(())
+    Ok(storage_paths)
 }
 
-// --- Messages ---
+// --- Channel categories ---
 
-pub async fn get_messages(
+pub async fn get_channel_categories(pool: &PgPool) -> Result<Vec<ChannelCategory>, AppError> {
+    let categories: Vec<ChannelCategory> =
+        sqlx::query_as("SELECT id, name, position FROM channel_categories ORDER BY position, id")
+            .fetch_all(pool)
+            .await?;
+    Ok(categories)
+}
+
+pub async fn create_channel_category(
     pool: &PgPool,
-    channel_id: Uuid,
-    limit: i64,
-    before: Option<DateTime<Utc>>,
-    requesting_user_id: Uuid,
-) -> Result<Vec<Message>, AppError> {
-    // Subquery fetches newest N messages DESC, outer query re-sorts ASC
-    // to return messages in chronological order without a .reverse() in Rust.
-    let rows: Vec<MessageRow> = if let Some(before_ts) = before {
-        sqlx::query_as(
-            "SELECT sub.id, sub.content, u.username, u.display_name, sub.author_id, sub.channel_id, sub.created_at, sub.edited_at, sub.reply_to_id
-             FROM (
-                 SELECT id, content, author_id, channel_id, created_at, edited_at, reply_to_id
-                 FROM messages WHERE channel_id = $1 AND created_at < $2 ORDER BY created_at DESC LIMIT $3
-             ) sub
-             JOIN users u ON u.id = sub.author_id
-             ORDER BY sub.created_at ASC",
-        )
-        .bind(channel_id)
-        .bind(before_ts)
-        .bind(limit)
-        .fetch_all(pool)
-        .await?
+    name: &str,
+) -> Result<ChannelCategory, AppError> {
+    let category: ChannelCategory = sqlx::query_as(
+        "INSERT INTO channel_categories (id, name, position)
+         VALUES ($1, $2, (SELECT COALESCE(MAX(position) + 1, 0) FROM channel_categories))
+         RETURNING id, name, position",
+    )
+    .bind(Uuid::now_v7())
+    .bind(name)
+    .fetch_one(pool)
+    .await?;
+    Ok(category)
+}
+
+pub async fn update_channel_category(
+    pool: &PgPool,
+    category_id: Uuid,
+    name: &str,
+) -> Result<ChannelCategory, AppError> {
+    let category: Option<ChannelCategory> = sqlx::query_as(
+        "UPDATE channel_categories SET name = $1 WHERE id = $2 RETURNING id, name, position",
+    )
+    .bind(name)
+    .bind(category_id)
+    .fetch_optional(pool)
+    .await?;
+    category.ok_or_else(|| AppError::not_found("Category not found"))
+}
+
+/// Delete a category and either delete its channels or move them to the end
+/// of `move_to` (None = uncategorized). Returns the ids of deleted channels
+/// and the storage paths of their attachments to remove.
+pub async fn delete_channel_category(
+    pool: &PgPool,
+    category_id: Uuid,
+    delete_channels: bool,
+    move_to: Option<Uuid>,
+) -> Result<(Vec<Uuid>, Vec<String>), AppError> {
+    let mut tx = pool.begin().await?;
+
+    let mut deleted = Vec::new();
+    let mut storage_paths = Vec::new();
+    if delete_channels {
+        deleted = sqlx::query_scalar("SELECT id FROM channels WHERE category_id = $1")
+            .bind(category_id)
+            .fetch_all(&mut *tx)
+            .await?;
+        storage_paths = delete_channel_messages(&mut tx, &deleted).await?;
+        sqlx::query("DELETE FROM channels WHERE id = ANY($1)")
+            .bind(&deleted)
+            .execute(&mut *tx)
+            .await?;
     } else {
-        sqlx::query_as(
-            "SELECT sub.id, sub.content, u.username, u.display_name, sub.author_id, sub.channel_id, sub.created_at, sub.edited_at, sub.reply_to_id
-             FROM (
-                 SELECT id, content, author_id, channel_id, created_at, edited_at, reply_to_id
-                 FROM messages WHERE channel_id = $1 ORDER BY created_at DESC LIMIT $2
-             ) sub
-             JOIN users u ON u.id = sub.author_id
-             ORDER BY sub.created_at ASC",
+        sqlx::query(
+            "UPDATE channels SET category_id = $2, position = position + (
+                 SELECT COALESCE(MAX(position) + 1, 0) FROM channels
+                 WHERE category_id IS NOT DISTINCT FROM $2 AND channel_type IN ('text', 'voice')
+             )
+             WHERE category_id = $1",
         )
-        .bind(channel_id)
-        .bind(limit)
-        .fetch_all(pool)
-        .await?
-    };
+        .bind(category_id)
+        .bind(move_to)
+        .execute(&mut *tx)
+        .await?;
+    }
 
-    let mut messages: Vec<Message> = rows.into_iter().map(Message::from).collect();
+    let result = sqlx::query("DELETE FROM channel_categories WHERE id = $1")
+        .bind(category_id)
+        .execute(&mut *tx)
+        .await?;
+    require_rows_affected(result, "Category not found")?;
 
-    // Batch-fetch reply previews
-    let reply_ids: Vec<Uuid> = messages.iter().filter_map(|m| m.reply_to_id).collect();
-    if !reply_ids.is_empty() {
-        let previews = get_reply_previews(pool, &reply_ids).await?;
-        for msg in &mut messages {
-            if let Some(reply_id) = msg.reply_to_id {
-                msg.reply_to = previews.get(&reply_id).cloned();
-            }
-        }
+    tx.commit().await?;
+    Ok((deleted, storage_paths))
+}
+
+pub async fn get_channel_positions(pool: &PgPool) -> Result<Vec<ChannelPosition>, AppError> {
+    let positions: Vec<ChannelPosition> = sqlx::query_as(
+        "SELECT id, category_id, position FROM channels
+         WHERE channel_type IN ('text', 'voice')
+         ORDER BY position, id",
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(positions)
+}
+
+/// Apply a batch of category and channel moves atomically.
+pub async fn reorder_channels(
+    pool: &PgPool,
+    categories: &[CategoryPosition],
+    channels: &[ChannelPosition],
+) -> Result<(), AppError> {
+    let mut tx = pool.begin().await?;
+
+    for category in categories {
+        let result = sqlx::query("UPDATE channel_categories SET position = $1 WHERE id = $2")
+            .bind(category.position)
+            .bind(category.id)
+            .execute(&mut *tx)
+            .await?;
+        require_rows_affected(result, "Category not found")?;
     }
 
-    // Batch-fetch reactions, link previews, and attachments concurrently
-    let message_ids: Vec<Uuid> = messages.iter().map(|m| m.id).collect();
-    if !message_ids.is_empty() {
-        let (reactions_map, previews_map, attachments_map) = tokio::join!(
-            get_reactions_for_messages(pool, &message_ids, requesting_user_id),
-            get_link_previews_for_messages(pool, &message_ids),
-            get_attachments_for_messages(pool, &message_ids),
-        );
-        let reactions_map = reactions_map?;
-        let previews_map = previews_map?;
-        let attachments_map = attachments_map?;
-
-        for msg in &mut messages {
-            if let Some(reactions) = reactions_map.get(&msg.id)
-                && !reactions.is_empty()
-            {
-                msg.reactions = Some(reactions.clone());
-            }
-            if let Some(previews) = previews_map.get(&msg.id)
-                && !previews.is_empty()
-            {
-                msg.link_previews = Some(previews.clone());
-            }
-            if let Some(attachments) = attachments_map.get(&msg.id)
-                && !attachments.is_empty()
-            {
-                msg.attachments = Some(attachments.clone());
-            }
-        }
+    for channel in channels {
+        let result = sqlx::query(
+            "UPDATE channels SET category_id = $1, position = $2
+             WHERE id = $3 AND channel_type IN ('text', 'voice')",
+        )
+        .bind(channel.category_id)
+        .bind(channel.position)
+        .bind(channel.id)
+        .execute(&mut *tx)
+        .await?;
+        require_rows_affected(result, "Channel not found")?;
     }
 
-    Ok(messages)
+    tx.commit().await?;
+    Ok(())
 }
 
-pub async fn get_message_by_id(
+// --- Channel permission overwrites ---
+
+#[derive(FromRow)]
+struct ApplicableOverwriteRow {
+    /// 0 = default role, 1 = one of the user's roles, 2 = the user.
+    scope: i32,
+    allow: Permissions,
+    deny: Permissions,
+}
+
+pub async fn get_channel_overwrites(
     pool: &PgPool,
-    message_id: Uuid,
-) -> Result<Option<Message>, AppError> {
-    let row: Option<MessageRow> = sqlx::query_as(
-        "SELECT m.id, m.content, u.username, u.display_name, m.author_id, m.channel_id, m.created_at, m.edited_at, m.reply_to_id
-         FROM messages m
-         JOIN users u ON u.id = m.author_id
-         WHERE m.id = $1",
+    channel_id: Uuid,
+) -> Result<Vec<PermissionOverwrite>, AppError> {
+    let overwrites: Vec<PermissionOverwrite> = sqlx::query_as(
+        "SELECT channel_id, role_id, user_id, allow, deny FROM channel_overwrites
+         WHERE channel_id = $1",
     )
-    .bind(message_id)
-    .fetch_optional(pool)
+    .bind(channel_id)
+    .fetch_all(pool)
     .await?;
+    Ok(overwrites)
+}
 
-    Ok(row.map(Message::from))
+pub async fn get_all_channel_overwrites(
+    pool: &PgPool,
+) -> Result<Vec<PermissionOverwrite>, AppError> {
+    let overwrites: Vec<PermissionOverwrite> =
+        sqlx::query_as("SELECT channel_id, role_id, user_id, allow, deny FROM channel_overwrites")
+            .fetch_all(pool)
+            .await?;
+    Ok(overwrites)
 }
 
-pub async fn get_full_message_by_id(
+/// Create or replace the overwrite for a role or user on a channel.
+pub async fn set_channel_overwrite(
     pool: &PgPool,
-    message_id: Uuid,
-    requesting_user_id: Uuid,
-) -> Result<Option<Message>, AppError> {
-    let Some(mut msg) = get_message_by_id(pool, message_id).await? else {
-        return Ok(None);
-    };
+    channel_id: Uuid,
+    target_type: OverwriteTargetType,
+    target_id: Uuid,
+    allow: Permissions,
+    deny: Permissions,
+) -> Result<PermissionOverwrite, AppError> {
+    let column = overwrite_target_column(target_type);
+    let overwrite: PermissionOverwrite = sqlx::query_as(&format!(
+        "INSERT INTO channel_overwrites (id, channel_id, {column}, allow, deny)
+         VALUES ($1, $2, $3, $4, $5)
+         ON CONFLICT (channel_id, {column}) WHERE {column} IS NOT NULL
+         DO UPDATE SET allow = EXCLUDED.allow, deny = EXCLUDED.deny
+         RETURNING channel_id, role_id, user_id, allow, deny"
+    ))
+    .bind(Uuid::now_v7())
+    .bind(channel_id)
+    .bind(target_id)
+    .bind(allow)
+    .bind(deny)
+    .fetch_one(pool)
+    .await?;
+    Ok(overwrite)
+}
 
-    // Enrich with reply preview, reactions, link previews, and attachments
-    if let Some(reply_id) = msg.reply_to_id {
-        msg.reply_to = get_reply_preview(pool, reply_id).await?;
-    }
+pub async fn delete_channel_overwrite(
+    pool: &PgPool,
+    channel_id: Uuid,
+    target_type: OverwriteTargetType,
+    target_id: Uuid,
+) -> Result<(), AppError> {
+    let column = overwrite_target_column(target_type);
+    let result = sqlx::query(&format!(
+        "DELETE FROM channel_overwrites WHERE channel_id = $1 AND {column} = $2"
+    ))
+    .bind(channel_id)
+    .bind(target_id)
+    .execute(pool)
+    .await?;
 
-    let ids = &[message_id];
-    let (reactions_map, previews_map, attachments_map) = tokio::join!(
-        get_reactions_for_messages(pool, ids, requesting_user_id),
-        get_link_previews_for_messages(pool, ids),
-        get_attachments_for_messages(pool, ids),
-    );
+    require_rows_affected(result, "Overwrite not found")
+}
 
-    if let Some(reactions) = reactions_map?.get(&message_id)
-        && !reactions.is_empty()
-    {
-        msg.reactions = Some(reactions.clone());
-    }
-    if let Some(previews) = previews_map?.get(&message_id)
-        && !previews.is_empty()
-    {
-        msg.link_previews = Some(previews.clone());
+fn overwrite_target_column(target_type: OverwriteTargetType) -> &'static str {
+    match target_type {
+        OverwriteTargetType::Role => "role_id",
+        OverwriteTargetType::User => "user_id",
     }
-    if let Some(attachments) = attachments_map?.get(&message_id)
-        && !attachments.is_empty()
-    {
-        msg.attachments = Some(attachments.clone());
-    }
-
-    Ok(Some(msg))
 }
 
-pub async fn create_message(
+/// The user's effective permissions in a channel after overwrites.
+pub async fn get_channel_permissions(
     pool: &PgPool,
-    message: &Message,
-    author_id: Uuid,
-) -> Result<(), AppError> {
-    sqlx::query(
-        "INSERT INTO messages (id, content, author_id, channel_id, created_at, reply_to_id)
-         VALUES ($1, $2, $3, $4, $5, $6)",
+    channel_id: Uuid,
+    user_id: Uuid,
+) -> Result<Permissions, AppError> {
+    let member = get_member_permissions(pool, user_id).await?;
+    if member.has(Permissions::ADMINISTRATOR) {
+        return Ok(Permissions::ALL);
+    }
+
+    let rows: Vec<ApplicableOverwriteRow> = sqlx::query_as(
+        "SELECT CASE WHEN o.user_id IS NOT NULL THEN 2 WHEN r.is_default THEN 0 ELSE 1 END AS scope,
+                o.allow, o.deny
+         FROM channel_overwrites o
+         LEFT JOIN roles r ON r.id = o.role_id
+         WHERE o.channel_id = $1
+           AND (o.user_id = $2 OR r.is_default
+                OR o.role_id IN (SELECT role_id FROM user_roles WHERE user_id = $2))",
     )
-    .bind(message.id)
-    .bind(&message.content)
-    .bind(author_id)
-    .bind(message.channel_id)
-    .bind(message.timestamp)
-    .bind(message.reply_to_id)
-    .execute(pool)
+    .bind(channel_id)
+    .bind(user_id)
+    .fetch_all(pool)
     .await?;
 
-    Ok(())
+    let mut layers = [Overwrite::default(); 3];
+    for row in rows {
+        let layer = &mut layers[row.scope as usize];
+        *layer = layer.combine(Overwrite {
+            allow: row.allow,
+            deny: row.deny,
+        });
+    }
+    Ok(member.in_channel(layers[0], layers[1], layers[2]))
 }
 
-pub async fn update_message(
-    pool: &PgPool,
-    message_id: Uuid,
-    content: &str,
-) -> Result<(), AppError> {
-    let result = sqlx::query("UPDATE messages SET content = $1, edited_at = $2 WHERE id = $3")
-        .bind(content)
-        .bind(Utc::now())
-        .bind(message_id)
-        .execute(pool)
+// --- Direct messages ---
+
+/// All (channel_id, user_id) memberships of private channels, used to seed
+/// the in-memory membership cache at startup.
+pub async fn get_all_private_channel_members(pool: &PgPool) -> Result<Vec<(Uuid, Uuid)>, AppError> {
+    let rows: Vec<(Uuid, Uuid)> = sqlx::query_as("SELECT channel_id, user_id FROM channel_members")
+        .fetch_all(pool)
         .await?;
 
-    require_rows_affected(result, "Message not found")
+    Ok(rows)
 }
 
-pub async fn delete_message(pool: &PgPool, message_id: Uuid) -> Result<(), AppError> {
-    let result = sqlx::query("DELETE FROM messages WHERE id = $1")
-        .bind(message_id)
-        .execute(pool)
-        .await?;
+/// Returns the 1:1 DM channel between two users, creating it if needed.
+/// Either user may have left an existing channel, so both memberships are
+/// restored; the second value lists the users who were (re)added.
+pub async fn get_or_create_dm(
+    pool: &PgPool,
+    user_id: Uuid,
+    other_user_id: Uuid,
+) -> Result<(Uuid, Vec<Uuid>), AppError> {
+    let (a, b) = if user_id < other_user_id {
+        (user_id, other_user_id)
+    } else {
+        (other_user_id, user_id)
+    };
+    let dm_key = format!("{a}:{b}");
+
+    let mut tx = pool.begin().await?;
+
+    let created: Option<(Uuid,)> = sqlx::query_as(
+        "INSERT INTO channels (id, name, channel_type, created_by, created_at, dm_key)
+         VALUES ($1, '', 'dm', $2, NOW(), $3)
+         ON CONFLICT (dm_key) DO NOTHING
+         RETURNING id",
+    )
+    .bind(Uuid::now_v7())
+    .bind(user_id)
+    .bind(&dm_key)
+    .fetch_optional(&mut *tx)
+    .await?;
 
-    require_rows_affected(result, "Message not found")
+    let channel_id = match created {
+        Some((channel_id,)) => channel_id,
+        None => {
+            let (channel_id,): (Uuid,) =
+                sqlx::query_as("SELECT id FROM channels WHERE dm_key = $1")
+                    .bind(&dm_key)
+                    .fetch_one(&mut *tx)
+                    .await?;
+            channel_id
+        }
+    };
+
+    let added: Vec<(Uuid,)> = sqlx::query_as(
+        "INSERT INTO channel_members (channel_id, user_id) SELECT $1, unnest($2::uuid[])
+         ON CONFLICT DO NOTHING
+         RETURNING user_id",
+    )
+    .bind(channel_id)
+    .bind(&[a, b][..])
+    .fetch_all(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+    Ok((channel_id, added.into_iter().map(|(id,)| id).collect()))
 }
 
-pub async fn get_reply_previews(
+pub async fn create_group_dm(
+    pool: &PgPool,
+    channel_id: Uuid,
+    name: Option<&str>,
+    created_by: Uuid,
+    member_ids: &[Uuid],
+) -> Result<(), AppError> {
+    let mut tx = pool.begin().await?;
+
+    sqlx::query(
+        "INSERT INTO channels (id, name, channel_type, created_by, created_at)
+         VALUES ($1, $2, 'group_dm', $3, NOW())",
+    )
+    .bind(channel_id)
+    .bind(name.unwrap_or_default())
+    .bind(created_by)
+    .execute(&mut *tx)
+    .await?;
+
+    sqlx::query("INSERT INTO channel_members (channel_id, user_id) SELECT $1, unnest($2::uuid[])")
+        .bind(channel_id)
+        .bind(member_ids)
+        .execute(&mut *tx)
+        .await?;
+
+    tx.commit().await?;
+    Ok(())
+}
+
+pub async fn get_dm_channels_for_user(
+    pool: &PgPool,
+    user_id: Uuid,
+) -> Result<Vec<DmChannel>, AppError> {
+    let rows: Vec<DmChannelRow> = sqlx::query_as(
+        "SELECT c.id, c.name, c.channel_type, c.created_at
+         FROM channels c
+         JOIN channel_members cm ON cm.channel_id = c.id
+         WHERE cm.user_id = $1 AND c.channel_type IN ('dm', 'group_dm')
+         ORDER BY c.created_at DESC",
+    )
+    .bind(user_id)
+    .fetch_all(pool)
+    .await?;
+
+    build_dm_channels(pool, rows).await
+}
+
+pub async fn get_dm_channel(
+    pool: &PgPool,
+    channel_id: Uuid,
+) -> Result<Option<DmChannel>, AppError> {
+    let row: Option<DmChannelRow> = sqlx::query_as(
+        "SELECT id, name, channel_type, created_at FROM channels
+         WHERE id = $1 AND channel_type IN ('dm', 'group_dm')",
+    )
+    .bind(channel_id)
+    .fetch_optional(pool)
+    .await?;
+
+    let Some(row) = row else {
+        return Ok(None);
+    };
+    Ok(build_dm_channels(pool, vec![row]).await?.pop())
+}
+
+async fn build_dm_channels(
+    pool: &PgPool,
+    rows: Vec<DmChannelRow>,
+) -> Result<Vec<DmChannel>, AppError> {
+    let channel_ids: Vec<Uuid> = rows.iter().map(|r| r.id).collect();
+    let member_rows: Vec<ChannelMemberRow> = sqlx::query_as(&format!(
+        "SELECT cm.channel_id, u.id, u.username, u.display_name, u.is_owner, {USER_ROLE_IDS}, u.avatar_path
+         FROM channel_members cm
+         JOIN users u ON u.id = cm.user_id
+         WHERE cm.channel_id = ANY($1)
+         ORDER BY cm.joined_at ASC"
+    ))
+    .bind(&channel_ids)
+    .fetch_all(pool)
+    .await?;
+
+    let mut members_map: HashMap<Uuid, Vec<MemberInfo>> = HashMap::new();
+    for row in member_rows {
+        members_map
+            .entry(row.channel_id)
+            .or_default()
+            .push(MemberInfo::from(row));
+    }
+
+    Ok(rows
+        .into_iter()
+        .map(|row| DmChannel {
+            members: members_map.remove(&row.id).unwrap_or_default(),
+            id: row.id,
+            channel_type: row.channel_type,
+            name: Some(row.name).filter(|n| !n.is_empty()),
+            created_at: row.created_at,
+        })
+        .collect())
+}
+
+pub async fn add_channel_member(
+    pool: &PgPool,
+    channel_id: Uuid,
+    user_id: Uuid,
+) -> Result<(), AppError> {
+    sqlx::query(
+        "INSERT INTO channel_members (channel_id, user_id) VALUES ($1, $2)
+         ON CONFLICT DO NOTHING",
+    )
+    .bind(channel_id)
+    .bind(user_id)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+/// Removes a member and deletes the channel once nobody is left. When the
+/// channel was deleted, returns the storage paths of attachments to remove.
+pub async fn remove_channel_member(
+    pool: &PgPool,
+    channel_id: Uuid,
+    user_id: Uuid,
+) -> Result<Option<Vec<String>>, AppError> {
+    let mut tx = pool.begin().await?;
+
+    let result = sqlx::query("DELETE FROM channel_members WHERE channel_id = $1 AND user_id = $2")
+        .bind(channel_id)
+        .bind(user_id)
+        .execute(&mut *tx)
+        .await?;
+    require_rows_affected(result, "Not a member of this channel")?;
+
+    let (remaining,): (i64,) =
+        sqlx::query_as("SELECT COUNT(*) FROM channel_members WHERE channel_id = $1")
+            .bind(channel_id)
+            .fetch_one(&mut *tx)
+            .await?;
+
+    let mut storage_paths = None;
+    if remaining == 0 {
+        storage_paths = Some(delete_channel_messages(&mut tx, &[channel_id]).await?);
+        sqlx::query("DELETE FROM channels WHERE id = $1")
+            .bind(channel_id)
+            .execute(&mut *tx)
+            .await?;
+    }
+
+    tx.commit().await?;
+    Ok(storage_paths)
+}
+
+// --- Messages ---
+
+/// Message timeline a page is read from. Thread replies live in their own
+/// timeline and are excluded from the channel's.
+#[derive(Debug, Clone, Copy)]
+enum Timeline {
+    Channel(Uuid),
+    Thread(Uuid),
+}
+
+impl Timeline {
+    fn filter(self) -> (&'static str, Uuid) {
+        match self {
+            Timeline::Channel(id) => ("channel_id = $1 AND thread_id IS NULL", id),
+            Timeline::Thread(id) => ("thread_id = $1", id),
+        }
+    }
+}
+
+/// Up to `limit` messages on the `cmp` side of `cursor`, ordered by
+/// `(created_at, id)` so messages sharing a timestamp are never skipped.
+/// Older pages (`<`, `<=`) take the newest matches. Rows come back in
+/// chronological order. Tombstones are only included for moderators.
+async fn fetch_timeline(
+    pool: &PgPool,
+    timeline: Timeline,
+    cursor: Option<Uuid>,
+    cmp: &'static str,
+    limit: i64,
+    include_deleted: bool,
+) -> Result<Vec<MessageRow>, AppError> {
+    let (filter, scope_id) = timeline.filter();
+    let direction = if cmp.starts_with('<') { "DESC" } else { "ASC" };
+    let rows: Vec<MessageRow> = sqlx::query_as(&format!(
+        "SELECT sub.id, sub.content, u.username, u.display_name, sub.author_id, sub.channel_id, sub.created_at, sub.edited_at, sub.reply_to_id, sub.thread_id, sub.mention_everyone, sub.kind, sub.pinned_at, sub.pinned_by, sub.deleted_at, sub.deleted_by
+         FROM (
+             SELECT id, content, author_id, channel_id, created_at, edited_at, reply_to_id, thread_id, mention_everyone, kind, pinned_at, pinned_by, deleted_at, deleted_by
+             FROM messages
+             WHERE {filter} AND (deleted_at IS NULL OR $4)
+                 AND ($2::uuid IS NULL
+                     OR (created_at, id) {cmp} (SELECT created_at, id FROM messages WHERE id = $2))
+             ORDER BY created_at {direction}, id {direction} LIMIT $3
+         ) sub
+         JOIN users u ON u.id = sub.author_id
+         ORDER BY sub.created_at ASC, sub.id ASC"
+    ))
+    .bind(scope_id)
+    .bind(cursor)
+    .bind(limit)
+    .bind(include_deleted)
+    .fetch_all(pool)
+    .await?;
+    Ok(rows)
+}
+
+/// Whether any message lies on the `cmp` side of `cursor`.
+async fn timeline_has_messages(
+    pool: &PgPool,
+    timeline: Timeline,
+    cursor: Uuid,
+    cmp: &'static str,
+    include_deleted: bool,
+) -> Result<bool, AppError> {
+    let (filter, scope_id) = timeline.filter();
+    let exists: bool = sqlx::query_scalar(&format!(
+        "SELECT EXISTS (
+             SELECT 1 FROM messages
+             WHERE {filter} AND (deleted_at IS NULL OR $3)
+                 AND (created_at, id) {cmp} (SELECT created_at, id FROM messages WHERE id = $2)
+         )"
+    ))
+    .bind(scope_id)
+    .bind(cursor)
+    .bind(include_deleted)
+    .fetch_one(pool)
+    .await?;
+    Ok(exists)
+}
+
+/// Keep at most `limit` rows, dropping from the end far from the cursor.
+/// Returns whether any were dropped.
+fn trim_page(rows: &mut Vec<MessageRow>, limit: i64, keep_newest: bool) -> bool {
+    let limit = limit as usize;
+    if rows.len() <= limit {
+        return false;
+    }
+    if keep_newest {
+        rows.drain(..rows.len() - limit);
+    } else {
+        rows.truncate(limit);
+    }
+    true
+}
+
+async fn get_timeline_page(
+    pool: &PgPool,
+    timeline: Timeline,
+    limit: i64,
+    cursor: MessageCursor,
+    requesting_user_id: Uuid,
+    include_deleted: bool,
+) -> Result<MessagePage, AppError> {
+    // Each side fetches one extra row to learn whether more lie beyond it.
+    let (rows, has_more_before, has_more_after) = match cursor {
+        MessageCursor::Latest => {
+            let mut rows =
+                fetch_timeline(pool, timeline, None, "<", limit + 1, include_deleted).await?;
+            let has_more_before = trim_page(&mut rows, limit, true);
+            (rows, has_more_before, false)
+        }
+        MessageCursor::Before(id) => {
+            let mut rows =
+                fetch_timeline(pool, timeline, Some(id), "<", limit + 1, include_deleted).await?;
+            let has_more_before = trim_page(&mut rows, limit, true);
+            let has_more_after =
+                timeline_has_messages(pool, timeline, id, ">=", include_deleted).await?;
+            (rows, has_more_before, has_more_after)
+        }
+        MessageCursor::After(id) => {
+            let mut rows =
+                fetch_timeline(pool, timeline, Some(id), ">", limit + 1, include_deleted).await?;
+            let has_more_after = trim_page(&mut rows, limit, false);
+            let has_more_before =
+                timeline_has_messages(pool, timeline, id, "<=", include_deleted).await?;
+            (rows, has_more_before, has_more_after)
+        }
+        MessageCursor::Around(id) => {
+            // The target and older messages fill the first half of the page.
+            let after_limit = limit / 2;
+            let before_limit = limit - after_limit;
+            let (older, newer) = tokio::join!(
+                fetch_timeline(
+                    pool,
+                    timeline,
+                    Some(id),
+                    "<=",
+                    before_limit + 1,
+                    include_deleted
+                ),
+                fetch_timeline(
+                    pool,
+                    timeline,
+                    Some(id),
+                    ">",
+                    after_limit + 1,
+                    include_deleted
+                ),
+            );
+            let (mut older, mut newer) = (older?, newer?);
+            let has_more_before = trim_page(&mut older, before_limit, true);
+            let has_more_after = trim_page(&mut newer, after_limit, false);
+            older.append(&mut newer);
+            (older, has_more_before, has_more_after)
+        }
+    };
+
+    let mut messages: Vec<Message> = rows.into_iter().map(Message::from).collect();
+    enrich_messages(pool, &mut messages, requesting_user_id).await?;
+    Ok(MessagePage {
+        messages,
+        has_more_before,
+        has_more_after,
+    })
+}
+
+/// A page of a channel's top-level messages in chronological order.
+pub async fn get_messages(
+    pool: &PgPool,
+    channel_id: Uuid,
+    limit: i64,
+    cursor: MessageCursor,
+    requesting_user_id: Uuid,
+    include_deleted: bool,
+) -> Result<MessagePage, AppError> {
+    get_timeline_page(
+        pool,
+        Timeline::Channel(channel_id),
+        limit,
+        cursor,
+        requesting_user_id,
+        include_deleted,
+    )
+    .await
+}
+
+/// Batch-fetch reply previews, reactions, link previews, attachments, and
+/// thread summaries for a page of messages.
+pub async fn enrich_messages(
+    pool: &PgPool,
+    messages: &mut [Message],
+    requesting_user_id: Uuid,
+) -> Result<(), AppError> {
+    // Batch-fetch reply previews
+    let reply_ids: Vec<Uuid> = messages.iter().filter_map(|m| m.reply_to_id).collect();
+    if !reply_ids.is_empty() {
+        let previews = get_reply_previews(pool, &reply_ids).await?;
+        for msg in messages.iter_mut() {
+            if let Some(reply_id) = msg.reply_to_id {
+                msg.reply_to = previews.get(&reply_id).cloned();
+            }
+        }
+    }
+
+    // Batch-fetch reactions, link previews, attachments, and threads concurrently
+    let message_ids: Vec<Uuid> = messages.iter().map(|m| m.id).collect();
+    if message_ids.is_empty() {
+        return Ok(());
+    }
+
+    let (
+        reactions_map,
+        previews_map,
+        attachments_map,
+        threads_map,
+        mentions_map,
+        polls_map,
+        forwards_map,
+        webhooks_map,
+    ) = tokio::join!(
+        get_reactions_for_messages(pool, &message_ids, requesting_user_id),
+        get_link_previews_for_messages(pool, &message_ids),
+        get_attachments_for_messages(pool, &message_ids),
+        get_threads_for_messages(pool, &message_ids),
+        get_mentions_for_messages(pool, &message_ids),
+        get_polls_for_messages(pool, &message_ids, requesting_user_id),
+        get_forwards_for_messages(pool, &message_ids),
+        get_webhook_messages(pool, &message_ids),
+    );
+    let reactions_map = reactions_map?;
+    let previews_map = previews_map?;
+    let attachments_map = attachments_map?;
+    let mut threads_map = threads_map?;
+    let mut mentions_map = mentions_map?;
+    let mut polls_map = polls_map?;
+    let mut forwards_map = forwards_map?;
+    let mut webhooks_map = webhooks_map?;
+
+    for msg in messages.iter_mut() {
+        if let Some(reactions) = reactions_map.get(&msg.id)
+            && !reactions.is_empty()
+        {
+            msg.reactions = Some(reactions.clone());
+        }
+        if let Some(previews) = previews_map.get(&msg.id)
+            && !previews.is_empty()
+        {
+            msg.link_previews = Some(previews.clone());
+        }
+        if let Some(attachments) = attachments_map.get(&msg.id)
+            && !attachments.is_empty()
+        {
+            msg.attachments = Some(attachments.clone());
+        }
+        msg.thread = threads_map.remove(&msg.id);
+        msg.mentions = mentions_map.remove(&msg.id);
+        msg.poll = polls_map.remove(&msg.id);
+        msg.forwarded_from = forwards_map.remove(&msg.id);
+        if let Some((webhook, embeds)) = webhooks_map.remove(&msg.id) {
+            msg.webhook = Some(webhook);
+            msg.embeds = Some(embeds).filter(|e| !e.is_empty());
+        }
+    }
+
+    Ok(())
+}
+
+pub async fn get_message_by_id(
+    pool: &PgPool,
+    message_id: Uuid,
+) -> Result<Option<Message>, AppError> {
+    let row: Option<MessageRow> = sqlx::query_as(
+        "SELECT m.id, m.content, u.username, u.display_name, m.author_id, m.channel_id, m.created_at, m.edited_at, m.reply_to_id, m.thread_id, m.mention_everyone, m.kind, m.pinned_at, m.pinned_by, m.deleted_at, m.deleted_by
+         FROM messages m
+         JOIN users u ON u.id = m.author_id
+         WHERE m.id = $1 AND m.deleted_at IS NULL",
+    )
+    .bind(message_id)
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(row.map(Message::from))
+}
+
+/// The channel and thread of a message, used to validate paging cursors.
+/// Tombstones are only found when `include_deleted` is set.
+pub async fn get_message_location(
+    pool: &PgPool,
+    message_id: Uuid,
+    include_deleted: bool,
+) -> Result<Option<(Uuid, Option<Uuid>)>, AppError> {
+    let row: Option<(Uuid, Option<Uuid>)> = sqlx::query_as(
+        "SELECT channel_id, thread_id FROM messages
+         WHERE id = $1 AND ($2 OR deleted_at IS NULL)",
+    )
+    .bind(message_id)
+    .bind(include_deleted)
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(row)
+}
+
+pub async fn get_full_message_by_id(
+    pool: &PgPool,
+    message_id: Uuid,
+    requesting_user_id: Uuid,
+) -> Result<Option<Message>, AppError> {
+    let Some(msg) = get_message_by_id(pool, message_id).await? else {
+        return Ok(None);
+    };
+
+    let mut messages = [msg];
+    enrich_messages(pool, &mut messages, requesting_user_id).await?;
+    let [msg] = messages;
+
+    Ok(Some(msg))
+}
+
+/// Full-text search over message content combined with optional filters.
+/// With a text query, results carry a highlighted snippet.
+pub async fn search_messages(
+    pool: &PgPool,
+    query: &SearchQuery,
+    limit: i64,
+    offset: i64,
+    requesting_user_id: Uuid,
+    hidden_channel_ids: &[Uuid],
+) -> Result<Vec<SearchResult>, AppError> {
+    let text = query.q.as_deref().map(str::trim).filter(|q| !q.is_empty());
+
+    // Snippets are generated from HTML-escaped content so the only markup in
+    // the highlight is the <mark> tags added by ts_headline.
+    let rows: Vec<SearchRow> = sqlx::query_as(
+        "SELECT m.id, m.content, u.username, u.display_name, m.author_id, m.channel_id, m.created_at, m.edited_at, m.reply_to_id, m.thread_id, m.mention_everyone, m.kind, m.pinned_at, m.pinned_by, m.deleted_at, m.deleted_by,
+                CASE WHEN $1::text IS NULL THEN NULL ELSE ts_headline(
+                    'english',
+                    replace(replace(replace(m.content, '&', '&amp;'), '<', '&lt;'), '>', '&gt;'),
+                    websearch_to_tsquery('english', $1),
+                    'StartSel=<mark>, StopSel=</mark>, MaxFragments=2, MaxWords=30, MinWords=10'
+                ) END AS highlight
+         FROM messages m
+         JOIN users u ON u.id = m.author_id
+         JOIN channels c ON c.id = m.channel_id
+         WHERE (c.channel_type IN ('text', 'voice') OR EXISTS (
+                    SELECT 1 FROM channel_members cm WHERE cm.channel_id = c.id AND cm.user_id = $11
+                ))
+           AND NOT (m.channel_id = ANY($12))
+           AND m.deleted_at IS NULL
+           AND ($1::text IS NULL OR m.search_vector @@ websearch_to_tsquery('english', $1))
+           AND ($2::uuid IS NULL OR m.channel_id = $2)
+           AND ($3::uuid IS NULL OR m.author_id = $3)
+           AND ($4::timestamptz IS NULL OR m.created_at < $4)
+           AND ($5::timestamptz IS NULL OR m.created_at > $5)
+           AND (NOT $6 OR EXISTS (SELECT 1 FROM attachments a WHERE a.message_id = m.id))
+           AND (NOT $7 OR m.content ~* 'https?://')
+           AND (NOT $8 OR m.mention_everyone OR EXISTS (
+                    SELECT 1 FROM message_mentions mm WHERE mm.message_id = m.id AND mm.user_id = $11
+                ))
+         ORDER BY m.created_at DESC
+         LIMIT $9 OFFSET $10",
+    )
+    .bind(text)
+    .bind(query.channel_id)
+    .bind(query.author_id)
+    .bind(query.before)
+    .bind(query.after)
+    .bind(query.has_attachment)
+    .bind(query.has_link)
+    .bind(query.mentions_me)
+    .bind(limit)
+    .bind(offset)
+    .bind(requesting_user_id)
+    .bind(hidden_channel_ids)
+    .fetch_all(pool)
+    .await?;
+
+    let mut highlights = Vec::with_capacity(rows.len());
+    let mut messages = Vec::with_capacity(rows.len());
+    for row in rows {
+        highlights.push(row.highlight);
+        messages.push(Message::from(row.message));
+    }
+    enrich_messages(pool, &mut messages, requesting_user_id).await?;
+
+    Ok(messages
+        .into_iter()
+        .zip(highlights)
+        .map(|(message, highlight)| SearchResult { message, highlight })
+        .collect())
+}
+
+pub async fn create_message(
+    pool: &PgPool,
+    message: &Message,
+    author_id: Uuid,
+) -> Result<(), AppError> {
+    sqlx::query(
+        "INSERT INTO messages (id, content, author_id, channel_id, created_at, reply_to_id, thread_id, kind)
+         VALUES ($1, $2, $3, $4, $5, $6, $7, $8)",
+    )
+    .bind(message.id)
+    .bind(&message.content)
+    .bind(author_id)
+    .bind(message.channel_id)
+    .bind(message.timestamp)
+    .bind(message.reply_to_id)
+    .bind(message.thread_id)
+    .bind(message.kind)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+/// Replace a message's content, keeping the previous content as a revision.
+pub async fn update_message(
+    pool: &PgPool,
+    message_id: Uuid,
+    content: &str,
+) -> Result<(), AppError> {
+    let mut tx = pool.begin().await?;
+    let now = Utc::now();
+
+    let result = sqlx::query(
+        "INSERT INTO message_revisions (id, message_id, content, created_at, replaced_at)
+         SELECT $1, id, content, COALESCE(edited_at, created_at), $2 FROM messages WHERE id = $3",
+    )
+    .bind(Uuid::now_v7())
+    .bind(now)
+    .bind(message_id)
+    .execute(&mut *tx)
+    .await?;
+    require_rows_affected(result, "Message not found")?;
+
+    sqlx::query("UPDATE messages SET content = $1, edited_at = $2 WHERE id = $3")
+        .bind(content)
+        .bind(now)
+        .bind(message_id)
+        .execute(&mut *tx)
+        .await?;
+
+    tx.commit().await?;
+    Ok(())
+}
+
+/// Prior contents of a message, oldest first.
+pub async fn get_message_revisions(
+    pool: &PgPool,
+    message_id: Uuid,
+) -> Result<Vec<MessageRevision>, AppError> {
+    let revisions = sqlx::query_as(
+        "SELECT id, message_id, content, created_at, replaced_at FROM message_revisions
+         WHERE message_id = $1 ORDER BY replaced_at, id",
+    )
+    .bind(message_id)
+    .fetch_all(pool)
+    .await?;
+    Ok(revisions)
+}
+
+/// Delete revisions replaced before `cutoff`. Returns how many were removed.
+pub async fn purge_message_revisions(
+    pool: &PgPool,
+    cutoff: DateTime<Utc>,
+) -> Result<u64, AppError> {
+    let result = sqlx::query("DELETE FROM message_revisions WHERE replaced_at < $1")
+        .bind(cutoff)
+        .execute(pool)
+        .await?;
+    Ok(result.rows_affected())
+}
+
+/// Soft-delete a message, leaving a tombstone until it is purged. Returns
+/// when the message was deleted.
+pub async fn delete_message(
+    pool: &PgPool,
+    message_id: Uuid,
+    deleted_by: Uuid,
+) -> Result<DateTime<Utc>, AppError> {
+    let mut tx = pool.begin().await?;
+    let deleted_at = Utc::now();
+
+    let deleted: Option<(Option<Uuid>,)> = sqlx::query_as(
+        "UPDATE messages SET deleted_at = $2, deleted_by = $3
+         WHERE id = $1 AND deleted_at IS NULL
+         RETURNING thread_id",
+    )
+    .bind(message_id)
+    .bind(deleted_at)
+    .bind(deleted_by)
+    .fetch_optional(&mut *tx)
+    .await?;
+
+    let Some((thread_id,)) = deleted else {
+        return Err(AppError::not_found("Message not found"));
+    };
+
+    // Quotes embedded elsewhere would otherwise keep showing the content
+    sqlx::query("DELETE FROM link_previews WHERE quoted_message_id = $1")
+        .bind(message_id)
+        .execute(&mut *tx)
+        .await?;
+
+    if let Some(thread_id) = thread_id {
+        sqlx::query("UPDATE threads SET reply_count = GREATEST(reply_count - 1, 0) WHERE id = $1")
+            .bind(thread_id)
+            .execute(&mut *tx)
+            .await?;
+    }
+
+    tx.commit().await?;
+    Ok(deleted_at)
+}
+
+/// Soft-delete the newest channel messages matching a purge request in a
+/// single statement. Thread replies are left alone. Returns the ids deleted.
+pub async fn purge_channel_messages(
+    pool: &PgPool,
+    channel_id: Uuid,
+    filter: &PurgeMessagesRequest,
+    limit: i64,
+    deleted_by: Uuid,
+    deleted_at: DateTime<Utc>,
+) -> Result<Vec<Uuid>, AppError> {
+    let mut tx = pool.begin().await?;
+
+    let ids: Vec<Uuid> = sqlx::query_scalar(
+        "UPDATE messages SET deleted_at = $8, deleted_by = $9
+         WHERE id IN (
+             SELECT m.id FROM messages m
+             WHERE m.channel_id = $1 AND m.thread_id IS NULL AND m.deleted_at IS NULL
+               AND ($2::uuid IS NULL OR m.author_id = $2)
+               AND ($3::timestamptz IS NULL OR m.created_at < $3)
+               AND ($4::timestamptz IS NULL OR m.created_at > $4)
+               AND (NOT ($5 OR $6)
+                    OR ($5 AND m.content ~* 'https?://')
+                    OR ($6 AND EXISTS (SELECT 1 FROM attachments a WHERE a.message_id = m.id)))
+             ORDER BY m.created_at DESC
+             LIMIT $7
+         )
+         RETURNING id",
+    )
+    .bind(channel_id)
+    .bind(filter.author_id)
+    .bind(filter.before)
+    .bind(filter.after)
+    .bind(filter.has_link)
+    .bind(filter.has_attachment)
+    .bind(limit)
+    .bind(deleted_at)
+    .bind(deleted_by)
+    .fetch_all(&mut *tx)
+    .await?;
+
+    sqlx::query("DELETE FROM link_previews WHERE quoted_message_id = ANY($1)")
+        .bind(&ids)
+        .execute(&mut *tx)
+        .await?;
+
+    tx.commit().await?;
+    Ok(ids)
+}
+
+/// Undo an author's own deletion of a message deleted after `since`.
+pub async fn restore_message(
+    pool: &PgPool,
+    message_id: Uuid,
+    channel_id: Uuid,
+    author_id: Uuid,
+    since: DateTime<Utc>,
+) -> Result<(), AppError> {
+    let mut tx = pool.begin().await?;
+
+    let restored: Option<(Option<Uuid>,)> = sqlx::query_as(
+        "UPDATE messages SET deleted_at = NULL, deleted_by = NULL
+         WHERE id = $1 AND channel_id = $2 AND author_id = $3 AND deleted_by = $3 AND deleted_at > $4
+         RETURNING thread_id",
+    )
+    .bind(message_id)
+    .bind(channel_id)
+    .bind(author_id)
+    .bind(since)
+    .fetch_optional(&mut *tx)
+    .await?;
+
+    let Some((thread_id,)) = restored else {
+        return Err(AppError::not_found(
+            "Message not found or can no longer be restored",
+        ));
+    };
+
+    if let Some(thread_id) = thread_id {
+        sqlx::query("UPDATE threads SET reply_count = reply_count + 1 WHERE id = $1")
+            .bind(thread_id)
+            .execute(&mut *tx)
+            .await?;
+    }
+
+    tx.commit().await?;
+    Ok(())
+}
+
+/// Hard-delete messages soft-deleted before `cutoff`. Returns the number of
+/// messages removed and the storage paths of their attachments, including
+/// those of thread replies removed along with a purged parent.
+/// Hard-delete every message in the given channels. Returns the storage
+/// paths of their attachments that nothing else references, for the caller
+/// to remove once the transaction commits.
+pub async fn delete_channel_messages(
+    tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    channel_ids: &[Uuid],
+) -> Result<Vec<String>, AppError> {
+    let storage_paths: Vec<String> = sqlx::query_scalar(
+        "SELECT a.storage_path FROM attachments a
+         JOIN messages m ON m.id = a.message_id
+         WHERE m.channel_id = ANY($1)",
+    )
+    .bind(channel_ids)
+    .fetch_all(&mut **tx)
+    .await?;
+
+    sqlx::query("DELETE FROM messages WHERE channel_id = ANY($1)")
+        .bind(channel_ids)
+        .execute(&mut **tx)
+        .await?;
+
+    // Forwarded copies share stored files, so keep any still referenced
+    let storage_paths: Vec<String> = sqlx::query_scalar(
+        "SELECT DISTINCT path FROM UNNEST($1::text[]) AS path
+         WHERE NOT EXISTS (SELECT 1 FROM attachments a WHERE a.storage_path = path)",
+    )
+    .bind(&storage_paths)
+    .fetch_all(&mut **tx)
+    .await?;
+
+    Ok(storage_paths)
+}
+
+pub async fn purge_deleted_messages(
+    pool: &PgPool,
+    cutoff: DateTime<Utc>,
+) -> Result<(u64, Vec<String>), AppError> {
+    let mut tx = pool.begin().await?;
+
+    let storage_paths: Vec<String> = sqlx::query_scalar(
+        "SELECT a.storage_path FROM attachments a
+         JOIN messages m ON m.id = a.message_id
+         LEFT JOIN threads t ON t.id = m.thread_id
+         LEFT JOIN messages parent ON parent.id = t.parent_message_id
+         WHERE m.deleted_at < $1 OR parent.deleted_at < $1",
+    )
+    .bind(cutoff)
+    .fetch_all(&mut *tx)
+    .await?;
+
+    let result = sqlx::query("DELETE FROM messages WHERE deleted_at < $1")
+        .bind(cutoff)
+        .execute(&mut *tx)
+        .await?;
+
+    // Forwarded copies share stored files, so keep any still referenced
+    let storage_paths: Vec<String> = sqlx::query_scalar(
+        "SELECT DISTINCT path FROM UNNEST($1::text[]) AS path
+         WHERE NOT EXISTS (SELECT 1 FROM attachments a WHERE a.storage_path = path)",
+    )
+    .bind(&storage_paths)
+    .fetch_all(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+    Ok((result.rows_affected(), storage_paths))
+}
+
+// --- Forwards ---
+
+pub async fn create_message_forward(
+    pool: &PgPool,
+    message_id: Uuid,
+    source: &ForwardedFrom,
+) -> Result<(), AppError> {
+    sqlx::query(
+        "INSERT INTO message_forwards (message_id, source_message_id, source_channel_id, source_author_id, source_created_at)
+         VALUES ($1, $2, $3, $4, $5)",
+    )
+    .bind(message_id)
+    .bind(source.message_id)
+    .bind(source.channel_id)
+    .bind(source.author_id)
+    .bind(source.timestamp)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+pub async fn get_forwards_for_messages(
+    pool: &PgPool,
+    message_ids: &[Uuid],
+) -> Result<HashMap<Uuid, ForwardedFrom>, AppError> {
+    let rows: Vec<ForwardRow> = sqlx::query_as(
+        "SELECT f.message_id, f.source_message_id, f.source_channel_id, f.source_author_id,
+                u.username, u.display_name, f.source_created_at
+         FROM message_forwards f
+         LEFT JOIN users u ON u.id = f.source_author_id
+         WHERE f.message_id = ANY($1)",
+    )
+    .bind(message_ids)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows
+        .into_iter()
+        .map(|row| {
+            (
+                row.message_id,
+                ForwardedFrom {
+                    message_id: row.source_message_id,
+                    channel_id: row.source_channel_id,
+                    author_id: row.source_author_id,
+                    username: row.username,
+                    display_name: row.display_name,
+                    timestamp: row.source_created_at,
+                },
+            )
+        })
+        .collect())
+}
+
+// --- Webhooks ---
+
+pub async fn create_webhook(
+    pool: &PgPool,
+    webhook: &Webhook,
+    token_hash: &str,
+) -> Result<(), AppError> {
+    sqlx::query(
+        "INSERT INTO webhooks (id, channel_id, name, avatar_url, token_hash, created_by, created_at)
+         VALUES ($1, $2, $3, $4, $5, $6, $7)",
+    )
+    .bind(webhook.id)
+    .bind(webhook.channel_id)
+    .bind(&webhook.name)
+    .bind(&webhook.avatar_url)
+    .bind(token_hash)
+    .bind(webhook.created_by)
+    .bind(webhook.created_at)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+pub async fn get_channel_webhooks(
+    pool: &PgPool,
+    channel_id: Uuid,
+) -> Result<Vec<Webhook>, AppError> {
+    let webhooks: Vec<Webhook> = sqlx::query_as(&format!(
+        "SELECT {WEBHOOK_COLUMNS} FROM webhooks WHERE channel_id = $1 ORDER BY created_at"
+    ))
+    .bind(channel_id)
+    .fetch_all(pool)
+    .await?;
+    Ok(webhooks)
+}
+
+pub async fn count_channel_webhooks(pool: &PgPool, channel_id: Uuid) -> Result<i64, AppError> {
+    let count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM webhooks WHERE channel_id = $1")
+        .bind(channel_id)
+        .fetch_one(pool)
+        .await?;
+    Ok(count)
+}
+
+pub async fn get_webhook(pool: &PgPool, webhook_id: Uuid) -> Result<Option<Webhook>, AppError> {
+    let webhook: Option<Webhook> = sqlx::query_as(&format!(
+        "SELECT {WEBHOOK_COLUMNS} FROM webhooks WHERE id = $1"
+    ))
+    .bind(webhook_id)
+    .fetch_optional(pool)
+    .await?;
+    Ok(webhook)
+}
+
+/// Look up a webhook by id and the hash of its secret token.
+pub async fn get_webhook_by_token(
+    pool: &PgPool,
+    webhook_id: Uuid,
+    token_hash: &str,
+) -> Result<Option<Webhook>, AppError> {
+    let webhook: Option<Webhook> = sqlx::query_as(&format!(
+        "SELECT {WEBHOOK_COLUMNS} FROM webhooks WHERE id = $1 AND token_hash = $2"
+    ))
+    .bind(webhook_id)
+    .bind(token_hash)
+    .fetch_optional(pool)
+    .await?;
+    Ok(webhook)
+}
+
+pub async fn update_webhook_token(
+    pool: &PgPool,
+    webhook_id: Uuid,
+    token_hash: &str,
+) -> Result<(), AppError> {
+    let result = sqlx::query("UPDATE webhooks SET token_hash = $2 WHERE id = $1")
+        .bind(webhook_id)
+        .bind(token_hash)
+        .execute(pool)
+        .await?;
+    require_rows_affected(result, "Webhook not found")
+}
+
+pub async fn delete_webhook(pool: &PgPool, webhook_id: Uuid) -> Result<(), AppError> {
+    let result = sqlx::query("DELETE FROM webhooks WHERE id = $1")
+        .bind(webhook_id)
+        .execute(pool)
+        .await?;
+    require_rows_affected(result, "Webhook not found")
+}
+
+pub async fn create_webhook_message(
+    pool: &PgPool,
+    message_id: Uuid,
+    author: &WebhookAuthor,
+    embeds: &[Embed],
+) -> Result<(), AppError> {
+    sqlx::query(
+        "INSERT INTO webhook_messages (message_id, webhook_id, name, avatar_url, embeds)
+         VALUES ($1, $2, $3, $4, $5)",
+    )
+    .bind(message_id)
+    .bind(author.id)
+    .bind(&author.name)
+    .bind(&author.avatar_url)
+    .bind(sqlx::types::Json(embeds))
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+pub async fn is_webhook_message(pool: &PgPool, message_id: Uuid) -> Result<bool, AppError> {
+    let exists: bool =
+        sqlx::query_scalar("SELECT EXISTS (SELECT 1 FROM webhook_messages WHERE message_id = $1)")
+            .bind(message_id)
+            .fetch_one(pool)
+            .await?;
+    Ok(exists)
+}
+
+/// Webhook authors and embeds for the messages posted through webhooks.
+pub async fn get_webhook_messages(
+    pool: &PgPool,
+    message_ids: &[Uuid],
+) -> Result<HashMap<Uuid, (WebhookAuthor, Vec<Embed>)>, AppError> {
+    let rows: Vec<WebhookMessageRow> = sqlx::query_as(
+        "SELECT message_id, webhook_id, name, avatar_url, embeds
+         FROM webhook_messages WHERE message_id = ANY($1)",
+    )
+    .bind(message_ids)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows
+        .into_iter()
+        .map(|row| {
+            let author = WebhookAuthor {
+                id: row.webhook_id,
+                name: row.name,
+                avatar_url: row.avatar_url,
+            };
+            (row.message_id, (author, row.embeds))
+        })
+        .collect())
+}
+
+// --- Outgoing webhooks ---
+
+pub async fn create_outgoing_webhook(
+    pool: &PgPool,
+    webhook: &OutgoingWebhook,
+    secret: &str,
+) -> Result<(), AppError> {
+    sqlx::query(
+        "INSERT INTO outgoing_webhooks (id, name, url, secret, event_types, enabled, created_by, created_at)
+         VALUES ($1, $2, $3, $4, $5, $6, $7, $8)",
+    )
+    .bind(webhook.id)
+    .bind(&webhook.name)
+    .bind(&webhook.url)
+    .bind(secret)
+    .bind(&webhook.event_types)
+    .bind(webhook.enabled)
+    .bind(webhook.created_by)
+    .bind(webhook.created_at)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+pub async fn get_outgoing_webhooks(pool: &PgPool) -> Result<Vec<OutgoingWebhook>, AppError> {
+    let webhooks: Vec<OutgoingWebhook> = sqlx::query_as(&format!(
+        "SELECT {OUTGOING_WEBHOOK_COLUMNS} FROM outgoing_webhooks ORDER BY created_at"
+    ))
+    .fetch_all(pool)
+    .await?;
+    Ok(webhooks)
+}
+
+pub async fn count_outgoing_webhooks(pool: &PgPool) -> Result<i64, AppError> {
+    let count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM outgoing_webhooks")
+        .fetch_one(pool)
+        .await?;
+    Ok(count)
+}
+
+pub async fn get_outgoing_webhook(
+    pool: &PgPool,
+    webhook_id: Uuid,
+) -> Result<Option<OutgoingWebhook>, AppError> {
+    let webhook: Option<OutgoingWebhook> = sqlx::query_as(&format!(
+        "SELECT {OUTGOING_WEBHOOK_COLUMNS} FROM outgoing_webhooks WHERE id = $1"
+    ))
+    .bind(webhook_id)
+    .fetch_optional(pool)
+    .await?;
+    Ok(webhook)
+}
+
+pub async fn update_outgoing_webhook(
+    pool: &PgPool,
+    webhook: &OutgoingWebhook,
+) -> Result<(), AppError> {
+    let result = sqlx::query(
+        "UPDATE outgoing_webhooks SET name = $2, url = $3, event_types = $4, enabled = $5
+         WHERE id = $1",
+    )
+    .bind(webhook.id)
+    .bind(&webhook.name)
+    .bind(&webhook.url)
+    .bind(&webhook.event_types)
+    .bind(webhook.enabled)
+    .execute(pool)
+    .await?;
+    require_rows_affected(result, "Webhook not found")
+}
+
+pub async fn update_outgoing_webhook_secret(
+    pool: &PgPool,
+    webhook_id: Uuid,
+    secret: &str,
+) -> Result<(), AppError> {
+    let result = sqlx::query("UPDATE outgoing_webhooks SET secret = $2 WHERE id = $1")
+        .bind(webhook_id)
+        .bind(secret)
+        .execute(pool)
+        .await?;
+    require_rows_affected(result, "Webhook not found")
+}
+
+pub async fn delete_outgoing_webhook(pool: &PgPool, webhook_id: Uuid) -> Result<(), AppError> {
+    let result = sqlx::query("DELETE FROM outgoing_webhooks WHERE id = $1")
+        .bind(webhook_id)
+        .execute(pool)
+        .await?;
+    require_rows_affected(result, "Webhook not found")
+}
+
+/// Queue one delivery of `payload` to each webhook.
+pub async fn create_outgoing_webhook_deliveries(
+    pool: &PgPool,
+    webhook_ids: &[Uuid],
+    event_type: &str,
+    payload: &str,
+) -> Result<(), AppError> {
+    let ids: Vec<Uuid> = webhook_ids.iter().map(|_| Uuid::now_v7()).collect();
+    sqlx::query(
+        "INSERT INTO outgoing_webhook_deliveries (id, webhook_id, event_type, payload)
+         SELECT id, webhook_id, $3, $4 FROM UNNEST($1::uuid[], $2::uuid[]) AS t(id, webhook_id)",
+    )
+    .bind(&ids)
+    .bind(webhook_ids)
+    .bind(event_type)
+    .bind(payload)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// Claim pending deliveries that are due, skipping webhooks that were
+/// disabled since they were queued. Claims older than `stale_before` are
+/// assumed abandoned and taken over.
+pub async fn claim_due_deliveries(
+    pool: &PgPool,
+    now: DateTime<Utc>,
+    stale_before: DateTime<Utc>,
+    limit: i64,
+) -> Result<Vec<PendingDelivery>, AppError> {
+    let deliveries: Vec<PendingDelivery> = sqlx::query_as(
+        "UPDATE outgoing_webhook_deliveries d SET claimed_at = $1
+         FROM outgoing_webhooks w
+         WHERE w.id = d.webhook_id AND d.id IN (
+             SELECT d2.id FROM outgoing_webhook_deliveries d2
+             JOIN outgoing_webhooks w2 ON w2.id = d2.webhook_id
+             WHERE d2.status = 'pending' AND d2.next_attempt_at <= $1 AND w2.enabled
+               AND (d2.claimed_at IS NULL OR d2.claimed_at < $2)
+             ORDER BY d2.next_attempt_at
+             LIMIT $3
+             FOR UPDATE OF d2 SKIP LOCKED
+         )
+         RETURNING d.id, d.webhook_id, d.event_type, d.payload, d.attempts, w.url, w.secret",
+    )
+    .bind(now)
+    .bind(stale_before)
+    .bind(limit)
+    .fetch_all(pool)
+    .await?;
+    Ok(deliveries)
+}
+
+/// Record an attempt. `next_attempt_at` of `None` ends the delivery, as
+/// succeeded or failed depending on `succeeded`.
+pub async fn record_delivery_attempt(
+    pool: &PgPool,
+    delivery_id: Uuid,
+    succeeded: bool,
+    status_code: Option<i32>,
+    error: Option<&str>,
+    next_attempt_at: Option<DateTime<Utc>>,
+) -> Result<(), AppError> {
+    sqlx::query(
+        "UPDATE outgoing_webhook_deliveries
+         SET attempts = attempts + 1, status_code = $3, error = $4, claimed_at = NULL,
+             next_attempt_at = COALESCE($5, next_attempt_at),
+             status = CASE WHEN $2 THEN 'succeeded' WHEN $5 IS NULL THEN 'failed' ELSE 'pending' END,
+             completed_at = CASE WHEN $2 OR $5 IS NULL THEN NOW() END
+         WHERE id = $1",
+    )
+    .bind(delivery_id)
+    .bind(succeeded)
+    .bind(status_code)
+    .bind(error)
+    .bind(next_attempt_at)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// A webhook's delivery log, newest first, paged by `before`.
+pub async fn get_outgoing_webhook_deliveries(
+    pool: &PgPool,
+    webhook_id: Uuid,
+    before: Option<Uuid>,
+    limit: i64,
+) -> Result<Vec<OutgoingWebhookDelivery>, AppError> {
+    let deliveries: Vec<OutgoingWebhookDelivery> = sqlx::query_as(&format!(
+        "SELECT {DELIVERY_COLUMNS} FROM outgoing_webhook_deliveries
+         WHERE webhook_id = $1 AND ($2::uuid IS NULL OR id < $2)
+         ORDER BY id DESC
+         LIMIT $3"
+    ))
+    .bind(webhook_id)
+    .bind(before)
+    .bind(limit)
+    .fetch_all(pool)
+    .await?;
+    Ok(deliveries)
+}
+
+/// Put a finished delivery back in the queue for an immediate retry.
+pub async fn retry_outgoing_webhook_delivery(
+    pool: &PgPool,
+    webhook_id: Uuid,
+    delivery_id: Uuid,
+) -> Result<OutgoingWebhookDelivery, AppError> {
+    let delivery: Option<OutgoingWebhookDelivery> = sqlx::query_as(&format!(
+        "UPDATE outgoing_webhook_deliveries
+         SET status = 'pending', attempts = 0, next_attempt_at = NOW(), completed_at = NULL
+         WHERE id = $1 AND webhook_id = $2 AND status <> 'pending'
+         RETURNING {DELIVERY_COLUMNS}"
+    ))
+    .bind(delivery_id)
+    .bind(webhook_id)
+    .fetch_optional(pool)
+    .await?;
+    delivery.ok_or_else(|| AppError::not_found("Delivery not found or still pending"))
+}
+
+/// Drop finished deliveries created before `cutoff` from the log.
+pub async fn prune_outgoing_webhook_deliveries(
+    pool: &PgPool,
+    cutoff: DateTime<Utc>,
+) -> Result<u64, AppError> {
+    let result = sqlx::query(
+        "DELETE FROM outgoing_webhook_deliveries WHERE status <> 'pending' AND created_at < $1",
+    )
+    .bind(cutoff)
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected())
+}
+
+// --- Pins ---
+
+pub async fn get_pinned_messages(
+    pool: &PgPool,
+    channel_id: Uuid,
+    requesting_user_id: Uuid,
+) -> Result<Vec<Message>, AppError> {
+    let rows: Vec<MessageRow> = sqlx::query_as(
+        "SELECT m.id, m.content, u.username, u.display_name, m.author_id, m.channel_id, m.created_at, m.edited_at, m.reply_to_id, m.thread_id, m.mention_everyone, m.kind, m.pinned_at, m.pinned_by, m.deleted_at, m.deleted_by
+         FROM messages m
+         JOIN users u ON u.id = m.author_id
+         WHERE m.channel_id = $1 AND m.pinned_at IS NOT NULL AND m.deleted_at IS NULL
+         ORDER BY m.pinned_at DESC",
+    )
+    .bind(channel_id)
+    .fetch_all(pool)
+    .await?;
+
+    let mut messages: Vec<Message> = rows.into_iter().map(Message::from).collect();
+    enrich_messages(pool, &mut messages, requesting_user_id).await?;
+    Ok(messages)
+}
+
+/// Pin a message unless its channel already has `max_pins` pinned. Returns
+/// when the message was pinned, or None if the channel is full.
+pub async fn pin_message(
+    pool: &PgPool,
+    channel_id: Uuid,
+    message_id: Uuid,
+    pinned_by: Uuid,
+    max_pins: i64,
+) -> Result<Option<DateTime<Utc>>, AppError> {
+    let mut tx = pool.begin().await?;
+
+    // Serialize pins per channel so concurrent requests cannot overshoot the
+    // cap. NO KEY UPDATE does not block messages being posted meanwhile.
+    sqlx::query("SELECT 1 FROM channels WHERE id = $1 FOR NO KEY UPDATE")
+        .bind(channel_id)
+        .execute(&mut *tx)
+        .await?;
+
+    let pinned_at = Utc::now();
+    let result = sqlx::query(
+        "UPDATE messages SET pinned_at = $1, pinned_by = $2
+         WHERE id = $3 AND pinned_at IS NULL
+           AND (SELECT COUNT(*) FROM messages
+                WHERE channel_id = $4 AND pinned_at IS NOT NULL AND deleted_at IS NULL) < $5",
+    )
+    .bind(pinned_at)
+    .bind(pinned_by)
+    .bind(message_id)
+    .bind(channel_id)
+    .bind(max_pins)
+    .execute(&mut *tx)
+    .await?;
+
+    if result.rows_affected() == 0 {
+        let already_pinned: bool = sqlx::query_scalar(
+            "SELECT EXISTS(SELECT 1 FROM messages WHERE id = $1 AND pinned_at IS NOT NULL)",
+        )
+        .bind(message_id)
+        .fetch_one(&mut *tx)
+        .await?;
+        if already_pinned {
+            return Err(AppError::conflict("Message is already pinned"));
+        }
+        return Ok(None);
+    }
+
+    tx.commit().await?;
+    Ok(Some(pinned_at))
+}
+
+pub async fn unpin_message(pool: &PgPool, message_id: Uuid) -> Result<(), AppError> {
+    let result = sqlx::query(
+        "UPDATE messages SET pinned_at = NULL, pinned_by = NULL
+         WHERE id = $1 AND pinned_at IS NOT NULL",
+    )
+    .bind(message_id)
+    .execute(pool)
+    .await?;
+
+    require_rows_affected(result, "Message is not pinned")
+}
+
+pub async fn get_reply_previews(
+    pool: &PgPool,
+    reply_ids: &[Uuid],
+) -> Result<HashMap<Uuid, ReplyPreview>, AppError> {
+    let rows: Vec<ReplyPreviewRow> = sqlx::query_as(
+        "SELECT m.id, u.username, u.display_name, m.content
+         FROM messages m
+         JOIN users u ON u.id = m.author_id
+         WHERE m.id = ANY($1) AND m.deleted_at IS NULL",
+    )
+    .bind(reply_ids)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows
+        .into_iter()
+        .map(|row| {
+            (
+                row.id,
+                ReplyPreview {
+                    id: row.id,
+                    username: row.username,
+                    display_name: row.display_name,
+                    content: truncate_string(&row.content, REPLY_PREVIEW_LENGTH),
+                },
+            )
+        })
+        .collect())
+}
+
+pub async fn get_reply_preview(
+    pool: &PgPool,
+    message_id: Uuid,
+) -> Result<Option<ReplyPreview>, AppError> {
+    let row: Option<ReplyPreviewRow> = sqlx::query_as(
+        "SELECT m.id, u.username, u.display_name, m.content
+         FROM messages m
+         JOIN users u ON u.id = m.author_id
+         WHERE m.id = $1 AND m.deleted_at IS NULL",
+    )
+    .bind(message_id)
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(row.map(|r| ReplyPreview {
+        id: r.id,
+        username: r.username,
+        display_name: r.display_name,
+        content: truncate_string(&r.content, REPLY_PREVIEW_LENGTH),
+    }))
+}
+
+pub async fn get_reactions_for_messages(
+    pool: &PgPool,
+    message_ids: &[Uuid],
+    requesting_user_id: Uuid,
+) -> Result<HashMap<Uuid, Vec<Reaction>>, AppError> {
+    let rows: Vec<(Uuid, String, i64, bool, Vec<String>)> = sqlx::query_as(
+        "SELECT r.message_id, r.emoji, COUNT(*) as count,
+                BOOL_OR(r.user_id = $2) as reacted,
+                ARRAY_AGG(u.username ORDER BY r.created_at) as users
+         FROM reactions r
+         JOIN users u ON u.id = r.user_id
+         WHERE r.message_id = ANY($1)
+         GROUP BY r.message_id, r.emoji
+         ORDER BY MIN(r.created_at)",
+    )
+    .bind(message_ids)
+    .bind(requesting_user_id)
+    .fetch_all(pool)
+    .await?;
+
+    let mut reactions_map: HashMap<Uuid, Vec<Reaction>> = HashMap::new();
+    for (message_id, emoji, count, reacted, users) in rows {
+        reactions_map.entry(message_id).or_default().push(Reaction {
+            emoji,
+            count,
+            reacted,
+            users,
+        });
+    }
+
+    Ok(reactions_map)
+}
+
+pub async fn add_reaction(
+    pool: &PgPool,
+    message_id: Uuid,
+    user_id: Uuid,
+    emoji: &str,
+) -> Result<(), AppError> {
+    sqlx::query(
+        "INSERT INTO reactions (message_id, user_id, emoji) VALUES ($1, $2, $3)
+         ON CONFLICT DO NOTHING",
+    )
+    .bind(message_id)
+    .bind(user_id)
+    .bind(emoji)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn remove_reaction(
+    pool: &PgPool,
+    message_id: Uuid,
+    user_id: Uuid,
+    emoji: &str,
+) -> Result<(), AppError> {
+    sqlx::query("DELETE FROM reactions WHERE message_id = $1 AND user_id = $2 AND emoji = $3")
+        .bind(message_id)
+        .bind(user_id)
+        .bind(emoji)
+        .execute(pool)
+        .await?;
+
+    Ok(())
+}
+
+// --- Polls ---
+
+#[derive(FromRow)]
+struct PollRow {
+    message_id: Uuid,
+    question: String,
+    allow_multiple: bool,
+    anonymous: bool,
+    expires_at: Option<DateTime<Utc>>,
+    closed_at: Option<DateTime<Utc>>,
+    total_voters: i64,
+}
+
+#[derive(FromRow)]
+struct PollOptionRow {
+    message_id: Uuid,
+    id: Uuid,
+    text: String,
+    votes: i64,
+    voted: bool,
+    voters: Vec<Uuid>,
+}
+
+pub async fn create_poll(pool: &PgPool, poll: &Poll) -> Result<(), AppError> {
+    let mut tx = pool.begin().await?;
+
+    sqlx::query(
+        "INSERT INTO polls (message_id, question, allow_multiple, anonymous, expires_at)
+         VALUES ($1, $2, $3, $4, $5)",
+    )
+    .bind(poll.message_id)
+    .bind(&poll.question)
+    .bind(poll.allow_multiple)
+    .bind(poll.anonymous)
+    .bind(poll.expires_at)
+    .execute(&mut *tx)
+    .await?;
+
+    let ids: Vec<Uuid> = poll.options.iter().map(|o| o.id).collect();
+    let texts: Vec<&str> = poll.options.iter().map(|o| o.text.as_str()).collect();
+    sqlx::query(
+        "INSERT INTO poll_options (id, message_id, position, text)
+         SELECT id, $2, (ord - 1)::int, text
+         FROM UNNEST($1::uuid[], $3::text[]) WITH ORDINALITY AS o(id, text, ord)",
+    )
+    .bind(&ids)
+    .bind(poll.message_id)
+    .bind(&texts)
+    .execute(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+    Ok(())
+}
+
+/// Polls keyed by message id. Closed polls report their final snapshot.
+pub async fn get_polls_for_messages(
+    pool: &PgPool,
+    message_ids: &[Uuid],
+    requesting_user_id: Uuid,
+) -> Result<HashMap<Uuid, Poll>, AppError> {
+    let polls: Vec<PollRow> = sqlx::query_as(
+        "SELECT p.message_id, p.question, p.allow_multiple, p.anonymous, p.expires_at, p.closed_at,
+                COALESCE(p.final_voters::bigint, (
+                    SELECT COUNT(DISTINCT v.user_id) FROM poll_votes v WHERE v.message_id = p.message_id
+                )) AS total_voters
+         FROM polls p
+         WHERE p.message_id = ANY($1)",
+    )
+    .bind(message_ids)
+    .fetch_all(pool)
+    .await?;
+    if polls.is_empty() {
+        return Ok(HashMap::new());
+    }
+
+    let options: Vec<PollOptionRow> = sqlx::query_as(
+        "SELECT o.message_id, o.id, o.text,
+                COALESCE(o.final_votes::bigint, COUNT(v.user_id)) AS votes,
+                COALESCE(BOOL_OR(v.user_id = $2), FALSE) AS voted,
+                COALESCE(ARRAY_AGG(v.user_id ORDER BY v.created_at)
+                    FILTER (WHERE v.user_id IS NOT NULL), '{}') AS voters
+         FROM poll_options o
+         LEFT JOIN poll_votes v ON v.option_id = o.id
+         WHERE o.message_id = ANY($1)
+         GROUP BY o.message_id, o.id
+         ORDER BY o.message_id, o.position",
+    )
+    .bind(message_ids)
+    .bind(requesting_user_id)
+    .fetch_all(pool)
+    .await?;
+
+    let mut map: HashMap<Uuid, Poll> = polls
+        .into_iter()
+        .map(|row| {
+            (
+                row.message_id,
+                Poll {
+                    message_id: row.message_id,
+                    question: row.question,
+                    options: Vec::new(),
+                    allow_multiple: row.allow_multiple,
+                    anonymous: row.anonymous,
+                    expires_at: row.expires_at,
+                    closed_at: row.closed_at,
+                    total_voters: row.total_voters,
+                },
+            )
+        })
+        .collect();
+    for row in options {
+        if let Some(poll) = map.get_mut(&row.message_id) {
+            poll.options.push(PollOption {
+                id: row.id,
+                text: row.text,
+                votes: row.votes,
+                voted: row.voted,
+                voters: (!poll.anonymous).then_some(row.voters),
+            });
+        }
+    }
+    Ok(map)
+}
+
+pub async fn get_poll(
+    pool: &PgPool,
+    message_id: Uuid,
+    requesting_user_id: Uuid,
+) -> Result<Option<Poll>, AppError> {
+    let mut polls = get_polls_for_messages(pool, &[message_id], requesting_user_id).await?;
+    Ok(polls.remove(&message_id))
+}
+
+/// Record a vote. On single-choice polls it replaces the user's other vote.
+pub async fn add_poll_vote(
+    pool: &PgPool,
+    message_id: Uuid,
+    option_id: Uuid,
+    user_id: Uuid,
+    allow_multiple: bool,
+) -> Result<(), AppError> {
+    let mut tx = pool.begin().await?;
+
+    if !allow_multiple {
+        sqlx::query(
+            "DELETE FROM poll_votes WHERE message_id = $1 AND user_id = $2 AND option_id <> $3",
+        )
+        .bind(message_id)
+        .bind(user_id)
+        .bind(option_id)
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    let result = sqlx::query(
+        "INSERT INTO poll_votes (option_id, message_id, user_id)
+         SELECT id, message_id, $3 FROM poll_options WHERE id = $1 AND message_id = $2
+         ON CONFLICT DO NOTHING",
+    )
+    .bind(option_id)
+    .bind(message_id)
+    .bind(user_id)
+    .execute(&mut *tx)
+    .await?;
+
+    if result.rows_affected() == 0 {
+        let exists: bool = sqlx::query_scalar(
+            "SELECT EXISTS (SELECT 1 FROM poll_options WHERE id = $1 AND message_id = $2)",
+        )
+        .bind(option_id)
+        .bind(message_id)
+        .fetch_one(&mut *tx)
+        .await?;
+        if !exists {
+            return Err(AppError::not_found("Poll option not found"));
+        }
+    }
+
+    tx.commit().await?;
+    Ok(())
+}
+
+pub async fn remove_poll_vote(
+    pool: &PgPool,
+    message_id: Uuid,
+    option_id: Uuid,
+    user_id: Uuid,
+) -> Result<(), AppError> {
+    let result = sqlx::query(
+        "DELETE FROM poll_votes WHERE message_id = $1 AND option_id = $2 AND user_id = $3",
+    )
+    .bind(message_id)
+    .bind(option_id)
+    .bind(user_id)
+    .execute(pool)
+    .await?;
+
+    require_rows_affected(result, "Vote not found")
+}
+
+/// Close polls whose expiry has passed and freeze their results. Returns
+/// the message ids of the polls closed.
+pub async fn close_expired_polls(pool: &PgPool, now: DateTime<Utc>) -> Result<Vec<Uuid>, AppError> {
+    let mut tx = pool.begin().await?;
+
+    let closed: Vec<Uuid> = sqlx::query_scalar(
+        "UPDATE polls p SET closed_at = $1, final_voters = (
+             SELECT COUNT(DISTINCT v.user_id) FROM poll_votes v WHERE v.message_id = p.message_id
+         )
+         WHERE p.closed_at IS NULL AND p.expires_at <= $1
+         RETURNING p.message_id",
+    )
+    .bind(now)
+    .fetch_all(&mut *tx)
+    .await?;
+
+    if !closed.is_empty() {
+        sqlx::query(
+            "UPDATE poll_options o SET final_votes = (
+                 SELECT COUNT(*) FROM poll_votes v WHERE v.option_id = o.id
+             )
+             WHERE o.message_id = ANY($1)",
+        )
+        .bind(&closed)
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    tx.commit().await?;
+    Ok(closed)
+}
+
+// --- Scheduled messages ---
+
+pub async fn create_scheduled_message(
+    pool: &PgPool,
+    scheduled: &ScheduledMessage,
+) -> Result<(), AppError> {
+    sqlx::query(
+        "INSERT INTO scheduled_messages (id, author_id, channel_id, content, reply_to_id, attachment_ids, poll, send_at, created_at)
+         VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)",
+    )
+    .bind(scheduled.id)
+    .bind(scheduled.author_id)
+    .bind(scheduled.channel_id)
+    .bind(&scheduled.content)
+    .bind(scheduled.reply_to_id)
+    .bind(&scheduled.attachment_ids)
+    .bind(scheduled.poll.as_ref().map(sqlx::types::Json))
+    .bind(scheduled.send_at)
+    .bind(scheduled.created_at)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+pub async fn get_scheduled_messages(
+    pool: &PgPool,
+    author_id: Uuid,
+) -> Result<Vec<ScheduledMessage>, AppError> {
+    let scheduled: Vec<ScheduledMessage> = sqlx::query_as(&format!(
+        "SELECT {SCHEDULED_MESSAGE_COLUMNS} FROM scheduled_messages
+         WHERE author_id = $1
+         ORDER BY send_at"
+    ))
+    .bind(author_id)
+    .fetch_all(pool)
+    .await?;
+    Ok(scheduled)
+}
+
+pub async fn get_scheduled_message(
+    pool: &PgPool,
+    id: Uuid,
+) -> Result<Option<ScheduledMessage>, AppError> {
+    let scheduled: Option<ScheduledMessage> = sqlx::query_as(&format!(
+        "SELECT {SCHEDULED_MESSAGE_COLUMNS} FROM scheduled_messages WHERE id = $1"
+    ))
+    .bind(id)
+    .fetch_optional(pool)
+    .await?;
+    Ok(scheduled)
+}
+
+pub async fn count_scheduled_messages(pool: &PgPool, author_id: Uuid) -> Result<i64, AppError> {
+    let count: i64 =
+        sqlx::query_scalar("SELECT COUNT(*) FROM scheduled_messages WHERE author_id = $1")
+            .bind(author_id)
+            .fetch_one(pool)
+            .await?;
+    Ok(count)
+}
+
+/// Update a scheduled message that no node has started sending. Changing
+/// `send_at` clears any earlier delivery failure.
+pub async fn update_scheduled_message(
+    pool: &PgPool,
+    id: Uuid,
+    content: Option<&str>,
+    send_at: DateTime<Utc>,
+    clear_failure: bool,
+) -> Result<ScheduledMessage, AppError> {
+    let scheduled: Option<ScheduledMessage> = sqlx::query_as(&format!(
+        "UPDATE scheduled_messages
+         SET content = $2, send_at = $3,
+             failure = CASE WHEN $4 THEN NULL ELSE failure END
+         WHERE id = $1 AND claimed_at IS NULL
+         RETURNING {SCHEDULED_MESSAGE_COLUMNS}"
+    ))
+    .bind(id)
+    .bind(content)
+    .bind(send_at)
+    .bind(clear_failure)
+    .fetch_optional(pool)
+    .await?;
+
+    scheduled.ok_or_else(|| AppError::conflict("Message is already being sent"))
+}
+
+pub async fn delete_scheduled_message(pool: &PgPool, id: Uuid) -> Result<(), AppError> {
+    let result = sqlx::query("DELETE FROM scheduled_messages WHERE id = $1 AND claimed_at IS NULL")
+        .bind(id)
+        .execute(pool)
+        .await?;
+
+    if result.rows_affected() == 0 {
+        return Err(AppError::conflict("Message is already being sent"));
+    }
+    Ok(())
+}
+
+/// Claim up to `limit` due messages for delivery. Claims older than
+/// `stale_before` belong to a node that stopped mid-delivery and are taken
+/// over, so queued messages survive restarts.
+pub async fn claim_due_scheduled_messages(
+    pool: &PgPool,
+    now: DateTime<Utc>,
+    stale_before: DateTime<Utc>,
+    limit: i64,
+) -> Result<Vec<ScheduledMessage>, AppError> {
+    let scheduled: Vec<ScheduledMessage> = sqlx::query_as(&format!(
+        "UPDATE scheduled_messages SET claimed_at = $1
+         WHERE id IN (
+             SELECT id FROM scheduled_messages
+             WHERE send_at <= $1 AND failure IS NULL
+               AND (claimed_at IS NULL OR claimed_at < $2)
+             ORDER BY send_at
+             LIMIT $3
+             FOR UPDATE SKIP LOCKED
+         )
+         RETURNING {SCHEDULED_MESSAGE_COLUMNS}"
+    ))
+    .bind(now)
+    .bind(stale_before)
+    .bind(limit)
+    .fetch_all(pool)
+    .await?;
+    Ok(scheduled)
+}
+
+/// Remove a claimed message once it has been posted.
+pub async fn complete_scheduled_message(pool: &PgPool, id: Uuid) -> Result<(), AppError> {
+    sqlx::query("DELETE FROM scheduled_messages WHERE id = $1")
+        .bind(id)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+/// Release a claim so the message is retried on the next pass, or park it
+/// with a failure reason when retrying would not help.
+pub async fn release_scheduled_message(
+    pool: &PgPool,
+    id: Uuid,
+    failure: Option<&str>,
+) -> Result<Option<ScheduledMessage>, AppError> {
+    let scheduled: Option<ScheduledMessage> = sqlx::query_as(&format!(
+        "UPDATE scheduled_messages SET claimed_at = NULL, failure = $2
+         WHERE id = $1
+         RETURNING {SCHEDULED_MESSAGE_COLUMNS}"
+    ))
+    .bind(id)
+    .bind(failure)
+    .fetch_optional(pool)
+    .await?;
+    Ok(scheduled)
+}
+
+// --- Threads ---
+
+pub async fn create_thread(pool: &PgPool, thread: &Thread) -> Result<(), AppError> {
+    sqlx::query(
+        "INSERT INTO threads (id, channel_id, parent_message_id, name, created_by, reply_count, last_activity_at, archived, created_at)
+         VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)",
+    )
+    .bind(thread.id)
+    .bind(thread.channel_id)
+    .bind(thread.parent_message_id)
+    .bind(&thread.name)
+    .bind(thread.created_by)
+    .bind(thread.reply_count)
+    .bind(thread.last_activity_at)
+    .bind(thread.archived)
+    .bind(thread.created_at)
+    .execute(pool)
+    .await
+    .map_err(|e| match &e {
+        sqlx::Error::Database(db_err) if db_err.is_unique_violation() => {
+            AppError::conflict("A thread already exists for this message")
+        }
+        _ => AppError::from(e),
+    })?;
+
+    Ok(())
+}
+
+pub async fn get_thread_by_id(pool: &PgPool, thread_id: Uuid) -> Result<Option<Thread>, AppError> {
+    let thread: Option<Thread> = sqlx::query_as(
+        "SELECT id, channel_id, parent_message_id, name, created_by, reply_count, last_activity_at, archived, created_at
+         FROM threads WHERE id = $1",
+    )
+    .bind(thread_id)
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(thread)
+}
+
+pub async fn get_channel_threads(
+    pool: &PgPool,
+    channel_id: Uuid,
+    archived: bool,
+) -> Result<Vec<Thread>, AppError> {
+    let threads: Vec<Thread> = sqlx::query_as(
+        "SELECT id, channel_id, parent_message_id, name, created_by, reply_count, last_activity_at, archived, created_at
+         FROM threads WHERE channel_id = $1 AND archived = $2
+         ORDER BY last_activity_at DESC",
+    )
+    .bind(channel_id)
+    .bind(archived)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(threads)
+}
+
+/// Threads keyed by their parent message id.
+pub async fn get_threads_for_messages(
+    pool: &PgPool,
+    message_ids: &[Uuid],
+) -> Result<HashMap<Uuid, Thread>, AppError> {
+    let threads: Vec<Thread> = sqlx::query_as(
+        "SELECT id, channel_id, parent_message_id, name, created_by, reply_count, last_activity_at, archived, created_at
+         FROM threads WHERE parent_message_id = ANY($1)",
+    )
+    .bind(message_ids)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(threads
+        .into_iter()
+        .map(|t| (t.parent_message_id, t))
+        .collect())
+}
+
+pub async fn get_thread_messages(
+    pool: &PgPool,
+    thread_id: Uuid,
+    limit: i64,
+    cursor: MessageCursor,
+    requesting_user_id: Uuid,
+    include_deleted: bool,
+) -> Result<MessagePage, AppError> {
+    get_timeline_page(
+        pool,
+        Timeline::Thread(thread_id),
+        limit,
+        cursor,
+        requesting_user_id,
+        include_deleted,
+    )
+    .await
+}
+
+/// Bump the reply count and activity time for a new reply. Posting into an
+/// archived thread unarchives it.
+pub async fn record_thread_reply(
+    pool: &PgPool,
+    thread_id: Uuid,
+    at: DateTime<Utc>,
+) -> Result<Thread, AppError> {
+    let thread: Thread = sqlx::query_as(
+        "UPDATE threads SET reply_count = reply_count + 1, last_activity_at = $2, archived = FALSE
+         WHERE id = $1
+         RETURNING id, channel_id, parent_message_id, name, created_by, reply_count, last_activity_at, archived, created_at",
+    )
+    .bind(thread_id)
+    .bind(at)
+    .fetch_one(pool)
+    .await?;
+
+    Ok(thread)
+}
+
+/// Archive every active thread with no activity since `inactive_since`.
+pub async fn archive_inactive_threads(
+    pool: &PgPool,
+    inactive_since: DateTime<Utc>,
+) -> Result<Vec<Thread>, AppError> {
+    let threads: Vec<Thread> = sqlx::query_as(
+        "UPDATE threads SET archived = TRUE
+         WHERE NOT archived AND last_activity_at < $1
+         RETURNING id, channel_id, parent_message_id, name, created_by, reply_count, last_activity_at, archived, created_at",
+    )
+    .bind(inactive_since)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(threads)
+}
+
+// --- Mentions ---
+
+/// Resolve usernames (case-insensitive) to user ids.
+pub async fn get_user_ids_by_usernames(
+    pool: &PgPool,
+    usernames: &[String],
+) -> Result<Vec<(Uuid, String)>, AppError> {
+    let lowered: Vec<String> = usernames.iter().map(|u| u.to_lowercase()).collect();
+    let rows: Vec<(Uuid, String)> =
+        sqlx::query_as("SELECT id, LOWER(username) FROM users WHERE LOWER(username) = ANY($1)")
+            .bind(&lowered)
+            .fetch_all(pool)
+            .await?;
+
+    Ok(rows)
+}
+
+/// Holders of the non-default roles whose lowercased names are in `names`.
+pub async fn get_user_ids_by_role_names(
     pool: &PgPool,
-    reply_ids: &[Uuid],
-) -> Result<HashMap<Uuid, ReplyPreview>, AppError> {
-    let rows: Vec<ReplyPreviewRow> = sqlx::query_as(
-        "SELECT m.id, u.username, u.display_name, m.content
-         FROM messages m
-         JOIN users u ON u.id = m.author_id
-         WHERE m.id = ANY($1)",
+    names: &[String],
+) -> Result<Vec<Uuid>, AppError> {
+    let rows: Vec<(Uuid,)> = sqlx::query_as(
+        "SELECT DISTINCT ur.user_id FROM user_roles ur
+         JOIN roles r ON r.id = ur.role_id
+         WHERE LOWER(r.name) = ANY($1)",
     )
-    .bind(reply_ids)
+    .bind(names)
     .fetch_all(pool)
     .await?;
 
-    Ok(rows
-        .into_iter()
-        .map(|row| {
-            (
-                row.id,
-                ReplyPreview {
-                    id: row.id,
-                    username: row.username,
-                    display_name: row.display_name,
-                    content: truncate_string(&row.content, REPLY_PREVIEW_LENGTH),
-                },
-            )
-        })
-        .collect())
+    Ok(rows.into_iter().map(|(id,)| id).collect())
 }
 
-pub async fn get_reply_preview(
+/// Replace the mentions of a message. Returns the user ids that were not
+/// mentioned before and whether @everyone was newly added, so edits only
+/// notify new mentions.
+pub async fn set_message_mentions(
     pool: &PgPool,
     message_id: Uuid,
-) -> Result<Option<ReplyPreview>, AppError> {
-    let row: Option<ReplyPreviewRow> = sqlx::query_as(
-        "SELECT m.id, u.username, u.display_name, m.content
-         FROM messages m
-         JOIN users u ON u.id = m.author_id
-         WHERE m.id = $1",
+    user_ids: &[Uuid],
+    mention_everyone: bool,
+) -> Result<(Vec<Uuid>, bool), AppError> {
+    let mut tx = pool.begin().await?;
+
+    sqlx::query("DELETE FROM message_mentions WHERE message_id = $1 AND NOT (user_id = ANY($2))")
+        .bind(message_id)
+        .bind(user_ids)
+        .execute(&mut *tx)
+        .await?;
+
+    let added: Vec<(Uuid,)> = sqlx::query_as(
+        "INSERT INTO message_mentions (message_id, user_id)
+         SELECT $1, unnest($2::uuid[])
+         ON CONFLICT DO NOTHING
+         RETURNING user_id",
     )
     .bind(message_id)
-    .fetch_optional(pool)
+    .bind(user_ids)
+    .fetch_all(&mut *tx)
     .await?;
 
-    Ok(row.map(|r| ReplyPreview {
-        id: r.id,
-        username: r.username,
-        display_name: r.display_name,
-        content: truncate_string(&r.content, REPLY_PREVIEW_LENGTH),
-    }))
+    let (was_everyone,): (bool,) = sqlx::query_as(
+        "UPDATE messages m SET mention_everyone = $2
+         FROM (SELECT mention_everyone FROM messages WHERE id = $1) old
+         WHERE m.id = $1
+         RETURNING old.mention_everyone",
+    )
+    .bind(message_id)
+    .bind(mention_everyone)
+    .fetch_one(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+    Ok((
+        added.into_iter().map(|(id,)| id).collect(),
+        mention_everyone && !was_everyone,
+    ))
 }
 
-pub async fn get_reactions_for_messages(
+pub async fn get_mentions_for_messages(
     pool: &PgPool,
     message_ids: &[Uuid],
-    requesting_user_id: Uuid,
-) -> Result<HashMap<Uuid, Vec<Reaction>>, AppError> {
-    let rows: Vec<(Uuid, String, i64, bool, Vec<String>)> = sqlx::query_as(
-        "SELECT r.message_id, r.emoji, COUNT(*) as count,
-                BOOL_OR(r.user_id = $2) as reacted,
-                ARRAY_AGG(u.username ORDER BY r.created_at) as users
-         FROM reactions r
-         JOIN users u ON u.id = r.user_id
-         WHERE r.message_id = ANY($1)
-         GROUP BY r.message_id, r.emoji
-         ORDER BY MIN(r.created_at)",
+) -> Result<HashMap<Uuid, Vec<Uuid>>, AppError> {
+    let rows: Vec<(Uuid, Uuid)> = sqlx::query_as(
+        "SELECT message_id, user_id FROM message_mentions WHERE message_id = ANY($1)",
     )
     .bind(message_ids)
-    .bind(requesting_user_id)
     .fetch_all(pool)
     .await?;
 
-    let mut reactions_map: HashMap<Uuid, Vec<Reaction>> = HashMap::new();
-    for (message_id, emoji, count, reacted, users) in rows {
-        reactions_map.entry(message_id).or_default().push(Reaction {
-            emoji,
-            count,
-            reacted,
-            users,
-        });
+    let mut map: HashMap<Uuid, Vec<Uuid>> = HashMap::new();
+    for (message_id, user_id) in rows {
+        map.entry(message_id).or_default().push(user_id);
     }
-
-    Ok(reactions_map)
+    Ok(map)
 }
 
-pub async fn add_reaction(
+// --- Read states ---
+
+/// Read position and unread/mention counts for every channel the user can
+/// see. Thread replies and the user's own messages do not count as unread.
+pub async fn get_read_states(
     pool: &PgPool,
-    message_id: Uuid,
     user_id: Uuid,
-    emoji: &str,
-) -> Result<(), AppError> {
-    sqlx::query(
-        "INSERT INTO reactions (message_id, user_id, emoji) VALUES ($1, $2, $3)
-         ON CONFLICT DO NOTHING",
+) -> Result<Vec<ChannelReadState>, AppError> {
+    let states: Vec<ChannelReadState> = sqlx::query_as(
+        "SELECT c.id AS channel_id, rs.last_read_message_id,
+                (SELECT COUNT(*) FROM (
+                    SELECT 1 FROM messages m
+                    WHERE m.channel_id = c.id AND m.thread_id IS NULL AND m.author_id <> $1
+                      AND m.deleted_at IS NULL
+                      AND (rs.last_read_at IS NULL OR m.created_at > rs.last_read_at)
+                    LIMIT $2
+                 ) unread) AS unread_count,
+                (SELECT COUNT(*) FROM (
+                    SELECT 1 FROM messages m
+                    WHERE m.channel_id = c.id AND m.thread_id IS NULL AND m.author_id <> $1
+                      AND m.deleted_at IS NULL
+                      AND (rs.last_read_at IS NULL OR m.created_at > rs.last_read_at)
+                      AND (m.mention_everyone OR EXISTS (
+                           SELECT 1 FROM message_mentions mm WHERE mm.message_id = m.id AND mm.user_id = $1
+                      ))
+                    LIMIT $2
+                 ) mentioned) AS mention_count
+         FROM channels c
+         LEFT JOIN channel_read_states rs ON rs.channel_id = c.id AND rs.user_id = $1
+         WHERE c.channel_type IN ('text', 'voice') OR EXISTS (
+             SELECT 1 FROM channel_members cm WHERE cm.channel_id = c.id AND cm.user_id = $1
+         )",
     )
-    .bind(message_id)
     .bind(user_id)
-    .bind(emoji)
-    .execute(pool)
+    .bind(MAX_UNREAD_COUNT)
+    .fetch_all(pool)
     .await?;
 
-    Ok(())
+    Ok(states)
 }
 
-pub async fn remove_reaction(
+/// Move the user's read marker forward to the given message. Acks for
+/// messages older than the current marker are ignored.
+pub async fn ack_channel(
     pool: &PgPool,
-    message_id: Uuid,
     user_id: Uuid,
-    emoji: &str,
+    channel_id: Uuid,
+    message_id: Uuid,
+    message_created_at: DateTime<Utc>,
 ) -> Result<(), AppError> {
-    sqlx::query("DELETE FROM reactions WHERE message_id = $1 AND user_id = $2 AND emoji = $3")
-        .bind(message_id)
-        .bind(user_id)
-        .bind(emoji)
-        .execute(pool)
-        .await?;
+    sqlx::query(
+        "INSERT INTO channel_read_states (user_id, channel_id, last_read_message_id, last_read_at, updated_at)
+         VALUES ($1, $2, $3, $4, NOW())
+         ON CONFLICT (user_id, channel_id) DO UPDATE
+         SET last_read_message_id = EXCLUDED.last_read_message_id,
+             last_read_at = EXCLUDED.last_read_at,
+             updated_at = NOW()
+         WHERE channel_read_states.last_read_at < EXCLUDED.last_read_at",
+    )
+    .bind(user_id)
+    .bind(channel_id)
+    .bind(message_id)
+    .bind(message_created_at)
+    .execute(pool)
+    .await?;
 
     Ok(())
 }
@@ -543,13 +2895,14 @@ pub async fn remove_reaction(
 pub async fn upsert_link_preview(pool: &PgPool, data: &LinkPreviewData) -> Result<Uuid, AppError> {
     let id = Uuid::now_v7();
     let row: (Uuid,) = sqlx::query_as(
-        "INSERT INTO link_previews (id, url, title, description, image_url, site_name)
-         VALUES ($1, $2, $3, $4, $5, $6)
+        "INSERT INTO link_previews (id, url, title, description, image_url, site_name, quoted_message_id)
+         VALUES ($1, $2, $3, $4, $5, $6, $7)
          ON CONFLICT (url) DO UPDATE SET
            title = EXCLUDED.title,
            description = EXCLUDED.description,
            image_url = EXCLUDED.image_url,
            site_name = EXCLUDED.site_name,
+           quoted_message_id = EXCLUDED.quoted_message_id,
            fetched_at = NOW()
          RETURNING id",
     )
@@ -559,12 +2912,21 @@ pub async fn upsert_link_preview(pool: &PgPool, data: &LinkPreviewData) -> Resul
     .bind(&data.description)
     .bind(&data.image_url)
     .bind(&data.site_name)
+    .bind(data.quoted_message_id)
     .fetch_one(pool)
     .await?;
 
     Ok(row.0)
 }
 
+pub async fn detach_message_previews(pool: &PgPool, message_id: Uuid) -> Result<(), AppError> {
+    sqlx::query("DELETE FROM message_link_previews WHERE message_id = $1")
+        .bind(message_id)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
 pub async fn attach_preview_to_message(
     pool: &PgPool,
     message_id: Uuid,
@@ -587,7 +2949,7 @@ pub async fn get_link_previews_for_messages(
     message_ids: &[Uuid],
 ) -> Result<HashMap<Uuid, Vec<LinkPreview>>, AppError> {
     let rows: Vec<LinkPreviewJoinRow> = sqlx::query_as(
-        "SELECT mlp.message_id, lp.id, lp.url, lp.title, lp.description, lp.image_url, lp.site_name
+        "SELECT mlp.message_id, lp.id, lp.url, lp.title, lp.description, lp.image_url, lp.site_name, lp.quoted_message_id
          FROM message_link_previews mlp
          JOIN link_previews lp ON lp.id = mlp.preview_id
          WHERE mlp.message_id = ANY($1)",
@@ -608,6 +2970,7 @@ pub async fn get_link_previews_for_messages(
                 description: row.description,
                 image_url: row.image_url,
                 site_name: row.site_name,
+                quoted_message_id: row.quoted_message_id,
             });
     }
 
@@ -659,18 +3022,48 @@ pub async fn link_attachments_to_message(
     Ok(attachments)
 }
 
+/// Copy attachments as unlinked uploads owned by `uploader_id`, ready to be
+/// linked to a new message. The copies share the originals' stored files.
+pub async fn copy_attachments(
+    pool: &PgPool,
+    attachments: &[Attachment],
+    uploader_id: Uuid,
+) -> Result<Vec<Uuid>, AppError> {
+    let mut tx = pool.begin().await?;
+    let mut ids = Vec::with_capacity(attachments.len());
+    for attachment in attachments {
+        let id = Uuid::now_v7();
+        sqlx::query(
+            "INSERT INTO attachments (id, filename, content_type, size, storage_path, uploader_id, created_at)
+             VALUES ($1, $2, $3, $4, $5, $6, $7)",
+        )
+        .bind(id)
+        .bind(&attachment.filename)
+        .bind(&attachment.content_type)
+        .bind(attachment.size)
+        .bind(&attachment.storage_path)
+        .bind(uploader_id)
+        .bind(attachment.created_at)
+        .execute(&mut *tx)
+        .await?;
+        ids.push(id);
+    }
+    tx.commit().await?;
+    Ok(ids)
+}
+
 // --- Users ---
 
 pub async fn create_user(pool: &PgPool, user: &User) -> Result<(), AppError> {
     sqlx::query(
-        "INSERT INTO users (id, username, email, password_hash, role, created_at)
+        "INSERT INTO users (id, username, email, password_hash, is_owner, created_at)
          VALUES ($1, $2, $3, $4, $5, $6)",
     )
     .bind(user.id)
     .bind(&user.username)
     .bind(&user.email)
     .bind(&user.password_hash)
-    .bind(user.role)
+    .bind(user.is_owner)
     .bind(user.created_at)
     .execute(pool)
     .await
@@ -693,7 +3086,7 @@ pub async fn create_user(pool: &PgPool, user: &User) -> Result<(), AppError> {
 
 pub async fn get_user_by_id(pool: &PgPool, user_id: Uuid) -> Result<Option<User>, AppError> {
     let user: Option<User> = sqlx::query_as(
-        "SELECT id, username, email, password_hash, role, created_at, avatar_path, display_name FROM users WHERE id = $1",
+        "SELECT id, username, email, password_hash, is_owner, created_at, avatar_path, display_name FROM users WHERE id = $1",
     )
     .bind(user_id)
     .fetch_optional(pool)
@@ -704,7 +3097,7 @@ pub async fn get_user_by_id(pool: &PgPool, user_id: Uuid) -> Result<Option<User>
 
 pub async fn get_user_by_username(pool: &PgPool, username: &str) -> Result<Option<User>, AppError> {
     let user: Option<User> = sqlx::query_as(
-        "SELECT id, username, email, password_hash, role, created_at, avatar_path, display_name FROM users WHERE LOWER(username) = LOWER($1)",
+        "SELECT id, username, email, password_hash, is_owner, created_at, avatar_path, display_name FROM users WHERE LOWER(username) = LOWER($1)",
     )
     .bind(username)
     .fetch_optional(pool)
@@ -720,22 +3113,88 @@ pub async fn get_user_count(pool: &PgPool) -> Result<i64, AppError> {
     Ok(count)
 }
 
-pub async fn get_user_role(pool: &PgPool, user_id: Uuid) -> Result<Role, AppError> {
-    let row: (Role,) = sqlx::query_as("SELECT role FROM users WHERE id = $1")
-        .bind(user_id)
-        .fetch_one(pool)
-        .await
-        .map_err(|_| AppError::not_found("User not found"))?;
-    Ok(row.0)
+/// Resolve a user's server-wide permissions from the default role and
+/// every role they hold.
+pub async fn get_member_permissions(
+    pool: &PgPool,
+    user_id: Uuid,
+) -> Result<MemberPermissions, AppError> {
+    let row: MemberPermissionsRow = sqlx::query_as(
+        "SELECT u.is_owner,
+                COALESCE(BIT_OR(r.permissions), 0) AS permissions,
+                COALESCE(MAX(r.position), 0) AS top_position
+         FROM users u
+         LEFT JOIN roles r ON r.is_default
+             OR r.id IN (SELECT ur.role_id FROM user_roles ur WHERE ur.user_id = u.id)
+         WHERE u.id = $1
+         GROUP BY u.id",
+    )
+    .bind(user_id)
+    .fetch_optional(pool)
+    .await?
+    .ok_or_else(|| AppError::not_found("User not found"))?;
+
+    Ok(MemberPermissions::new(
+        row.is_owner,
+        Permissions::from(row.permissions),
+        row.top_position,
+    ))
+}
+
+/// Whether the user holds any of the given roles. Everyone holds the
+/// default role.
+pub async fn user_has_any_role(
+    pool: &PgPool,
+    user_id: Uuid,
+    role_ids: &[Uuid],
+) -> Result<bool, AppError> {
+    let held: bool = sqlx::query_scalar(
+        "SELECT EXISTS(
+             SELECT 1 FROM roles r
+             WHERE r.id = ANY($2)
+               AND (r.is_default OR EXISTS(
+                   SELECT 1 FROM user_roles ur WHERE ur.user_id = $1 AND ur.role_id = r.id
+               ))
+         )",
+    )
+    .bind(user_id)
+    .bind(role_ids)
+    .fetch_one(pool)
+    .await?;
+    Ok(held)
+}
+
+pub async fn get_user_role_ids(pool: &PgPool, user_id: Uuid) -> Result<Vec<Uuid>, AppError> {
+    let role_ids: Vec<Uuid> =
+        sqlx::query_scalar("SELECT role_id FROM user_roles WHERE user_id = $1")
+            .bind(user_id)
+            .fetch_all(pool)
+            .await?;
+    Ok(role_ids)
 }
 
-pub async fn set_user_role(pool: &PgPool, user_id: Uuid, role: Role) -> Result<(), AppError> {
-    let result = sqlx::query("UPDATE users SET role = $1 WHERE id = $2")
-        .bind(role)
+/// Replace the non-default roles a user holds.
+pub async fn set_user_roles(
+    pool: &PgPool,
+    user_id: Uuid,
+    role_ids: &[Uuid],
+) -> Result<(), AppError> {
+    let mut tx = pool.begin().await?;
+    sqlx::query("DELETE FROM user_roles WHERE user_id = $1")
         .bind(user_id)
-        .execute(pool)
+        .execute(&mut *tx)
         .await?;
-    require_rows_affected(result, "User not found")
+    sqlx::query(
+        "INSERT INTO user_roles (user_id, role_id)
+         SELECT $1, UNNEST($2::uuid[])
+         ON CONFLICT DO NOTHING",
+    )
+    .bind(user_id)
+    .bind(role_ids)
+    .execute(&mut *tx)
+    .await?;
+    tx.commit().await?;
+    Ok(())
 }
 
 pub async fn delete_user(pool: &PgPool, user_id: Uuid) -> Result<(), AppError> {
@@ -778,6 +3237,10 @@ pub async fn delete_user(pool: &PgPool, user_id: Uuid) -> Result<(), AppError> {
         .bind(user_id)
         .execute(&mut *tx)
         .await?;
+    sqlx::query("UPDATE threads SET created_by = NULL WHERE created_by = $1")
+        .bind(user_id)
+        .execute(&mut *tx)
+        .await?;
     sqlx::query("DELETE FROM invites WHERE created_by = $1")
         .bind(user_id)
         .execute(&mut *tx)
@@ -824,47 +3287,55 @@ pub async fn delete_user(pool: &PgPool, user_id: Uuid) -> Result<(), AppError> {
 }
 
 pub async fn get_all_users(pool: &PgPool) -> Result<Vec<UserSummary>, AppError> {
-    let rows: Vec<(Uuid, String, String, Role, chrono::DateTime<chrono::Utc>, Option<String>)> = sqlx::query_as(
-        "SELECT id, username, email, role, created_at, avatar_path FROM users ORDER BY created_at ASC",
-    )
+    let rows: Vec<MemberRow> = sqlx::query_as(&format!(
+        "SELECT u.id, u.username, u.email, u.display_name, u.is_owner, {USER_ROLE_IDS}, u.created_at, u.avatar_path
+         FROM users u ORDER BY u.created_at ASC"
+    ))
     .fetch_all(pool)
     .await?;
 
     let users = rows
         .into_iter()
-        .map(
-            |(id, username, email, role, created_at, avatar_path)| UserSummary {
-                id,
-                username,
-                email,
-                role,
-                created_at,
-                avatar_url: crate::models::avatar_url_from_path(id, &avatar_path),
-            },
-        )
+        .map(|row| UserSummary {
+            avatar_url: crate::models::avatar_url_from_path(row.id, &row.avatar_path),
+            id: row.id,
+            username: row.username,
+            email: row.email,
+            is_owner: row.is_owner,
+            role_ids: row.role_ids,
+            created_at: row.created_at,
+        })
         .collect();
 
     Ok(users)
 }
 
+pub async fn get_all_user_ids(pool: &PgPool) -> Result<Vec<Uuid>, AppError> {
+    let rows: Vec<(Uuid,)> = sqlx::query_as("SELECT id FROM users")
+        .fetch_all(pool)
+        .await?;
+
+    Ok(rows.into_iter().map(|(id,)| id).collect())
+}
+
 pub async fn get_all_members(pool: &PgPool) -> Result<Vec<MemberInfo>, AppError> {
-    let rows: Vec<(Uuid, String, Option<String>, Role, Option<String>)> = sqlx::query_as(
-        "SELECT id, username, display_name, role, avatar_path FROM users ORDER BY username ASC",
-    )
+    let rows: Vec<MemberRow> = sqlx::query_as(&format!(
+        "SELECT u.id, u.username, u.email, u.display_name, u.is_owner, {USER_ROLE_IDS}, u.created_at, u.avatar_path
+         FROM users u ORDER BY u.username ASC"
+    ))
     .fetch_all(pool)
     .await?;
 
     Ok(rows
         .into_iter()
-        .map(
-            |(id, username, display_name, role, avatar_path)| MemberInfo {
-                id,
-                username,
-                display_name,
-                role,
-                avatar_url: crate::models::avatar_url_from_path(id, &avatar_path),
-            },
-        )
+        .map(|row| MemberInfo {
+            avatar_url: crate::models::avatar_url_from_path(row.id, &row.avatar_path),
+            id: row.id,
+            username: row.username,
+            display_name: row.display_name,
+            is_owner: row.is_owner,
+            role_ids: row.role_ids,
+        })
         .collect())
 }
 
@@ -894,6 +3365,53 @@ pub async fn update_user_display_name(
     require_rows_affected(result, "User not found")
 }
 
+pub async fn get_presence_settings(
+    pool: &PgPool,
+    user_id: Uuid,
+) -> Result<PresenceSettings, AppError> {
+    let settings: Option<PresenceSettings> = sqlx::query_as(
+        "SELECT status, custom_status_text, custom_status_emoji, custom_status_expires_at
+         FROM users WHERE id = $1",
+    )
+    .bind(user_id)
+    .fetch_optional(pool)
+    .await?;
+    settings.ok_or_else(|| AppError::not_found("User not found"))
+}
+
+pub async fn update_presence_settings(
+    pool: &PgPool,
+    user_id: Uuid,
+    status: PresenceStatus,
+    custom_status: Option<&CustomStatus>,
+) -> Result<(), AppError> {
+    let result = sqlx::query(
+        "UPDATE users SET status = $1, custom_status_text = $2, custom_status_emoji = $3,
+         custom_status_expires_at = $4 WHERE id = $5",
+    )
+    .bind(status)
+    .bind(custom_status.and_then(|c| c.text.as_deref()))
+    .bind(custom_status.and_then(|c| c.emoji.as_deref()))
+    .bind(custom_status.and_then(|c| c.expires_at))
+    .bind(user_id)
+    .execute(pool)
+    .await?;
+    require_rows_affected(result, "User not found")
+}
+
+/// Clear custom statuses past their expiry. Returns the affected users.
+pub async fn clear_expired_custom_statuses(pool: &PgPool) -> Result<Vec<Uuid>, AppError> {
+    let user_ids: Vec<Uuid> = sqlx::query_scalar(
+        "UPDATE users SET custom_status_text = NULL, custom_status_emoji = NULL,
+         custom_status_expires_at = NULL
+         WHERE custom_status_expires_at <= NOW()
+         RETURNING id",
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(user_ids)
+}
+
 pub async fn update_user_password(
     pool: &PgPool,
     user_id: Uuid,
@@ -907,6 +3425,114 @@ pub async fn update_user_password(
     require_rows_affected(result, "User not found")
 }
 
+// --- Roles ---
+
+/// All roles, lowest position first.
+pub async fn get_roles(pool: &PgPool) -> Result<Vec<Role>, AppError> {
+    let roles: Vec<Role> = sqlx::query_as(
+        "SELECT id, name, color, position, permissions, is_default, created_at
+         FROM roles ORDER BY position ASC",
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(roles)
+}
+
+pub async fn get_role_by_id(pool: &PgPool, role_id: Uuid) -> Result<Option<Role>, AppError> {
+    let role: Option<Role> = sqlx::query_as(
+        "SELECT id, name, color, position, permissions, is_default, created_at
+         FROM roles WHERE id = $1",
+    )
+    .bind(role_id)
+    .fetch_optional(pool)
+    .await?;
+    Ok(role)
+}
+
+/// Insert a role just above the default role, shifting the others up.
+pub async fn create_role(
+    pool: &PgPool,
+    name: &str,
+    color: Option<i32>,
+    permissions: Permissions,
+) -> Result<Role, AppError> {
+    let mut tx = pool.begin().await?;
+    sqlx::query("UPDATE roles SET position = position + 1 WHERE NOT is_default")
+        .execute(&mut *tx)
+        .await?;
+    let role: Role = sqlx::query_as(
+        "INSERT INTO roles (id, name, color, position, permissions, is_default, created_at)
+         VALUES ($1, $2, $3, 1, $4, FALSE, NOW())
+         RETURNING id, name, color, position, permissions, is_default, created_at",
+    )
+    .bind(Uuid::now_v7())
+    .bind(name)
+    .bind(color)
+    .bind(permissions)
+    .fetch_one(&mut *tx)
+    .await?;
+    tx.commit().await?;
+    Ok(role)
+}
+
+pub async fn update_role(
+    pool: &PgPool,
+    role_id: Uuid,
+    name: &str,
+    color: Option<i32>,
+    permissions: Permissions,
+) -> Result<Role, AppError> {
+    let role: Option<Role> = sqlx::query_as(
+        "UPDATE roles SET name = $1, color = $2, permissions = $3 WHERE id = $4
+         RETURNING id, name, color, position, permissions, is_default, created_at",
+    )
+    .bind(name)
+    .bind(color)
+    .bind(permissions)
+    .bind(role_id)
+    .fetch_optional(pool)
+    .await?;
+    role.ok_or_else(|| AppError::not_found("Role not found"))
+}
+
+/// Delete a non-default role and close the gap in positions.
+pub async fn delete_role(pool: &PgPool, role_id: Uuid) -> Result<(), AppError> {
+    let mut tx = pool.begin().await?;
+    let position: Option<i32> =
+        sqlx::query_scalar("DELETE FROM roles WHERE id = $1 AND NOT is_default RETURNING position")
+            .bind(role_id)
+            .fetch_optional(&mut *tx)
+            .await?;
+    let position = position.ok_or_else(|| AppError::not_found("Role not found"))?;
+    sqlx::query("UPDATE roles SET position = position - 1 WHERE position > $1")
+        .bind(position)
+        .execute(&mut *tx)
+        .await?;
+    tx.commit().await?;
+    Ok(())
+}
+
+/// Assign positions 1..=n to the given non-default roles in order.
+pub async fn reorder_roles(pool: &PgPool, role_ids: &[Uuid]) -> Result<Vec<Role>, AppError> {
+    let mut tx = pool.begin().await?;
+    sqlx::query(
+        "UPDATE roles SET position = ord.position::int
+         FROM UNNEST($1::uuid[]) WITH ORDINALITY AS ord(id, position)
+         WHERE roles.id = ord.id AND NOT roles.is_default",
+    )
+    .bind(role_ids)
+    .execute(&mut *tx)
+    .await?;
+    let roles: Vec<Role> = sqlx::query_as(
+        "SELECT id, name, color, position, permissions, is_default, created_at
+         FROM roles ORDER BY position ASC",
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+    tx.commit().await?;
+    Ok(roles)
+}
+
 // --- Bans (atomic upsert) ---
 
 pub async fn create_ban(pool: &PgPool, ban: &Ban) -> Result<(), AppError> {
@@ -1037,6 +3663,35 @@ pub async fn cleanup_expired_mutes(pool: &PgPool) -> Result<u64, AppError> {
     Ok(result.rows_affected())
 }
 
+// --- Event bus ---
+
+pub async fn store_event_bus_payload(pool: &PgPool, payload: &str) -> Result<i64, AppError> {
+    let (id,): (i64,) =
+        sqlx::query_as("INSERT INTO event_bus_payloads (payload) VALUES ($1) RETURNING id")
+            .bind(payload)
+            .fetch_one(pool)
+            .await?;
+    Ok(id)
+}
+
+pub async fn get_event_bus_payload(pool: &PgPool, id: i64) -> Result<Option<String>, AppError> {
+    let row: Option<(String,)> =
+        sqlx::query_as("SELECT payload FROM event_bus_payloads WHERE id = $1")
+            .bind(id)
+            .fetch_optional(pool)
+            .await?;
+    Ok(row.map(|(payload,)| payload))
+}
+
+pub async fn cleanup_event_bus_payloads(pool: &PgPool) -> Result<u64, AppError> {
+    let result = sqlx::query(
+        "DELETE FROM event_bus_payloads WHERE created_at < NOW() - INTERVAL '5 minutes'",
+    )
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected())
+}
+
 // --- Invites ---
 
 pub async fn create_invite(pool: &PgPool, invite: &Invite) -> Result<(), AppError> {
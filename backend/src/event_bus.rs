@@ -0,0 +1,321 @@
+//! Cross-node event fan-out.
+//!
+//! Every change to state that other backend processes need -- broadcasts,
+//! presence, voice states and the ban/mute/private channel and outgoing
+//! webhook caches -- goes through [`AppState::emit`], which applies it
+//! locally and hands it to the configured [`EventBus`]. The default [`LocalBus`] does nothing else, so a
+//! single node behaves exactly as before. [`PgNotifyBus`] relays events to
+//! every other node over PostgreSQL `LISTEN/NOTIFY`.
+
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use sqlx::postgres::PgListener;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+use crate::models::{AppState, UserPresence, VoiceState};
+use crate::shared::AppError;
+
+/// Postgres notification channel shared by all nodes.
+const NOTIFY_CHANNEL: &str = "echora_events";
+/// NOTIFY payloads are limited to 8000 bytes. Larger events are stored in
+/// `event_bus_payloads` and referenced by id.
+const MAX_INLINE_PAYLOAD: usize = 7900;
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(15);
+/// A node that has not been heard from for this long is considered dead and
+/// its users' presence and voice states are dropped.
+const NODE_TIMEOUT: Duration = Duration::from_secs(60);
+/// Wait before retrying after the listener failed to reconnect.
+const RECONNECT_DELAY: Duration = Duration::from_secs(1);
+
+/// State change replicated between nodes.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ClusterEvent {
+    /// Serialized gateway event for every connected client.
+    Global {
+        payload: String,
+    },
+    /// Serialized gateway event for subscribers of a channel or thread.
+    Channel {
+        topic_id: Uuid,
+        payload: String,
+    },
+    /// Serialized gateway event for every connection of one user.
+    User {
+        user_id: Uuid,
+        payload: String,
+    },
+    /// The user has a connection on the publishing node.
+    PresenceOnline {
+        presence: UserPresence,
+    },
+    /// The publishing node no longer has a connection for the user.
+    PresenceOffline {
+        user_id: Uuid,
+    },
+    /// Profile fields of an online user changed; does not affect which
+    /// nodes the user is connected to.
+    PresenceUpdated {
+        presence: UserPresence,
+    },
+    VoiceStateSet {
+        voice_state: VoiceState,
+    },
+    VoiceStateRemoved {
+        channel_id: Uuid,
+        user_id: Uuid,
+    },
+    PrivateMemberAdded {
+        channel_id: Uuid,
+        user_id: Uuid,
+    },
+    PrivateMemberRemoved {
+        channel_id: Uuid,
+        user_id: Uuid,
+    },
+    /// Viewers of a server channel were recomputed from its overwrites.
+    ChannelViewersSet {
+        channel_id: Uuid,
+        user_ids: Option<Vec<Uuid>>,
+    },
+    ChannelRemoved {
+        channel_id: Uuid,
+    },
+    BanCached {
+        user_id: Uuid,
+    },
+    BanUncached {
+        user_id: Uuid,
+    },
+    MuteCached {
+        user_id: Uuid,
+    },
+    MuteUncached {
+        user_id: Uuid,
+    },
+    /// An outgoing webhook's subscriptions changed. `None` when it was
+    /// disabled or deleted.
+    OutgoingWebhookSet {
+        webhook_id: Uuid,
+        event_types: Option<Vec<String>>,
+    },
+    /// A node started and wants everyone's presence and voice states.
+    Hello,
+    Heartbeat,
+}
+
+pub trait EventBus: Send + Sync {
+    /// Relay an event to every other node. The publishing node has already
+    /// applied it locally.
+    fn publish(&self, node_id: Uuid, event: ClusterEvent);
+
+    /// Start receiving events from other nodes.
+    fn start(&self, _state: Arc<AppState>) {}
+}
+
+/// Single-node bus: nothing to relay.
+pub struct LocalBus;
+
+impl EventBus for LocalBus {
+    fn publish(&self, _node_id: Uuid, _event: ClusterEvent) {}
+}
+
+/// Build the bus selected by `EVENT_BUS` (`local` or `postgres`).
+pub fn from_env(db: &PgPool) -> Arc<dyn EventBus> {
+    match std::env::var("EVENT_BUS").as_deref() {
+        Ok("postgres") => {
+            info!("Using PostgreSQL LISTEN/NOTIFY event bus");
+            Arc::new(PgNotifyBus::new(db.clone()))
+        }
+        Ok("local") | Err(_) => Arc::new(LocalBus),
+        Ok(other) => panic!("Unknown EVENT_BUS: {other} (expected 'local' or 'postgres')"),
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "t", rename_all = "snake_case")]
+enum Notification {
+    Inline { node: Uuid, event: ClusterEvent },
+    Stored { node: Uuid, id: i64 },
+}
+
+/// Relays events between nodes with PostgreSQL `LISTEN/NOTIFY`. Publishing
+/// is queued so it never blocks the caller; a background task sends the
+/// notifications in order.
+pub struct PgNotifyBus {
+    db: PgPool,
+    tx: mpsc::UnboundedSender<(Uuid, ClusterEvent)>,
+}
+
+impl PgNotifyBus {
+    pub fn new(db: PgPool) -> Self {
+        let (tx, rx) = mpsc::unbounded_channel();
+        tokio::spawn(run_publisher(db.clone(), rx));
+        Self { db, tx }
+    }
+}
+
+impl EventBus for PgNotifyBus {
+    fn publish(&self, node_id: Uuid, event: ClusterEvent) {
+        let _ = self.tx.send((node_id, event));
+    }
+
+    fn start(&self, state: Arc<AppState>) {
+        tokio::spawn(run_listener(self.db.clone(), self.tx.clone(), state));
+    }
+}
+
+async fn run_publisher(db: PgPool, mut rx: mpsc::UnboundedReceiver<(Uuid, ClusterEvent)>) {
+    let mut last_cleanup = Instant::now();
+    while let Some((node, event)) = rx.recv().await {
+        if let Err(e) = send_notification(&db, node, event).await {
+            error!("Event bus: failed to publish event: {e}");
+        }
+        if last_cleanup.elapsed() > NODE_TIMEOUT {
+            last_cleanup = Instant::now();
+            if let Err(e) = crate::database::cleanup_event_bus_payloads(&db).await {
+                warn!("Event bus: failed to clean up stored payloads: {e}");
+            }
+        }
+    }
+}
+
+async fn send_notification(db: &PgPool, node: Uuid, event: ClusterEvent) -> Result<(), AppError> {
+    let inline = serde_json::to_string(&Notification::Inline { node, event })
+        .expect("cluster events always serialize");
+    let payload = if inline.len() <= MAX_INLINE_PAYLOAD {
+        inline
+    } else {
+        let id = crate::database::store_event_bus_payload(db, &inline).await?;
+        serde_json::to_string(&Notification::Stored { node, id })
+            .expect("cluster events always serialize")
+    };
+    sqlx::query("SELECT pg_notify($1, $2)")
+        .bind(NOTIFY_CHANNEL)
+        .bind(payload)
+        .execute(db)
+        .await?;
+    Ok(())
+}
+
+async fn run_listener(
+    db: PgPool,
+    tx: mpsc::UnboundedSender<(Uuid, ClusterEvent)>,
+    state: Arc<AppState>,
+) {
+    let mut listener = match PgListener::connect_with(&db).await {
+        Ok(listener) => listener,
+        Err(e) => {
+            error!("Event bus: failed to connect listener: {e}");
+            return;
+        }
+    };
+    if let Err(e) = listener.listen(NOTIFY_CHANNEL).await {
+        error!("Event bus: failed to LISTEN on {NOTIFY_CHANNEL}: {e}");
+        return;
+    }
+    info!("Event bus: node {} listening", state.node_id);
+
+    let _ = tx.send((state.node_id, ClusterEvent::Hello));
+
+    let mut last_seen: HashMap<Uuid, Instant> = HashMap::new();
+    let mut heartbeat = tokio::time::interval(HEARTBEAT_INTERVAL);
+
+    loop {
+        tokio::select! {
+            _ = heartbeat.tick() => {
+                let _ = tx.send((state.node_id, ClusterEvent::Heartbeat));
+                let now = Instant::now();
+                let dead: Vec<Uuid> = last_seen
+                    .iter()
+                    .filter(|(_, seen)| now.duration_since(**seen) > NODE_TIMEOUT)
+                    .map(|(node, _)| *node)
+                    .collect();
+                for node in dead {
+                    last_seen.remove(&node);
+                    warn!("Event bus: node {node} timed out");
+                    state.forget_node(node).await;
+                }
+            }
+            notification = listener.try_recv() => {
+                // PgListener reconnects on its own, but events sent while the
+                // connection was down are lost. Caches other nodes keep up to
+                // date are rebuilt from the database once it is back.
+                let notification = match notification {
+                    Ok(Some(notification)) => notification,
+                    Ok(None) => {
+                        warn!("Event bus: listener reconnected");
+                        resync_caches(&state).await;
+                        continue;
+                    }
+                    Err(e) => {
+                        warn!("Event bus: listener error: {e}");
+                        // Reconnect now rather than on the next notification
+                        tokio::time::sleep(RECONNECT_DELAY).await;
+                        if listener.listen(NOTIFY_CHANNEL).await.is_ok() {
+                            resync_caches(&state).await;
+                        }
+                        continue;
+                    }
+                };
+                let (node, event) = match decode_notification(&db, state.node_id, notification.payload()).await {
+                    Ok(Some(decoded)) => decoded,
+                    Ok(None) => continue,
+                    Err(e) => {
+                        warn!("Event bus: dropping undecodable notification: {e}");
+                        continue;
+                    }
+                };
+                if last_seen.insert(node, Instant::now()).is_none()
+                    || matches!(event, ClusterEvent::Hello)
+                {
+                    // New (or restarted) peer: tell it who is connected here
+                    for event in state.local_cluster_snapshot() {
+                        let _ = tx.send((state.node_id, event));
+                    }
+                }
+                state.apply_remote(node, event).await;
+            }
+        }
+    }
+}
+
+async fn resync_caches(state: &AppState) {
+    match state.reload_caches().await {
+        Ok(()) => info!("Event bus: caches resynced"),
+        Err(e) => error!("Event bus: failed to resync caches: {e}"),
+    }
+}
+
+/// Decode a notification, fetching stored payloads. Returns None for the
+/// node's own events and for stored payloads that were already cleaned up.
+async fn decode_notification(
+    db: &PgPool,
+    own_node: Uuid,
+    payload: &str,
+) -> Result<Option<(Uuid, ClusterEvent)>, Box<dyn std::error::Error + Send + Sync>> {
+    let notification = serde_json::from_str::<Notification>(payload)?;
+    let (node, event) = match notification {
+        Notification::Inline { node, .. } | Notification::Stored { node, .. }
+            if node == own_node =>
+        {
+            return Ok(None);
+        }
+        Notification::Inline { node, event } => (node, event),
+        Notification::Stored { node, id } => {
+            let Some(stored) = crate::database::get_event_bus_payload(db, id).await? else {
+                return Ok(None);
+            };
+            match serde_json::from_str::<Notification>(&stored)? {
+                Notification::Inline { event, .. } => (node, event),
+                Notification::Stored { .. } => return Ok(None),
+            }
+        }
+    };
+    Ok(Some((node, event)))
+}
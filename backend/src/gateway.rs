@@ -0,0 +1,354 @@
+//! Typed WebSocket gateway protocol.
+//!
+//! Every event the server pushes is a [`GatewayEvent`], serialized as
+//! `{"type": "<event>", "data": {...}}`. Every op a client sends is a
+//! [`GatewayCommand`], tagged by `message_type`. These two enums are the
+//! single source of truth for the wire format; the Svelte client's
+//! `WsIncomingMessage`/`WsOutgoingMessage` types mirror them.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::models::{
+    Channel, ChannelCategory, ChannelPosition, DmChannel, LinkPreview, MemberInfo, Message, Poll,
+    Role, ScheduledMessage, SoundboardSound, Thread, UserPresence, VoiceState,
+};
+use crate::sfu::models::ProducerInfo;
+
+/// Protocol version spoken by this server. Clients send the version they
+/// speak in the auth message; the lower of the two is used.
+pub const GATEWAY_PROTOCOL_VERSION: u32 = 1;
+/// Oldest client protocol version still accepted.
+pub const MIN_GATEWAY_PROTOCOL_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", content = "data", rename_all = "snake_case")]
+pub enum GatewayEvent {
+    // --- Session ---
+    Ready {
+        session_id: Uuid,
+        protocol_version: u32,
+        resume_window_secs: u64,
+    },
+    Resumed {
+        session_id: Uuid,
+        protocol_version: u32,
+    },
+    SyncRequired {
+        reason: &'static str,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        channel_id: Option<Uuid>,
+    },
+    Error {
+        code: &'static str,
+        message: String,
+    },
+    Pong,
+
+    // --- Messages ---
+    Message(Message),
+    MessageEdited(Message),
+    MessageDeleted {
+        id: Uuid,
+        channel_id: Uuid,
+        thread_id: Option<Uuid>,
+        /// Lets moderators' clients keep the message as a tombstone.
+        deleted_at: DateTime<Utc>,
+    },
+    MessageRestored(Message),
+    MessagesBulkDeleted {
+        channel_id: Uuid,
+        ids: Vec<Uuid>,
+        deleted_at: DateTime<Utc>,
+    },
+    ReactionAdded(ReactionEvent),
+    ReactionRemoved(ReactionEvent),
+    MessagePinned {
+        channel_id: Uuid,
+        message_id: Uuid,
+        pinned_by: Uuid,
+        pinned_at: DateTime<Utc>,
+    },
+    MessageUnpinned {
+        channel_id: Uuid,
+        message_id: Uuid,
+    },
+    /// `voted` flags are never set; voters get their own from the vote response.
+    PollUpdated {
+        channel_id: Uuid,
+        message_id: Uuid,
+        poll: Poll,
+    },
+    /// Sent to the author when one of their scheduled messages is posted.
+    ScheduledMessageSent {
+        id: Uuid,
+        channel_id: Uuid,
+        message_id: Uuid,
+    },
+    ScheduledMessageFailed(ScheduledMessage),
+    LinkPreviewReady {
+        message_id: Uuid,
+        channel_id: Uuid,
+        thread_id: Option<Uuid>,
+        link_previews: Vec<LinkPreview>,
+    },
+    ChannelActivity {
+        channel_id: Uuid,
+        thread_id: Option<Uuid>,
+        message_id: Uuid,
+        author_id: Uuid,
+        timestamp: DateTime<Utc>,
+        mentions: Option<Vec<Uuid>>,
+        mention_everyone: bool,
+    },
+    Mention(MentionEvent),
+    ChannelAck {
+        channel_id: Uuid,
+        message_id: Uuid,
+    },
+    Typing {
+        user_id: Uuid,
+        username: String,
+        channel_id: Uuid,
+    },
+
+    // --- Channels, threads and DMs ---
+    ChannelCreated(Channel),
+    ChannelUpdated(Channel),
+    ChannelDeleted {
+        id: Uuid,
+    },
+    CategoryCreated(ChannelCategory),
+    CategoryUpdated(ChannelCategory),
+    CategoryDeleted {
+        id: Uuid,
+    },
+    /// Positions of every category and server channel after a reorder.
+    ChannelsReordered {
+        categories: Vec<ChannelCategory>,
+        channels: Vec<ChannelPosition>,
+    },
+    ThreadCreated(Thread),
+    ThreadUpdated(Thread),
+    ThreadArchived(Thread),
+    DmCreated(DmChannel),
+    DmUpdated(DmChannel),
+    DmDeleted {
+        id: Uuid,
+    },
+    DmMemberLeft {
+        channel_id: Uuid,
+        user_id: Uuid,
+    },
+
+    // --- Users and moderation ---
+    /// A new account was registered.
+    MemberJoined(MemberInfo),
+    UserOnline(UserPresence),
+    UserOffline {
+        user_id: Uuid,
+        username: String,
+    },
+    PresenceUpdated(UserPresence),
+    UserProfileUpdated {
+        user_id: Uuid,
+        username: String,
+        display_name: Option<String>,
+        avatar_url: Option<String>,
+    },
+    UserAvatarUpdated {
+        user_id: Uuid,
+        avatar_url: Option<String>,
+    },
+    UserRolesUpdated {
+        user_id: Uuid,
+        role_ids: Vec<Uuid>,
+    },
+    UserDeleted {
+        user_id: Uuid,
+    },
+    UserKicked {
+        user_id: Uuid,
+        reason: Option<String>,
+    },
+    UserBanned {
+        user_id: Uuid,
+        reason: Option<String>,
+    },
+    UserUnbanned {
+        user_id: Uuid,
+    },
+    UserMuted {
+        user_id: Uuid,
+        reason: Option<String>,
+        expires_at: Option<DateTime<Utc>>,
+    },
+    UserUnmuted {
+        user_id: Uuid,
+    },
+    SettingsUpdated {
+        key: String,
+        value: String,
+    },
+    RoleCreated(Role),
+    RoleUpdated(Role),
+    RoleDeleted {
+        id: Uuid,
+    },
+    RolesReordered {
+        roles: Vec<Role>,
+    },
+
+    // --- Voice ---
+    VoiceUserJoined(VoiceState),
+    VoiceUserLeft {
+        user_id: Uuid,
+        channel_id: Uuid,
+    },
+    VoiceStateUpdated(VoiceState),
+    VoiceSpeaking {
+        user_id: Uuid,
+        channel_id: Uuid,
+        is_speaking: bool,
+    },
+    ScreenShareUpdated(VoiceState),
+    CameraUpdated(VoiceState),
+    NewProducer(ProducerInfo),
+
+    // --- Soundboard ---
+    SoundboardSoundCreated(SoundboardSound),
+    SoundboardSoundUpdated(SoundboardSound),
+    SoundboardSoundDeleted {
+        sound_id: Uuid,
+    },
+    SoundboardPlay {
+        channel_id: Uuid,
+        user_id: Uuid,
+        sound_id: Uuid,
+        sound_volume: f64,
+    },
+}
+
+impl GatewayEvent {
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("gateway events always serialize")
+    }
+
+    pub fn error(code: &'static str, message: impl Into<String>) -> Self {
+        Self::Error {
+            code,
+            message: message.into(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ReactionEvent {
+    pub message_id: Uuid,
+    pub emoji: String,
+    pub user_id: Uuid,
+    pub username: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MentionEvent {
+    pub message_id: Uuid,
+    pub channel_id: Uuid,
+    pub thread_id: Option<Uuid>,
+    pub author_id: Uuid,
+    pub username: String,
+    pub content: String,
+    pub everyone: bool,
+}
+
+/// Ops a client may send after authenticating.
+#[derive(Debug, Deserialize)]
+#[serde(tag = "message_type", rename_all = "snake_case")]
+pub enum GatewayCommand {
+    Message(ChatMessage),
+    /// Legacy single-channel op: replaces every current subscription.
+    Join(SubscriptionTarget),
+    #[serde(alias = "join_thread")]
+    Subscribe(SubscriptionTarget),
+    Unsubscribe(SubscriptionTarget),
+    Leave(LeaveTarget),
+    Ack(AckMessage),
+    Typing(ChannelTarget),
+    VoiceStateUpdate(VoiceStateUpdate),
+    VoiceSpeaking(VoiceSpeakingUpdate),
+    ScreenShareUpdate(ScreenShareUpdate),
+    CameraUpdate(CameraUpdate),
+    IdleUpdate(IdleUpdate),
+    Ping,
+    /// Any `message_type` this server does not know.
+    #[serde(other)]
+    Unknown,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ChatMessage {
+    pub channel_id: Uuid,
+    pub content: Option<String>,
+    pub reply_to_id: Option<Uuid>,
+    pub thread_id: Option<Uuid>,
+    #[serde(default)]
+    pub attachment_ids: Vec<Uuid>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ChannelTarget {
+    pub channel_id: Uuid,
+}
+
+/// Target of a subscribe/unsubscribe op: a channel or a thread.
+#[derive(Debug, Deserialize)]
+pub struct SubscriptionTarget {
+    pub channel_id: Option<Uuid>,
+    pub thread_id: Option<Uuid>,
+}
+
+/// `leave` without a target drops every subscription (the pre-multi-channel
+/// behavior); with one it unsubscribes only that channel.
+#[derive(Debug, Default, Deserialize)]
+pub struct LeaveTarget {
+    pub channel_id: Option<Uuid>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AckMessage {
+    pub channel_id: Uuid,
+    pub message_id: Uuid,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct VoiceStateUpdate {
+    pub channel_id: Uuid,
+    pub is_muted: Option<bool>,
+    pub is_deafened: Option<bool>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct VoiceSpeakingUpdate {
+    pub channel_id: Uuid,
+    pub is_speaking: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ScreenShareUpdate {
+    pub channel_id: Uuid,
+    pub is_screen_sharing: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CameraUpdate {
+    pub channel_id: Uuid,
+    pub is_camera_sharing: bool,
+}
+
+/// Client-detected inactivity. Only affects users whose chosen status is
+/// `online`.
+#[derive(Debug, Deserialize)]
+pub struct IdleUpdate {
+    pub idle: bool,
+}
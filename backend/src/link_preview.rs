@@ -8,12 +8,14 @@ use tracing::{error, info};
 use uuid::Uuid;
 
 use crate::database;
+use crate::gateway::GatewayEvent;
 use crate::models::AppState;
 
 type HmacSha256 = Hmac<Sha256>;
 
 const MAX_BODY_SIZE: usize = 256 * 1024; // 256KB
 const MAX_URLS_PER_MESSAGE: usize = 5;
+const MAX_QUOTE_LENGTH: usize = 300;
 
 #[derive(Debug, Clone)]
 pub struct LinkPreviewData {
@@ -22,6 +24,8 @@ pub struct LinkPreviewData {
     pub description: Option<String>,
     pub image_url: Option<String>,
     pub site_name: Option<String>,
+    /// Set when the URL is a permalink to a message on this server.
+    pub quoted_message_id: Option<Uuid>,
 }
 
 /// Extract URLs from message content using linkify
@@ -36,6 +40,18 @@ pub fn extract_urls(content: &str) -> Vec<String> {
         .collect()
 }
 
+/// Extract the message id from a permalink (`<origin>/messages/<id>`).
+/// The origin is not checked: message ids are unique, so a link to another
+/// server simply fails to resolve here.
+pub fn parse_permalink(url: &str) -> Option<Uuid> {
+    let parsed = url::Url::parse(url).ok()?;
+    let mut segments = parsed.path_segments()?;
+    match (segments.next(), segments.next(), segments.next()) {
+        (Some("messages"), Some(id), None) => id.parse().ok(),
+        _ => None,
+    }
+}
+
 /// Check if a URL scheme is safe (http/https only)
 pub fn is_safe_scheme(url: &str) -> bool {
     url.starts_with("http://") || url.starts_with("https://")
@@ -137,6 +153,7 @@ async fn fetch_preview(client: &reqwest::Client, url: &str) -> Result<LinkPrevie
             description: None,
             image_url: Some(url.to_string()),
             site_name,
+            quoted_message_id: None,
         });
     }
 
@@ -259,7 +276,46 @@ fn parse_og_tags(html: &str, url: &str) -> LinkPreviewData {
         description,
         image_url,
         site_name,
+        quoted_message_id: None,
+    }
+}
+
+/// Resolve a permalink into a quote of the linked message without leaving
+/// the server. A message is only quoted if the author can see it and so can
+/// everyone reading the channel the quote is posted in, so quoting never
+/// leaks history out of a private channel.
+async fn resolve_quote(
+    state: &AppState,
+    url: &str,
+    quoted_id: Uuid,
+    channel_id: Uuid,
+    author_id: Uuid,
+) -> Result<LinkPreviewData, String> {
+    let quoted = database::get_message_by_id(&state.db, quoted_id)
+        .await
+        .map_err(|e| e.to_string())?
+        .filter(|m| m.kind.is_default())
+        .filter(|m| state.can_access_channel(m.channel_id, author_id))
+        .filter(|m| state.audience_can_see(channel_id, m.channel_id))
+        .ok_or("Message is not quotable here")?;
+    let channel = database::get_channel_by_id(&state.db, quoted.channel_id)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or("Channel not found")?;
+
+    let mut description: String = quoted.content.chars().take(MAX_QUOTE_LENGTH).collect();
+    if description.len() < quoted.content.len() {
+        description.push('…');
     }
+
+    Ok(LinkPreviewData {
+        url: url.to_string(),
+        title: Some(quoted.display_name.unwrap_or(quoted.username)),
+        description: Some(description).filter(|d| !d.is_empty()),
+        image_url: None,
+        site_name: (!channel.channel_type.is_private()).then(|| format!("#{}", channel.name)),
+        quoted_message_id: Some(quoted.id),
+    })
 }
 
 /// Sign an image URL with HMAC-SHA256 for the proxy endpoint
@@ -286,11 +342,14 @@ pub fn verify_image_signature(image_url: &str, sig: &str, secret: &str) -> bool
     mac.verify_slice(&sig_bytes).is_ok()
 }
 
-/// Spawn an async task to fetch link previews for a message
+/// Spawn an async task to fetch link previews for a message. Permalinks to
+/// messages on this server become quotes instead of being fetched.
 pub fn spawn_preview_fetch(
     state: Arc<AppState>,
     message_id: Uuid,
     channel_id: Uuid,
+    thread_id: Option<Uuid>,
+    author_id: Uuid,
     content: String,
 ) {
     let urls = extract_urls(&content);
@@ -303,8 +362,16 @@ pub fn spawn_preview_fetch(
 
         // Fetch all URLs concurrently
         let fetch_results: Vec<_> = futures_util::future::join_all(urls.iter().map(|url| {
-            let client = &state.http_client;
-            async move { (url.clone(), fetch_preview(client, url).await) }
+            let state = &state;
+            async move {
+                let result = match parse_permalink(url) {
+                    Some(quoted_id) => {
+                        resolve_quote(state, url, quoted_id, channel_id, author_id).await
+                    }
+                    None => fetch_preview(&state.http_client, url).await,
+                };
+                (url.clone(), result)
+            }
         }))
         .await;
 
@@ -343,6 +410,7 @@ pub fn spawn_preview_fetch(
                                 description: data.description,
                                 image_url: data.image_url,
                                 site_name: data.site_name,
+                                quoted_message_id: data.quoted_message_id,
                             });
                         }
                         Err(e) => {
@@ -357,14 +425,15 @@ pub fn spawn_preview_fetch(
         }
 
         if !previews.is_empty() {
-            state.broadcast_channel(
+            state.broadcast_topic(
                 channel_id,
-                "link_preview_ready",
-                serde_json::json!({
-                    "message_id": message_id,
-                    "channel_id": channel_id,
-                    "link_previews": previews,
-                }),
+                thread_id.unwrap_or(channel_id),
+                GatewayEvent::LinkPreviewReady {
+                    message_id,
+                    channel_id,
+                    thread_id,
+                    link_previews: previews,
+                },
             );
         }
     });
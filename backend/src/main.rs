@@ -13,6 +13,8 @@ mod admin;
 mod auth;
 mod auth_routes;
 mod database;
+mod event_bus;
+mod gateway;
 mod link_preview;
 mod models;
 mod passkey_routes;
@@ -40,15 +42,6 @@ async fn main() {
         .await
         .expect("Failed to seed database");
 
-    // Pre-populate ban/mute caches from the database so they are accurate
-    // on startup without waiting for moderation events.
-    let initial_bans = database::get_all_bans(&db)
-        .await
-        .expect("Failed to load initial bans");
-    let initial_mutes = database::get_all_mutes(&db)
-        .await
-        .expect("Failed to load initial mutes");
-
     let sfu_service = sfu::service::SfuService::new()
         .await
         .expect("Failed to initialize SFU service");
@@ -72,29 +65,34 @@ async fn main() {
             .expect("Failed to build Webauthn"),
     );
 
+    let event_bus = event_bus::from_env(&db);
+
     let state = Arc::new(AppState::new(
         db,
         sfu_service,
         http_client,
         file_store,
         webauthn,
+        event_bus,
     ));
+    state.event_bus.start(state.clone());
 
-    // Seed in-memory ban/mute caches
-    for ban in &initial_bans {
-        state.cache_ban(ban.user_id);
-    }
-    for mute in &initial_mutes {
-        state.cache_mute(mute.user_id);
-    }
+    // Pre-populate ban/mute/private channel and outgoing webhook caches from
+    // the database so they are accurate on startup without waiting for
+    // events. Every node loads these itself, so nothing is published.
+    state
+        .reload_caches()
+        .await
+        .expect("Failed to load in-memory caches");
 
-    // Spawn periodic cleanup of expired bans and mutes.
+    // Spawn periodic cleanup of expired bans, mutes and slowmode posts.
     // Also refreshes the in-memory caches to remove expired entries.
     let cleanup_state = state.clone();
     tokio::spawn(async move {
         let mut interval = tokio::time::interval(std::time::Duration::from_secs(300));
         loop {
             interval.tick().await;
+            cleanup_state.prune_slowmode();
             let _ = database::cleanup_expired_bans(&cleanup_state.db).await;
             let _ = database::cleanup_expired_mutes(&cleanup_state.db).await;
 
@@ -106,7 +104,7 @@ async fn main() {
                     .banned_users
                     .retain(|id| active_ids.contains(id));
                 for ban in &active_bans {
-                    cleanup_state.cache_ban(ban.user_id);
+                    cleanup_state.banned_users.insert(ban.user_id);
                 }
             }
             if let Ok(active_mutes) = database::get_all_mutes(&cleanup_state.db).await {
@@ -116,8 +114,137 @@ async fn main() {
                     .muted_users
                     .retain(|id| active_ids.contains(id));
                 for mute in &active_mutes {
-                    cleanup_state.cache_mute(mute.user_id);
+                    cleanup_state.muted_users.insert(mute.user_id);
+                }
+            }
+        }
+    });
+
+    // Spawn periodic expiry of custom statuses.
+    let presence_state = state.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(std::time::Duration::from_secs(60));
+        loop {
+            interval.tick().await;
+            if let Err(e) = services::presence::expire_custom_statuses(&presence_state).await {
+                tracing::warn!("Failed to expire custom statuses: {e}");
+            }
+        }
+    });
+
+    // Spawn periodic auto-archive of threads with no recent replies.
+    let archive_state = state.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(std::time::Duration::from_secs(300));
+        loop {
+            interval.tick().await;
+            let cutoff = chrono::Utc::now()
+                - chrono::Duration::hours(shared::validation::THREAD_AUTO_ARCHIVE_HOURS);
+            match database::archive_inactive_threads(&archive_state.db, cutoff).await {
+                Ok(threads) => {
+                    for thread in threads {
+                        archive_state.broadcast_channel(
+                            thread.channel_id,
+                            gateway::GatewayEvent::ThreadArchived(thread),
+                        );
+                    }
                 }
+                Err(e) => tracing::warn!("Failed to archive inactive threads: {e}"),
+            }
+        }
+    });
+
+    // Spawn periodic purge of message revisions past the retention setting.
+    let revision_state = state.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(std::time::Duration::from_secs(3600));
+        loop {
+            interval.tick().await;
+            let days = database::get_server_setting(&revision_state.db, "revision_retention_days")
+                .await
+                .ok()
+                .and_then(|value| value.parse::<i64>().ok())
+                .unwrap_or(0);
+            if days <= 0 {
+                continue;
+            }
+            let cutoff = chrono::Utc::now() - chrono::Duration::days(days);
+            if let Err(e) = database::purge_message_revisions(&revision_state.db, cutoff).await {
+                tracing::warn!("Failed to purge message revisions: {e}");
+            }
+        }
+    });
+
+    // Spawn periodic closing of polls past their expiry.
+    let poll_state = state.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(std::time::Duration::from_secs(15));
+        loop {
+            interval.tick().await;
+            if let Err(e) = services::poll::close_expired_polls(&poll_state).await {
+                tracing::warn!("Failed to close expired polls: {e}");
+            }
+        }
+    });
+
+    // Spawn periodic delivery of scheduled messages that are due.
+    let schedule_state = state.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(std::time::Duration::from_secs(10));
+        loop {
+            interval.tick().await;
+            if let Err(e) = services::scheduled_message::deliver_due_messages(&schedule_state).await
+            {
+                tracing::warn!("Failed to deliver scheduled messages: {e}");
+            }
+        }
+    });
+
+    // Spawn periodic delivery of queued outgoing webhook events.
+    let webhook_state = state.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(std::time::Duration::from_secs(5));
+        loop {
+            interval.tick().await;
+            if let Err(e) = services::outgoing_webhook::deliver_due_webhooks(&webhook_state).await {
+                tracing::warn!("Failed to deliver outgoing webhooks: {e}");
+            }
+        }
+    });
+
+    // Spawn periodic pruning of the outgoing webhook delivery log.
+    let delivery_log_state = state.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(std::time::Duration::from_secs(3600));
+        loop {
+            interval.tick().await;
+            let cutoff = chrono::Utc::now()
+                - chrono::Duration::days(shared::validation::DELIVERY_LOG_RETENTION_DAYS);
+            if let Err(e) =
+                database::prune_outgoing_webhook_deliveries(&delivery_log_state.db, cutoff).await
+            {
+                tracing::warn!("Failed to prune webhook delivery log: {e}");
+            }
+        }
+    });
+
+    // Spawn periodic purge of deleted messages past the retention setting.
+    let purge_state = state.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(std::time::Duration::from_secs(3600));
+        loop {
+            interval.tick().await;
+            let days =
+                database::get_server_setting(&purge_state.db, "deleted_message_retention_days")
+                    .await
+                    .ok()
+                    .and_then(|value| value.parse::<i64>().ok())
+                    .unwrap_or(30);
+            let cutoff = chrono::Utc::now() - chrono::Duration::days(days.max(1));
+            match services::message::purge_deleted_messages(&purge_state, cutoff).await {
+                Ok(0) => {}
+                Ok(purged) => tracing::info!("Purged {purged} deleted messages"),
+                Err(e) => tracing::warn!("Failed to purge deleted messages: {e}"),
             }
         }
     });
@@ -146,6 +273,7 @@ async fn main() {
             "/api/auth/me",
             get(auth_routes::me).put(auth_routes::update_profile),
         )
+        .route("/api/auth/me/presence", put(auth_routes::update_presence))
         .route("/api/auth/password", post(auth_routes::change_password))
         .route("/api/auth/register", post(auth_routes::register))
         .route("/api/auth/login", post(auth_routes::login))
@@ -174,10 +302,23 @@ async fn main() {
             "/api/channels",
             get(routes::get_channels).post(routes::create_channel),
         )
+        .route("/api/channels/positions", put(routes::reorder_channels))
         .route(
             "/api/channels/{channel_id}",
             put(routes::update_channel).delete(routes::delete_channel),
         )
+        .route(
+            "/api/categories",
+            get(routes::get_categories).post(routes::create_category),
+        )
+        .route(
+            "/api/categories/{category_id}",
+            put(routes::update_category).delete(routes::delete_category),
+        )
+        .route(
+            "/api/channels/{channel_id}/overwrites/{target_type}/{target_id}",
+            put(routes::set_channel_overwrite).delete(routes::delete_channel_overwrite),
+        )
         .route(
             "/api/users/{user_id}/profile",
             get(auth_routes::get_user_profile),
@@ -192,14 +333,88 @@ async fn main() {
             "/api/channels/{channel_id}/messages",
             post(routes::send_message),
         )
+        .route(
+            "/api/channels/{channel_id}/scheduled-messages",
+            post(routes::schedule_message),
+        )
+        .route(
+            "/api/scheduled-messages",
+            get(routes::get_scheduled_messages),
+        )
+        .route(
+            "/api/scheduled-messages/{scheduled_id}",
+            put(routes::update_scheduled_message).delete(routes::cancel_scheduled_message),
+        )
+        .route("/api/messages/{message_id}", get(routes::get_message_link))
+        .route(
+            "/api/channels/{channel_id}/webhooks",
+            get(routes::get_webhooks).post(routes::create_webhook),
+        )
+        .route("/api/webhooks/{webhook_id}", delete(routes::delete_webhook))
+        .route(
+            "/api/webhooks/{webhook_id}/token",
+            post(routes::rotate_webhook_token),
+        )
+        .route(
+            "/api/webhooks/{webhook_id}/{token}",
+            post(routes::execute_webhook),
+        )
+        .route("/api/channels/{channel_id}/ack", post(routes::ack_channel))
         .route(
             "/api/channels/{channel_id}/messages/{message_id}",
             put(routes::edit_message).delete(routes::delete_message),
         )
+        .route(
+            "/api/channels/{channel_id}/messages/{message_id}/poll/votes/{option_id}",
+            put(routes::vote_poll).delete(routes::unvote_poll),
+        )
+        .route(
+            "/api/channels/{channel_id}/messages/{message_id}/forward",
+            post(routes::forward_message),
+        )
+        .route(
+            "/api/channels/{channel_id}/messages/purge",
+            post(routes::purge_messages),
+        )
+        .route(
+            "/api/channels/{channel_id}/messages/{message_id}/restore",
+            post(routes::restore_message),
+        )
         .route(
             "/api/channels/{channel_id}/messages/{message_id}/reactions/{emoji}",
             put(routes::add_reaction).delete(routes::remove_reaction),
         )
+        .route(
+            "/api/channels/{channel_id}/messages/{message_id}/history",
+            get(routes::get_message_history),
+        )
+        .route(
+            "/api/channels/{channel_id}/messages/{message_id}/pin",
+            put(routes::pin_message).delete(routes::unpin_message),
+        )
+        .route(
+            "/api/channels/{channel_id}/pins",
+            get(routes::get_pinned_messages),
+        )
+        .route(
+            "/api/channels/{channel_id}/messages/{message_id}/thread",
+            post(routes::create_thread),
+        )
+        .route(
+            "/api/channels/{channel_id}/threads",
+            get(routes::get_threads),
+        )
+        .route(
+            "/api/channels/{channel_id}/threads/{thread_id}/messages",
+            get(routes::get_thread_messages).post(routes::send_thread_message),
+        )
+        .route("/api/search", get(routes::search_messages))
+        .route("/api/dms", get(routes::get_dms).post(routes::create_dm))
+        .route("/api/dms/{channel_id}", delete(routes::leave_dm))
+        .route(
+            "/api/dms/{channel_id}/members/{user_id}",
+            put(routes::add_dm_member),
+        )
         .route("/api/voice/join", post(voice::join_voice_channel))
         .route("/api/voice/leave", post(voice::leave_voice_channel))
         .route("/api/voice/states", get(voice::get_all_voice_states))
@@ -237,8 +452,15 @@ async fn main() {
         .route("/api/admin/users", get(admin::get_all_users))
         .route("/api/admin/users/{user_id}", delete(admin::delete_user))
         .route(
-            "/api/admin/users/{user_id}/role",
-            put(admin::change_user_role),
+            "/api/admin/users/{user_id}/roles",
+            put(admin::set_user_roles),
+        )
+        .route("/api/roles", get(admin::list_roles))
+        .route("/api/admin/roles", post(admin::create_role))
+        .route("/api/admin/roles/positions", put(admin::reorder_roles))
+        .route(
+            "/api/admin/roles/{role_id}",
+            put(admin::update_role).delete(admin::delete_role),
         )
         .route("/api/admin/kick", post(admin::kick_user))
         .route("/api/admin/ban", post(admin::ban_user))
@@ -252,6 +474,26 @@ async fn main() {
             get(admin::get_settings).put(admin::update_setting),
         )
         .route("/api/admin/modlog", get(admin::get_moderation_log))
+        .route(
+            "/api/admin/webhooks",
+            get(admin::list_outgoing_webhooks).post(admin::create_outgoing_webhook),
+        )
+        .route(
+            "/api/admin/webhooks/{webhook_id}",
+            put(admin::update_outgoing_webhook).delete(admin::delete_outgoing_webhook),
+        )
+        .route(
+            "/api/admin/webhooks/{webhook_id}/secret",
+            post(admin::rotate_outgoing_webhook_secret),
+        )
+        .route(
+            "/api/admin/webhooks/{webhook_id}/deliveries",
+            get(admin::list_outgoing_webhook_deliveries),
+        )
+        .route(
+            "/api/admin/webhooks/{webhook_id}/deliveries/{delivery_id}/retry",
+            post(admin::retry_outgoing_webhook_delivery),
+        )
         .route(
             "/api/invites",
             get(admin::list_invites).post(admin::create_invite),
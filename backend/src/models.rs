@@ -2,6 +2,7 @@ use chrono::{DateTime, Utc};
 use dashmap::{DashMap, DashSet};
 use serde::{Deserialize, Serialize};
 use sqlx::PgPool;
+use std::collections::{HashMap, HashSet};
 use std::fmt;
 use std::sync::Arc;
 use tokio::sync::broadcast;
@@ -10,10 +11,13 @@ use webauthn_rs::prelude::*;
 
 use object_store::ObjectStore;
 
-use crate::permissions::Role;
+use crate::event_bus::{ClusterEvent, EventBus};
+use crate::gateway::GatewayEvent;
+use crate::permissions::{Overwrite, Permissions};
 use crate::sfu::service::SfuService;
+use crate::shared::AppError;
 use crate::shared::validation::{
-    BROADCAST_CHANNEL_CAPACITY, MESSAGE_RATE_LIMIT, MESSAGE_RATE_REFILL_PER_SEC,
+    BROADCAST_CHANNEL_CAPACITY, MAX_SLOWMODE_SECS, MESSAGE_RATE_LIMIT, MESSAGE_RATE_REFILL_PER_SEC,
 };
 
 #[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
@@ -21,14 +25,105 @@ pub struct Channel {
     pub id: Uuid,
     pub name: String,
     pub channel_type: ChannelType,
+    pub category_id: Option<Uuid>,
+    /// Order within the channel's category.
+    pub position: i32,
+    pub topic: Option<String>,
+    /// Minimum seconds between messages from the same member. 0 disables it.
+    pub slowmode_secs: i32,
+    /// Only members holding one of `announcement_role_ids` may post.
+    pub announcement: bool,
+    pub announcement_role_ids: Vec<Uuid>,
+    #[sqlx(skip)]
+    #[serde(default)]
+    pub overwrites: Vec<PermissionOverwrite>,
+}
+
+/// Collapsible group of server channels.
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct ChannelCategory {
+    pub id: Uuid,
+    pub name: String,
+    pub position: i32,
+}
+
+/// Posting restrictions layered on top of the global message rate limit.
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct ChannelRules {
+    pub slowmode_secs: i32,
+    pub announcement: bool,
+    pub announcement_role_ids: Vec<Uuid>,
+}
+
+/// Where a channel sits in the channel list.
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct ChannelPosition {
+    pub id: Uuid,
+    pub category_id: Option<Uuid>,
+    pub position: i32,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CategoryPosition {
+    pub id: Uuid,
+    pub position: i32,
+}
+
+/// Allow/deny channel permissions for one role or user. Exactly one of
+/// `role_id` and `user_id` is set.
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct PermissionOverwrite {
+    pub channel_id: Uuid,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub role_id: Option<Uuid>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub user_id: Option<Uuid>,
+    pub allow: Permissions,
+    pub deny: Permissions,
+}
+
+impl PermissionOverwrite {
+    pub fn pair(&self) -> Overwrite {
+        Overwrite {
+            allow: self.allow,
+            deny: self.deny,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OverwriteTargetType {
+    Role,
+    User,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct OverwriteRequest {
+    pub allow: Permissions,
+    pub deny: Permissions,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
-#[sqlx(type_name = "text", rename_all = "lowercase")]
-#[serde(rename_all = "lowercase")]
+#[sqlx(type_name = "text", rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
 pub enum ChannelType {
     Text,
     Voice,
+    Dm,
+    GroupDm,
+}
+
+impl ChannelType {
+    /// Private channels are only visible to their members.
+    pub fn is_private(&self) -> bool {
+        matches!(self, Self::Dm | Self::GroupDm)
+    }
+
+    /// Whether users can join a voice call in this channel.
+    pub fn supports_voice(&self) -> bool {
+        matches!(self, Self::Voice | Self::Dm | Self::GroupDm)
+    }
 }
 
 impl fmt::Display for ChannelType {
@@ -36,10 +131,30 @@ impl fmt::Display for ChannelType {
         match self {
             Self::Text => f.write_str("text"),
             Self::Voice => f.write_str("voice"),
+            Self::Dm => f.write_str("dm"),
+            Self::GroupDm => f.write_str("group_dm"),
         }
     }
 }
 
+/// A direct message channel as seen by one of its members.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DmChannel {
+    pub id: Uuid,
+    pub channel_type: ChannelType,
+    /// Optional name for group DMs. 1:1 DMs are named after the other member.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    pub members: Vec<MemberInfo>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateDmRequest {
+    pub user_ids: Vec<Uuid>,
+    pub name: Option<String>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Message {
     pub id: Uuid,
@@ -62,6 +177,54 @@ pub struct Message {
     pub link_previews: Option<Vec<LinkPreview>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub attachments: Option<Vec<Attachment>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub thread_id: Option<Uuid>,
+    /// Users mentioned by this message (@username, @role, @here).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mentions: Option<Vec<Uuid>>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub mention_everyone: bool,
+    /// Thread anchored to this message, if any (reply count and last activity).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub thread: Option<Thread>,
+    #[serde(skip_serializing_if = "MessageKind::is_default")]
+    pub kind: MessageKind,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pinned_at: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pinned_by: Option<Uuid>,
+    /// Only set on tombstones, which are shown to moderators.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub deleted_at: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub deleted_by: Option<Uuid>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub poll: Option<Poll>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub forwarded_from: Option<ForwardedFrom>,
+    /// Set when the message was posted through an incoming webhook.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub webhook: Option<WebhookAuthor>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub embeds: Option<Vec<Embed>>,
+}
+
+/// System messages are posted by the server on behalf of `author_id`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[sqlx(type_name = "text", rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum MessageKind {
+    #[default]
+    Default,
+    /// System message noting that the author pinned the message in
+    /// `reply_to_id`.
+    PinNotice,
+}
+
+impl MessageKind {
+    pub fn is_default(&self) -> bool {
+        *self == Self::Default
+    }
 }
 
 impl Message {
@@ -88,8 +251,152 @@ impl Message {
             reactions: None,
             link_previews: None,
             attachments: None,
+            thread_id: None,
+            thread: None,
+            mentions: None,
+            mention_everyone: false,
+            kind: MessageKind::Default,
+            pinned_at: None,
+            pinned_by: None,
+            deleted_at: None,
+            deleted_by: None,
+            poll: None,
+            forwarded_from: None,
+            webhook: None,
+            embeds: None,
         }
     }
+
+    /// Broadcast topic for events about this message: the thread if it is a
+    /// thread reply, otherwise the channel.
+    pub fn topic_id(&self) -> Uuid {
+        self.thread_id.unwrap_or(self.channel_id)
+    }
+}
+
+/// Where a page of message history starts, keyed on message id.
+#[derive(Debug, Clone, Copy)]
+pub enum MessageCursor {
+    /// The newest messages.
+    Latest,
+    /// Messages older than this one.
+    Before(Uuid),
+    /// Messages newer than this one.
+    After(Uuid),
+    /// This message with older and newer messages either side of it.
+    Around(Uuid),
+}
+
+impl MessageCursor {
+    pub fn message_id(self) -> Option<Uuid> {
+        match self {
+            MessageCursor::Latest => None,
+            MessageCursor::Before(id) | MessageCursor::After(id) | MessageCursor::Around(id) => {
+                Some(id)
+            }
+        }
+    }
+}
+
+/// Messages in chronological order, and whether history continues past
+/// either end of the page.
+#[derive(Debug, Serialize)]
+pub struct MessagePage {
+    pub messages: Vec<Message>,
+    pub has_more_before: bool,
+    pub has_more_after: bool,
+}
+
+/// A message resolved from its permalink, with the page of history around
+/// it in its channel or thread.
+#[derive(Debug, Serialize)]
+pub struct MessageLink {
+    pub message: Message,
+    pub context: MessagePage,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct Thread {
+    pub id: Uuid,
+    pub channel_id: Uuid,
+    pub parent_message_id: Uuid,
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub created_by: Option<Uuid>,
+    pub reply_count: i32,
+    pub last_activity_at: DateTime<Utc>,
+    pub archived: bool,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateThreadRequest {
+    pub name: Option<String>,
+}
+
+/// A user's read position and unread counters for one channel.
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct ChannelReadState {
+    pub channel_id: Uuid,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_read_message_id: Option<Uuid>,
+    /// Channel messages only, capped at `MAX_UNREAD_COUNT`.
+    pub unread_count: i64,
+    /// Capped at `MAX_UNREAD_COUNT` like `unread_count`.
+    pub mention_count: i64,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AckRequest {
+    pub message_id: Uuid,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SearchQuery {
+    pub q: Option<String>,
+    pub channel_id: Option<Uuid>,
+    pub author_id: Option<Uuid>,
+    pub before: Option<DateTime<Utc>>,
+    pub after: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub has_attachment: bool,
+    #[serde(default)]
+    pub has_link: bool,
+    #[serde(default)]
+    pub mentions_me: bool,
+    pub limit: Option<i64>,
+    pub offset: Option<i64>,
+}
+
+/// Filters for a bulk purge. Every filter given must match; the link and
+/// attachment filters match messages with either when both are set.
+#[derive(Debug, Deserialize)]
+pub struct PurgeMessagesRequest {
+    /// Purge at most this many of the newest matching messages.
+    pub limit: Option<i64>,
+    pub author_id: Option<Uuid>,
+    pub before: Option<DateTime<Utc>>,
+    pub after: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub has_link: bool,
+    #[serde(default)]
+    pub has_attachment: bool,
+    pub reason: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PurgeMessagesResponse {
+    pub deleted: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SearchResult {
+    #[serde(flatten)]
+    pub message: Message,
+    /// HTML-escaped excerpt of the content with matched terms wrapped in
+    /// `<mark>` tags. Absent when the search had no text query.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub highlight: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
@@ -136,6 +443,26 @@ pub struct ReplyPreview {
     pub content: String,
 }
 
+/// Where a forwarded message was first posted. The ids are cleared if the
+/// original message, its channel or its author is later removed.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ForwardedFrom {
+    pub message_id: Option<Uuid>,
+    pub channel_id: Option<Uuid>,
+    pub author_id: Option<Uuid>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub username: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub display_name: Option<String>,
+    pub timestamp: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ForwardMessageRequest {
+    /// Channel to post the forward into.
+    pub channel_id: Uuid,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Reaction {
     pub emoji: String,
@@ -156,6 +483,9 @@ pub struct LinkPreview {
     pub image_url: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub site_name: Option<String>,
+    /// Set when the preview is a quote of a message on this server.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub quoted_message_id: Option<Uuid>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
@@ -177,6 +507,286 @@ pub struct SendMessageRequest {
     pub reply_to_id: Option<Uuid>,
     #[serde(default)]
     pub attachment_ids: Vec<Uuid>,
+    pub poll: Option<CreatePollRequest>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreatePollRequest {
+    pub question: String,
+    pub options: Vec<String>,
+    #[serde(default)]
+    pub allow_multiple: bool,
+    #[serde(default)]
+    pub anonymous: bool,
+    /// Close the poll this many minutes after posting. Polls without a
+    /// duration stay open.
+    pub duration_minutes: Option<i64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Poll {
+    pub message_id: Uuid,
+    pub question: String,
+    pub options: Vec<PollOption>,
+    pub allow_multiple: bool,
+    pub anonymous: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub closed_at: Option<DateTime<Utc>>,
+    /// Distinct members who voted.
+    pub total_voters: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PollOption {
+    pub id: Uuid,
+    pub text: String,
+    pub votes: i64,
+    /// Whether the requesting user voted for this option.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub voted: bool,
+    /// Who voted for this option. Omitted on anonymous polls.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub voters: Option<Vec<Uuid>>,
+}
+
+impl Poll {
+    pub fn new(message_id: Uuid, request: CreatePollRequest, now: DateTime<Utc>) -> Self {
+        Self {
+            message_id,
+            question: request.question,
+            options: request
+                .options
+                .into_iter()
+                .map(|text| PollOption {
+                    id: Uuid::now_v7(),
+                    text,
+                    votes: 0,
+                    voted: false,
+                    voters: (!request.anonymous).then(Vec::new),
+                })
+                .collect(),
+            allow_multiple: request.allow_multiple,
+            anonymous: request.anonymous,
+            expires_at: request
+                .duration_minutes
+                .map(|minutes| now + chrono::Duration::minutes(minutes)),
+            closed_at: None,
+            total_voters: 0,
+        }
+    }
+
+    pub fn is_open(&self, now: DateTime<Utc>) -> bool {
+        self.closed_at.is_none() && self.expires_at.is_none_or(|at| at > now)
+    }
+
+    /// Copy for broadcasting to the whole channel, without the requesting
+    /// user's own votes.
+    pub fn for_broadcast(&self) -> Self {
+        let mut poll = self.clone();
+        for option in &mut poll.options {
+            option.voted = false;
+        }
+        poll
+    }
+}
+
+/// A message queued by its author to post at `send_at`.
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct ScheduledMessage {
+    pub id: Uuid,
+    pub author_id: Uuid,
+    pub channel_id: Uuid,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub content: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reply_to_id: Option<Uuid>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub attachment_ids: Vec<Uuid>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[sqlx(json(nullable))]
+    pub poll: Option<CreatePollRequest>,
+    pub send_at: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+    /// Why delivery failed. Failed messages stay queued until rescheduled
+    /// or cancelled.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub failure: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ScheduleMessageRequest {
+    pub content: Option<String>,
+    pub reply_to_id: Option<Uuid>,
+    #[serde(default)]
+    pub attachment_ids: Vec<Uuid>,
+    pub poll: Option<CreatePollRequest>,
+    pub send_at: DateTime<Utc>,
+}
+
+/// Omitted fields keep their current value. Rescheduling clears a failure.
+#[derive(Debug, Deserialize)]
+pub struct UpdateScheduledMessageRequest {
+    pub content: Option<String>,
+    pub send_at: Option<DateTime<Utc>>,
+}
+
+/// An incoming webhook that posts into one channel. The token is only
+/// returned when the webhook is created or its token is rotated.
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct Webhook {
+    pub id: Uuid,
+    pub channel_id: Uuid,
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub avatar_url: Option<String>,
+    pub created_by: Uuid,
+    pub created_at: DateTime<Utc>,
+    #[sqlx(skip)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateWebhookRequest {
+    pub name: String,
+    pub avatar_url: Option<String>,
+}
+
+/// Body of a webhook post. `username` and `avatar_url` override the
+/// webhook's own name and avatar for this message only.
+#[derive(Debug, Deserialize)]
+pub struct ExecuteWebhookRequest {
+    pub content: Option<String>,
+    pub username: Option<String>,
+    pub avatar_url: Option<String>,
+    #[serde(default)]
+    pub embeds: Vec<Embed>,
+}
+
+/// Who a webhook message was posted as. `id` is cleared if the webhook is
+/// deleted.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WebhookAuthor {
+    pub id: Option<Uuid>,
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub avatar_url: Option<String>,
+}
+
+/// Rich content block attached to a webhook message.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Embed {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    /// RGB color of the embed's accent bar.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub color: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub image_url: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub fields: Vec<EmbedField>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EmbedField {
+    pub name: String,
+    pub value: String,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub inline: bool,
+}
+
+/// Admin-configured endpoint that receives signed copies of gateway events.
+/// The signing `secret` is only returned when the webhook is created or its
+/// secret is rotated.
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct OutgoingWebhook {
+    pub id: Uuid,
+    pub name: String,
+    pub url: String,
+    pub event_types: Vec<String>,
+    pub enabled: bool,
+    pub created_by: Option<Uuid>,
+    pub created_at: DateTime<Utc>,
+    #[sqlx(skip)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub secret: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateOutgoingWebhookRequest {
+    pub name: String,
+    pub url: String,
+    pub event_types: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateOutgoingWebhookRequest {
+    pub name: Option<String>,
+    pub url: Option<String>,
+    pub event_types: Option<Vec<String>>,
+    pub enabled: Option<bool>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[sqlx(type_name = "text", rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum DeliveryStatus {
+    /// Not yet delivered; retried at `next_attempt_at`.
+    Pending,
+    Succeeded,
+    /// Gave up after the last retry.
+    Failed,
+}
+
+/// One event sent to an outgoing webhook. `status_code` and `error`
+/// describe the most recent attempt.
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct OutgoingWebhookDelivery {
+    pub id: Uuid,
+    pub webhook_id: Uuid,
+    pub event_type: String,
+    pub status: DeliveryStatus,
+    pub attempts: i32,
+    pub status_code: Option<i32>,
+    pub error: Option<String>,
+    pub next_attempt_at: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+    pub completed_at: Option<DateTime<Utc>>,
+}
+
+/// A claimed delivery with what is needed to send it.
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct PendingDelivery {
+    pub id: Uuid,
+    pub webhook_id: Uuid,
+    pub event_type: String,
+    pub payload: String,
+    pub attempts: i32,
+    pub url: String,
+    pub secret: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DeliveryLogQuery {
+    pub before: Option<Uuid>,
+    pub limit: Option<i64>,
+}
+
+/// Content a message had before an edit replaced it.
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct MessageRevision {
+    pub id: Uuid,
+    pub message_id: Uuid,
+    pub content: String,
+    /// When this content was posted or last edited in.
+    pub created_at: DateTime<Utc>,
+    pub replaced_at: DateTime<Utc>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -211,6 +821,64 @@ pub struct LeaveVoiceRequest {
     pub channel_id: Uuid,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[sqlx(type_name = "text", rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum PresenceStatus {
+    Online,
+    Idle,
+    Dnd,
+    Invisible,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CustomStatus {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub emoji: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+/// Persisted presence choice of a user.
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct PresenceSettings {
+    pub status: PresenceStatus,
+    pub custom_status_text: Option<String>,
+    pub custom_status_emoji: Option<String>,
+    pub custom_status_expires_at: Option<DateTime<Utc>>,
+}
+
+impl PresenceSettings {
+    /// The custom status, unless it is unset or has expired.
+    pub fn custom_status(&self) -> Option<CustomStatus> {
+        if self.custom_status_text.is_none() && self.custom_status_emoji.is_none() {
+            return None;
+        }
+        if self
+            .custom_status_expires_at
+            .is_some_and(|expires_at| expires_at <= Utc::now())
+        {
+            return None;
+        }
+        Some(CustomStatus {
+            text: self.custom_status_text.clone(),
+            emoji: self.custom_status_emoji.clone(),
+            expires_at: self.custom_status_expires_at,
+        })
+    }
+
+    /// Status shown to others: an online user the client reported as
+    /// inactive appears idle. Other explicit choices win over auto-idle.
+    pub fn effective_status(&self, auto_idle: bool) -> PresenceStatus {
+        match self.status {
+            PresenceStatus::Online if auto_idle => PresenceStatus::Idle,
+            status => status,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct UserPresence {
     pub user_id: Uuid,
@@ -219,18 +887,65 @@ pub struct UserPresence {
     pub display_name: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub avatar_url: Option<String>,
+    /// Effective status, including auto-idle.
+    pub status: PresenceStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub custom_status: Option<CustomStatus>,
     pub connected_at: DateTime<Utc>,
 }
 
+impl UserPresence {
+    /// Invisible users appear offline to everyone but themselves.
+    pub fn is_visible_to(&self, viewer_id: Uuid) -> bool {
+        self.status != PresenceStatus::Invisible || self.user_id == viewer_id
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdatePresenceRequest {
+    pub status: PresenceStatus,
+    pub custom_status: Option<CustomStatus>,
+}
+
 #[derive(Debug, Deserialize)]
 pub struct CreateChannelRequest {
     pub name: String,
     pub channel_type: ChannelType,
+    pub category_id: Option<Uuid>,
 }
 
 #[derive(Debug, Deserialize)]
 pub struct UpdateChannelRequest {
     pub name: String,
+    /// Omitted settings are left unchanged. An empty topic clears it.
+    pub topic: Option<String>,
+    pub slowmode_secs: Option<i32>,
+    pub announcement: Option<bool>,
+    pub announcement_role_ids: Option<Vec<Uuid>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CategoryRequest {
+    pub name: String,
+}
+
+/// Moves any number of categories and channels in one update. Items not
+/// listed keep their position.
+#[derive(Debug, Deserialize)]
+pub struct ReorderChannelsRequest {
+    #[serde(default)]
+    pub categories: Vec<CategoryPosition>,
+    #[serde(default)]
+    pub channels: Vec<ChannelPosition>,
+}
+
+/// What happens to a deleted category's channels: they move to `move_to`
+/// (or become uncategorized), or are deleted with it.
+#[derive(Debug, Default, Deserialize)]
+pub struct DeleteCategoryQuery {
+    #[serde(default)]
+    pub delete_channels: bool,
+    pub move_to: Option<Uuid>,
 }
 
 // --- Admin / Moderation models ---
@@ -240,7 +955,8 @@ pub struct UserSummary {
     pub id: Uuid,
     pub username: String,
     pub email: String,
-    pub role: Role,
+    pub is_owner: bool,
+    pub role_ids: Vec<Uuid>,
     pub created_at: DateTime<Utc>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub avatar_url: Option<String>,
@@ -253,11 +969,45 @@ pub struct MemberInfo {
     pub username: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub display_name: Option<String>,
-    pub role: Role,
+    pub is_owner: bool,
+    /// Roles held besides the default role.
+    pub role_ids: Vec<Uuid>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub avatar_url: Option<String>,
 }
 
+/// Admin-defined role. Every user holds the default role implicitly.
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct Role {
+    pub id: Uuid,
+    pub name: String,
+    /// `0xRRGGBB`, or None for the default name color.
+    pub color: Option<i32>,
+    /// Higher positions outrank lower ones. The default role is always 0.
+    pub position: i32,
+    pub permissions: Permissions,
+    pub is_default: bool,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RoleRequest {
+    pub name: String,
+    pub color: Option<i32>,
+    pub permissions: Permissions,
+}
+
+/// Non-default role ids from lowest to highest position.
+#[derive(Debug, Deserialize)]
+pub struct ReorderRolesRequest {
+    pub role_ids: Vec<Uuid>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SetUserRolesRequest {
+    pub role_ids: Vec<Uuid>,
+}
+
 pub fn avatar_url_from_path(user_id: Uuid, path: &Option<String>) -> Option<String> {
     path.as_ref()
         .map(|_| format!("/api/users/{}/avatar", user_id))
@@ -312,6 +1062,9 @@ pub enum ModAction {
     Unmute,
     RoleChange,
     DeleteUser,
+    PinMessage,
+    UnpinMessage,
+    PurgeMessages,
 }
 
 impl fmt::Display for ModAction {
@@ -324,6 +1077,9 @@ impl fmt::Display for ModAction {
             Self::Unmute => f.write_str("unmute"),
             Self::RoleChange => f.write_str("role_change"),
             Self::DeleteUser => f.write_str("delete_user"),
+            Self::PinMessage => f.write_str("pin_message"),
+            Self::UnpinMessage => f.write_str("unpin_message"),
+            Self::PurgeMessages => f.write_str("purge_messages"),
         }
     }
 }
@@ -333,7 +1089,8 @@ pub struct ModLogEntry {
     pub id: Uuid,
     pub action: ModAction,
     pub moderator_id: Uuid,
-    pub target_user_id: Uuid,
+    /// Absent for actions without a single target, such as a bulk purge.
+    pub target_user_id: Option<Uuid>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub reason: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -345,7 +1102,7 @@ impl ModLogEntry {
     pub fn new(
         action: ModAction,
         moderator_id: Uuid,
-        target_user_id: Uuid,
+        target_user_id: Option<Uuid>,
         reason: Option<String>,
         details: Option<String>,
     ) -> Self {
@@ -381,11 +1138,6 @@ pub struct KickRequest {
     pub reason: Option<String>,
 }
 
-#[derive(Debug, Deserialize)]
-pub struct RoleChangeRequest {
-    pub role: Role,
-}
-
 #[derive(Debug, Deserialize)]
 pub struct CreateInviteRequest {
     pub max_uses: Option<i32>,
@@ -413,6 +1165,9 @@ pub struct AppState {
     pub voice_states: DashMap<Uuid, DashMap<Uuid, VoiceState>>,
     pub sfu_service: Arc<SfuService>,
     pub message_rate_limits: DashMap<Uuid, RateLimitState>,
+    /// When each member last posted in a slowmode channel, keyed by
+    /// (channel, user).
+    pub slowmode_last_post: DashMap<(Uuid, Uuid), std::time::Instant>,
     pub webauthn: Arc<Webauthn>,
     pub webauthn_reg_state: DashMap<Uuid, (PasskeyRegistration, std::time::Instant)>,
     pub webauthn_auth_state: DashMap<String, (Uuid, PasskeyAuthentication, std::time::Instant)>,
@@ -421,6 +1176,27 @@ pub struct AppState {
     pub banned_users: DashSet<Uuid>,
     /// In-memory cache of currently muted user IDs.
     pub muted_users: DashSet<Uuid>,
+    /// In-memory cache of who can see private channels: DM members, and
+    /// the viewers of server channels restricted by permission overwrites.
+    /// Channels absent from this map are public. Avoids per-event DB queries
+    /// when checking access and routing WS events to participants.
+    pub private_channel_members: DashMap<Uuid, DashSet<Uuid>>,
+    /// Per-user broadcast channels for events targeted at specific users.
+    /// Every WS connection of a user subscribes to that user's sender.
+    pub user_broadcasts: DashMap<Uuid, broadcast::Sender<String>>,
+    /// Gateway sessions whose connection dropped and which can still be
+    /// resumed. Keyed by session id.
+    pub ws_sessions: DashMap<Uuid, crate::websocket::ParkedSession>,
+    /// Identifies this process on the event bus.
+    pub node_id: Uuid,
+    /// Relays broadcasts and shared state changes to other backend nodes.
+    pub event_bus: Arc<dyn EventBus>,
+    /// Nodes holding a connection for each online user. A user goes offline
+    /// only when the last node drops them.
+    pub presence_nodes: DashMap<Uuid, DashSet<Uuid>>,
+    /// Event types each enabled outgoing webhook subscribes to. Checked on
+    /// every broadcast, so it is cached rather than queried.
+    pub outgoing_webhook_events: DashMap<Uuid, Vec<String>>,
 }
 
 impl AppState {
@@ -430,6 +1206,7 @@ impl AppState {
         http_client: reqwest::Client,
         file_store: Option<Arc<dyn ObjectStore>>,
         webauthn: Arc<Webauthn>,
+        event_bus: Arc<dyn EventBus>,
     ) -> Self {
         let (global_tx, _) = broadcast::channel(BROADCAST_CHANNEL_CAPACITY);
         Self {
@@ -442,11 +1219,19 @@ impl AppState {
             voice_states: DashMap::new(),
             sfu_service: Arc::new(sfu_service),
             message_rate_limits: DashMap::new(),
+            slowmode_last_post: DashMap::new(),
             webauthn,
             webauthn_reg_state: DashMap::new(),
             webauthn_auth_state: DashMap::new(),
             banned_users: DashSet::new(),
             muted_users: DashSet::new(),
+            private_channel_members: DashMap::new(),
+            user_broadcasts: DashMap::new(),
+            ws_sessions: DashMap::new(),
+            node_id: Uuid::new_v4(),
+            event_bus,
+            presence_nodes: DashMap::new(),
+            outgoing_webhook_events: DashMap::new(),
         }
     }
 
@@ -459,22 +1244,160 @@ impl AppState {
     }
 
     pub fn cache_ban(&self, user_id: Uuid) {
-        self.banned_users.insert(user_id);
+        self.emit(ClusterEvent::BanCached { user_id });
     }
 
     pub fn uncache_ban(&self, user_id: Uuid) {
-        self.banned_users.remove(&user_id);
+        self.emit(ClusterEvent::BanUncached { user_id });
     }
 
     pub fn cache_mute(&self, user_id: Uuid) {
-        self.muted_users.insert(user_id);
+        self.emit(ClusterEvent::MuteCached { user_id });
     }
 
     pub fn uncache_mute(&self, user_id: Uuid) {
-        self.muted_users.remove(&user_id);
+        self.emit(ClusterEvent::MuteUncached { user_id });
+    }
+
+    /// Update the cached subscriptions of an outgoing webhook. `None` stops
+    /// deliveries to it.
+    pub fn cache_outgoing_webhook(&self, webhook_id: Uuid, event_types: Option<Vec<String>>) {
+        self.emit(ClusterEvent::OutgoingWebhookSet {
+            webhook_id,
+            event_types,
+        });
+    }
+
+    /// Rebuild the ban, mute, private channel and outgoing webhook caches
+    /// from the database. Every node does this itself at startup and after
+    /// its event bus reconnects, when updates from other nodes may have been
+    /// missed, so nothing is published. Entries are overwritten before stale
+    /// ones are dropped, so a private channel is never briefly public.
+    pub async fn reload_caches(&self) -> Result<(), AppError> {
+        let bans: HashSet<Uuid> = crate::database::get_all_bans(&self.db)
+            .await?
+            .into_iter()
+            .map(|ban| ban.user_id)
+            .collect();
+        let mutes: HashSet<Uuid> = crate::database::get_all_mutes(&self.db)
+            .await?
+            .into_iter()
+            .map(|mute| mute.user_id)
+            .collect();
+
+        let mut private_members: HashMap<Uuid, DashSet<Uuid>> = HashMap::new();
+        for (channel_id, user_id) in
+            crate::database::get_all_private_channel_members(&self.db).await?
+        {
+            private_members
+                .entry(channel_id)
+                .or_default()
+                .insert(user_id);
+        }
+        for (channel_id, viewers) in
+            crate::services::channel_access::restricted_channel_viewers(self).await?
+        {
+            private_members.insert(channel_id, viewers.into_iter().collect());
+        }
+
+        let webhooks: HashMap<Uuid, Vec<String>> = crate::database::get_outgoing_webhooks(&self.db)
+            .await?
+            .into_iter()
+            .filter(|webhook| webhook.enabled)
+            .map(|webhook| (webhook.id, webhook.event_types))
+            .collect();
+
+        for &user_id in &bans {
+            self.banned_users.insert(user_id);
+        }
+        self.banned_users.retain(|id| bans.contains(id));
+        for &user_id in &mutes {
+            self.muted_users.insert(user_id);
+        }
+        self.muted_users.retain(|id| mutes.contains(id));
+
+        let channel_ids: HashSet<Uuid> = private_members.keys().copied().collect();
+        for (channel_id, members) in private_members {
+            self.private_channel_members.insert(channel_id, members);
+        }
+        self.private_channel_members
+            .retain(|id, _| channel_ids.contains(id));
+
+        self.outgoing_webhook_events
+            .retain(|id, _| webhooks.contains_key(id));
+        for (webhook_id, event_types) in webhooks {
+            self.outgoing_webhook_events.insert(webhook_id, event_types);
+        }
+        Ok(())
     }
 
-    /// Returns true if the user is allowed to send a message, false if rate-limited.
+    pub fn cache_private_channel_member(&self, channel_id: Uuid, user_id: Uuid) {
+        self.emit(ClusterEvent::PrivateMemberAdded {
+            channel_id,
+            user_id,
+        });
+    }
+
+    pub fn uncache_private_channel_member(&self, channel_id: Uuid, user_id: Uuid) {
+        self.emit(ClusterEvent::PrivateMemberRemoved {
+            channel_id,
+            user_id,
+        });
+    }
+
+    /// Close a deleted channel's broadcast topic and drop its private
+    /// membership cache.
+    pub fn forget_channel(&self, channel_id: Uuid) {
+        self.emit(ClusterEvent::ChannelRemoved { channel_id });
+    }
+
+    /// Public channels are accessible to everyone; private channels only to
+    /// their members.
+    pub fn can_access_channel(&self, channel_id: Uuid, user_id: Uuid) -> bool {
+        self.private_channel_members
+            .get(&channel_id)
+            .is_none_or(|members| members.contains(&user_id))
+    }
+
+    /// Private channels the user cannot see, for filtering queries that span
+    /// channels.
+    pub fn hidden_channel_ids(&self, user_id: Uuid) -> Vec<Uuid> {
+        self.private_channel_members
+            .iter()
+            .filter(|members| !members.contains(&user_id))
+            .map(|members| *members.key())
+            .collect()
+    }
+
+    /// True if everyone who can read `channel_id` can also read `source_id`,
+    /// so content copied from the source cannot leak to a wider audience.
+    pub fn audience_can_see(&self, channel_id: Uuid, source_id: Uuid) -> bool {
+        let Some(source_viewers) = self.private_channel_members.get(&source_id) else {
+            return true;
+        };
+        self.private_channel_members
+            .get(&channel_id)
+            .is_some_and(|viewers| viewers.iter().all(|id| source_viewers.contains(&*id)))
+    }
+
+    /// Replace the set of members who can view a server channel; None makes
+    /// it visible to everyone.
+    pub fn set_channel_viewers(&self, channel_id: Uuid, user_ids: Option<Vec<Uuid>>) {
+        self.emit(ClusterEvent::ChannelViewersSet {
+            channel_id,
+            user_ids,
+        });
+    }
+
+    /// Members of a private channel, or None if the channel is public.
+    pub fn private_channel_member_ids(&self, channel_id: Uuid) -> Option<Vec<Uuid>> {
+        self.private_channel_members
+            .get(&channel_id)
+            .map(|members| members.iter().map(|id| *id).collect())
+    }
+
+    /// Returns true if the user or webhook is allowed to send a message, false
+    /// if rate-limited.
     pub fn check_message_rate_limit(&self, user_id: Uuid) -> bool {
         let now = std::time::Instant::now();
         let mut entry = self
@@ -498,24 +1421,117 @@ impl AppState {
         }
     }
 
-    pub fn broadcast_global(&self, event_type: &str, data: serde_json::Value) {
-        let msg = serde_json::json!({
-            "type": event_type,
-            "data": data,
-        });
-        let _ = self.global_broadcast.send(msg.to_string());
+    /// Reserves a post in a slowmode channel. Returns the seconds left to
+    /// wait if the member posted there less than `interval_secs` ago. The
+    /// reservation must be released if the post is not persisted.
+    pub fn check_slowmode(
+        &self,
+        channel_id: Uuid,
+        user_id: Uuid,
+        interval_secs: i32,
+    ) -> Option<u64> {
+        if interval_secs <= 0 {
+            return None;
+        }
+        let now = std::time::Instant::now();
+        let interval = std::time::Duration::from_secs(interval_secs as u64);
+        let mut last_post = self
+            .slowmode_last_post
+            .entry((channel_id, user_id))
+            .or_insert(now);
+
+        let elapsed = now.duration_since(*last_post);
+        if *last_post != now && elapsed < interval {
+            return Some((interval - elapsed).as_secs_f64().ceil() as u64);
+        }
+        *last_post = now;
+        None
     }
 
-    pub fn broadcast_channel(&self, channel_id: Uuid, event_type: &str, data: serde_json::Value) {
-        let msg = serde_json::json!({
-            "type": event_type,
-            "data": data,
-        });
-        if let Some(tx) = self.channel_broadcasts.get(&channel_id) {
-            let _ = tx.send(msg.to_string());
+    /// Forget slowmode posts older than the longest slowmode interval; they
+    /// can no longer hold anyone back.
+    pub fn prune_slowmode(&self) {
+        let max_interval = std::time::Duration::from_secs(MAX_SLOWMODE_SECS as u64);
+        self.slowmode_last_post
+            .retain(|_, last_post| last_post.elapsed() < max_interval);
+    }
+
+    /// Give back a slowmode reservation whose post failed. The previous post
+    /// was at least an interval ago, so forgetting it is equivalent.
+    pub fn release_slowmode(&self, channel_id: Uuid, user_id: Uuid) {
+        self.slowmode_last_post.remove(&(channel_id, user_id));
+    }
+
+    pub fn broadcast_global(&self, event: GatewayEvent) {
+        let payload = event.to_json();
+        crate::services::outgoing_webhook::queue_event(self, None, &payload);
+        self.emit(ClusterEvent::Global { payload });
+    }
+
+    pub fn broadcast_channel(&self, channel_id: Uuid, event: GatewayEvent) {
+        self.broadcast_topic(channel_id, channel_id, event);
+    }
+
+    /// Broadcast to subscribers of a topic in `channel_id`: the channel
+    /// itself or one of its threads.
+    pub fn broadcast_topic(&self, channel_id: Uuid, topic_id: Uuid, event: GatewayEvent) {
+        let payload = event.to_json();
+        crate::services::outgoing_webhook::queue_event(self, Some(channel_id), &payload);
+        self.emit(ClusterEvent::Channel { topic_id, payload });
+    }
+
+    pub fn subscribe_user(&self, user_id: Uuid) -> broadcast::Receiver<String> {
+        self.user_broadcasts
+            .entry(user_id)
+            .or_insert_with(|| broadcast::channel(BROADCAST_CHANNEL_CAPACITY).0)
+            .subscribe()
+    }
+
+    /// Drop the user's broadcast sender once their last connection is gone.
+    pub fn release_user_broadcast(&self, user_id: Uuid) {
+        self.user_broadcasts
+            .remove_if(&user_id, |_, tx| tx.receiver_count() == 0);
+    }
+
+    pub fn broadcast_user(&self, user_id: Uuid, event: GatewayEvent) {
+        self.send_to_user(user_id, event.to_json());
+    }
+
+    fn send_to_user(&self, user_id: Uuid, payload: String) {
+        self.emit(ClusterEvent::User { user_id, payload });
+    }
+
+    /// Broadcast an event about a channel to everyone who can see it:
+    /// globally for public channels, only to members for private ones.
+    pub fn broadcast_for_channel(&self, channel_id: Uuid, event: GatewayEvent) {
+        match self.private_channel_member_ids(channel_id) {
+            Some(member_ids) => {
+                let msg = event.to_json();
+                for member_id in member_ids {
+                    self.send_to_user(member_id, msg.clone());
+                }
+            }
+            None => self.broadcast_global(event),
         }
     }
 
+    /// Voice states of every channel the user can access.
+    pub fn visible_voice_states(&self, user_id: Uuid) -> Vec<VoiceState> {
+        self.all_voice_states()
+            .into_iter()
+            .filter(|vs| self.can_access_channel(vs.channel_id, user_id))
+            .collect()
+    }
+
+    /// Online users as seen by `viewer_id`, hiding other invisible users.
+    pub fn visible_online_users(&self, viewer_id: Uuid) -> Vec<UserPresence> {
+        self.online_users
+            .iter()
+            .filter(|entry| entry.is_visible_to(viewer_id))
+            .map(|entry| entry.value().clone())
+            .collect()
+    }
+
     pub fn all_voice_states(&self) -> Vec<VoiceState> {
         // Collect outer keys first, then iterate one at a time to avoid
         // holding nested DashMap shard locks simultaneously.
@@ -532,26 +1548,324 @@ impl AppState {
     /// Remove a user from all voice channels, close their SFU connections,
     /// and broadcast departure events.
     pub async fn remove_user_from_voice(&self, user_id: Uuid) {
-        let mut left_channels = Vec::new();
-        for mut channel_entry in self.voice_states.iter_mut() {
-            let channel_id = *channel_entry.key();
-            if channel_entry.value_mut().remove(&user_id).is_some() {
-                left_channels.push(channel_id);
-            }
+        let left_channels: Vec<Uuid> = self
+            .voice_states
+            .iter()
+            .filter(|channel_users| channel_users.contains_key(&user_id))
+            .map(|channel_users| *channel_users.key())
+            .collect();
+        for channel_id in left_channels {
+            self.leave_voice(channel_id, user_id).await;
+        }
+    }
+
+    /// Remove a user from one voice channel, close their SFU connections
+    /// there, and broadcast the departure.
+    pub async fn leave_voice(&self, channel_id: Uuid, user_id: Uuid) {
+        self.emit(ClusterEvent::VoiceStateRemoved {
+            channel_id,
+            user_id,
+        });
+        self.sfu_service
+            .close_user_connections(channel_id, user_id)
+            .await;
+        self.broadcast_for_channel(
+            channel_id,
+            GatewayEvent::VoiceUserLeft {
+                user_id,
+                channel_id,
+            },
+        );
+    }
+
+    pub fn set_voice_state(&self, voice_state: VoiceState) {
+        self.emit(ClusterEvent::VoiceStateSet { voice_state });
+    }
+
+    /// Mark the user as connected to this node.
+    pub fn set_online(&self, presence: UserPresence) {
+        self.emit(ClusterEvent::PresenceOnline { presence });
+    }
+
+    /// Mark the user as no longer connected to this node. Returns true if
+    /// they have no connection left on any node.
+    pub fn set_offline(&self, user_id: Uuid) -> bool {
+        self.emit(ClusterEvent::PresenceOffline { user_id });
+        !self.online_users.contains_key(&user_id)
+    }
+
+    /// Replace the presence of a user who is already online.
+    pub fn update_presence(&self, presence: UserPresence) {
+        self.emit(ClusterEvent::PresenceUpdated { presence });
+    }
+
+    pub fn set_cached_display_name(&self, user_id: Uuid, display_name: Option<String>) {
+        self.update_cached_profile(user_id, |name, _| *name = display_name.clone());
+    }
+
+    pub fn set_cached_avatar_url(&self, user_id: Uuid, avatar_url: Option<String>) {
+        self.update_cached_profile(user_id, |_, avatar| *avatar = avatar_url.clone());
+    }
+
+    /// Apply a profile change to the user's presence and voice states.
+    fn update_cached_profile(
+        &self,
+        user_id: Uuid,
+        update: impl Fn(&mut Option<String>, &mut Option<String>),
+    ) {
+        let presence = self.online_users.get(&user_id).map(|presence| {
+            let mut presence = presence.clone();
+            update(&mut presence.display_name, &mut presence.avatar_url);
+            presence
+        });
+        if let Some(presence) = presence {
+            self.emit(ClusterEvent::PresenceUpdated { presence });
         }
-        for channel_id in &left_channels {
-            self.voice_states
-                .remove_if(channel_id, |_, users| users.is_empty());
+
+        let voice_states: Vec<VoiceState> = self
+            .all_voice_states()
+            .into_iter()
+            .filter(|vs| vs.user_id == user_id)
+            .collect();
+        for mut voice_state in voice_states {
+            update(&mut voice_state.display_name, &mut voice_state.avatar_url);
+            self.set_voice_state(voice_state);
+        }
+    }
+
+    // --- Event bus ---
+
+    /// Apply a shared state change locally and relay it to other nodes.
+    pub fn emit(&self, event: ClusterEvent) {
+        self.apply_cluster_event(self.node_id, &event);
+        self.event_bus.publish(self.node_id, event);
+    }
+
+    /// Apply an event received from another node.
+    pub async fn apply_remote(&self, node_id: Uuid, event: ClusterEvent) {
+        self.apply_cluster_event(node_id, &event);
+        // Voice media lives on the node that served the join; close it there.
+        if let ClusterEvent::VoiceStateRemoved {
+            channel_id,
+            user_id,
+        } = event
+        {
             self.sfu_service
-                .close_user_connections(*channel_id, user_id)
+                .close_user_connections(channel_id, user_id)
                 .await;
-            self.broadcast_global(
-                "voice_user_left",
-                serde_json::json!({
-                    "user_id": user_id,
-                    "channel_id": channel_id,
-                }),
-            );
+        }
+    }
+
+    fn apply_cluster_event(&self, node_id: Uuid, event: &ClusterEvent) {
+        match event {
+            ClusterEvent::Global { payload } => {
+                let _ = self.global_broadcast.send(payload.clone());
+            }
+            ClusterEvent::Channel { topic_id, payload } => {
+                if let Some(tx) = self.channel_broadcasts.get(topic_id) {
+                    let _ = tx.send(payload.clone());
+                }
+            }
+            ClusterEvent::User { user_id, payload } => {
+                if let Some(tx) = self.user_broadcasts.get(user_id) {
+                    let _ = tx.send(payload.clone());
+                }
+            }
+            ClusterEvent::PresenceOnline { presence } => {
+                self.presence_nodes
+                    .entry(presence.user_id)
+                    .or_default()
+                    .insert(node_id);
+                self.online_users.insert(presence.user_id, presence.clone());
+            }
+            ClusterEvent::PresenceOffline { user_id } => {
+                self.drop_presence_node(*user_id, node_id);
+            }
+            ClusterEvent::PresenceUpdated { presence } => {
+                if let Some(mut current) = self.online_users.get_mut(&presence.user_id) {
+                    *current = presence.clone();
+                }
+            }
+            ClusterEvent::VoiceStateSet { voice_state } => {
+                self.voice_states
+                    .entry(voice_state.channel_id)
+                    .or_default()
+                    .insert(voice_state.user_id, voice_state.clone());
+            }
+            ClusterEvent::VoiceStateRemoved {
+                channel_id,
+                user_id,
+            } => {
+                if let Some(channel_users) = self.voice_states.get(channel_id) {
+                    channel_users.remove(user_id);
+                }
+                self.voice_states
+                    .remove_if(channel_id, |_, users| users.is_empty());
+            }
+            ClusterEvent::PrivateMemberAdded {
+                channel_id,
+                user_id,
+            } => {
+                self.private_channel_members
+                    .entry(*channel_id)
+                    .or_default()
+                    .insert(*user_id);
+            }
+            ClusterEvent::PrivateMemberRemoved {
+                channel_id,
+                user_id,
+            } => {
+                if let Some(members) = self.private_channel_members.get(channel_id) {
+                    members.remove(user_id);
+                }
+            }
+            ClusterEvent::ChannelViewersSet {
+                channel_id,
+                user_ids,
+            } => match user_ids {
+                Some(user_ids) => {
+                    self.private_channel_members
+                        .insert(*channel_id, user_ids.iter().copied().collect());
+                }
+                None => {
+                    self.private_channel_members.remove(channel_id);
+                }
+            },
+            ClusterEvent::ChannelRemoved { channel_id } => {
+                self.private_channel_members.remove(channel_id);
+                self.channel_broadcasts.remove(channel_id);
+            }
+            ClusterEvent::BanCached { user_id } => {
+                self.banned_users.insert(*user_id);
+            }
+            ClusterEvent::BanUncached { user_id } => {
+                self.banned_users.remove(user_id);
+            }
+            ClusterEvent::MuteCached { user_id } => {
+                self.muted_users.insert(*user_id);
+            }
+            ClusterEvent::MuteUncached { user_id } => {
+                self.muted_users.remove(user_id);
+            }
+            ClusterEvent::OutgoingWebhookSet {
+                webhook_id,
+                event_types,
+            } => match event_types {
+                Some(event_types) => {
+                    self.outgoing_webhook_events
+                        .insert(*webhook_id, event_types.clone());
+                }
+                None => {
+                    self.outgoing_webhook_events.remove(webhook_id);
+                }
+            },
+            ClusterEvent::Hello | ClusterEvent::Heartbeat => {}
+        }
+    }
+
+    /// Remove a node from a user's connections, dropping their presence once
+    /// no node holds them.
+    fn drop_presence_node(&self, user_id: Uuid, node_id: Uuid) {
+        if let Some(nodes) = self.presence_nodes.get(&user_id) {
+            nodes.remove(&node_id);
+        }
+        if self
+            .presence_nodes
+            .remove_if(&user_id, |_, nodes| nodes.is_empty())
+            .is_some()
+        {
+            self.online_users.remove(&user_id);
+        }
+    }
+
+    /// Presence and voice states of users connected to this node, replayed
+    /// to a peer that just joined the bus.
+    pub fn local_cluster_snapshot(&self) -> Vec<ClusterEvent> {
+        let local_users: Vec<Uuid> = self
+            .presence_nodes
+            .iter()
+            .filter(|nodes| nodes.contains(&self.node_id))
+            .map(|nodes| *nodes.key())
+            .collect();
+
+        let mut events: Vec<ClusterEvent> = local_users
+            .iter()
+            .filter_map(|user_id| self.online_users.get(user_id))
+            .map(|presence| ClusterEvent::PresenceOnline {
+                presence: presence.clone(),
+            })
+            .collect();
+        events.extend(
+            self.all_voice_states()
+                .into_iter()
+                .filter(|vs| local_users.contains(&vs.user_id))
+                .map(|voice_state| ClusterEvent::VoiceStateSet { voice_state }),
+        );
+        events
+    }
+
+    /// `broadcast_for_channel` for this node's clients only.
+    fn broadcast_local_for_channel(&self, channel_id: Uuid, event: GatewayEvent) {
+        let payload = event.to_json();
+        match self.private_channel_member_ids(channel_id) {
+            Some(member_ids) => {
+                for user_id in member_ids {
+                    if let Some(tx) = self.user_broadcasts.get(&user_id) {
+                        let _ = tx.send(payload.clone());
+                    }
+                }
+            }
+            None => {
+                let _ = self.global_broadcast.send(payload);
+            }
+        }
+    }
+
+    /// A peer node stopped responding: drop the presence and voice states of
+    /// users that were only connected through it and tell local clients.
+    /// Every node does this independently, so nothing is published.
+    pub async fn forget_node(&self, node_id: Uuid) {
+        let users: Vec<Uuid> = self
+            .presence_nodes
+            .iter()
+            .filter(|nodes| nodes.contains(&node_id))
+            .map(|nodes| *nodes.key())
+            .collect();
+
+        for user_id in users {
+            // Invisible users already look offline to everyone else
+            let username = self
+                .online_users
+                .get(&user_id)
+                .filter(|p| p.status != PresenceStatus::Invisible)
+                .map(|p| p.username.clone());
+            self.drop_presence_node(user_id, node_id);
+            if self.online_users.contains_key(&user_id) {
+                continue;
+            }
+
+            for voice_state in self.all_voice_states() {
+                if voice_state.user_id != user_id {
+                    continue;
+                }
+                let event = ClusterEvent::VoiceStateRemoved {
+                    channel_id: voice_state.channel_id,
+                    user_id,
+                };
+                self.apply_cluster_event(node_id, &event);
+                self.broadcast_local_for_channel(
+                    voice_state.channel_id,
+                    GatewayEvent::VoiceUserLeft {
+                        user_id,
+                        channel_id: voice_state.channel_id,
+                    },
+                );
+            }
+            if let Some(username) = username {
+                let _ = self
+                    .global_broadcast
+                    .send(GatewayEvent::UserOffline { user_id, username }.to_json());
+            }
         }
     }
 }
@@ -200,7 +200,7 @@ pub async fn finish_passkey_auth(
         }
     }
 
-    let token = create_jwt(user.id, &user.username, user.role)?;
+    let token = create_jwt(user.id, &user.username)?;
 
     Ok(Json(AuthResponse {
         token,
@@ -208,7 +208,7 @@ pub async fn finish_passkey_auth(
             id: user.id,
             username: user.username.clone(),
             email: user.email.clone(),
-            role: user.role,
+            is_owner: user.is_owner,
             avatar_url: avatar_url_from_path(user.id, &user.avatar_path),
             display_name: user.display_name.clone(),
         },
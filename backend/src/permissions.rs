@@ -1,63 +1,227 @@
 use serde::{Deserialize, Serialize};
 use sqlx::PgPool;
-use std::fmt;
 use uuid::Uuid;
 
 use crate::database;
+use crate::models::AppState;
 use crate::shared::AppError;
 
-/// Role levels, ordered by power (higher number = more power).
-#[derive(
-    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, sqlx::Type,
-)]
-#[sqlx(type_name = "text", rename_all = "lowercase")]
-#[serde(rename_all = "lowercase")]
-pub enum Role {
-    Member = 0,
-    Moderator = 1,
-    Admin = 2,
-    Owner = 3,
+/// Bitfield of server-wide permissions, stored on each role. A user's
+/// permissions are the union of the default role and every role they hold.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize, sqlx::Type)]
+#[serde(from = "i64")]
+#[sqlx(transparent)]
+pub struct Permissions(i64);
+
+impl From<i64> for Permissions {
+    /// Unknown bits are dropped.
+    fn from(bits: i64) -> Self {
+        Self(bits & Self::ALL.0)
+    }
 }
 
-impl fmt::Display for Role {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        match self {
-            Self::Owner => f.write_str("owner"),
-            Self::Admin => f.write_str("admin"),
-            Self::Moderator => f.write_str("moderator"),
-            Self::Member => f.write_str("member"),
+impl Permissions {
+    /// Grants every permission.
+    pub const ADMINISTRATOR: Self = Self(1 << 0);
+    pub const MANAGE_SERVER: Self = Self(1 << 1);
+    pub const MANAGE_ROLES: Self = Self(1 << 2);
+    pub const MANAGE_CHANNELS: Self = Self(1 << 3);
+    /// Delete custom emojis uploaded by others.
+    pub const MANAGE_EMOJIS: Self = Self(1 << 4);
+    /// Edit or delete soundboard sounds uploaded by others.
+    pub const MANAGE_SOUNDBOARD: Self = Self(1 << 5);
+    /// Delete messages sent by others.
+    pub const MANAGE_MESSAGES: Self = Self(1 << 6);
+    pub const MANAGE_INVITES: Self = Self(1 << 7);
+    pub const VIEW_MOD_LOG: Self = Self(1 << 8);
+    pub const KICK_MEMBERS: Self = Self(1 << 9);
+    pub const BAN_MEMBERS: Self = Self(1 << 10);
+    pub const MUTE_MEMBERS: Self = Self(1 << 11);
+    pub const ATTACH_FILES: Self = Self(1 << 12);
+    pub const USE_SOUNDBOARD: Self = Self(1 << 13);
+    /// Use `@everyone` and `@here`.
+    pub const MENTION_EVERYONE: Self = Self(1 << 14);
+    pub const VIEW_CHANNEL: Self = Self(1 << 15);
+    pub const SEND_MESSAGES: Self = Self(1 << 16);
+    pub const ADD_REACTIONS: Self = Self(1 << 17);
+    /// Join voice calls.
+    pub const CONNECT: Self = Self(1 << 18);
+    /// Send microphone audio in voice calls.
+    pub const SPEAK: Self = Self(1 << 19);
+    /// Share a screen or camera in voice calls.
+    pub const STREAM: Self = Self(1 << 20);
+    pub const PIN_MESSAGES: Self = Self(1 << 21);
+
+    pub const ALL: Self = Self((1 << 22) - 1);
+
+    /// Permissions that make the user list and moderation tools available.
+    pub const MODERATION: Self = Self(
+        Self::KICK_MEMBERS.0 | Self::BAN_MEMBERS.0 | Self::MUTE_MEMBERS.0 | Self::MANAGE_ROLES.0,
+    );
+
+    /// Permissions that channel overwrites may allow or deny.
+    pub const CHANNEL: Self = Self(
+        Self::VIEW_CHANNEL.0
+            | Self::SEND_MESSAGES.0
+            | Self::ADD_REACTIONS.0
+            | Self::ATTACH_FILES.0
+            | Self::CONNECT.0
+            | Self::SPEAK.0
+            | Self::STREAM.0
+            | Self::PIN_MESSAGES.0,
+    );
+
+    pub fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    pub fn intersects(self, other: Self) -> bool {
+        self.0 & other.0 != 0
+    }
+
+    /// Err(Forbidden) unless every bit of `permission` is present.
+    pub fn require(self, permission: Self) -> Result<(), AppError> {
+        if self.contains(permission) {
+            Ok(())
+        } else {
+            Err(AppError::forbidden(format!(
+                "Requires the {} permission",
+                permission.name()
+            )))
         }
     }
+
+    fn name(self) -> &'static str {
+        PERMISSION_NAMES
+            .iter()
+            .find(|(perm, _)| *perm == self)
+            .map_or("unknown", |(_, name)| name)
+    }
+}
+
+impl std::ops::BitOr for Permissions {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        Self(self.0 | rhs.0)
+    }
+}
+
+impl std::ops::BitOrAssign for Permissions {
+    fn bitor_assign(&mut self, rhs: Self) {
+        self.0 |= rhs.0;
+    }
 }
 
-impl std::str::FromStr for Role {
-    type Err = AppError;
+const PERMISSION_NAMES: &[(Permissions, &str)] = &[
+    (Permissions::ADMINISTRATOR, "administrator"),
+    (Permissions::MANAGE_SERVER, "manage_server"),
+    (Permissions::MANAGE_ROLES, "manage_roles"),
+    (Permissions::MANAGE_CHANNELS, "manage_channels"),
+    (Permissions::MANAGE_EMOJIS, "manage_emojis"),
+    (Permissions::MANAGE_SOUNDBOARD, "manage_soundboard"),
+    (Permissions::MANAGE_MESSAGES, "manage_messages"),
+    (Permissions::MANAGE_INVITES, "manage_invites"),
+    (Permissions::VIEW_MOD_LOG, "view_mod_log"),
+    (Permissions::KICK_MEMBERS, "kick_members"),
+    (Permissions::BAN_MEMBERS, "ban_members"),
+    (Permissions::MUTE_MEMBERS, "mute_members"),
+    (Permissions::ATTACH_FILES, "attach_files"),
+    (Permissions::USE_SOUNDBOARD, "use_soundboard"),
+    (Permissions::MENTION_EVERYONE, "mention_everyone"),
+    (Permissions::VIEW_CHANNEL, "view_channel"),
+    (Permissions::SEND_MESSAGES, "send_messages"),
+    (Permissions::ADD_REACTIONS, "add_reactions"),
+    (Permissions::CONNECT, "connect"),
+    (Permissions::SPEAK, "speak"),
+    (Permissions::STREAM, "stream"),
+    (Permissions::PIN_MESSAGES, "pin_messages"),
+];
+
+/// A user's resolved server-wide permissions and rank.
+#[derive(Debug, Clone, Copy)]
+pub struct MemberPermissions {
+    /// The server owner has every permission and outranks everyone.
+    pub is_owner: bool,
+    /// Effective permissions; `ALL` for the owner and administrators.
+    pub permissions: Permissions,
+    /// Position of the user's highest role. The default role is 0.
+    pub top_position: i32,
+}
+
+impl MemberPermissions {
+    pub fn new(is_owner: bool, granted: Permissions, top_position: i32) -> Self {
+        let permissions = if is_owner || granted.contains(Permissions::ADMINISTRATOR) {
+            Permissions::ALL
+        } else {
+            granted
+        };
+        Self {
+            is_owner,
+            permissions,
+            top_position,
+        }
+    }
+
+    pub fn has(&self, permission: Permissions) -> bool {
+        self.permissions.contains(permission)
+    }
+
+    /// Err(Forbidden) unless the user has `permission`.
+    pub fn require(&self, permission: Permissions) -> Result<(), AppError> {
+        self.permissions.require(permission)
+    }
 
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        match s {
-            "owner" => Ok(Self::Owner),
-            "admin" => Ok(Self::Admin),
-            "moderator" => Ok(Self::Moderator),
-            "member" => Ok(Self::Member),
-            _ => Err(AppError::bad_request(format!("Invalid role: {s}"))),
+    /// Effective permissions in a channel. Overwrites apply in order: the
+    /// default role's, the combined overwrites of the member's roles, then
+    /// the member's own. The owner and administrators bypass them.
+    pub fn in_channel(
+        &self,
+        everyone: Overwrite,
+        roles: Overwrite,
+        member: Overwrite,
+    ) -> Permissions {
+        if self.has(Permissions::ADMINISTRATOR) {
+            return Permissions::ALL;
         }
+        member.apply(roles.apply(everyone.apply(self.permissions)))
+    }
+
+    /// Whether a role at `position` is below this user's highest role, so
+    /// they may edit, assign or remove it.
+    pub fn can_manage_role(&self, position: i32) -> bool {
+        self.is_owner || position < self.top_position
     }
 }
 
-/// Check that the user's role meets the minimum required level.
-pub fn require_role(user_role: Role, minimum: Role) -> Result<Role, AppError> {
-    if user_role >= minimum {
-        Ok(user_role)
-    } else {
-        Err(AppError::forbidden(format!(
-            "Requires {minimum} role or higher",
-        )))
+/// An allow/deny pair from a channel overwrite, or several combined.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Overwrite {
+    pub allow: Permissions,
+    pub deny: Permissions,
+}
+
+impl Overwrite {
+    /// Merge another overwrite at the same level (e.g. two of the member's
+    /// roles). Allows win over denies when applied.
+    pub fn combine(self, other: Self) -> Self {
+        Self {
+            allow: self.allow | other.allow,
+            deny: self.deny | other.deny,
+        }
+    }
+
+    fn apply(self, permissions: Permissions) -> Permissions {
+        Permissions((permissions.0 & !self.deny.0) | self.allow.0)
     }
 }
 
-/// Check that actor has a strictly higher role than target (for moderation actions).
-pub fn require_higher_role(actor_role: Role, target_role: Role) -> Result<(), AppError> {
-    if actor_role > target_role {
+/// Check that actor's highest role is strictly above target's (for moderation actions).
+pub fn require_higher_rank(
+    actor: &MemberPermissions,
+    target: &MemberPermissions,
+) -> Result<(), AppError> {
+    if !target.is_owner && (actor.is_owner || actor.top_position > target.top_position) {
         Ok(())
     } else {
         Err(AppError::forbidden(
@@ -66,18 +230,24 @@ pub fn require_higher_role(actor_role: Role, target_role: Role) -> Result<(), Ap
     }
 }
 
-/// Check that actor can assign the given role (must be strictly above it, cannot assign owner).
-pub fn can_assign_role(actor_role: Role, new_role: Role) -> Result<(), AppError> {
-    if new_role == Role::Owner {
-        return Err(AppError::forbidden("Cannot assign the owner role"));
+/// Check that actor may place a role at `position` with `permissions`: the
+/// role must sit below their highest role and grant nothing they lack.
+pub fn require_role_manageable(
+    actor: &MemberPermissions,
+    position: i32,
+    permissions: Permissions,
+) -> Result<(), AppError> {
+    if !actor.can_manage_role(position) {
+        return Err(AppError::forbidden(
+            "Cannot manage a role equal to or above your own",
+        ));
     }
-    if actor_role > new_role {
-        Ok(())
-    } else {
-        Err(AppError::forbidden(
-            "Cannot assign a role equal to or above your own",
-        ))
+    if !actor.permissions.contains(permissions) {
+        return Err(AppError::forbidden(
+            "Cannot grant permissions you do not have",
+        ));
     }
+    Ok(())
 }
 
 /// Returns Err(Forbidden) if user is banned. For REST/auth routes.
@@ -95,3 +265,139 @@ pub async fn check_not_muted(db: &PgPool, user_id: Uuid) -> Result<(), AppError>
     }
     Ok(())
 }
+
+/// Returns Err(NotFound) if the channel is private and the user is not a
+/// member. Uses the in-memory membership cache so it is cheap on hot paths.
+pub fn require_channel_access(
+    state: &AppState,
+    channel_id: Uuid,
+    user_id: Uuid,
+) -> Result<(), AppError> {
+    if state.can_access_channel(channel_id, user_id) {
+        Ok(())
+    } else {
+        Err(AppError::not_found("Channel not found"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn member(granted: Permissions) -> MemberPermissions {
+        MemberPermissions::new(false, granted, 1)
+    }
+
+    fn overwrite(allow: Permissions, deny: Permissions) -> Overwrite {
+        Overwrite { allow, deny }
+    }
+
+    #[test]
+    fn from_bits_drops_unknown_bits() {
+        assert_eq!(Permissions::from(-1), Permissions::ALL);
+        assert_eq!(Permissions::from(1 << 40), Permissions::default());
+    }
+
+    #[test]
+    fn contains_requires_every_bit() {
+        let perms = Permissions::SEND_MESSAGES | Permissions::ADD_REACTIONS;
+        assert!(perms.contains(Permissions::SEND_MESSAGES));
+        assert!(!perms.contains(Permissions::SEND_MESSAGES | Permissions::ATTACH_FILES));
+        assert!(perms.intersects(Permissions::SEND_MESSAGES | Permissions::ATTACH_FILES));
+        assert!(perms.require(Permissions::ADD_REACTIONS).is_ok());
+        assert!(perms.require(Permissions::MANAGE_MESSAGES).is_err());
+    }
+
+    #[test]
+    fn every_permission_is_named() {
+        let named = PERMISSION_NAMES
+            .iter()
+            .fold(Permissions::default(), |acc, (perm, _)| acc | *perm);
+        assert_eq!(named, Permissions::ALL);
+    }
+
+    #[test]
+    fn owner_and_administrator_get_everything() {
+        assert_eq!(
+            MemberPermissions::new(true, Permissions::default(), 0).permissions,
+            Permissions::ALL
+        );
+        assert_eq!(
+            member(Permissions::ADMINISTRATOR).permissions,
+            Permissions::ALL
+        );
+        assert_eq!(
+            member(Permissions::SEND_MESSAGES).permissions,
+            Permissions::SEND_MESSAGES
+        );
+    }
+
+    #[test]
+    fn overwrites_apply_from_everyone_to_member() {
+        let base = member(Permissions::VIEW_CHANNEL | Permissions::SEND_MESSAGES);
+        let deny_send = overwrite(Permissions::default(), Permissions::SEND_MESSAGES);
+        let allow_send = overwrite(Permissions::SEND_MESSAGES, Permissions::default());
+        let none = Overwrite::default();
+
+        // A role allow undoes a default-role deny
+        assert!(
+            base.in_channel(deny_send, allow_send, none)
+                .contains(Permissions::SEND_MESSAGES)
+        );
+        // A role deny beats a default-role allow
+        assert!(
+            !base
+                .in_channel(allow_send, deny_send, none)
+                .contains(Permissions::SEND_MESSAGES)
+        );
+        // The member's own overwrite has the last word
+        assert!(
+            !base
+                .in_channel(none, allow_send, deny_send)
+                .contains(Permissions::SEND_MESSAGES)
+        );
+        assert!(
+            base.in_channel(deny_send, deny_send, allow_send)
+                .contains(Permissions::SEND_MESSAGES)
+        );
+    }
+
+    #[test]
+    fn combined_role_overwrites_prefer_allow() {
+        let base = member(Permissions::VIEW_CHANNEL);
+        let roles = overwrite(Permissions::default(), Permissions::VIEW_CHANNEL)
+            .combine(overwrite(Permissions::VIEW_CHANNEL, Permissions::default()));
+        assert!(
+            base.in_channel(Overwrite::default(), roles, Overwrite::default())
+                .contains(Permissions::VIEW_CHANNEL)
+        );
+    }
+
+    #[test]
+    fn administrators_bypass_overwrites() {
+        let admin = member(Permissions::ADMINISTRATOR);
+        let deny_all = overwrite(Permissions::default(), Permissions::CHANNEL);
+        assert_eq!(
+            admin.in_channel(deny_all, deny_all, deny_all),
+            Permissions::ALL
+        );
+    }
+
+    #[test]
+    fn rank_checks() {
+        let owner = MemberPermissions::new(true, Permissions::default(), 0);
+        let high = MemberPermissions::new(false, Permissions::BAN_MEMBERS, 5);
+        let low = MemberPermissions::new(false, Permissions::default(), 2);
+
+        assert!(require_higher_rank(&high, &low).is_ok());
+        assert!(require_higher_rank(&low, &high).is_err());
+        assert!(require_higher_rank(&high, &high).is_err());
+        assert!(require_higher_rank(&owner, &high).is_ok());
+        assert!(require_higher_rank(&high, &owner).is_err());
+
+        assert!(require_role_manageable(&high, 4, Permissions::BAN_MEMBERS).is_ok());
+        assert!(require_role_manageable(&high, 5, Permissions::default()).is_err());
+        assert!(require_role_manageable(&high, 1, Permissions::KICK_MEMBERS).is_err());
+        assert!(require_role_manageable(&owner, 100, Permissions::ALL).is_ok());
+    }
+}
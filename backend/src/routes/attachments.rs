@@ -9,7 +9,9 @@ use std::sync::Arc;
 use uuid::Uuid;
 
 use crate::auth::AuthUser;
+use crate::database;
 use crate::models::{AppState, Attachment};
+use crate::permissions::Permissions;
 use crate::shared::validation::{
     MAX_ATTACHMENT_SIZE, validate_attachment_content_type, validate_filename,
 };
@@ -30,6 +32,9 @@ pub async fn upload_attachment(
     let store = require_storage(&state)?;
     let user_id = auth_user.user_id();
     crate::permissions::check_not_muted(&state.db, user_id).await?;
+    database::get_member_permissions(&state.db, user_id)
+        .await?
+        .require(Permissions::ATTACH_FILES)?;
 
     let field = multipart
         .next_field()
@@ -1,5 +1,5 @@
 use axum::{
-    extract::{Path, State},
+    extract::{Path, Query, State},
     response::Json,
 };
 use std::sync::Arc;
@@ -7,16 +7,28 @@ use uuid::Uuid;
 
 use crate::auth::AuthUser;
 use crate::database;
-use crate::models::{AppState, Channel, CreateChannelRequest, UpdateChannelRequest, UserPresence};
-use crate::permissions::{self, Role};
-use crate::shared::validation::validate_channel_name;
+use crate::gateway::GatewayEvent;
+use crate::models::{
+    AppState, CategoryRequest, Channel, ChannelCategory, CreateChannelRequest, DeleteCategoryQuery,
+    OverwriteRequest, OverwriteTargetType, PermissionOverwrite, ReorderChannelsRequest,
+    UpdateChannelRequest, UserPresence,
+};
+use crate::permissions::Permissions;
+use crate::services::channel_access;
+use crate::services::message::delete_attachment_files;
+use crate::shared::validation::{
+    MAX_CATEGORIES, validate_category_name, validate_channel_name, validate_channel_topic,
+    validate_slowmode,
+};
 use crate::shared::{AppError, AppResult};
 
 pub async fn get_channels(
     State(state): State<Arc<AppState>>,
-    _auth_user: AuthUser,
+    auth_user: AuthUser,
 ) -> AppResult<Json<Vec<Channel>>> {
-    let channels = database::get_channels(&state.db).await?;
+    let user_id = auth_user.user_id();
+    let mut channels = database::get_channels(&state.db).await?;
+    channels.retain(|c| state.can_access_channel(c.id, user_id));
     Ok(Json(channels))
 }
 
@@ -26,20 +38,36 @@ pub async fn create_channel(
     Json(payload): Json<CreateChannelRequest>,
 ) -> AppResult<Json<Channel>> {
     let user_id = auth_user.user_id();
-    let actor_role = database::get_user_role(&state.db, user_id).await?;
-    permissions::require_role(actor_role, Role::Admin)?;
+    let actor = database::get_member_permissions(&state.db, user_id).await?;
+    actor.require(Permissions::MANAGE_CHANNELS)?;
 
     let name = validate_channel_name(&payload.name)?;
+    if let Some(category_id) = payload.category_id {
+        require_category_exists(&state, category_id).await?;
+    }
 
     let channel = Channel {
         id: Uuid::now_v7(),
         name,
         channel_type: payload.channel_type,
+        category_id: payload.category_id,
+        position: 0,
+        topic: None,
+        slowmode_secs: 0,
+        announcement: false,
+        announcement_role_ids: Vec::new(),
+        overwrites: Vec::new(),
     };
 
     database::create_channel(&state.db, &channel, user_id).await?;
 
-    state.broadcast_global("channel_created", serde_json::json!(channel));
+    // Re-read for the position assigned by the database
+    let channel = database::get_channel_by_id(&state.db, channel.id)
+        .await?
+        .ok_or_else(|| AppError::not_found("Channel not found"))?;
+
+    channel_access::init_channel_access(&state, &channel).await?;
+    state.broadcast_for_channel(channel.id, GatewayEvent::ChannelCreated(channel.clone()));
 
     Ok(Json(channel))
 }
@@ -51,49 +79,336 @@ pub async fn update_channel(
     Json(payload): Json<UpdateChannelRequest>,
 ) -> AppResult<Json<Channel>> {
     let user_id = auth_user.user_id();
-    let actor_role = database::get_user_role(&state.db, user_id).await?;
-    permissions::require_role(actor_role, Role::Admin)?;
+    let actor = database::get_member_permissions(&state.db, user_id).await?;
+    actor.require(Permissions::MANAGE_CHANNELS)?;
 
     let name = validate_channel_name(&payload.name)?;
 
-    database::update_channel(&state.db, channel_id, &name).await?;
+    reject_private_channel(&state, channel_id).await?;
+    let existing = database::get_channel_by_id(&state.db, channel_id)
+        .await?
+        .ok_or_else(|| AppError::not_found("Channel not found"))?;
+
+    let topic = match payload.topic.as_deref() {
+        Some(topic) => validate_channel_topic(topic)?,
+        None => existing.topic,
+    };
+    let slowmode_secs = match payload.slowmode_secs {
+        Some(secs) => validate_slowmode(secs)?,
+        None => existing.slowmode_secs,
+    };
+    let announcement = payload.announcement.unwrap_or(existing.announcement);
+    let announcement_role_ids = match payload.announcement_role_ids {
+        Some(mut role_ids) => {
+            role_ids.sort();
+            role_ids.dedup();
+            let roles = database::get_roles(&state.db).await?;
+            if role_ids.iter().any(|id| !roles.iter().any(|r| r.id == *id)) {
+                return Err(AppError::bad_request("Role not found"));
+            }
+            role_ids
+        }
+        None => existing.announcement_role_ids,
+    };
+
+    database::update_channel(
+        &state.db,
+        channel_id,
+        &name,
+        topic.as_deref(),
+        slowmode_secs,
+        announcement,
+        &announcement_role_ids,
+    )
+    .await?;
 
     let channel = database::get_channel_by_id(&state.db, channel_id)
         .await?
         .ok_or_else(|| AppError::not_found("Channel not found"))?;
 
-    state.broadcast_global("channel_updated", serde_json::json!(channel));
+    state.broadcast_for_channel(channel_id, GatewayEvent::ChannelUpdated(channel.clone()));
 
     Ok(Json(channel))
 }
 
+pub async fn get_categories(
+    State(state): State<Arc<AppState>>,
+    _auth_user: AuthUser,
+) -> AppResult<Json<Vec<ChannelCategory>>> {
+    let categories = database::get_channel_categories(&state.db).await?;
+    Ok(Json(categories))
+}
+
+pub async fn create_category(
+    State(state): State<Arc<AppState>>,
+    auth_user: AuthUser,
+    Json(payload): Json<CategoryRequest>,
+) -> AppResult<Json<ChannelCategory>> {
+    let actor = database::get_member_permissions(&state.db, auth_user.user_id()).await?;
+    actor.require(Permissions::MANAGE_CHANNELS)?;
+
+    let name = validate_category_name(&payload.name)?;
+    if database::get_channel_categories(&state.db).await?.len() >= MAX_CATEGORIES {
+        return Err(AppError::bad_request(format!(
+            "Maximum of {MAX_CATEGORIES} categories reached"
+        )));
+    }
+
+    let category = database::create_channel_category(&state.db, &name).await?;
+
+    state.broadcast_global(GatewayEvent::CategoryCreated(category.clone()));
+
+    Ok(Json(category))
+}
+
+pub async fn update_category(
+    State(state): State<Arc<AppState>>,
+    auth_user: AuthUser,
+    Path(category_id): Path<Uuid>,
+    Json(payload): Json<CategoryRequest>,
+) -> AppResult<Json<ChannelCategory>> {
+    let actor = database::get_member_permissions(&state.db, auth_user.user_id()).await?;
+    actor.require(Permissions::MANAGE_CHANNELS)?;
+
+    let name = validate_category_name(&payload.name)?;
+    let category = database::update_channel_category(&state.db, category_id, &name).await?;
+
+    state.broadcast_global(GatewayEvent::CategoryUpdated(category.clone()));
+
+    Ok(Json(category))
+}
+
+/// Delete a category. Its channels move to `move_to` (or out of any
+/// category) unless `delete_channels` is set, in which case they are deleted
+/// too.
+pub async fn delete_category(
+    State(state): State<Arc<AppState>>,
+    auth_user: AuthUser,
+    Path(category_id): Path<Uuid>,
+    Query(query): Query<DeleteCategoryQuery>,
+) -> AppResult<()> {
+    let actor = database::get_member_permissions(&state.db, auth_user.user_id()).await?;
+    actor.require(Permissions::MANAGE_CHANNELS)?;
+
+    if let Some(move_to) = query.move_to {
+        if query.delete_channels || move_to == category_id {
+            return Err(AppError::bad_request(
+                "move_to must be another category and cannot be combined with delete_channels",
+            ));
+        }
+        require_category_exists(&state, move_to).await?;
+    }
+
+    let (deleted, storage_paths) = database::delete_channel_category(
+        &state.db,
+        category_id,
+        query.delete_channels,
+        query.move_to,
+    )
+    .await?;
+
+    for channel_id in deleted {
+        channel_deleted(&state, channel_id).await;
+    }
+    delete_attachment_files(&state, storage_paths).await;
+    state.broadcast_global(GatewayEvent::CategoryDeleted { id: category_id });
+    if !query.delete_channels {
+        broadcast_channel_positions(&state).await?;
+    }
+
+    Ok(())
+}
+
+/// Move categories and channels in one transaction, then broadcast the
+/// resulting layout as a single `channels_reordered` event.
+pub async fn reorder_channels(
+    State(state): State<Arc<AppState>>,
+    auth_user: AuthUser,
+    Json(payload): Json<ReorderChannelsRequest>,
+) -> AppResult<()> {
+    let actor = database::get_member_permissions(&state.db, auth_user.user_id()).await?;
+    actor.require(Permissions::MANAGE_CHANNELS)?;
+
+    let categories = database::get_channel_categories(&state.db).await?;
+    if payload
+        .channels
+        .iter()
+        .filter_map(|c| c.category_id)
+        .any(|id| !categories.iter().any(|c| c.id == id))
+    {
+        return Err(AppError::not_found("Category not found"));
+    }
+
+    database::reorder_channels(&state.db, &payload.categories, &payload.channels).await?;
+
+    broadcast_channel_positions(&state).await
+}
+
+/// Restricted channels are only listed for members who can see them, so
+/// their layout is sent per user whenever any exist.
+async fn broadcast_channel_positions(state: &AppState) -> AppResult<()> {
+    let categories = database::get_channel_categories(&state.db).await?;
+    let channels = database::get_channel_positions(&state.db).await?;
+    if !channels
+        .iter()
+        .any(|c| state.private_channel_members.contains_key(&c.id))
+    {
+        state.broadcast_global(GatewayEvent::ChannelsReordered {
+            categories,
+            channels,
+        });
+        return Ok(());
+    }
+
+    for user_id in database::get_all_user_ids(&state.db).await? {
+        let visible = channels
+            .iter()
+            .filter(|c| state.can_access_channel(c.id, user_id))
+            .cloned()
+            .collect();
+        state.broadcast_user(
+            user_id,
+            GatewayEvent::ChannelsReordered {
+                categories: categories.clone(),
+                channels: visible,
+            },
+        );
+    }
+    Ok(())
+}
+
+async fn require_category_exists(state: &AppState, category_id: Uuid) -> AppResult<()> {
+    let categories = database::get_channel_categories(&state.db).await?;
+    if !categories.iter().any(|c| c.id == category_id) {
+        return Err(AppError::not_found("Category not found"));
+    }
+    Ok(())
+}
+
+/// Create or replace the overwrite for a role or user, then recompute who
+/// can see the channel.
+pub async fn set_channel_overwrite(
+    State(state): State<Arc<AppState>>,
+    auth_user: AuthUser,
+    Path((channel_id, target_type, target_id)): Path<(Uuid, OverwriteTargetType, Uuid)>,
+    Json(payload): Json<OverwriteRequest>,
+) -> AppResult<Json<PermissionOverwrite>> {
+    let actor = database::get_member_permissions(&state.db, auth_user.user_id()).await?;
+    actor.require(Permissions::MANAGE_CHANNELS)?;
+    reject_private_channel(&state, channel_id).await?;
+
+    let requested = payload.allow | payload.deny;
+    if !Permissions::CHANNEL.contains(requested) {
+        return Err(AppError::bad_request(
+            "Overwrites may only set channel permissions",
+        ));
+    }
+    if !actor.permissions.contains(requested) {
+        return Err(AppError::forbidden(
+            "Cannot overwrite permissions you do not have",
+        ));
+    }
+
+    let target_exists = match target_type {
+        OverwriteTargetType::Role => database::get_role_by_id(&state.db, target_id)
+            .await?
+            .is_some(),
+        OverwriteTargetType::User => database::get_user_by_id(&state.db, target_id)
+            .await?
+            .is_some(),
+    };
+    if !target_exists {
+        return Err(AppError::not_found("Overwrite target not found"));
+    }
+
+    let overwrite = database::set_channel_overwrite(
+        &state.db,
+        channel_id,
+        target_type,
+        target_id,
+        payload.allow,
+        payload.deny,
+    )
+    .await?;
+
+    overwrites_changed(&state, channel_id).await?;
+
+    Ok(Json(overwrite))
+}
+
+pub async fn delete_channel_overwrite(
+    State(state): State<Arc<AppState>>,
+    auth_user: AuthUser,
+    Path((channel_id, target_type, target_id)): Path<(Uuid, OverwriteTargetType, Uuid)>,
+) -> AppResult<()> {
+    let actor = database::get_member_permissions(&state.db, auth_user.user_id()).await?;
+    actor.require(Permissions::MANAGE_CHANNELS)?;
+    reject_private_channel(&state, channel_id).await?;
+
+    database::delete_channel_overwrite(&state.db, channel_id, target_type, target_id).await?;
+
+    overwrites_changed(&state, channel_id).await?;
+
+    Ok(())
+}
+
+async fn overwrites_changed(state: &Arc<AppState>, channel_id: Uuid) -> AppResult<()> {
+    channel_access::refresh_channel_access(state).await?;
+    let channel = database::get_channel_by_id(&state.db, channel_id)
+        .await?
+        .ok_or_else(|| AppError::not_found("Channel not found"))?;
+    state.broadcast_for_channel(channel_id, GatewayEvent::ChannelUpdated(channel));
+    Ok(())
+}
+
 pub async fn delete_channel(
     State(state): State<Arc<AppState>>,
     auth_user: AuthUser,
     Path(channel_id): Path<Uuid>,
 ) -> AppResult<()> {
     let user_id = auth_user.user_id();
-    let actor_role = database::get_user_role(&state.db, user_id).await?;
-    permissions::require_role(actor_role, Role::Admin)?;
+    let actor = database::get_member_permissions(&state.db, user_id).await?;
+    actor.require(Permissions::MANAGE_CHANNELS)?;
 
-    database::delete_channel(&state.db, channel_id).await?;
+    reject_private_channel(&state, channel_id).await?;
+    let storage_paths = database::delete_channel(&state.db, channel_id).await?;
 
-    // Clean up broadcast channel
-    state.channel_broadcasts.remove(&channel_id);
+    channel_deleted(&state, channel_id).await;
+    delete_attachment_files(&state, storage_paths).await;
+
+    Ok(())
+}
 
-    state.broadcast_global("channel_deleted", serde_json::json!({ "id": channel_id }));
+/// Clean up after a deleted channel: end its voice call, announce the
+/// deletion, then drop its broadcast channel and viewer cache. Announcing
+/// first keeps restricted channels visible only to their viewers.
+async fn channel_deleted(state: &AppState, channel_id: Uuid) {
+    let voice_users: Vec<Uuid> = state
+        .voice_states
+        .get(&channel_id)
+        .map(|users| users.iter().map(|entry| *entry.key()).collect())
+        .unwrap_or_default();
+    for user_id in voice_users {
+        state.leave_voice(channel_id, user_id).await;
+    }
 
+    state.broadcast_for_channel(channel_id, GatewayEvent::ChannelDeleted { id: channel_id });
+    state.forget_channel(channel_id);
+}
+
+/// DM channels are managed by their members through the DM routes, not by
+/// the server channel admin routes.
+async fn reject_private_channel(state: &AppState, channel_id: Uuid) -> AppResult<()> {
+    let channel_type = database::get_channel_type(&state.db, channel_id).await?;
+    if channel_type.is_private() {
+        return Err(AppError::not_found("Channel not found"));
+    }
     Ok(())
 }
 
 pub async fn get_online_users(
     State(state): State<Arc<AppState>>,
-    _auth_user: AuthUser,
+    auth_user: AuthUser,
 ) -> AppResult<Json<Vec<UserPresence>>> {
-    let users: Vec<UserPresence> = state
-        .online_users
-        .iter()
-        .map(|entry| entry.value().clone())
-        .collect();
-    Ok(Json(users))
+    Ok(Json(state.visible_online_users(auth_user.user_id())))
 }
@@ -11,7 +11,7 @@ use uuid::Uuid;
 use crate::auth::AuthUser;
 use crate::database;
 use crate::models::{AppState, CustomEmoji};
-use crate::permissions::{self, Role};
+use crate::permissions::{self, Permissions};
 use crate::shared::AppError;
 use crate::shared::validation::{
     MAX_CUSTOM_EMOJI_SIZE, validate_emoji_content_type, validate_emoji_name,
@@ -158,8 +158,8 @@ pub async fn delete_custom_emoji(
 
     // Only the uploader or mods/admins can delete
     if emoji.uploaded_by != user_id {
-        let role = database::get_user_role(&state.db, user_id).await?;
-        if !matches!(role, Role::Admin | Role::Owner | Role::Moderator) {
+        let actor = database::get_member_permissions(&state.db, user_id).await?;
+        if !actor.has(Permissions::MANAGE_EMOJIS) {
             return Err(AppError::forbidden(
                 "Only the uploader or moderators can delete custom emojis",
             ));
@@ -0,0 +1,176 @@
+use axum::{
+    extract::{Path, State},
+    response::Json,
+};
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::auth::AuthUser;
+use crate::database;
+use crate::gateway::GatewayEvent;
+use crate::models::{AppState, ChannelType, CreateDmRequest, DmChannel};
+use crate::permissions;
+use crate::services::message::delete_attachment_files;
+use crate::shared::validation::{MAX_GROUP_DM_MEMBERS, validate_channel_name};
+use crate::shared::{AppError, AppResult};
+
+pub async fn get_dms(
+    State(state): State<Arc<AppState>>,
+    auth_user: AuthUser,
+) -> AppResult<Json<Vec<DmChannel>>> {
+    let dms = database::get_dm_channels_for_user(&state.db, auth_user.user_id()).await?;
+    Ok(Json(dms))
+}
+
+/// Opens a DM. A single recipient returns the existing 1:1 channel with that
+/// user (creating it on first use); several recipients create a group DM.
+pub async fn create_dm(
+    State(state): State<Arc<AppState>>,
+    auth_user: AuthUser,
+    Json(payload): Json<CreateDmRequest>,
+) -> AppResult<Json<DmChannel>> {
+    let user_id = auth_user.user_id();
+
+    let mut member_ids = vec![user_id];
+    for id in payload.user_ids {
+        if !member_ids.contains(&id) {
+            member_ids.push(id);
+        }
+    }
+
+    if member_ids.len() < 2 {
+        return Err(AppError::bad_request(
+            "A direct message needs at least one other user",
+        ));
+    }
+    if member_ids.len() > MAX_GROUP_DM_MEMBERS {
+        return Err(AppError::bad_request(format!(
+            "Group DMs are limited to {MAX_GROUP_DM_MEMBERS} members"
+        )));
+    }
+
+    for &id in &member_ids[1..] {
+        if database::get_user_by_id(&state.db, id).await?.is_none() {
+            return Err(AppError::not_found("User not found"));
+        }
+    }
+
+    let (channel_id, joined) = if member_ids.len() == 2 && payload.name.is_none() {
+        database::get_or_create_dm(&state.db, user_id, member_ids[1]).await?
+    } else {
+        let name = payload
+            .name
+            .as_deref()
+            .map(validate_channel_name)
+            .transpose()?;
+        let channel_id = Uuid::now_v7();
+        database::create_group_dm(&state.db, channel_id, name.as_deref(), user_id, &member_ids)
+            .await?;
+        (channel_id, member_ids.clone())
+    };
+
+    let dm = database::get_dm_channel(&state.db, channel_id)
+        .await?
+        .ok_or_else(|| AppError::not_found("Channel not found"))?;
+
+    if !joined.is_empty() {
+        for &id in &joined {
+            state.cache_private_channel_member(channel_id, id);
+        }
+        for member in &dm.members {
+            let event = if joined.contains(&member.id) {
+                GatewayEvent::DmCreated(dm.clone())
+            } else {
+                GatewayEvent::DmUpdated(dm.clone())
+            };
+            state.broadcast_user(member.id, event);
+        }
+    }
+
+    Ok(Json(dm))
+}
+
+pub async fn add_dm_member(
+    State(state): State<Arc<AppState>>,
+    auth_user: AuthUser,
+    Path((channel_id, member_id)): Path<(Uuid, Uuid)>,
+) -> AppResult<Json<DmChannel>> {
+    let user_id = auth_user.user_id();
+    permissions::require_channel_access(&state, channel_id, user_id)?;
+
+    let dm = database::get_dm_channel(&state.db, channel_id)
+        .await?
+        .ok_or_else(|| AppError::not_found("Channel not found"))?;
+
+    if dm.channel_type != ChannelType::GroupDm {
+        return Err(AppError::bad_request(
+            "Members can only be added to group DMs",
+        ));
+    }
+    if dm.members.iter().any(|m| m.id == member_id) {
+        return Ok(Json(dm));
+    }
+    if dm.members.len() >= MAX_GROUP_DM_MEMBERS {
+        return Err(AppError::bad_request(format!(
+            "Group DMs are limited to {MAX_GROUP_DM_MEMBERS} members"
+        )));
+    }
+    if database::get_user_by_id(&state.db, member_id)
+        .await?
+        .is_none()
+    {
+        return Err(AppError::not_found("User not found"));
+    }
+
+    database::add_channel_member(&state.db, channel_id, member_id).await?;
+    state.cache_private_channel_member(channel_id, member_id);
+
+    let dm = database::get_dm_channel(&state.db, channel_id)
+        .await?
+        .ok_or_else(|| AppError::not_found("Channel not found"))?;
+
+    state.broadcast_user(member_id, GatewayEvent::DmCreated(dm.clone()));
+    for member in dm.members.iter().filter(|m| m.id != member_id) {
+        state.broadcast_user(member.id, GatewayEvent::DmUpdated(dm.clone()));
+    }
+
+    Ok(Json(dm))
+}
+
+/// Leave a DM. The channel and its history are deleted once the last
+/// member leaves.
+pub async fn leave_dm(
+    State(state): State<Arc<AppState>>,
+    auth_user: AuthUser,
+    Path(channel_id): Path<Uuid>,
+) -> AppResult<()> {
+    let user_id = auth_user.user_id();
+    permissions::require_channel_access(&state, channel_id, user_id)?;
+
+    if database::get_dm_channel(&state.db, channel_id)
+        .await?
+        .is_none()
+    {
+        return Err(AppError::not_found("Channel not found"));
+    }
+
+    let deleted = database::remove_channel_member(&state.db, channel_id, user_id).await?;
+    state.uncache_private_channel_member(channel_id, user_id);
+
+    state.broadcast_user(user_id, GatewayEvent::DmDeleted { id: channel_id });
+
+    if let Some(storage_paths) = deleted {
+        state.forget_channel(channel_id);
+        delete_attachment_files(&state, storage_paths).await;
+    } else {
+        state.broadcast_for_channel(
+            channel_id,
+            GatewayEvent::DmMemberLeft {
+                channel_id,
+                user_id,
+            },
+        );
+    }
+
+    Ok(())
+}
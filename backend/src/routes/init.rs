@@ -4,8 +4,11 @@ use std::sync::Arc;
 
 use crate::auth::AuthUser;
 use crate::database;
-use crate::models::{AppState, Channel, MemberInfo, UserPresence, UserSummary, VoiceState};
-use crate::permissions::Role;
+use crate::models::{
+    AppState, Channel, ChannelCategory, ChannelReadState, DmChannel, MemberInfo, Role,
+    UserPresence, UserSummary, VoiceState,
+};
+use crate::permissions::Permissions;
 use crate::shared::AppResult;
 
 #[derive(Serialize)]
@@ -13,9 +16,15 @@ pub struct InitResponse {
     pub server_name: String,
     pub version: String,
     pub channels: Vec<Channel>,
+    pub categories: Vec<ChannelCategory>,
+    pub dm_channels: Vec<DmChannel>,
+    pub read_states: Vec<ChannelReadState>,
     pub online_users: Vec<UserPresence>,
     pub voice_states: Vec<VoiceState>,
     pub members: Vec<MemberInfo>,
+    pub roles: Vec<Role>,
+    /// The requesting user's effective permissions.
+    pub permissions: Permissions,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub users: Option<Vec<UserSummary>>,
 }
@@ -27,27 +36,32 @@ pub async fn get_init(
     let user_id = auth_user.user_id();
 
     // Run independent DB queries concurrently
-    let (server_name, channels, actor_role, members) = tokio::join!(
+    let (server_name, channels, categories, dm_channels, read_states, actor, members, roles) = tokio::join!(
         database::get_server_setting(&state.db, "server_name"),
         database::get_channels(&state.db),
-        database::get_user_role(&state.db, user_id),
+        database::get_channel_categories(&state.db),
+        database::get_dm_channels_for_user(&state.db, user_id),
+        database::get_read_states(&state.db, user_id),
+        database::get_member_permissions(&state.db, user_id),
         database::get_all_members(&state.db),
+        database::get_roles(&state.db),
     );
     let server_name = server_name?;
-    let channels = channels?;
-    let actor_role = actor_role?;
+    let mut channels = channels?;
+    channels.retain(|c| state.can_access_channel(c.id, user_id));
+    let categories = categories?;
+    let dm_channels = dm_channels?;
+    let read_states = read_states?;
+    let actor = actor?;
     let members = members?;
+    let roles = roles?;
 
-    let online_users: Vec<UserPresence> = state
-        .online_users
-        .iter()
-        .map(|entry| entry.value().clone())
-        .collect();
+    let online_users: Vec<UserPresence> = state.visible_online_users(user_id);
 
-    let voice_states: Vec<VoiceState> = state.all_voice_states();
+    let voice_states: Vec<VoiceState> = state.visible_voice_states(user_id);
 
-    // Include user list for moderators+ (needed for admin panel)
-    let users = if actor_role >= Role::Moderator {
+    // Include user list for moderators (needed for admin panel)
+    let users = if actor.permissions.intersects(Permissions::MODERATION) {
         Some(database::get_all_users(&state.db).await?)
     } else {
         None
@@ -57,9 +71,14 @@ pub async fn get_init(
         server_name,
         version: env!("CARGO_PKG_VERSION").to_string(),
         channels,
+        categories,
+        dm_channels,
+        read_states,
         online_users,
         voice_states,
         members,
+        roles,
+        permissions: actor.permissions,
         users,
     }))
 }
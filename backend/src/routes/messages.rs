@@ -2,23 +2,53 @@ use axum::{
     extract::{Path, Query, State},
     response::Json,
 };
-use chrono::{DateTime, Utc};
-use serde::{Deserialize, Serialize};
+use chrono::Utc;
+use serde::Deserialize;
 use sqlx::PgPool;
 use std::sync::Arc;
 use uuid::Uuid;
 
 use crate::auth::AuthUser;
 use crate::database;
-use crate::models::{AppState, EditMessageRequest, Message, SendMessageRequest};
-use crate::permissions::{self, Role};
-use crate::shared::validation::{MAX_EMOJI_LENGTH, validate_message_content};
+use crate::gateway::{GatewayEvent, ReactionEvent};
+use crate::link_preview;
+use crate::models::{
+    AckRequest, AppState, EditMessageRequest, ForwardMessageRequest, ForwardedFrom, Message,
+    MessageCursor, MessageLink, MessagePage, MessageRevision, ModAction, ModLogEntry,
+    PurgeMessagesRequest, PurgeMessagesResponse, SendMessageRequest,
+};
+use crate::permissions::{self, Permissions};
+use crate::shared::validation::{
+    self, MAX_EMOJI_LENGTH, MAX_PURGE_MESSAGES, MESSAGE_UNDO_WINDOW_SECS, validate_message_content,
+};
 use crate::shared::{AppError, AppResult};
 
+/// Message history paging. At most one of `before`, `after` and `around`
+/// may be given; with none, the newest messages are returned.
 #[derive(Debug, Deserialize)]
 pub struct MessageQuery {
     pub limit: Option<i64>,
-    pub before: Option<DateTime<Utc>>,
+    pub before: Option<Uuid>,
+    pub after: Option<Uuid>,
+    pub around: Option<Uuid>,
+}
+
+impl MessageQuery {
+    pub(crate) fn limit(&self) -> i64 {
+        self.limit.unwrap_or(50).clamp(1, 100)
+    }
+
+    pub(crate) fn cursor(&self) -> AppResult<MessageCursor> {
+        match (self.before, self.after, self.around) {
+            (None, None, None) => Ok(MessageCursor::Latest),
+            (Some(id), None, None) => Ok(MessageCursor::Before(id)),
+            (None, Some(id), None) => Ok(MessageCursor::After(id)),
+            (None, None, Some(id)) => Ok(MessageCursor::Around(id)),
+            _ => Err(AppError::bad_request(
+                "Only one of before, after or around may be given",
+            )),
+        }
+    }
 }
 
 pub async fn get_messages(
@@ -26,13 +56,92 @@ pub async fn get_messages(
     auth_user: AuthUser,
     Path(channel_id): Path<Uuid>,
     Query(query): Query<MessageQuery>,
-) -> AppResult<Json<Vec<Message>>> {
+) -> AppResult<Json<MessagePage>> {
+    let user_id = auth_user.user_id();
+    permissions::require_channel_access(&state, channel_id, user_id)?;
+
+    let include_deleted = database::get_member_permissions(&state.db, user_id)
+        .await?
+        .has(Permissions::MANAGE_MESSAGES);
+
+    // Moderators see tombstones, so their cursor may point at one
+    let cursor = query.cursor()?;
+    if let Some(message_id) = cursor.message_id() {
+        match database::get_message_location(&state.db, message_id, include_deleted).await? {
+            Some((message_channel_id, _)) if message_channel_id != channel_id => {
+                return Err(AppError::not_found("Message not found in this channel"));
+            }
+            Some((_, Some(_))) => {
+                return Err(AppError::bad_request(
+                    "Message is a thread reply; page the thread instead",
+                ));
+            }
+            Some(_) => {}
+            None => return Err(AppError::not_found("Message not found")),
+        }
+    }
+    let page = database::get_messages(
+        &state.db,
+        channel_id,
+        query.limit(),
+        cursor,
+        user_id,
+        include_deleted,
+    )
+    .await?;
+    Ok(Json(page))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct MessageLinkQuery {
+    pub limit: Option<i64>,
+}
+
+/// Resolve a permalink. Messages in channels the caller cannot see are
+/// reported as missing so links do not reveal that a channel exists.
+pub async fn get_message_link(
+    State(state): State<Arc<AppState>>,
+    auth_user: AuthUser,
+    Path(message_id): Path<Uuid>,
+    Query(query): Query<MessageLinkQuery>,
+) -> AppResult<Json<MessageLink>> {
     let user_id = auth_user.user_id();
+    let message = database::get_full_message_by_id(&state.db, message_id, user_id)
+        .await?
+        .filter(|m| state.can_access_channel(m.channel_id, user_id))
+        .ok_or_else(|| AppError::not_found("Message not found"))?;
 
     let limit = query.limit.unwrap_or(50).clamp(1, 100);
-    let messages =
-        database::get_messages(&state.db, channel_id, limit, query.before, user_id).await?;
-    Ok(Json(messages))
+    let cursor = MessageCursor::Around(message_id);
+    let include_deleted = database::get_member_permissions(&state.db, user_id)
+        .await?
+        .has(Permissions::MANAGE_MESSAGES);
+    let context = match message.thread_id {
+        Some(thread_id) => {
+            database::get_thread_messages(
+                &state.db,
+                thread_id,
+                limit,
+                cursor,
+                user_id,
+                include_deleted,
+            )
+            .await?
+        }
+        None => {
+            database::get_messages(
+                &state.db,
+                message.channel_id,
+                limit,
+                cursor,
+                user_id,
+                include_deleted,
+            )
+            .await?
+        }
+    };
+
+    Ok(Json(MessageLink { message, context }))
 }
 
 pub async fn send_message(
@@ -49,24 +158,137 @@ pub async fn send_message(
             "You are sending messages too fast",
         ));
     }
+    crate::services::message::check_channel_rules(&state, channel_id, user_id).await?;
 
     let result = crate::services::message::create_message(
         &state,
         &state.db,
         crate::services::message::CreateMessageParams {
             user_id,
+            author: crate::services::message::MessageAuthor::User,
             channel_id,
             content: payload.content,
             reply_to_id: payload.reply_to_id,
+            thread_id: None,
             attachment_ids: payload.attachment_ids,
+            poll: payload.poll,
             validate_reply_channel: true,
         },
     )
-    .await?;
+    .await
+    .inspect_err(|_| state.release_slowmode(channel_id, user_id))?;
+
+    crate::services::message::broadcast_new_message(&state, &result);
+
+    Ok(Json(result.message))
+}
+
+/// Repost a message into another channel with attribution to where it was
+/// first posted. Attachments are re-linked to the stored files rather than
+/// uploaded again. Forwarding is refused if anyone who can read the target
+/// channel could not read the source, so private content stays private.
+pub async fn forward_message(
+    State(state): State<Arc<AppState>>,
+    auth_user: AuthUser,
+    Path((channel_id, message_id)): Path<(Uuid, Uuid)>,
+    Json(payload): Json<ForwardMessageRequest>,
+) -> AppResult<Json<Message>> {
+    let user_id = auth_user.user_id();
+    permissions::require_channel_access(&state, channel_id, user_id)?;
+    verify_message_in_channel(&state.db, message_id, channel_id).await?;
+    let source = database::get_full_message_by_id(&state.db, message_id, user_id)
+        .await?
+        .ok_or_else(|| AppError::not_found("Message not found"))?;
+    if !source.kind.is_default() {
+        return Err(AppError::bad_request("System messages cannot be forwarded"));
+    }
+    let attachments = source.attachments.unwrap_or_default();
+    // Only text and attachments are carried over
+    if source.content.is_empty() && attachments.is_empty() {
+        let reason = if source.poll.is_some() {
+            "Polls cannot be forwarded"
+        } else if source.embeds.is_some() {
+            "Embeds cannot be forwarded"
+        } else {
+            "Message has nothing to forward"
+        };
+        return Err(AppError::bad_request(reason));
+    }
+
+    let target_id = payload.channel_id;
+    if !state.audience_can_see(target_id, channel_id) {
+        return Err(AppError::forbidden(
+            "Messages cannot be forwarded to a channel with a wider audience",
+        ));
+    }
+
+    permissions::check_not_muted(&state.db, user_id).await?;
+    if !state.check_message_rate_limit(user_id) {
+        return Err(AppError::too_many_requests(
+            "You are sending messages too fast",
+        ));
+    }
+    let target_permissions =
+        database::get_channel_permissions(&state.db, target_id, user_id).await?;
+    target_permissions.require(Permissions::SEND_MESSAGES)?;
+    if !attachments.is_empty() {
+        target_permissions.require(Permissions::ATTACH_FILES)?;
+    }
+    crate::services::message::check_channel_rules(&state, target_id, user_id).await?;
+
+    // Forwarding a forward keeps the original attribution
+    let forwarded_from = source.forwarded_from.unwrap_or(ForwardedFrom {
+        message_id: Some(source.id),
+        channel_id: Some(source.channel_id),
+        author_id: Some(source.author_id),
+        username: Some(source.username),
+        display_name: source.display_name,
+        timestamp: source.timestamp,
+    });
+
+    let attachment_ids = database::copy_attachments(&state.db, &attachments, user_id)
+        .await
+        .inspect_err(|_| state.release_slowmode(target_id, user_id))?;
+    let mut result = crate::services::message::create_message(
+        &state,
+        &state.db,
+        crate::services::message::CreateMessageParams {
+            user_id,
+            author: crate::services::message::MessageAuthor::User,
+            channel_id: target_id,
+            content: Some(source.content).filter(|c| !c.is_empty()),
+            reply_to_id: None,
+            thread_id: None,
+            attachment_ids,
+            poll: None,
+            validate_reply_channel: true,
+        },
+    )
+    .await
+    .inspect_err(|_| state.release_slowmode(target_id, user_id))?;
+    database::create_message_forward(&state.db, result.message.id, &forwarded_from).await?;
+    result.message.forwarded_from = Some(forwarded_from);
+
+    crate::services::message::broadcast_new_message(&state, &result);
 
     Ok(Json(result.message))
 }
 
+pub async fn ack_channel(
+    State(state): State<Arc<AppState>>,
+    auth_user: AuthUser,
+    Path(channel_id): Path<Uuid>,
+    Json(payload): Json<AckRequest>,
+) -> AppResult<()> {
+    crate::services::read_state::ack_channel(
+        &state,
+        auth_user.user_id(),
+        channel_id,
+        payload.message_id,
+    )
+    .await
+}
+
 pub async fn edit_message(
     State(state): State<Arc<AppState>>,
     auth_user: AuthUser,
@@ -74,56 +296,199 @@ pub async fn edit_message(
     Json(payload): Json<EditMessageRequest>,
 ) -> AppResult<Json<Message>> {
     let user_id = auth_user.user_id();
-    verify_message_ownership(&state.db, message_id, channel_id, user_id).await?;
+    permissions::require_channel_access(&state, channel_id, user_id)?;
+    let mut message = verify_message_ownership(&state.db, message_id, channel_id, user_id).await?;
+    if !message.kind.is_default() {
+        return Err(AppError::bad_request("System messages cannot be edited"));
+    }
+    if database::is_webhook_message(&state.db, message_id).await? {
+        return Err(AppError::bad_request("Webhook messages cannot be edited"));
+    }
 
     validate_message_content(&payload.content)?;
 
-    database::update_message(&state.db, message_id, &payload.content).await?;
+    if payload.content != message.content {
+        database::update_message(&state.db, message_id, &payload.content).await?;
+
+        let urls_changed = link_preview::extract_urls(&message.content)
+            != link_preview::extract_urls(&payload.content);
+        if urls_changed {
+            database::detach_message_previews(&state.db, message_id).await?;
+            link_preview::spawn_preview_fetch(
+                state.clone(),
+                message_id,
+                channel_id,
+                message.thread_id,
+                user_id,
+                payload.content.clone(),
+            );
+        }
+
+        message.content = payload.content;
+        crate::services::mention::process_mentions(&state, &message).await?;
+    }
 
     let updated_message = database::get_full_message_by_id(&state.db, message_id, user_id)
         .await?
         .ok_or_else(|| AppError::not_found("Message not found"))?;
 
-    state.broadcast_channel(
-        channel_id,
-        "message_edited",
-        serde_json::json!(updated_message),
+    state.broadcast_topic(
+        message.channel_id,
+        message.topic_id(),
+        GatewayEvent::MessageEdited(updated_message.clone()),
     );
 
     Ok(Json(updated_message))
 }
 
+/// Prior revisions of a message. Authors may view their own; viewing anyone
+/// else's requires the manage messages permission.
+pub async fn get_message_history(
+    State(state): State<Arc<AppState>>,
+    auth_user: AuthUser,
+    Path((channel_id, message_id)): Path<(Uuid, Uuid)>,
+) -> AppResult<Json<Vec<MessageRevision>>> {
+    let user_id = auth_user.user_id();
+    permissions::require_channel_access(&state, channel_id, user_id)?;
+    let message = verify_message_in_channel(&state.db, message_id, channel_id).await?;
+
+    if message.author_id != user_id {
+        database::get_member_permissions(&state.db, user_id)
+            .await?
+            .require(Permissions::MANAGE_MESSAGES)?;
+    }
+
+    let revisions = database::get_message_revisions(&state.db, message_id).await?;
+    Ok(Json(revisions))
+}
+
 pub async fn delete_message(
     State(state): State<Arc<AppState>>,
     auth_user: AuthUser,
     Path((channel_id, message_id)): Path<(Uuid, Uuid)>,
 ) -> AppResult<()> {
     let user_id = auth_user.user_id();
-    let role = database::get_user_role(&state.db, user_id).await?;
+    permissions::require_channel_access(&state, channel_id, user_id)?;
+    let actor = database::get_member_permissions(&state.db, user_id).await?;
 
-    if role >= Role::Moderator {
-        verify_message_in_channel(&state.db, message_id, channel_id).await?;
+    let message = if actor.has(Permissions::MANAGE_MESSAGES) {
+        verify_message_in_channel(&state.db, message_id, channel_id).await?
     } else {
-        verify_message_ownership(&state.db, message_id, channel_id, user_id).await?;
+        verify_message_ownership(&state.db, message_id, channel_id, user_id).await?
+    };
+
+    let deleted_at = database::delete_message(&state.db, message_id, user_id).await?;
+
+    state.broadcast_topic(
+        message.channel_id,
+        message.topic_id(),
+        GatewayEvent::MessageDeleted {
+            id: message_id,
+            channel_id,
+            thread_id: message.thread_id,
+            deleted_at,
+        },
+    );
+    if let Some(thread_id) = message.thread_id {
+        crate::services::message::broadcast_thread_updated(&state, thread_id).await?;
+    }
+
+    Ok(())
+}
+
+/// Soft-delete many channel messages at once, e.g. to clean up a spam raid.
+pub async fn purge_messages(
+    State(state): State<Arc<AppState>>,
+    auth_user: AuthUser,
+    Path(channel_id): Path<Uuid>,
+    Json(payload): Json<PurgeMessagesRequest>,
+) -> AppResult<Json<PurgeMessagesResponse>> {
+    let user_id = auth_user.user_id();
+    permissions::require_channel_access(&state, channel_id, user_id)?;
+    database::get_member_permissions(&state.db, user_id)
+        .await?
+        .require(Permissions::MANAGE_MESSAGES)?;
+    validation::validate_reason(&payload.reason)?;
+
+    let has_filter = payload.limit.is_some()
+        || payload.author_id.is_some()
+        || payload.before.is_some()
+        || payload.after.is_some()
+        || payload.has_link
+        || payload.has_attachment;
+    if !has_filter {
+        return Err(AppError::bad_request("Specify at least one purge filter"));
+    }
+    let limit = payload.limit.unwrap_or(MAX_PURGE_MESSAGES);
+    if !(1..=MAX_PURGE_MESSAGES).contains(&limit) {
+        return Err(AppError::bad_request(format!(
+            "Purge limit must be between 1 and {MAX_PURGE_MESSAGES}"
+        )));
+    }
+
+    let deleted_at = Utc::now();
+    let ids = database::purge_channel_messages(
+        &state.db, channel_id, &payload, limit, user_id, deleted_at,
+    )
+    .await?;
+    if ids.is_empty() {
+        return Ok(Json(PurgeMessagesResponse { deleted: 0 }));
     }
 
-    database::delete_message(&state.db, message_id).await?;
+    database::create_mod_log_entry(
+        &state.db,
+        &ModLogEntry::new(
+            ModAction::PurgeMessages,
+            user_id,
+            payload.author_id,
+            payload.reason,
+            Some(format!(
+                "Purged {} messages in channel {channel_id}",
+                ids.len()
+            )),
+        ),
+    )
+    .await?;
 
+    let deleted = ids.len();
     state.broadcast_channel(
         channel_id,
-        "message_deleted",
-        serde_json::json!({ "id": message_id, "channel_id": channel_id }),
+        GatewayEvent::MessagesBulkDeleted {
+            channel_id,
+            ids,
+            deleted_at,
+        },
     );
 
-    Ok(())
+    Ok(Json(PurgeMessagesResponse { deleted }))
 }
 
-#[derive(Debug, Serialize)]
-pub struct ReactionEvent {
-    pub message_id: Uuid,
-    pub emoji: String,
-    pub user_id: Uuid,
-    pub username: String,
+/// Undo deleting your own message within the undo window.
+pub async fn restore_message(
+    State(state): State<Arc<AppState>>,
+    auth_user: AuthUser,
+    Path((channel_id, message_id)): Path<(Uuid, Uuid)>,
+) -> AppResult<Json<Message>> {
+    let user_id = auth_user.user_id();
+    permissions::require_channel_access(&state, channel_id, user_id)?;
+
+    let since = Utc::now() - chrono::Duration::seconds(MESSAGE_UNDO_WINDOW_SECS);
+    database::restore_message(&state.db, message_id, channel_id, user_id, since).await?;
+
+    let message = database::get_full_message_by_id(&state.db, message_id, user_id)
+        .await?
+        .ok_or_else(|| AppError::not_found("Message not found"))?;
+
+    state.broadcast_topic(
+        message.channel_id,
+        message.topic_id(),
+        GatewayEvent::MessageRestored(message.clone()),
+    );
+    if let Some(thread_id) = message.thread_id {
+        crate::services::message::broadcast_thread_updated(&state, thread_id).await?;
+    }
+
+    Ok(Json(message))
 }
 
 pub async fn add_reaction(
@@ -139,14 +504,18 @@ pub async fn add_reaction(
         ));
     }
 
-    verify_message_in_channel(&state.db, message_id, channel_id).await?;
+    permissions::require_channel_access(&state, channel_id, user_id)?;
+    database::get_channel_permissions(&state.db, channel_id, user_id)
+        .await?
+        .require(Permissions::ADD_REACTIONS)?;
+    let message = verify_message_in_channel(&state.db, message_id, channel_id).await?;
 
     database::add_reaction(&state.db, message_id, user_id, &emoji).await?;
 
-    state.broadcast_channel(
-        channel_id,
-        "reaction_added",
-        serde_json::json!(ReactionEvent {
+    state.broadcast_topic(
+        message.channel_id,
+        message.topic_id(),
+        GatewayEvent::ReactionAdded(ReactionEvent {
             message_id,
             emoji,
             user_id,
@@ -163,15 +532,16 @@ pub async fn remove_reaction(
     Path((channel_id, message_id, emoji)): Path<(Uuid, Uuid, String)>,
 ) -> AppResult<()> {
     let user_id = auth_user.user_id();
+    permissions::require_channel_access(&state, channel_id, user_id)?;
 
-    verify_message_in_channel(&state.db, message_id, channel_id).await?;
+    let message = verify_message_in_channel(&state.db, message_id, channel_id).await?;
 
     database::remove_reaction(&state.db, message_id, user_id, &emoji).await?;
 
-    state.broadcast_channel(
-        channel_id,
-        "reaction_removed",
-        serde_json::json!(ReactionEvent {
+    state.broadcast_topic(
+        message.channel_id,
+        message.topic_id(),
+        GatewayEvent::ReactionRemoved(ReactionEvent {
             message_id,
             emoji,
             user_id,
@@ -182,11 +552,11 @@ pub async fn remove_reaction(
     Ok(())
 }
 
-async fn verify_message_in_channel(
+pub(crate) async fn verify_message_in_channel(
     db: &PgPool,
     message_id: Uuid,
     channel_id: Uuid,
-) -> AppResult<()> {
+) -> AppResult<Message> {
     let message = database::get_message_by_id(db, message_id)
         .await?
         .ok_or_else(|| AppError::not_found("Message not found"))?;
@@ -195,7 +565,7 @@ async fn verify_message_in_channel(
         return Err(AppError::not_found("Message not found in this channel"));
     }
 
-    Ok(())
+    Ok(message)
 }
 
 async fn verify_message_ownership(
@@ -203,7 +573,7 @@ async fn verify_message_ownership(
     message_id: Uuid,
     channel_id: Uuid,
     user_id: Uuid,
-) -> AppResult<()> {
+) -> AppResult<Message> {
     let message = database::get_message_by_id(db, message_id)
         .await?
         .ok_or_else(|| AppError::not_found("Message not found"))?;
@@ -216,5 +586,5 @@ async fn verify_message_ownership(
         return Err(AppError::not_found("Message not found in this channel"));
     }
 
-    Ok(())
+    Ok(message)
 }
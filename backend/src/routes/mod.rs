@@ -1,15 +1,29 @@
 mod attachments;
 mod channels;
 mod custom_emojis;
+mod dms;
 mod init;
 mod messages;
+mod pins;
+mod polls;
 mod proxy;
+mod scheduled_messages;
+mod search;
 mod soundboard;
+mod threads;
+mod webhooks;
 
 pub use attachments::*;
 pub use channels::*;
 pub use custom_emojis::*;
+pub use dms::*;
 pub use init::*;
 pub use messages::*;
+pub use pins::*;
+pub use polls::*;
 pub use proxy::*;
+pub use scheduled_messages::*;
+pub use search::*;
 pub use soundboard::*;
+pub use threads::*;
+pub use webhooks::*;
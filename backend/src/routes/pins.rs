@@ -0,0 +1,164 @@
+use axum::{
+    extract::{Path, State},
+    response::Json,
+};
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::auth::AuthUser;
+use crate::database;
+use crate::gateway::GatewayEvent;
+use crate::models::{AppState, Message, MessageKind, ModAction, ModLogEntry};
+use crate::permissions::{self, Permissions};
+use crate::routes::verify_message_in_channel;
+use crate::services::message::{CreateMessageResult, broadcast_new_message};
+use crate::shared::validation::MAX_PINS_PER_CHANNEL;
+use crate::shared::{AppError, AppResult};
+
+pub async fn get_pinned_messages(
+    State(state): State<Arc<AppState>>,
+    auth_user: AuthUser,
+    Path(channel_id): Path<Uuid>,
+) -> AppResult<Json<Vec<Message>>> {
+    let user_id = auth_user.user_id();
+    permissions::require_channel_access(&state, channel_id, user_id)?;
+
+    let messages = database::get_pinned_messages(&state.db, channel_id, user_id).await?;
+    Ok(Json(messages))
+}
+
+pub async fn pin_message(
+    State(state): State<Arc<AppState>>,
+    auth_user: AuthUser,
+    Path((channel_id, message_id)): Path<(Uuid, Uuid)>,
+) -> AppResult<()> {
+    let user_id = auth_user.user_id();
+    permissions::require_channel_access(&state, channel_id, user_id)?;
+    database::get_channel_permissions(&state.db, channel_id, user_id)
+        .await?
+        .require(Permissions::PIN_MESSAGES)?;
+
+    let message = verify_message_in_channel(&state.db, message_id, channel_id).await?;
+    if message.thread_id.is_some() {
+        return Err(AppError::bad_request("Thread replies cannot be pinned"));
+    }
+    if !message.kind.is_default() {
+        return Err(AppError::bad_request("System messages cannot be pinned"));
+    }
+    if message.pinned_at.is_some() {
+        return Err(AppError::conflict("Message is already pinned"));
+    }
+
+    let pinned_at = database::pin_message(
+        &state.db,
+        channel_id,
+        message_id,
+        user_id,
+        MAX_PINS_PER_CHANNEL as i64,
+    )
+    .await?
+    .ok_or_else(|| {
+        AppError::bad_request(format!(
+            "Maximum of {MAX_PINS_PER_CHANNEL} pinned messages per channel"
+        ))
+    })?;
+
+    database::create_mod_log_entry(
+        &state.db,
+        &ModLogEntry::new(
+            ModAction::PinMessage,
+            user_id,
+            Some(message.author_id),
+            None,
+            Some(format!("Message {message_id} in channel {channel_id}")),
+        ),
+    )
+    .await?;
+
+    state.broadcast_channel(
+        channel_id,
+        GatewayEvent::MessagePinned {
+            channel_id,
+            message_id,
+            pinned_by: user_id,
+            pinned_at,
+        },
+    );
+
+    post_pin_notice(&state, channel_id, message_id, user_id).await
+}
+
+pub async fn unpin_message(
+    State(state): State<Arc<AppState>>,
+    auth_user: AuthUser,
+    Path((channel_id, message_id)): Path<(Uuid, Uuid)>,
+) -> AppResult<()> {
+    let user_id = auth_user.user_id();
+    permissions::require_channel_access(&state, channel_id, user_id)?;
+    database::get_channel_permissions(&state.db, channel_id, user_id)
+        .await?
+        .require(Permissions::PIN_MESSAGES)?;
+
+    let message = verify_message_in_channel(&state.db, message_id, channel_id).await?;
+
+    database::unpin_message(&state.db, message_id).await?;
+
+    database::create_mod_log_entry(
+        &state.db,
+        &ModLogEntry::new(
+            ModAction::UnpinMessage,
+            user_id,
+            Some(message.author_id),
+            None,
+            Some(format!("Message {message_id} in channel {channel_id}")),
+        ),
+    )
+    .await?;
+
+    state.broadcast_channel(
+        channel_id,
+        GatewayEvent::MessageUnpinned {
+            channel_id,
+            message_id,
+        },
+    );
+
+    Ok(())
+}
+
+/// Post the system message noting who pinned which message. It replies to
+/// the pinned message so clients can show and jump to it.
+async fn post_pin_notice(
+    state: &Arc<AppState>,
+    channel_id: Uuid,
+    message_id: Uuid,
+    user_id: Uuid,
+) -> AppResult<()> {
+    let user = database::get_user_by_id(&state.db, user_id)
+        .await?
+        .ok_or_else(|| AppError::not_found("User not found"))?;
+    let reply_to = database::get_reply_preview(&state.db, message_id).await?;
+
+    let mut notice = Message::new(
+        String::new(),
+        user.username,
+        user.display_name,
+        user_id,
+        channel_id,
+        Some(message_id),
+        reply_to,
+    );
+    notice.kind = MessageKind::PinNotice;
+    database::create_message(&state.db, &notice, user_id).await?;
+
+    broadcast_new_message(
+        state,
+        &CreateMessageResult {
+            message: notice,
+            channel_id,
+            thread: None,
+        },
+    );
+
+    Ok(())
+}
@@ -0,0 +1,79 @@
+use axum::{
+    extract::{Path, State},
+    response::Json,
+};
+use chrono::Utc;
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::auth::AuthUser;
+use crate::database;
+use crate::models::{AppState, Poll};
+use crate::permissions;
+use crate::routes::verify_message_in_channel;
+use crate::services::poll::broadcast_poll_updated;
+use crate::shared::{AppError, AppResult};
+
+/// Load the poll on a message and check that it still accepts votes.
+async fn require_open_poll(
+    state: &AppState,
+    channel_id: Uuid,
+    message_id: Uuid,
+    user_id: Uuid,
+) -> AppResult<Poll> {
+    permissions::require_channel_access(state, channel_id, user_id)?;
+    verify_message_in_channel(&state.db, message_id, channel_id).await?;
+
+    let poll = database::get_poll(&state.db, message_id, user_id)
+        .await?
+        .ok_or_else(|| AppError::not_found("Message has no poll"))?;
+    if !poll.is_open(Utc::now()) {
+        return Err(AppError::bad_request("This poll has closed"));
+    }
+    Ok(poll)
+}
+
+/// Vote for an option. On single-choice polls this replaces any earlier vote.
+/// Returns the poll as the voter sees it.
+pub async fn vote_poll(
+    State(state): State<Arc<AppState>>,
+    auth_user: AuthUser,
+    Path((channel_id, message_id, option_id)): Path<(Uuid, Uuid, Uuid)>,
+) -> AppResult<Json<Poll>> {
+    let user_id = auth_user.user_id();
+    let poll = require_open_poll(&state, channel_id, message_id, user_id).await?;
+
+    database::add_poll_vote(
+        &state.db,
+        message_id,
+        option_id,
+        user_id,
+        poll.allow_multiple,
+    )
+    .await?;
+
+    let poll = database::get_poll(&state.db, message_id, user_id)
+        .await?
+        .ok_or_else(|| AppError::not_found("Message has no poll"))?;
+    broadcast_poll_updated(&state, message_id, &poll).await?;
+
+    Ok(Json(poll))
+}
+
+pub async fn unvote_poll(
+    State(state): State<Arc<AppState>>,
+    auth_user: AuthUser,
+    Path((channel_id, message_id, option_id)): Path<(Uuid, Uuid, Uuid)>,
+) -> AppResult<Json<Poll>> {
+    let user_id = auth_user.user_id();
+    require_open_poll(&state, channel_id, message_id, user_id).await?;
+
+    database::remove_poll_vote(&state.db, message_id, option_id, user_id).await?;
+
+    let poll = database::get_poll(&state.db, message_id, user_id)
+        .await?
+        .ok_or_else(|| AppError::not_found("Message has no poll"))?;
+    broadcast_poll_updated(&state, message_id, &poll).await?;
+
+    Ok(Json(poll))
+}
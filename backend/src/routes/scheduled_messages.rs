@@ -0,0 +1,144 @@
+use axum::{
+    extract::{Path, State},
+    response::Json,
+};
+use chrono::Utc;
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::auth::AuthUser;
+use crate::database;
+use crate::models::{
+    AppState, ScheduleMessageRequest, ScheduledMessage, UpdateScheduledMessageRequest,
+};
+use crate::permissions::{self, Permissions};
+use crate::routes::verify_message_in_channel;
+use crate::shared::validation::{
+    MAX_ATTACHMENTS_PER_MESSAGE, MAX_SCHEDULED_MESSAGES_PER_USER,
+    validate_message_content_optional, validate_poll, validate_send_at,
+};
+use crate::shared::{AppError, AppResult};
+
+pub async fn get_scheduled_messages(
+    State(state): State<Arc<AppState>>,
+    auth_user: AuthUser,
+) -> AppResult<Json<Vec<ScheduledMessage>>> {
+    let scheduled = database::get_scheduled_messages(&state.db, auth_user.user_id()).await?;
+    Ok(Json(scheduled))
+}
+
+/// Queue a message to post at `send_at`. Permissions are checked now and
+/// again at delivery, where the channel's announcement and slowmode rules
+/// also apply.
+pub async fn schedule_message(
+    State(state): State<Arc<AppState>>,
+    auth_user: AuthUser,
+    Path(channel_id): Path<Uuid>,
+    Json(payload): Json<ScheduleMessageRequest>,
+) -> AppResult<Json<ScheduledMessage>> {
+    let user_id = auth_user.user_id();
+    permissions::check_not_muted(&state.db, user_id).await?;
+    permissions::require_channel_access(&state, channel_id, user_id)?;
+    let channel_permissions =
+        database::get_channel_permissions(&state.db, channel_id, user_id).await?;
+    channel_permissions.require(Permissions::SEND_MESSAGES)?;
+
+    let now = Utc::now();
+    validate_send_at(payload.send_at, now)?;
+
+    let has_attachments = !payload.attachment_ids.is_empty();
+    validate_message_content_optional(&payload.content, has_attachments || payload.poll.is_some())?;
+    let poll = payload.poll.map(validate_poll).transpose()?;
+
+    if payload.attachment_ids.len() > MAX_ATTACHMENTS_PER_MESSAGE {
+        return Err(AppError::bad_request(format!(
+            "Maximum {MAX_ATTACHMENTS_PER_MESSAGE} attachments per message"
+        )));
+    }
+    if has_attachments {
+        channel_permissions.require(Permissions::ATTACH_FILES)?;
+    }
+
+    if let Some(reply_id) = payload.reply_to_id {
+        let replied_msg = verify_message_in_channel(&state.db, reply_id, channel_id).await?;
+        if replied_msg.thread_id.is_some() {
+            return Err(AppError::bad_request(
+                "Scheduled messages cannot reply to thread messages",
+            ));
+        }
+    }
+
+    if database::count_scheduled_messages(&state.db, user_id).await?
+        >= MAX_SCHEDULED_MESSAGES_PER_USER
+    {
+        return Err(AppError::bad_request(format!(
+            "Maximum of {MAX_SCHEDULED_MESSAGES_PER_USER} scheduled messages"
+        )));
+    }
+
+    let scheduled = ScheduledMessage {
+        id: Uuid::now_v7(),
+        author_id: user_id,
+        channel_id,
+        content: payload.content,
+        reply_to_id: payload.reply_to_id,
+        attachment_ids: payload.attachment_ids,
+        poll,
+        send_at: payload.send_at,
+        created_at: now,
+        failure: None,
+    };
+    database::create_scheduled_message(&state.db, &scheduled).await?;
+
+    Ok(Json(scheduled))
+}
+
+pub async fn update_scheduled_message(
+    State(state): State<Arc<AppState>>,
+    auth_user: AuthUser,
+    Path(scheduled_id): Path<Uuid>,
+    Json(payload): Json<UpdateScheduledMessageRequest>,
+) -> AppResult<Json<ScheduledMessage>> {
+    let scheduled = verify_scheduled_ownership(&state, scheduled_id, auth_user.user_id()).await?;
+
+    if let Some(send_at) = payload.send_at {
+        validate_send_at(send_at, Utc::now())?;
+    }
+    let content = payload.content.or(scheduled.content);
+    validate_message_content_optional(
+        &content,
+        !scheduled.attachment_ids.is_empty() || scheduled.poll.is_some(),
+    )?;
+
+    let updated = database::update_scheduled_message(
+        &state.db,
+        scheduled_id,
+        content.as_deref(),
+        payload.send_at.unwrap_or(scheduled.send_at),
+        payload.send_at.is_some(),
+    )
+    .await?;
+
+    Ok(Json(updated))
+}
+
+pub async fn cancel_scheduled_message(
+    State(state): State<Arc<AppState>>,
+    auth_user: AuthUser,
+    Path(scheduled_id): Path<Uuid>,
+) -> AppResult<()> {
+    verify_scheduled_ownership(&state, scheduled_id, auth_user.user_id()).await?;
+    database::delete_scheduled_message(&state.db, scheduled_id).await
+}
+
+/// Other users' scheduled messages are reported as missing.
+async fn verify_scheduled_ownership(
+    state: &AppState,
+    scheduled_id: Uuid,
+    user_id: Uuid,
+) -> AppResult<ScheduledMessage> {
+    database::get_scheduled_message(&state.db, scheduled_id)
+        .await?
+        .filter(|scheduled| scheduled.author_id == user_id)
+        .ok_or_else(|| AppError::not_found("Scheduled message not found"))
+}
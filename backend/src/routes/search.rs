@@ -0,0 +1,54 @@
+use axum::{
+    extract::{Query, State},
+    response::Json,
+};
+use std::sync::Arc;
+
+use crate::auth::AuthUser;
+use crate::database;
+use crate::models::{AppState, SearchQuery, SearchResult};
+use crate::shared::validation::MAX_SEARCH_QUERY_LENGTH;
+use crate::shared::{AppError, AppResult};
+
+pub async fn search_messages(
+    State(state): State<Arc<AppState>>,
+    auth_user: AuthUser,
+    Query(query): Query<SearchQuery>,
+) -> AppResult<Json<Vec<SearchResult>>> {
+    let user_id = auth_user.user_id();
+
+    let text = query.q.as_deref().map(str::trim).unwrap_or_default();
+    if text.chars().count() > MAX_SEARCH_QUERY_LENGTH {
+        return Err(AppError::bad_request(format!(
+            "Search query must be at most {MAX_SEARCH_QUERY_LENGTH} characters"
+        )));
+    }
+
+    let has_filter = query.channel_id.is_some()
+        || query.author_id.is_some()
+        || query.before.is_some()
+        || query.after.is_some()
+        || query.has_attachment
+        || query.has_link
+        || query.mentions_me;
+    if text.is_empty() && !has_filter {
+        return Err(AppError::bad_request(
+            "Search requires a query or at least one filter",
+        ));
+    }
+
+    let limit = query.limit.unwrap_or(25).clamp(1, 100);
+    let offset = query.offset.unwrap_or(0).max(0);
+
+    let hidden_channel_ids = state.hidden_channel_ids(user_id);
+    let results = database::search_messages(
+        &state.db,
+        &query,
+        limit,
+        offset,
+        user_id,
+        &hidden_channel_ids,
+    )
+    .await?;
+    Ok(Json(results))
+}
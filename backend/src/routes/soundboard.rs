@@ -12,8 +12,9 @@ use uuid::Uuid;
 
 use crate::auth::AuthUser;
 use crate::database;
+use crate::gateway::GatewayEvent;
 use crate::models::{AppState, PlaySoundRequest, SoundboardSound, UpdateSoundRequest};
-use crate::permissions::Role;
+use crate::permissions::Permissions;
 use crate::shared::AppError;
 use crate::shared::validation::{
     MAX_SOUNDBOARD_SOUND_DURATION_MS, MAX_SOUNDBOARD_SOUND_SIZE, MAX_SOUNDBOARD_SOUNDS,
@@ -125,6 +126,9 @@ pub async fn upload_sound(
     let store = require_storage(&state)?;
     let user_id = auth_user.user_id();
     crate::permissions::check_not_muted(&state.db, user_id).await?;
+    database::get_member_permissions(&state.db, user_id)
+        .await?
+        .require(Permissions::USE_SOUNDBOARD)?;
 
     // Check sound count limit
     let count: (i64,) = sqlx::query_as("SELECT COUNT(*) FROM soundboard_sounds")
@@ -251,7 +255,7 @@ pub async fn upload_sound(
     .map_err(|e| AppError::internal(format!("Failed to save sound: {e}")))?;
 
     // Broadcast creation event
-    state.broadcast_global("soundboard_sound_created", serde_json::json!(sound));
+    state.broadcast_global(GatewayEvent::SoundboardSoundCreated(sound.clone()));
 
     Ok(Json(sound))
 }
@@ -274,8 +278,8 @@ pub async fn update_sound(
 
     // Only the uploader or mods/admins can update
     if existing.created_by != user_id {
-        let role = database::get_user_role(&state.db, user_id).await?;
-        if !matches!(role, Role::Admin | Role::Owner | Role::Moderator) {
+        let actor = database::get_member_permissions(&state.db, user_id).await?;
+        if !actor.has(Permissions::MANAGE_SOUNDBOARD) {
             return Err(AppError::forbidden(
                 "Only the uploader or moderators can edit sounds",
             ));
@@ -304,7 +308,7 @@ pub async fn update_sound(
     .await
     .map_err(|e| AppError::internal(format!("Failed to update sound: {e}")))?;
 
-    state.broadcast_global("soundboard_sound_updated", serde_json::json!(sound));
+    state.broadcast_global(GatewayEvent::SoundboardSoundUpdated(sound.clone()));
 
     Ok(Json(sound))
 }
@@ -326,8 +330,8 @@ pub async fn delete_sound(
 
     // Only the uploader or mods/admins can delete
     if sound.created_by != user_id {
-        let role = database::get_user_role(&state.db, user_id).await?;
-        if !matches!(role, Role::Admin | Role::Owner | Role::Moderator) {
+        let actor = database::get_member_permissions(&state.db, user_id).await?;
+        if !actor.has(Permissions::MANAGE_SOUNDBOARD) {
             return Err(AppError::forbidden(
                 "Only the uploader or moderators can delete sounds",
             ));
@@ -346,10 +350,7 @@ pub async fn delete_sound(
         .await
         .map_err(|e| AppError::internal(format!("Failed to delete sound: {e}")))?;
 
-    state.broadcast_global(
-        "soundboard_sound_deleted",
-        serde_json::json!({ "sound_id": sound_id }),
-    );
+    state.broadcast_global(GatewayEvent::SoundboardSoundDeleted { sound_id });
 
     Ok(())
 }
@@ -396,6 +397,9 @@ pub async fn play_sound(
     Json(req): Json<PlaySoundRequest>,
 ) -> Result<StatusCode, AppError> {
     let user_id = auth_user.user_id();
+    database::get_member_permissions(&state.db, user_id)
+        .await?
+        .require(Permissions::USE_SOUNDBOARD)?;
 
     // Verify user is in the voice channel
     let channel_users = state
@@ -431,15 +435,12 @@ pub async fn play_sound(
     SOUND_COOLDOWNS.insert(sound_id, Instant::now());
 
     // Broadcast play event to all connected clients
-    state.broadcast_global(
-        "soundboard_play",
-        serde_json::json!({
-            "channel_id": req.channel_id,
-            "user_id": user_id,
-            "sound_id": sound_id,
-            "sound_volume": sound.volume,
-        }),
-    );
+    state.broadcast_global(GatewayEvent::SoundboardPlay {
+        channel_id: req.channel_id,
+        user_id,
+        sound_id,
+        sound_volume: sound.volume,
+    });
 
     Ok(StatusCode::NO_CONTENT)
 }
@@ -0,0 +1,180 @@
+use axum::{
+    extract::{Path, Query, State},
+    response::Json,
+};
+use serde::Deserialize;
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::auth::AuthUser;
+use crate::database;
+use crate::gateway::GatewayEvent;
+use crate::models::{
+    AppState, CreateThreadRequest, Message, MessagePage, SendMessageRequest, Thread,
+};
+use crate::permissions::{self, Permissions};
+use crate::routes::MessageQuery;
+use crate::shared::validation::{MAX_THREAD_NAME_LENGTH, validate_thread_name};
+use crate::shared::{AppError, AppResult};
+
+#[derive(Debug, Deserialize)]
+pub struct ThreadListQuery {
+    #[serde(default)]
+    pub archived: bool,
+}
+
+pub async fn create_thread(
+    State(state): State<Arc<AppState>>,
+    auth_user: AuthUser,
+    Path((channel_id, message_id)): Path<(Uuid, Uuid)>,
+    Json(payload): Json<CreateThreadRequest>,
+) -> AppResult<Json<Thread>> {
+    let user_id = auth_user.user_id();
+    permissions::require_channel_access(&state, channel_id, user_id)?;
+    permissions::check_not_muted(&state.db, user_id).await?;
+    database::get_channel_permissions(&state.db, channel_id, user_id)
+        .await?
+        .require(Permissions::SEND_MESSAGES)?;
+
+    let parent =
+        crate::routes::verify_message_in_channel(&state.db, message_id, channel_id).await?;
+    if parent.thread_id.is_some() {
+        return Err(AppError::bad_request(
+            "Cannot start a thread from a thread reply",
+        ));
+    }
+
+    let name = match payload.name {
+        Some(name) => validate_thread_name(&name)?,
+        None => default_thread_name(&parent.content),
+    };
+
+    let now = chrono::Utc::now();
+    let thread = Thread {
+        id: Uuid::now_v7(),
+        channel_id,
+        parent_message_id: message_id,
+        name,
+        created_by: Some(user_id),
+        reply_count: 0,
+        last_activity_at: now,
+        archived: false,
+        created_at: now,
+    };
+
+    database::create_thread(&state.db, &thread).await?;
+
+    state.broadcast_channel(channel_id, GatewayEvent::ThreadCreated(thread.clone()));
+
+    Ok(Json(thread))
+}
+
+pub async fn get_threads(
+    State(state): State<Arc<AppState>>,
+    auth_user: AuthUser,
+    Path(channel_id): Path<Uuid>,
+    Query(query): Query<ThreadListQuery>,
+) -> AppResult<Json<Vec<Thread>>> {
+    permissions::require_channel_access(&state, channel_id, auth_user.user_id())?;
+    let threads = database::get_channel_threads(&state.db, channel_id, query.archived).await?;
+    Ok(Json(threads))
+}
+
+pub async fn get_thread_messages(
+    State(state): State<Arc<AppState>>,
+    auth_user: AuthUser,
+    Path((channel_id, thread_id)): Path<(Uuid, Uuid)>,
+    Query(query): Query<MessageQuery>,
+) -> AppResult<Json<MessagePage>> {
+    let user_id = auth_user.user_id();
+    permissions::require_channel_access(&state, channel_id, user_id)?;
+    verify_thread_in_channel(&state, thread_id, channel_id).await?;
+
+    let include_deleted = database::get_member_permissions(&state.db, user_id)
+        .await?
+        .has(Permissions::MANAGE_MESSAGES);
+
+    let cursor = query.cursor()?;
+    if let Some(message_id) = cursor.message_id() {
+        let location =
+            database::get_message_location(&state.db, message_id, include_deleted).await?;
+        if location.is_none_or(|(_, message_thread_id)| message_thread_id != Some(thread_id)) {
+            return Err(AppError::not_found("Message not found in this thread"));
+        }
+    }
+    let page = database::get_thread_messages(
+        &state.db,
+        thread_id,
+        query.limit(),
+        cursor,
+        user_id,
+        include_deleted,
+    )
+    .await?;
+    Ok(Json(page))
+}
+
+pub async fn send_thread_message(
+    State(state): State<Arc<AppState>>,
+    auth_user: AuthUser,
+    Path((channel_id, thread_id)): Path<(Uuid, Uuid)>,
+    Json(payload): Json<SendMessageRequest>,
+) -> AppResult<Json<Message>> {
+    let user_id = auth_user.user_id();
+    permissions::check_not_muted(&state.db, user_id).await?;
+
+    if !state.check_message_rate_limit(user_id) {
+        return Err(AppError::too_many_requests(
+            "You are sending messages too fast",
+        ));
+    }
+    crate::services::message::check_channel_rules(&state, channel_id, user_id).await?;
+
+    let result = crate::services::message::create_message(
+        &state,
+        &state.db,
+        crate::services::message::CreateMessageParams {
+            user_id,
+            author: crate::services::message::MessageAuthor::User,
+            channel_id,
+            content: payload.content,
+            reply_to_id: payload.reply_to_id,
+            thread_id: Some(thread_id),
+            attachment_ids: payload.attachment_ids,
+            poll: payload.poll,
+            validate_reply_channel: true,
+        },
+    )
+    .await
+    .inspect_err(|_| state.release_slowmode(channel_id, user_id))?;
+
+    crate::services::message::broadcast_new_message(&state, &result);
+
+    Ok(Json(result.message))
+}
+
+async fn verify_thread_in_channel(
+    state: &AppState,
+    thread_id: Uuid,
+    channel_id: Uuid,
+) -> AppResult<Thread> {
+    let thread = database::get_thread_by_id(&state.db, thread_id)
+        .await?
+        .ok_or_else(|| AppError::not_found("Thread not found"))?;
+
+    if thread.channel_id != channel_id {
+        return Err(AppError::not_found("Thread not found in this channel"));
+    }
+
+    Ok(thread)
+}
+
+/// Threads started without an explicit name take the parent message's first
+/// line, truncated to the maximum name length.
+fn default_thread_name(content: &str) -> String {
+    let first_line = content.lines().next().unwrap_or_default().trim();
+    if first_line.is_empty() {
+        return "Thread".to_string();
+    }
+    first_line.chars().take(MAX_THREAD_NAME_LENGTH).collect()
+}
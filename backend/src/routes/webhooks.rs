@@ -0,0 +1,223 @@
+use axum::{
+    extract::{Path, State},
+    response::Json,
+};
+use chrono::Utc;
+use sha2::{Digest, Sha256};
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::auth::AuthUser;
+use crate::database;
+use crate::link_preview::sign_image_url;
+use crate::models::{
+    AppState, ChannelType, CreateWebhookRequest, ExecuteWebhookRequest, Message, Webhook,
+    WebhookAuthor,
+};
+use crate::permissions::{self, Permissions};
+use crate::services::message::{
+    CreateMessageParams, MessageAuthor, broadcast_new_message, check_webhook_channel_rules,
+    create_message,
+};
+use crate::shared::validation::{
+    MAX_WEBHOOKS_PER_CHANNEL, validate_embeds, validate_http_url, validate_webhook_name,
+};
+use crate::shared::{AppError, AppResult};
+
+/// Random secret embedded in the webhook URL. Only its hash is stored.
+fn generate_token() -> (String, String) {
+    let token = hex::encode(rand::random::<[u8; 32]>());
+    let token_hash = hash_token(&token);
+    (token, token_hash)
+}
+
+fn hash_token(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
+
+/// External images are served through the signed image proxy so viewers
+/// never contact the webhook caller's servers directly.
+fn proxy_image_url(url: &str) -> String {
+    let (encoded, sig) = sign_image_url(url, crate::auth::hmac_secret());
+    format!("/api/proxy/image?url={encoded}&sig={sig}")
+}
+
+/// Managing webhooks requires MANAGE_CHANNELS in the webhook's channel.
+async fn require_manage_webhooks(
+    state: &AppState,
+    channel_id: Uuid,
+    user_id: Uuid,
+) -> AppResult<()> {
+    permissions::require_channel_access(state, channel_id, user_id)?;
+    database::get_channel_permissions(&state.db, channel_id, user_id)
+        .await?
+        .require(Permissions::MANAGE_CHANNELS)
+}
+
+/// Webhooks in channels the user cannot manage are reported as missing.
+async fn verify_webhook_manager(
+    state: &AppState,
+    webhook_id: Uuid,
+    user_id: Uuid,
+) -> AppResult<Webhook> {
+    let webhook = database::get_webhook(&state.db, webhook_id)
+        .await?
+        .ok_or_else(|| AppError::not_found("Webhook not found"))?;
+    require_manage_webhooks(state, webhook.channel_id, user_id)
+        .await
+        .map_err(|_| AppError::not_found("Webhook not found"))?;
+    Ok(webhook)
+}
+
+pub async fn get_webhooks(
+    State(state): State<Arc<AppState>>,
+    auth_user: AuthUser,
+    Path(channel_id): Path<Uuid>,
+) -> AppResult<Json<Vec<Webhook>>> {
+    require_manage_webhooks(&state, channel_id, auth_user.user_id()).await?;
+    let webhooks = database::get_channel_webhooks(&state.db, channel_id).await?;
+    Ok(Json(webhooks))
+}
+
+/// Create a webhook. The response carries the token, which is not shown
+/// again.
+pub async fn create_webhook(
+    State(state): State<Arc<AppState>>,
+    auth_user: AuthUser,
+    Path(channel_id): Path<Uuid>,
+    Json(payload): Json<CreateWebhookRequest>,
+) -> AppResult<Json<Webhook>> {
+    let user_id = auth_user.user_id();
+    require_manage_webhooks(&state, channel_id, user_id).await?;
+
+    if database::get_channel_type(&state.db, channel_id).await? != ChannelType::Text {
+        return Err(AppError::bad_request(
+            "Webhooks can only be added to text channels",
+        ));
+    }
+
+    let name = validate_webhook_name(&payload.name)?;
+    let avatar_url = payload.avatar_url.filter(|url| !url.trim().is_empty());
+    if let Some(ref url) = avatar_url {
+        validate_http_url(url, "Avatar URL")?;
+    }
+
+    if database::count_channel_webhooks(&state.db, channel_id).await? >= MAX_WEBHOOKS_PER_CHANNEL {
+        return Err(AppError::bad_request(format!(
+            "Maximum of {MAX_WEBHOOKS_PER_CHANNEL} webhooks per channel"
+        )));
+    }
+
+    let (token, token_hash) = generate_token();
+    let mut webhook = Webhook {
+        id: Uuid::now_v7(),
+        channel_id,
+        name,
+        avatar_url,
+        created_by: user_id,
+        created_at: Utc::now(),
+        token: None,
+    };
+    database::create_webhook(&state.db, &webhook, &token_hash).await?;
+
+    webhook.token = Some(token);
+    Ok(Json(webhook))
+}
+
+/// Replace a webhook's token, invalidating its old URL.
+pub async fn rotate_webhook_token(
+    State(state): State<Arc<AppState>>,
+    auth_user: AuthUser,
+    Path(webhook_id): Path<Uuid>,
+) -> AppResult<Json<Webhook>> {
+    let mut webhook = verify_webhook_manager(&state, webhook_id, auth_user.user_id()).await?;
+
+    let (token, token_hash) = generate_token();
+    database::update_webhook_token(&state.db, webhook_id, &token_hash).await?;
+
+    webhook.token = Some(token);
+    Ok(Json(webhook))
+}
+
+pub async fn delete_webhook(
+    State(state): State<Arc<AppState>>,
+    auth_user: AuthUser,
+    Path(webhook_id): Path<Uuid>,
+) -> AppResult<()> {
+    verify_webhook_manager(&state, webhook_id, auth_user.user_id()).await?;
+    database::delete_webhook(&state.db, webhook_id).await
+}
+
+/// Post a message through a webhook. The token in the URL is the only
+/// credential; unknown webhooks and wrong tokens are both reported as
+/// missing.
+pub async fn execute_webhook(
+    State(state): State<Arc<AppState>>,
+    Path((webhook_id, token)): Path<(Uuid, String)>,
+    Json(payload): Json<ExecuteWebhookRequest>,
+) -> AppResult<Json<Message>> {
+    let webhook = database::get_webhook_by_token(&state.db, webhook_id, &hash_token(&token))
+        .await?
+        .ok_or_else(|| AppError::not_found("Webhook not found"))?;
+
+    if !state.check_message_rate_limit(webhook.id) {
+        return Err(AppError::too_many_requests(
+            "This webhook is sending messages too fast",
+        ));
+    }
+
+    // The webhook speaks for its creator; it stops working when they could
+    // no longer post here themselves. Channel access and SEND_MESSAGES are
+    // checked by `create_message`.
+    if state.is_banned_cached(webhook.created_by) || state.is_muted_cached(webhook.created_by) {
+        return Err(AppError::forbidden("This webhook's creator cannot post"));
+    }
+    check_webhook_channel_rules(&state, webhook.channel_id, webhook.created_by).await?;
+
+    let name = match payload.username {
+        Some(ref username) => validate_webhook_name(username)?,
+        None => webhook.name,
+    };
+    let avatar_url = match payload.avatar_url {
+        Some(url) => {
+            validate_http_url(&url, "Avatar URL")?;
+            Some(url)
+        }
+        None => webhook.avatar_url,
+    };
+
+    let mut embeds = payload.embeds;
+    validate_embeds(&embeds)?;
+    for embed in &mut embeds {
+        if let Some(ref image_url) = embed.image_url {
+            embed.image_url = Some(proxy_image_url(image_url));
+        }
+    }
+
+    let result = create_message(
+        &state,
+        &state.db,
+        CreateMessageParams {
+            user_id: webhook.created_by,
+            author: MessageAuthor::Webhook {
+                author: WebhookAuthor {
+                    id: Some(webhook.id),
+                    name,
+                    avatar_url: avatar_url.as_deref().map(proxy_image_url),
+                },
+                embeds,
+            },
+            channel_id: webhook.channel_id,
+            content: payload.content,
+            reply_to_id: None,
+            thread_id: None,
+            attachment_ids: Vec::new(),
+            poll: None,
+            validate_reply_channel: false,
+        },
+    )
+    .await?;
+
+    broadcast_new_message(&state, &result);
+    Ok(Json(result.message))
+}
@@ -0,0 +1,294 @@
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::database;
+use crate::gateway::GatewayEvent;
+use crate::models::{AppState, Channel};
+use crate::permissions::{MemberPermissions, Overwrite, Permissions};
+use crate::shared::AppError;
+
+struct Member {
+    id: Uuid,
+    permissions: MemberPermissions,
+    role_ids: Vec<Uuid>,
+}
+
+/// Every member's server-wide permissions, resolved in one pass.
+struct Roster {
+    members: Vec<Member>,
+    default_role_id: Option<Uuid>,
+    /// A member holding only the default role, standing in for everyone who
+    /// joins later.
+    everyone: Member,
+}
+
+async fn load_members(state: &AppState) -> Result<Roster, AppError> {
+    let roles = database::get_roles(&state.db).await?;
+    let default_role = roles.iter().find(|r| r.is_default);
+    let base = default_role.map(|r| r.permissions).unwrap_or_default();
+
+    let members = database::get_all_members(&state.db)
+        .await?
+        .into_iter()
+        .map(|member| {
+            let mut granted = base;
+            let mut top_position = 0;
+            for role in roles.iter().filter(|r| member.role_ids.contains(&r.id)) {
+                granted |= role.permissions;
+                top_position = top_position.max(role.position);
+            }
+            Member {
+                id: member.id,
+                permissions: MemberPermissions::new(member.is_owner, granted, top_position),
+                role_ids: member.role_ids,
+            }
+        })
+        .collect();
+
+    Ok(Roster {
+        members,
+        default_role_id: default_role.map(|r| r.id),
+        everyone: Member {
+            id: Uuid::nil(),
+            permissions: MemberPermissions::new(false, base, 0),
+            role_ids: Vec::new(),
+        },
+    })
+}
+
+fn can_view(channel: &Channel, member: &Member, default_role_id: Option<Uuid>) -> bool {
+    // Layers: default role, the member's other roles, the member themselves
+    let mut layers = [Overwrite::default(); 3];
+    for overwrite in &channel.overwrites {
+        let layer = match (overwrite.role_id, overwrite.user_id) {
+            (_, Some(user_id)) if user_id == member.id => 2,
+            (Some(role_id), _) if Some(role_id) == default_role_id => 0,
+            (Some(role_id), _) if member.role_ids.contains(&role_id) => 1,
+            _ => continue,
+        };
+        layers[layer] = layers[layer].combine(overwrite.pair());
+    }
+    member
+        .permissions
+        .in_channel(layers[0], layers[1], layers[2])
+        .contains(Permissions::VIEW_CHANNEL)
+}
+
+/// Members who can view the channel, or None if everyone can. A channel is
+/// only public when the default role can view it, even without overwrites,
+/// so members who join later do not gain access by accident.
+fn channel_viewers(channel: &Channel, roster: &Roster) -> Option<Vec<Uuid>> {
+    let default_role_id = roster.default_role_id;
+    let everyone_can_view = can_view(channel, &roster.everyone, default_role_id);
+    let mut viewers: Vec<Uuid> = roster
+        .members
+        .iter()
+        .filter(|m| can_view(channel, m, default_role_id))
+        .map(|m| m.id)
+        .collect();
+    if everyone_can_view && viewers.len() == roster.members.len() {
+        return None;
+    }
+    viewers.sort();
+    Some(viewers)
+}
+
+/// Viewers of every restricted server channel, computed from the database.
+pub async fn restricted_channel_viewers(
+    state: &AppState,
+) -> Result<Vec<(Uuid, Vec<Uuid>)>, AppError> {
+    let channels = database::get_channels(&state.db).await?;
+    let roster = load_members(state).await?;
+    Ok(channels
+        .iter()
+        .filter_map(|channel| Some((channel.id, channel_viewers(channel, &roster)?)))
+        .collect())
+}
+
+/// Cache the viewers of a newly created channel before it is announced.
+pub async fn init_channel_access(state: &AppState, channel: &Channel) -> Result<(), AppError> {
+    let roster = load_members(state).await?;
+    if let Some(viewers) = channel_viewers(channel, &roster) {
+        state.set_channel_viewers(channel.id, Some(viewers));
+    }
+    Ok(())
+}
+
+/// Recompute who can view each server channel after overwrites, roles or
+/// membership change. Members who lose access get `channel_deleted` and are
+/// removed from its voice call; members who gain it get `channel_created`.
+pub async fn refresh_channel_access(state: &Arc<AppState>) -> Result<(), AppError> {
+    let channels = database::get_channels(&state.db).await?;
+    let roster = load_members(state).await?;
+
+    for channel in channels {
+        let viewers = channel_viewers(&channel, &roster);
+        let previous = state.private_channel_member_ids(channel.id).map(|mut ids| {
+            ids.sort();
+            ids
+        });
+        if previous == viewers {
+            continue;
+        }
+
+        state.set_channel_viewers(channel.id, viewers.clone());
+
+        let could_view = |id: &Uuid| previous.as_ref().is_none_or(|ids| ids.contains(id));
+        let can_view = |id: &Uuid| viewers.as_ref().is_none_or(|ids| ids.contains(id));
+        for member in &roster.members {
+            match (could_view(&member.id), can_view(&member.id)) {
+                (true, false) => {
+                    state
+                        .broadcast_user(member.id, GatewayEvent::ChannelDeleted { id: channel.id });
+                    let in_voice = state
+                        .voice_states
+                        .get(&channel.id)
+                        .is_some_and(|users| users.contains_key(&member.id));
+                    if in_voice {
+                        state.leave_voice(channel.id, member.id).await;
+                    }
+                }
+                (false, true) => {
+                    state.broadcast_user(member.id, GatewayEvent::ChannelCreated(channel.clone()));
+                }
+                _ => {}
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{ChannelType, PermissionOverwrite};
+
+    const DEFAULT_ROLE: Uuid = Uuid::from_u128(1);
+    const STAFF_ROLE: Uuid = Uuid::from_u128(2);
+
+    fn member(id: u128, granted: Permissions, role_ids: Vec<Uuid>) -> Member {
+        Member {
+            id: Uuid::from_u128(id),
+            permissions: MemberPermissions::new(false, granted, 0),
+            role_ids,
+        }
+    }
+
+    fn roster(members: Vec<Member>) -> Roster {
+        Roster {
+            members,
+            default_role_id: Some(DEFAULT_ROLE),
+            everyone: member(0, Permissions::VIEW_CHANNEL, Vec::new()),
+        }
+    }
+
+    fn channel(overwrites: Vec<PermissionOverwrite>) -> Channel {
+        Channel {
+            id: Uuid::from_u128(100),
+            name: "general".to_string(),
+            channel_type: ChannelType::Text,
+            category_id: None,
+            position: 0,
+            topic: None,
+            slowmode_secs: 0,
+            announcement: false,
+            announcement_role_ids: Vec::new(),
+            overwrites,
+        }
+    }
+
+    fn overwrite(
+        role_id: Option<Uuid>,
+        user_id: Option<Uuid>,
+        allow: Permissions,
+        deny: Permissions,
+    ) -> PermissionOverwrite {
+        PermissionOverwrite {
+            channel_id: Uuid::from_u128(100),
+            role_id,
+            user_id,
+            allow,
+            deny,
+        }
+    }
+
+    #[test]
+    fn channel_without_overwrites_is_public() {
+        let roster = roster(vec![
+            member(10, Permissions::VIEW_CHANNEL, Vec::new()),
+            member(11, Permissions::VIEW_CHANNEL, vec![STAFF_ROLE]),
+        ]);
+        assert_eq!(channel_viewers(&channel(Vec::new()), &roster), None);
+    }
+
+    #[test]
+    fn role_allow_overrides_default_role_deny() {
+        let roster = roster(vec![
+            member(10, Permissions::VIEW_CHANNEL, Vec::new()),
+            member(11, Permissions::VIEW_CHANNEL, vec![STAFF_ROLE]),
+            member(12, Permissions::ADMINISTRATOR, Vec::new()),
+        ]);
+        let channel = channel(vec![
+            overwrite(
+                Some(DEFAULT_ROLE),
+                None,
+                Permissions::default(),
+                Permissions::VIEW_CHANNEL,
+            ),
+            overwrite(
+                Some(STAFF_ROLE),
+                None,
+                Permissions::VIEW_CHANNEL,
+                Permissions::default(),
+            ),
+        ]);
+        assert_eq!(
+            channel_viewers(&channel, &roster),
+            Some(vec![Uuid::from_u128(11), Uuid::from_u128(12)])
+        );
+    }
+
+    #[test]
+    fn member_overwrite_wins_over_roles() {
+        let roster = roster(vec![
+            member(10, Permissions::VIEW_CHANNEL, vec![STAFF_ROLE]),
+            member(11, Permissions::VIEW_CHANNEL, vec![STAFF_ROLE]),
+        ]);
+        let channel = channel(vec![
+            overwrite(
+                Some(STAFF_ROLE),
+                None,
+                Permissions::VIEW_CHANNEL,
+                Permissions::default(),
+            ),
+            overwrite(
+                None,
+                Some(Uuid::from_u128(11)),
+                Permissions::default(),
+                Permissions::VIEW_CHANNEL,
+            ),
+        ]);
+        assert_eq!(
+            channel_viewers(&channel, &roster),
+            Some(vec![Uuid::from_u128(10)])
+        );
+    }
+
+    #[test]
+    fn channel_hidden_from_default_role_stays_restricted() {
+        // Every current member can view it through a role overwrite, but
+        // members who join later would not
+        let mut roster = roster(vec![member(10, Permissions::default(), vec![STAFF_ROLE])]);
+        roster.everyone = member(0, Permissions::default(), Vec::new());
+        let channel = channel(vec![overwrite(
+            Some(STAFF_ROLE),
+            None,
+            Permissions::VIEW_CHANNEL,
+            Permissions::default(),
+        )]);
+        assert_eq!(
+            channel_viewers(&channel, &roster),
+            Some(vec![Uuid::from_u128(10)])
+        );
+    }
+}
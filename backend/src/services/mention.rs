@@ -0,0 +1,188 @@
+use std::collections::HashSet;
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::database;
+use crate::gateway::{GatewayEvent, MentionEvent};
+use crate::models::{AppState, Message};
+use crate::permissions::Permissions;
+use crate::shared::AppError;
+use crate::shared::validation::{
+    MAX_MENTIONS_PER_MESSAGE, MAX_USERNAME_LENGTH, MIN_USERNAME_LENGTH,
+};
+
+#[derive(Debug, Default)]
+pub struct ParsedMentions {
+    /// Lowercased names following `@`, excluding `everyone` and `here`.
+    /// Each resolves to a username first, then to a role name.
+    pub names: Vec<String>,
+    pub everyone: bool,
+    pub here: bool,
+}
+
+/// Extract `@name` tokens from message content. A mention must start the
+/// content or follow a character that cannot be part of a username, so
+/// email addresses are not treated as mentions.
+pub fn parse_mentions(content: &str) -> ParsedMentions {
+    let is_name_char = |c: char| c.is_ascii_alphanumeric() || c == '_' || c == '-';
+
+    let mut parsed = ParsedMentions::default();
+    let mut seen = HashSet::new();
+    let mut prev: Option<char> = None;
+
+    for (i, c) in content.char_indices() {
+        let at_boundary = prev.is_none_or(|p| !is_name_char(p));
+        prev = Some(c);
+        if c != '@' || !at_boundary {
+            continue;
+        }
+
+        let rest = &content[i + 1..];
+        let end = rest.find(|c: char| !is_name_char(c)).unwrap_or(rest.len());
+        let name = rest[..end].to_lowercase();
+
+        if name == "everyone" {
+            parsed.everyone = true;
+        } else if name == "here" {
+            parsed.here = true;
+        } else if (MIN_USERNAME_LENGTH..=MAX_USERNAME_LENGTH).contains(&name.len())
+            && parsed.names.len() < MAX_MENTIONS_PER_MESSAGE
+            && seen.insert(name.clone())
+        {
+            parsed.names.push(name);
+        }
+    }
+
+    parsed
+}
+
+/// Resolves the mentions in a message, persists them, and sends a targeted
+/// `mention` event to every newly mentioned user. Called on create and edit;
+/// on edit only mentions that were not already present are notified.
+///
+/// `@everyone` and `@here` are ignored unless the author has the
+/// `MENTION_EVERYONE` permission. Returns the mentioned user ids and whether
+/// the message mentions everyone.
+pub async fn process_mentions(
+    state: &Arc<AppState>,
+    message: &Message,
+) -> Result<(Vec<Uuid>, bool), AppError> {
+    let parsed = parse_mentions(&message.content);
+
+    let mut user_ids: HashSet<Uuid> = HashSet::new();
+
+    if !parsed.names.is_empty() {
+        let users = database::get_user_ids_by_usernames(&state.db, &parsed.names).await?;
+        let matched: HashSet<&str> = users.iter().map(|(_, name)| name.as_str()).collect();
+
+        // Names that are not usernames may be role mentions. The default
+        // role is everybody, so it goes through @everyone instead.
+        let role_names: Vec<String> = parsed
+            .names
+            .iter()
+            .filter(|name| !matched.contains(name.as_str()))
+            .cloned()
+            .collect();
+
+        user_ids.extend(users.iter().map(|(id, _)| *id));
+        if !role_names.is_empty() {
+            user_ids.extend(database::get_user_ids_by_role_names(&state.db, &role_names).await?);
+        }
+    }
+
+    let mut mention_everyone = false;
+    if parsed.everyone || parsed.here {
+        let author = database::get_member_permissions(&state.db, message.author_id).await?;
+
+        if author.has(Permissions::MENTION_EVERYONE) {
+            mention_everyone = parsed.everyone;
+            if parsed.here && !parsed.everyone {
+                user_ids.extend(state.online_users.iter().map(|entry| *entry.key()));
+            }
+        }
+    }
+
+    user_ids.remove(&message.author_id);
+    let user_ids: Vec<Uuid> = user_ids
+        .into_iter()
+        .filter(|id| state.can_access_channel(message.channel_id, *id))
+        .collect();
+
+    let (added, everyone_added) =
+        database::set_message_mentions(&state.db, message.id, &user_ids, mention_everyone).await?;
+
+    let event = GatewayEvent::Mention(MentionEvent {
+        message_id: message.id,
+        channel_id: message.channel_id,
+        thread_id: message.thread_id,
+        author_id: message.author_id,
+        username: message.username.clone(),
+        content: message.content.clone(),
+        everyone: everyone_added,
+    });
+
+    if everyone_added {
+        state.broadcast_for_channel(message.channel_id, event);
+    } else {
+        for user_id in added {
+            state.broadcast_user(user_id, event.clone());
+        }
+    }
+
+    Ok((user_ids, mention_everyone))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_names_and_special_mentions() {
+        let parsed = parse_mentions("hey @Alice and @bob_2, @everyone @here");
+        assert_eq!(parsed.names, ["alice", "bob_2"]);
+        assert!(parsed.everyone);
+        assert!(parsed.here);
+    }
+
+    #[test]
+    fn deduplicates_names_case_insensitively() {
+        let parsed = parse_mentions("@alice @ALICE @Alice");
+        assert_eq!(parsed.names, ["alice"]);
+    }
+
+    #[test]
+    fn ignores_mentions_inside_words() {
+        let parsed = parse_mentions("mail alice@example.com or a@b");
+        assert!(parsed.names.is_empty());
+        assert!(!parsed.everyone && !parsed.here);
+    }
+
+    #[test]
+    fn stops_at_punctuation() {
+        let parsed = parse_mentions("(@alice) @bob's @carol.");
+        assert_eq!(parsed.names, ["alice", "bob", "carol"]);
+    }
+
+    #[test]
+    fn skips_names_outside_username_length() {
+        let long = "a".repeat(MAX_USERNAME_LENGTH + 1);
+        let parsed = parse_mentions(&format!("@ @a @{long} @ok"));
+        assert_eq!(parsed.names, ["ok"]);
+    }
+
+    #[test]
+    fn caps_mentions_per_message() {
+        let content: String = (0..MAX_MENTIONS_PER_MESSAGE + 5)
+            .map(|i| format!("@user{i} "))
+            .collect();
+        let parsed = parse_mentions(&content);
+        assert_eq!(parsed.names.len(), MAX_MENTIONS_PER_MESSAGE);
+        assert_eq!(parsed.names[0], "user0");
+    }
+
+    #[test]
+    fn handles_multibyte_content() {
+        let parsed = parse_mentions("héllo @zoë! ✨@alice");
+        assert_eq!(parsed.names, ["zo", "alice"]);
+    }
+}
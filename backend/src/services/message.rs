@@ -1,18 +1,43 @@
+use chrono::{DateTime, Utc};
+use object_store::ObjectStoreExt;
 use sqlx::PgPool;
 use std::sync::Arc;
 use uuid::Uuid;
 
 use crate::database;
-use crate::models::{AppState, Message, ReplyPreview};
+use crate::gateway::GatewayEvent;
+use crate::models::{
+    AppState, CreatePollRequest, Embed, Message, Poll, ReplyPreview, Thread, WebhookAuthor,
+};
+use crate::permissions::Permissions;
 use crate::shared::AppError;
-use crate::shared::validation::{MAX_ATTACHMENTS_PER_MESSAGE, validate_message_content_optional};
+use crate::shared::validation::{
+    MAX_ATTACHMENTS_PER_MESSAGE, validate_message_content_optional, validate_poll,
+};
+
+/// Who a new message is posted as.
+pub enum MessageAuthor {
+    /// The member `user_id`, subject to their channel permissions.
+    User,
+    /// An incoming webhook, authorized by its token. `user_id` is the
+    /// webhook's creator, who owns the message row and whose channel
+    /// permissions the post is checked against.
+    Webhook {
+        author: WebhookAuthor,
+        embeds: Vec<Embed>,
+    },
+}
 
 pub struct CreateMessageParams {
     pub user_id: Uuid,
+    pub author: MessageAuthor,
     pub channel_id: Uuid,
     pub content: Option<String>,
     pub reply_to_id: Option<Uuid>,
+    /// Thread to post into. Must belong to `channel_id`.
+    pub thread_id: Option<Uuid>,
     pub attachment_ids: Vec<Uuid>,
+    pub poll: Option<CreatePollRequest>,
     /// If true, verifies the replied-to message is in the same channel (REST behavior).
     /// If false, skips this check (WS behavior).
     pub validate_reply_channel: bool,
@@ -21,18 +46,97 @@ pub struct CreateMessageParams {
 pub struct CreateMessageResult {
     pub message: Message,
     pub channel_id: Uuid,
+    /// Updated thread summary when the message was posted into a thread.
+    pub thread: Option<Thread>,
+}
+
+/// Enforces a channel's announcement mode and slowmode for a member about
+/// to post. Members who can manage messages or channels are exempt. Called
+/// after the global per-user rate limit; callers release the slowmode slot
+/// with `AppState::release_slowmode` if the post then fails.
+pub async fn check_channel_rules(
+    state: &AppState,
+    channel_id: Uuid,
+    user_id: Uuid,
+) -> Result<(), AppError> {
+    enforce_channel_rules(state, channel_id, user_id, true).await
+}
+
+/// Channel rules for a webhook post, judged by the webhook's creator.
+/// Announcement mode applies; slowmode does not, since webhooks have their
+/// own rate limit and must not use up the creator's slowmode slot.
+pub async fn check_webhook_channel_rules(
+    state: &AppState,
+    channel_id: Uuid,
+    creator_id: Uuid,
+) -> Result<(), AppError> {
+    enforce_channel_rules(state, channel_id, creator_id, false).await
+}
+
+async fn enforce_channel_rules(
+    state: &AppState,
+    channel_id: Uuid,
+    user_id: Uuid,
+    slowmode: bool,
+) -> Result<(), AppError> {
+    crate::permissions::require_channel_access(state, channel_id, user_id)?;
+    let Some(rules) = database::get_channel_rules(&state.db, channel_id).await? else {
+        return Err(AppError::not_found("Channel not found"));
+    };
+    let slowmode_secs = if slowmode { rules.slowmode_secs } else { 0 };
+    if !rules.announcement && slowmode_secs == 0 {
+        return Ok(());
+    }
+
+    let actor = database::get_member_permissions(&state.db, user_id).await?;
+    if actor.has(Permissions::MANAGE_MESSAGES) || actor.has(Permissions::MANAGE_CHANNELS) {
+        return Ok(());
+    }
+
+    if rules.announcement
+        && !database::user_has_any_role(&state.db, user_id, &rules.announcement_role_ids).await?
+    {
+        return Err(AppError::forbidden(
+            "Only selected roles can post in this announcement channel",
+        ));
+    }
+
+    if let Some(wait) = state.check_slowmode(channel_id, user_id, slowmode_secs) {
+        return Err(AppError::too_many_requests(format!(
+            "Slowmode is enabled. Try again in {wait}s"
+        )));
+    }
+
+    Ok(())
 }
 
 /// Validates content, resolves reply preview, persists the message, links
-/// attachments, and spawns link preview fetch. Does NOT broadcast -- the
-/// caller handles that because REST and WS have different broadcast semantics.
+/// attachments, notifies mentioned users, and spawns link preview fetch.
+/// Does NOT broadcast the message -- the caller handles that because REST and
+/// WS have different broadcast semantics.
 pub async fn create_message(
     state: &Arc<AppState>,
     db: &PgPool,
     params: CreateMessageParams,
 ) -> Result<CreateMessageResult, AppError> {
     let has_attachments = !params.attachment_ids.is_empty();
-    validate_message_content_optional(&params.content, has_attachments)?;
+    // Webhooks post with their creator's permissions, so a creator who loses
+    // access also silences their webhooks
+    crate::permissions::require_channel_access(state, params.channel_id, params.user_id)?;
+    let channel_permissions =
+        database::get_channel_permissions(db, params.channel_id, params.user_id).await?;
+    channel_permissions.require(Permissions::SEND_MESSAGES)?;
+    if has_attachments {
+        channel_permissions.require(Permissions::ATTACH_FILES)?;
+    }
+
+    let has_embeds =
+        matches!(&params.author, MessageAuthor::Webhook { embeds, .. } if !embeds.is_empty());
+    validate_message_content_optional(
+        &params.content,
+        has_attachments || has_embeds || params.poll.is_some(),
+    )?;
+    let poll = params.poll.map(validate_poll).transpose()?;
 
     if params.attachment_ids.len() > MAX_ATTACHMENTS_PER_MESSAGE {
         return Err(AppError::bad_request(format!(
@@ -40,12 +144,23 @@ pub async fn create_message(
         )));
     }
 
+    if let Some(thread_id) = params.thread_id {
+        let thread = database::get_thread_by_id(db, thread_id)
+            .await?
+            .ok_or_else(|| AppError::not_found("Thread not found"))?;
+        if thread.channel_id != params.channel_id {
+            return Err(AppError::bad_request("Thread is not in this channel"));
+        }
+    }
+
     let reply_to: Option<ReplyPreview> = if let Some(reply_id) = params.reply_to_id {
         if params.validate_reply_channel {
             let replied_msg = database::get_message_by_id(db, reply_id)
                 .await?
                 .ok_or_else(|| AppError::not_found("Replied-to message not found"))?;
-            if replied_msg.channel_id != params.channel_id {
+            if replied_msg.channel_id != params.channel_id
+                || replied_msg.thread_id != params.thread_id
+            {
                 return Err(AppError::bad_request(
                     "Cannot reply to a message in a different channel",
                 ));
@@ -71,9 +186,29 @@ pub async fn create_message(
         params.reply_to_id,
         reply_to,
     );
+    new_message.thread_id = params.thread_id;
 
     database::create_message(db, &new_message, params.user_id).await?;
 
+    if let MessageAuthor::Webhook { author, embeds } = params.author {
+        database::create_webhook_message(db, new_message.id, &author, &embeds).await?;
+        new_message.webhook = Some(author);
+        new_message.embeds = Some(embeds).filter(|e| !e.is_empty());
+    }
+
+    if let Some(poll) = poll {
+        let poll = Poll::new(new_message.id, poll, new_message.timestamp);
+        database::create_poll(db, &poll).await?;
+        new_message.poll = Some(poll);
+    }
+
+    let thread = match params.thread_id {
+        Some(thread_id) => {
+            Some(database::record_thread_reply(db, thread_id, new_message.timestamp).await?)
+        }
+        None => None,
+    };
+
     if has_attachments {
         let attachments = database::link_attachments_to_message(
             db,
@@ -87,11 +222,20 @@ pub async fn create_message(
         }
     }
 
+    let (mentions, mention_everyone) =
+        crate::services::mention::process_mentions(state, &new_message).await?;
+    if !mentions.is_empty() {
+        new_message.mentions = Some(mentions);
+    }
+    new_message.mention_everyone = mention_everyone;
+
     if !content.is_empty() {
         crate::link_preview::spawn_preview_fetch(
             state.clone(),
             new_message.id,
             params.channel_id,
+            params.thread_id,
+            params.user_id,
             content,
         );
     }
@@ -99,5 +243,71 @@ pub async fn create_message(
     Ok(CreateMessageResult {
         message: new_message,
         channel_id: params.channel_id,
+        thread,
     })
 }
+
+/// Broadcasts a newly created message to its topic. Thread replies also
+/// notify the parent channel so reply counts stay current. A lightweight
+/// `channel_activity` event goes to everyone who can see the channel so
+/// sidebars can show unread state without subscribing.
+pub fn broadcast_new_message(state: &AppState, result: &CreateMessageResult) {
+    let message = &result.message;
+    state.broadcast_topic(
+        message.channel_id,
+        message.topic_id(),
+        GatewayEvent::Message(message.clone()),
+    );
+
+    state.broadcast_for_channel(
+        result.channel_id,
+        GatewayEvent::ChannelActivity {
+            channel_id: result.channel_id,
+            thread_id: message.thread_id,
+            message_id: message.id,
+            author_id: message.author_id,
+            timestamp: message.timestamp,
+            mentions: message.mentions.clone(),
+            mention_everyone: message.mention_everyone,
+        },
+    );
+
+    if let Some(ref thread) = result.thread {
+        state.broadcast_channel(
+            result.channel_id,
+            GatewayEvent::ThreadUpdated(thread.clone()),
+        );
+    }
+}
+
+/// Re-send a thread's summary to its parent channel after a reply was
+/// deleted or restored, so reply counts stay current there too.
+pub async fn broadcast_thread_updated(state: &AppState, thread_id: Uuid) -> Result<(), AppError> {
+    if let Some(thread) = database::get_thread_by_id(&state.db, thread_id).await? {
+        state.broadcast_channel(thread.channel_id, GatewayEvent::ThreadUpdated(thread));
+    }
+    Ok(())
+}
+
+/// Hard-delete tombstones older than `cutoff` and remove their attachment
+/// files from storage. Returns how many messages were purged.
+pub async fn purge_deleted_messages(
+    state: &AppState,
+    cutoff: DateTime<Utc>,
+) -> Result<u64, AppError> {
+    let (purged, storage_paths) = database::purge_deleted_messages(&state.db, cutoff).await?;
+    delete_attachment_files(state, storage_paths).await;
+    Ok(purged)
+}
+
+/// Remove the stored files of attachments whose rows were deleted.
+pub async fn delete_attachment_files(state: &AppState, storage_paths: Vec<String>) {
+    if let Some(ref store) = state.file_store {
+        for storage_path in storage_paths {
+            let object_path = object_store::path::Path::from(storage_path);
+            if let Err(e) = store.delete(&object_path).await {
+                tracing::warn!("Failed to delete attachment {object_path}: {e}");
+            }
+        }
+    }
+}
@@ -1 +1,8 @@
+pub mod channel_access;
+pub mod mention;
 pub mod message;
+pub mod outgoing_webhook;
+pub mod poll;
+pub mod presence;
+pub mod read_state;
+pub mod scheduled_message;
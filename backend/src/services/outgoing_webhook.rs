@@ -0,0 +1,294 @@
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use serde::Deserialize;
+use sha2::Sha256;
+use std::net::SocketAddr;
+use std::sync::{Arc, OnceLock};
+use uuid::Uuid;
+
+use crate::database;
+use crate::link_preview::{is_private_ip, is_safe_url};
+use crate::models::{AppState, PendingDelivery};
+use crate::shared::AppError;
+use crate::shared::validation::{
+    OUTGOING_WEBHOOK_CLAIM_TIMEOUT_SECS, OUTGOING_WEBHOOK_MAX_ATTEMPTS,
+};
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Most deliveries sent per pass.
+const DELIVERY_BATCH_SIZE: i64 = 50;
+const DELIVERY_TIMEOUT_SECS: u64 = 10;
+/// Wait before the first retry; doubled after each further failure.
+const RETRY_BASE_SECS: i64 = 30;
+const MAX_RETRY_DELAY_SECS: i64 = 6 * 3600;
+/// Most characters of an error response body kept in the delivery log.
+const MAX_ERROR_LENGTH: usize = 500;
+
+static ALLOWED_HOSTS: OnceLock<Vec<String>> = OnceLock::new();
+static CLIENT: OnceLock<reqwest::Client> = OnceLock::new();
+
+/// Hosts from `OUTGOING_WEBHOOK_ALLOWED_HOSTS` (comma separated) that may
+/// receive deliveries even though they resolve to private addresses.
+fn allowed_hosts() -> &'static [String] {
+    ALLOWED_HOSTS.get_or_init(|| {
+        std::env::var("OUTGOING_WEBHOOK_ALLOWED_HOSTS")
+            .unwrap_or_default()
+            .split(',')
+            .map(|host| host.trim().to_ascii_lowercase())
+            .filter(|host| !host.is_empty())
+            .collect()
+    })
+}
+
+/// Redirects are never followed, so a checked URL cannot bounce the request
+/// to an internal host.
+fn client() -> &'static reqwest::Client {
+    CLIENT.get_or_init(|| {
+        crate::shared::http::create_http_client_without_redirects(DELIVERY_TIMEOUT_SECS)
+            .expect("Failed to create webhook HTTP client")
+    })
+}
+
+/// Whether `url` is an http(s) URL whose host is allowlisted.
+fn is_allowlisted(url: &url::Url) -> bool {
+    matches!(url.scheme(), "http" | "https")
+        && url
+            .host_str()
+            .is_some_and(|host| allowed_hosts().iter().any(|h| h == host))
+}
+
+/// Whether deliveries may be sent to `url`: public addresses only, unless
+/// the host is explicitly allowlisted.
+pub async fn is_allowed_url(url: &str) -> bool {
+    url::Url::parse(url).is_ok_and(|parsed| is_allowlisted(&parsed)) || is_safe_url(url).await
+}
+
+/// Client for one delivery to `url`, or `None` if the URL may not receive
+/// deliveries. A hostname that is not allowlisted is resolved here and the
+/// client is pinned to the checked addresses, so the receiver's DNS cannot
+/// answer with an internal address on the connection's own lookup.
+async fn delivery_client(url: &str) -> Option<reqwest::Client> {
+    let parsed = url::Url::parse(url).ok()?;
+    if is_allowlisted(&parsed) {
+        return Some(client().clone());
+    }
+    if !matches!(parsed.scheme(), "http" | "https") {
+        return None;
+    }
+    let domain = match parsed.host()? {
+        url::Host::Ipv4(ip) => return (!is_private_ip(ip.into())).then(|| client().clone()),
+        url::Host::Ipv6(ip) => return (!is_private_ip(ip.into())).then(|| client().clone()),
+        url::Host::Domain(domain) => domain,
+    };
+
+    let port = parsed.port_or_known_default()?;
+    let addrs: Vec<SocketAddr> = tokio::net::lookup_host((domain, port))
+        .await
+        .ok()?
+        .collect();
+    if addrs.is_empty() || addrs.iter().any(|addr| is_private_ip(addr.ip())) {
+        return None;
+    }
+    crate::shared::http::client_builder_without_redirects(DELIVERY_TIMEOUT_SECS)
+        .resolve_to_addrs(domain, &addrs)
+        .build()
+        .inspect_err(|e| tracing::warn!("Failed to create webhook HTTP client: {e}"))
+        .ok()
+}
+
+/// Random key shared with the receiver to verify signatures.
+pub fn generate_secret() -> String {
+    hex::encode(rand::random::<[u8; 32]>())
+}
+
+/// Hex HMAC-SHA256 of `"{timestamp}.{body}"`. Including the timestamp lets
+/// receivers reject replayed deliveries.
+fn sign(secret: &str, timestamp: i64, body: &str) -> String {
+    let mut mac =
+        HmacSha256::new_from_slice(secret.as_bytes()).expect("HMAC can take key of any size");
+    mac.update(timestamp.to_string().as_bytes());
+    mac.update(b".");
+    mac.update(body.as_bytes());
+    hex::encode(mac.finalize().into_bytes())
+}
+
+/// Leading fields of a serialized gateway event. Only the tag is borrowed,
+/// so events no webhook subscribes to are never parsed into a tree.
+#[derive(Deserialize)]
+struct EventHeader<'a> {
+    #[serde(rename = "type", borrow)]
+    event_type: &'a str,
+}
+
+/// Queue deliveries of a broadcast gateway event to every enabled webhook
+/// subscribed to its type. `channel_id` is the channel the event belongs to
+/// (the parent for thread events); events about private channels are never
+/// sent. Called on the node that broadcasts the event, so each event is
+/// queued once across the cluster.
+pub fn queue_event(state: &AppState, channel_id: Option<Uuid>, payload: &str) {
+    if state.outgoing_webhook_events.is_empty() {
+        return;
+    }
+    let Ok(EventHeader { event_type }) = serde_json::from_str::<EventHeader>(payload) else {
+        return;
+    };
+
+    let webhook_ids: Vec<Uuid> = state
+        .outgoing_webhook_events
+        .iter()
+        .filter(|entry| entry.value().iter().any(|t| t == event_type))
+        .map(|entry| *entry.key())
+        .collect();
+    if webhook_ids.is_empty() {
+        return;
+    }
+
+    let Ok(event) = serde_json::from_str::<serde_json::Value>(payload) else {
+        return;
+    };
+    let data_channel_id = event["data"]["channel_id"]
+        .as_str()
+        .and_then(|id| id.parse::<Uuid>().ok());
+    if channel_id
+        .into_iter()
+        .chain(data_channel_id)
+        .any(|id| state.private_channel_members.contains_key(&id))
+    {
+        return;
+    }
+
+    let body = serde_json::json!({
+        "id": Uuid::now_v7(),
+        "type": event_type,
+        "created_at": Utc::now(),
+        "data": event["data"],
+    })
+    .to_string();
+    let event_type = event_type.to_string();
+    let db = state.db.clone();
+    tokio::spawn(async move {
+        if let Err(e) =
+            database::create_outgoing_webhook_deliveries(&db, &webhook_ids, &event_type, &body)
+                .await
+        {
+            tracing::warn!("Failed to queue {event_type} webhook deliveries: {e}");
+        }
+    });
+}
+
+/// Send every pending delivery that is due. Failed attempts are retried
+/// with exponential backoff until `OUTGOING_WEBHOOK_MAX_ATTEMPTS` is
+/// reached. Returns how many deliveries succeeded.
+pub async fn deliver_due_webhooks(state: &Arc<AppState>) -> Result<usize, AppError> {
+    let now = Utc::now();
+    let stale_before = now - chrono::Duration::seconds(OUTGOING_WEBHOOK_CLAIM_TIMEOUT_SECS);
+    let due =
+        database::claim_due_deliveries(&state.db, now, stale_before, DELIVERY_BATCH_SIZE).await?;
+
+    let results = futures_util::future::join_all(due.iter().map(send)).await;
+
+    let mut delivered = 0;
+    for (delivery, (status_code, error)) in due.iter().zip(results) {
+        let succeeded = error.is_none();
+        let next_attempt_at = if succeeded {
+            None
+        } else {
+            next_attempt_at(delivery.attempts + 1, Utc::now())
+        };
+        database::record_delivery_attempt(
+            &state.db,
+            delivery.id,
+            succeeded,
+            status_code,
+            error.as_deref(),
+            next_attempt_at,
+        )
+        .await?;
+        if succeeded {
+            delivered += 1;
+        }
+    }
+    Ok(delivered)
+}
+
+/// When to retry after `attempts` failures, or `None` to give up.
+fn next_attempt_at(attempts: i32, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+    if attempts >= OUTGOING_WEBHOOK_MAX_ATTEMPTS {
+        return None;
+    }
+    let delay = RETRY_BASE_SECS
+        .saturating_mul(1 << (attempts - 1).clamp(0, 20))
+        .min(MAX_RETRY_DELAY_SECS);
+    Some(now + chrono::Duration::seconds(delay))
+}
+
+/// POST a delivery. Returns the response status, if any, and an error
+/// unless the receiver answered with a 2xx status.
+async fn send(delivery: &PendingDelivery) -> (Option<i32>, Option<String>) {
+    let Some(client) = delivery_client(&delivery.url).await else {
+        return (None, Some("URL failed safety check".to_string()));
+    };
+
+    let timestamp = Utc::now().timestamp();
+    let signature = sign(&delivery.secret, timestamp, &delivery.payload);
+    let response = client
+        .post(&delivery.url)
+        .header(reqwest::header::CONTENT_TYPE, "application/json")
+        .header("X-Webhook-Id", delivery.webhook_id.to_string())
+        .header("X-Webhook-Delivery", delivery.id.to_string())
+        .header("X-Webhook-Event", &delivery.event_type)
+        .header("X-Webhook-Timestamp", timestamp.to_string())
+        .header("X-Webhook-Signature", format!("sha256={signature}"))
+        .body(delivery.payload.clone())
+        .send()
+        .await;
+
+    match response {
+        Ok(mut response) => {
+            let status = response.status();
+            if status.is_success() {
+                (Some(status.as_u16().into()), None)
+            } else {
+                // Only the first chunk is read; error bodies can be large.
+                let chunk = response.chunk().await.ok().flatten().unwrap_or_default();
+                let body = String::from_utf8_lossy(&chunk);
+                let error = if body.trim().is_empty() {
+                    format!("Receiver responded with {status}")
+                } else {
+                    format!(
+                        "Receiver responded with {status}: {}",
+                        crate::shared::truncate_string(body.trim(), MAX_ERROR_LENGTH)
+                    )
+                };
+                (Some(status.as_u16().into()), Some(error))
+            }
+        }
+        Err(e) => (None, Some(e.to_string())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn retries_back_off_exponentially() {
+        let now = Utc::now();
+        let delay = |attempts| next_attempt_at(attempts, now).map(|at| (at - now).num_seconds());
+        assert_eq!(delay(1), Some(RETRY_BASE_SECS));
+        assert_eq!(delay(2), Some(RETRY_BASE_SECS * 2));
+        assert_eq!(delay(3), Some(RETRY_BASE_SECS * 4));
+    }
+
+    #[test]
+    fn retries_stop_after_max_attempts() {
+        let now = Utc::now();
+        assert!(next_attempt_at(OUTGOING_WEBHOOK_MAX_ATTEMPTS - 1, now).is_some());
+        assert_eq!(next_attempt_at(OUTGOING_WEBHOOK_MAX_ATTEMPTS, now), None);
+        assert!(
+            next_attempt_at(OUTGOING_WEBHOOK_MAX_ATTEMPTS - 1, now).unwrap()
+                <= now + chrono::Duration::seconds(MAX_RETRY_DELAY_SECS)
+        );
+    }
+}
@@ -0,0 +1,44 @@
+use chrono::Utc;
+use uuid::Uuid;
+
+use crate::database;
+use crate::gateway::GatewayEvent;
+use crate::models::{AppState, Poll};
+use crate::shared::AppError;
+
+/// Send the poll's current results to everyone viewing its message, without
+/// the requesting user's own votes.
+pub async fn broadcast_poll_updated(
+    state: &AppState,
+    message_id: Uuid,
+    poll: &Poll,
+) -> Result<(), AppError> {
+    let Some(message) = database::get_message_by_id(&state.db, message_id).await? else {
+        return Ok(());
+    };
+    state.broadcast_topic(
+        message.channel_id,
+        message.topic_id(),
+        GatewayEvent::PollUpdated {
+            channel_id: message.channel_id,
+            message_id,
+            poll: poll.for_broadcast(),
+        },
+    );
+    Ok(())
+}
+
+/// Close polls past their expiry, snapshot the final results, and announce
+/// them to their channels.
+pub async fn close_expired_polls(state: &AppState) -> Result<(), AppError> {
+    let closed = database::close_expired_polls(&state.db, Utc::now()).await?;
+    if closed.is_empty() {
+        return Ok(());
+    }
+
+    let polls = database::get_polls_for_messages(&state.db, &closed, Uuid::nil()).await?;
+    for (message_id, poll) in polls {
+        broadcast_poll_updated(state, message_id, &poll).await?;
+    }
+    Ok(())
+}
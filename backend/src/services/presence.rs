@@ -0,0 +1,106 @@
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::database;
+use crate::gateway::GatewayEvent;
+use crate::models::{AppState, PresenceStatus, UpdatePresenceRequest, UserPresence};
+use crate::shared::AppError;
+use crate::shared::validation::validate_custom_status;
+
+/// Persists the user's chosen status and custom status, then updates their
+/// presence if they are connected. An explicit choice clears auto-idle.
+pub async fn update_presence(
+    state: &Arc<AppState>,
+    user_id: Uuid,
+    request: UpdatePresenceRequest,
+) -> Result<(), AppError> {
+    let custom_status = request
+        .custom_status
+        .as_ref()
+        .map(validate_custom_status)
+        .transpose()?;
+
+    database::update_presence_settings(&state.db, user_id, request.status, custom_status.as_ref())
+        .await?;
+
+    modify_presence(state, user_id, |presence| {
+        presence.status = request.status;
+        presence.custom_status = custom_status;
+    });
+    Ok(())
+}
+
+/// Applies a client-reported idle change. Users who picked anything other
+/// than `online` keep their status.
+pub async fn set_auto_idle(
+    state: &Arc<AppState>,
+    user_id: Uuid,
+    idle: bool,
+) -> Result<(), AppError> {
+    if !state.online_users.contains_key(&user_id) {
+        return Ok(());
+    }
+    let settings = database::get_presence_settings(&state.db, user_id).await?;
+    let status = settings.effective_status(idle);
+    modify_presence(state, user_id, |presence| presence.status = status);
+    Ok(())
+}
+
+/// Drops expired custom statuses and tells clients about the online users
+/// affected.
+pub async fn expire_custom_statuses(state: &Arc<AppState>) -> Result<(), AppError> {
+    for user_id in database::clear_expired_custom_statuses(&state.db).await? {
+        modify_presence(state, user_id, |presence| presence.custom_status = None);
+    }
+    Ok(())
+}
+
+fn modify_presence(state: &AppState, user_id: Uuid, update: impl FnOnce(&mut UserPresence)) {
+    let Some(mut presence) = state.online_users.get(&user_id).map(|p| p.clone()) else {
+        return;
+    };
+    let previous = presence.clone();
+    update(&mut presence);
+    if presence.status == previous.status && presence.custom_status == previous.custom_status {
+        return;
+    }
+    state.update_presence(presence.clone());
+    broadcast_presence_change(state, previous.status, &presence);
+}
+
+/// Broadcasts a presence change. Invisible users appear offline to
+/// everyone else, so going invisible is announced as `user_offline` and
+/// coming back as `user_online`. The user's own sessions always learn the
+/// new presence.
+pub fn broadcast_presence_change(
+    state: &AppState,
+    previous: PresenceStatus,
+    presence: &UserPresence,
+) {
+    let was_visible = previous != PresenceStatus::Invisible;
+    let is_visible = presence.status != PresenceStatus::Invisible;
+    match (was_visible, is_visible) {
+        (true, true) => {
+            state.broadcast_global(GatewayEvent::PresenceUpdated(presence.clone()));
+        }
+        (false, true) => {
+            state.broadcast_global(GatewayEvent::UserOnline(presence.clone()));
+        }
+        (true, false) => {
+            state.broadcast_global(GatewayEvent::UserOffline {
+                user_id: presence.user_id,
+                username: presence.username.clone(),
+            });
+            state.broadcast_user(
+                presence.user_id,
+                GatewayEvent::PresenceUpdated(presence.clone()),
+            );
+        }
+        (false, false) => {
+            state.broadcast_user(
+                presence.user_id,
+                GatewayEvent::PresenceUpdated(presence.clone()),
+            );
+        }
+    }
+}
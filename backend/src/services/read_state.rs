@@ -0,0 +1,52 @@
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::database;
+use crate::gateway::GatewayEvent;
+use crate::models::AppState;
+use crate::permissions;
+use crate::shared::AppError;
+
+/// Marks a channel as read up to `message_id` and tells the user's other
+/// sessions so they can clear the unread badge too. Shared by the REST and
+/// WS ack operations.
+pub async fn ack_channel(
+    state: &Arc<AppState>,
+    user_id: Uuid,
+    channel_id: Uuid,
+    message_id: Uuid,
+) -> Result<(), AppError> {
+    permissions::require_channel_access(state, channel_id, user_id)?;
+
+    let message = database::get_message_by_id(&state.db, message_id)
+        .await?
+        .ok_or_else(|| AppError::not_found("Message not found"))?;
+    if message.channel_id != channel_id {
+        return Err(AppError::not_found("Message not found in this channel"));
+    }
+    // Thread replies do not count as unread, so they cannot move the marker
+    if message.thread_id.is_some() {
+        return Err(AppError::bad_request(
+            "Thread replies cannot be acknowledged",
+        ));
+    }
+
+    database::ack_channel(
+        &state.db,
+        user_id,
+        channel_id,
+        message_id,
+        message.timestamp,
+    )
+    .await?;
+
+    state.broadcast_user(
+        user_id,
+        GatewayEvent::ChannelAck {
+            channel_id,
+            message_id,
+        },
+    );
+
+    Ok(())
+}
@@ -0,0 +1,104 @@
+use chrono::Utc;
+use std::sync::Arc;
+
+use crate::database;
+use crate::gateway::GatewayEvent;
+use crate::models::{AppState, ScheduledMessage};
+use crate::permissions;
+use crate::services::message::{
+    CreateMessageParams, CreateMessageResult, MessageAuthor, broadcast_new_message,
+    check_channel_rules, create_message,
+};
+use crate::shared::AppError;
+use crate::shared::validation::SCHEDULED_MESSAGE_CLAIM_TIMEOUT_SECS;
+
+/// Most scheduled messages delivered per scheduler pass.
+const DELIVERY_BATCH_SIZE: i64 = 100;
+
+/// Post every scheduled message that is due, exactly as if its author sent
+/// it now. Messages the author can no longer send are kept with a failure
+/// reason. Returns how many were posted.
+pub async fn deliver_due_messages(state: &Arc<AppState>) -> Result<usize, AppError> {
+    let now = Utc::now();
+    let stale_before = now - chrono::Duration::seconds(SCHEDULED_MESSAGE_CLAIM_TIMEOUT_SECS);
+    let due =
+        database::claim_due_scheduled_messages(&state.db, now, stale_before, DELIVERY_BATCH_SIZE)
+            .await?;
+
+    // Bookkeeping errors are logged rather than returned so one row cannot
+    // abort the rest of the batch.
+    let mut delivered = 0;
+    for scheduled in due {
+        match deliver(state, &scheduled).await {
+            Ok(result) => {
+                if let Err(e) = database::complete_scheduled_message(&state.db, scheduled.id).await
+                {
+                    tracing::warn!("Failed to complete scheduled message {}: {e}", scheduled.id);
+                }
+                broadcast_new_message(state, &result);
+                state.broadcast_user(
+                    scheduled.author_id,
+                    GatewayEvent::ScheduledMessageSent {
+                        id: scheduled.id,
+                        channel_id: scheduled.channel_id,
+                        message_id: result.message.id,
+                    },
+                );
+                delivered += 1;
+            }
+            // Transient, including slowmode; leave it queued for the next pass.
+            Err(
+                e @ (AppError::Database(_) | AppError::Internal(_) | AppError::TooManyRequests(_)),
+            ) => {
+                tracing::warn!("Failed to deliver scheduled message {}: {e}", scheduled.id);
+                if let Err(e) =
+                    database::release_scheduled_message(&state.db, scheduled.id, None).await
+                {
+                    tracing::warn!("Failed to release scheduled message {}: {e}", scheduled.id);
+                }
+            }
+            Err(e) => {
+                let failure = e.to_string();
+                match database::release_scheduled_message(&state.db, scheduled.id, Some(&failure))
+                    .await
+                {
+                    Ok(Some(failed)) => state.broadcast_user(
+                        failed.author_id,
+                        GatewayEvent::ScheduledMessageFailed(failed),
+                    ),
+                    Ok(None) => {}
+                    Err(e) => {
+                        tracing::warn!("Failed to release scheduled message {}: {e}", scheduled.id);
+                    }
+                }
+            }
+        }
+    }
+    Ok(delivered)
+}
+
+async fn deliver(
+    state: &Arc<AppState>,
+    scheduled: &ScheduledMessage,
+) -> Result<CreateMessageResult, AppError> {
+    permissions::check_not_muted(&state.db, scheduled.author_id).await?;
+    check_channel_rules(state, scheduled.channel_id, scheduled.author_id).await?;
+
+    create_message(
+        state,
+        &state.db,
+        CreateMessageParams {
+            user_id: scheduled.author_id,
+            author: MessageAuthor::User,
+            channel_id: scheduled.channel_id,
+            content: scheduled.content.clone(),
+            reply_to_id: scheduled.reply_to_id,
+            thread_id: None,
+            attachment_ids: scheduled.attachment_ids.clone(),
+            poll: scheduled.poll.clone(),
+            validate_reply_channel: true,
+        },
+    )
+    .await
+    .inspect_err(|_| state.release_slowmode(scheduled.channel_id, scheduled.author_id))
+}
@@ -9,7 +9,10 @@ use std::sync::Arc;
 use uuid::Uuid;
 
 use crate::auth::AuthUser;
+use crate::database;
+use crate::gateway::GatewayEvent;
 use crate::models::AppState;
+use crate::permissions::{self, Permissions};
 use crate::sfu::models::{ConsumerData, ProducerInfo, TransportOptions};
 use crate::shared::AppResult;
 
@@ -49,6 +52,10 @@ pub async fn create_transport(
     Json(req): Json<CreateTransportRequest>,
 ) -> AppResult<Json<TransportOptions>> {
     let user_id = auth_user.user_id();
+    permissions::require_channel_access(&state, req.channel_id, user_id)?;
+    database::get_channel_permissions(&state.db, req.channel_id, user_id)
+        .await?
+        .require(Permissions::CONNECT)?;
 
     state
         .sfu_service
@@ -85,23 +92,23 @@ pub async fn produce(
     state
         .sfu_service
         .verify_transport_owner(&transport_id, user_id)?;
+    let channel_id = state.sfu_service.transport_channel_id(&transport_id)?;
+    let channel_permissions =
+        database::get_channel_permissions(&state.db, channel_id, user_id).await?;
 
     let info = state
         .sfu_service
-        .produce(&transport_id, req.kind, req.rtp_parameters, req.label)
+        .produce(
+            &transport_id,
+            req.kind,
+            req.rtp_parameters,
+            req.label,
+            channel_permissions,
+        )
         .await?;
 
     // Broadcast new_producer event so other participants can consume immediately
-    state.broadcast_global(
-        "new_producer",
-        serde_json::json!({
-            "producer_id": info.producer_id,
-            "channel_id": info.channel_id,
-            "user_id": info.user_id,
-            "kind": info.kind,
-            "label": info.label,
-        }),
-    );
+    state.broadcast_for_channel(info.channel_id, GatewayEvent::NewProducer(info.clone()));
 
     Ok(Json(ProduceResponse {
         producer_id: info.producer_id,
@@ -147,17 +154,19 @@ pub async fn close_connection(
 
 pub async fn get_channel_producers(
     State(state): State<Arc<AppState>>,
-    _auth_user: AuthUser,
+    auth_user: AuthUser,
     Path(channel_id): Path<Uuid>,
 ) -> AppResult<Json<Vec<ProducerInfo>>> {
+    permissions::require_channel_access(&state, channel_id, auth_user.user_id())?;
     Ok(Json(state.sfu_service.get_channel_producers(channel_id)))
 }
 
 pub async fn get_router_capabilities(
     State(state): State<Arc<AppState>>,
-    _auth_user: AuthUser,
+    auth_user: AuthUser,
     Path(channel_id): Path<Uuid>,
 ) -> AppResult<Json<RtpCapabilitiesFinalized>> {
+    permissions::require_channel_access(&state, channel_id, auth_user.user_id())?;
     state
         .sfu_service
         .get_router_capabilities(channel_id)
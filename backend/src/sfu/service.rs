@@ -5,6 +5,7 @@ use std::time::{SystemTime, UNIX_EPOCH};
 use tokio::sync::Mutex;
 use uuid::Uuid;
 
+use crate::permissions::Permissions;
 use crate::sfu::models::{
     ConsumerData, ParticipantConnection, ProducerEntry, ProducerInfo, TransportOptions,
 };
@@ -131,6 +132,14 @@ impl SfuService {
         Ok(())
     }
 
+    /// The voice channel a transport was created for.
+    pub fn transport_channel_id(&self, transport_id: &str) -> Result<Uuid, AppError> {
+        self.connections
+            .get(transport_id)
+            .map(|conn| conn.channel_id)
+            .ok_or_else(|| AppError::not_found("Transport not found"))
+    }
+
     pub async fn get_or_create_router(&self, channel_id: Uuid) -> Result<Arc<Router>, AppError> {
         if let Some(router) = self.routers.get(&channel_id) {
             return Ok(router.clone());
@@ -257,7 +266,17 @@ impl SfuService {
         kind: MediaKind,
         rtp_parameters: RtpParameters,
         label: Option<String>,
+        permissions: Permissions,
     ) -> Result<ProducerInfo, AppError> {
+        // Screen and camera shares need STREAM; microphone audio needs SPEAK
+        let is_stream =
+            kind == MediaKind::Video || matches!(label.as_deref(), Some("screen") | Some("camera"));
+        permissions.require(if is_stream {
+            Permissions::STREAM
+        } else {
+            Permissions::SPEAK
+        })?;
+
         let transport = self
             .transports
             .get(transport_id)
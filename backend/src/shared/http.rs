@@ -10,3 +10,20 @@ pub fn create_http_client(timeout_secs: u64) -> Result<reqwest::Client, reqwest:
         .user_agent(USER_AGENT)
         .build()
 }
+
+/// Like [`create_http_client`], but redirects are returned to the caller
+/// instead of followed.
+pub fn create_http_client_without_redirects(
+    timeout_secs: u64,
+) -> Result<reqwest::Client, reqwest::Error> {
+    client_builder_without_redirects(timeout_secs).build()
+}
+
+/// Builder behind [`create_http_client_without_redirects`], for callers that
+/// need extra settings such as pinned DNS results.
+pub fn client_builder_without_redirects(timeout_secs: u64) -> reqwest::ClientBuilder {
+    reqwest::Client::builder()
+        .timeout(Duration::from_secs(timeout_secs))
+        .redirect(reqwest::redirect::Policy::none())
+        .user_agent(USER_AGENT)
+}
@@ -1,7 +1,21 @@
+use chrono::{DateTime, Utc};
+
+use crate::models::{CreatePollRequest, CustomStatus, Embed};
 use crate::shared::AppError;
 
 pub const MAX_MESSAGE_LENGTH: usize = 4000;
 pub const MAX_CHANNEL_NAME_LENGTH: usize = 50;
+pub const MAX_CHANNEL_TOPIC_LENGTH: usize = 1024;
+/// Six hours, the longest slowmode interval.
+pub const MAX_SLOWMODE_SECS: i32 = 21600;
+pub const MAX_PINS_PER_CHANNEL: usize = 50;
+pub const MAX_PURGE_MESSAGES: i64 = 1000;
+/// Unread and mention counts stop at this; clients show e.g. "99+".
+pub const MAX_UNREAD_COUNT: i64 = 100;
+pub const MAX_CATEGORY_NAME_LENGTH: usize = 50;
+pub const MAX_CATEGORIES: usize = 50;
+pub const MAX_ROLE_NAME_LENGTH: usize = 32;
+pub const MAX_ROLES: usize = 100;
 pub const MIN_USERNAME_LENGTH: usize = 2;
 pub const MAX_USERNAME_LENGTH: usize = 32;
 pub const MAX_EMAIL_LENGTH: usize = 254;
@@ -11,8 +25,15 @@ pub const MAX_EMOJI_LENGTH: usize = 32;
 pub const REPLY_PREVIEW_LENGTH: usize = 200;
 pub const MAX_REASON_LENGTH: usize = 500;
 pub const MAX_SERVER_NAME_LENGTH: usize = 100;
+pub const MAX_REVISION_RETENTION_DAYS: i64 = 3650;
+pub const MAX_DELETED_MESSAGE_RETENTION_DAYS: i64 = 3650;
+/// How long authors can undo deleting their own message.
+pub const MESSAGE_UNDO_WINDOW_SECS: i64 = 15;
 pub const MAX_IMAGE_PROXY_SIZE: usize = 10 * 1024 * 1024;
 pub const BROADCAST_CHANNEL_CAPACITY: usize = 256;
+pub const DEFAULT_WS_MAX_SUBSCRIPTIONS: usize = 25;
+pub const WS_REPLAY_BUFFER_SIZE: usize = 1000;
+pub const WS_SESSION_RESUME_SECS: u64 = 60;
 pub const MAX_ATTACHMENT_SIZE: usize = 250 * 1024 * 1024; // 250MB
 pub const MAX_CUSTOM_EMOJI_SIZE: usize = 1024 * 1024; // 1MB
 pub const MAX_CUSTOM_EMOJI_NAME_LENGTH: usize = 32;
@@ -20,6 +41,67 @@ pub const MAX_ATTACHMENTS_PER_MESSAGE: usize = 5;
 pub const MAX_FILENAME_LENGTH: usize = 255;
 pub const MESSAGE_RATE_LIMIT: f64 = 5.0;
 pub const MESSAGE_RATE_REFILL_PER_SEC: f64 = 1.0;
+pub const MAX_THREAD_NAME_LENGTH: usize = 100;
+pub const THREAD_AUTO_ARCHIVE_HOURS: i64 = 24;
+pub const MAX_SEARCH_QUERY_LENGTH: usize = 200;
+pub const MAX_GROUP_DM_MEMBERS: usize = 10;
+pub const MAX_MENTIONS_PER_MESSAGE: usize = 50;
+pub const MAX_POLL_QUESTION_LENGTH: usize = 300;
+pub const MAX_POLL_OPTION_LENGTH: usize = 100;
+pub const MIN_POLL_OPTIONS: usize = 2;
+pub const MAX_POLL_OPTIONS: usize = 10;
+/// Thirty days, the longest a poll can run before closing.
+pub const MAX_POLL_DURATION_MINUTES: i64 = 43200;
+pub const MAX_SCHEDULED_MESSAGES_PER_USER: i64 = 50;
+pub const MAX_SCHEDULE_AHEAD_DAYS: i64 = 90;
+/// How long a node may hold a due scheduled message before another node
+/// assumes it stopped and delivers it instead.
+pub const SCHEDULED_MESSAGE_CLAIM_TIMEOUT_SECS: i64 = 300;
+pub const MAX_WEBHOOKS_PER_CHANNEL: i64 = 10;
+pub const MAX_WEBHOOK_NAME_LENGTH: usize = 80;
+pub const MAX_EMBEDS_PER_MESSAGE: usize = 10;
+pub const MAX_EMBED_TITLE_LENGTH: usize = 256;
+pub const MAX_EMBED_DESCRIPTION_LENGTH: usize = 4096;
+pub const MAX_EMBED_FIELDS: usize = 25;
+pub const MAX_EMBED_FIELD_NAME_LENGTH: usize = 256;
+pub const MAX_EMBED_FIELD_VALUE_LENGTH: usize = 1024;
+pub const MAX_URL_LENGTH: usize = 2048;
+pub const MAX_OUTGOING_WEBHOOKS: i64 = 20;
+/// Attempts made for each outgoing webhook delivery before giving up.
+pub const OUTGOING_WEBHOOK_MAX_ATTEMPTS: i32 = 8;
+/// How long a node may hold a claimed delivery before another node retries it.
+pub const OUTGOING_WEBHOOK_CLAIM_TIMEOUT_SECS: i64 = 120;
+pub const MAX_DELIVERY_LOG_PAGE_SIZE: i64 = 100;
+/// Finished deliveries are kept in the log for this long.
+pub const DELIVERY_LOG_RETENTION_DAYS: i64 = 14;
+
+/// Gateway events outgoing webhooks can subscribe to. High-frequency events
+/// like typing, presence and voice activity are left out.
+pub const OUTGOING_WEBHOOK_EVENT_TYPES: &[&str] = &[
+    "message",
+    "message_edited",
+    "message_deleted",
+    "messages_bulk_deleted",
+    "message_pinned",
+    "message_unpinned",
+    "reaction_added",
+    "reaction_removed",
+    "channel_created",
+    "channel_updated",
+    "channel_deleted",
+    "thread_created",
+    "member_joined",
+    "user_deleted",
+    "user_kicked",
+    "user_banned",
+    "user_unbanned",
+    "user_muted",
+    "user_unmuted",
+    "user_roles_updated",
+    "role_created",
+    "role_updated",
+    "role_deleted",
+];
 
 pub const ALLOWED_CONTENT_TYPES: &[&str] = &[
     "image/jpeg",
@@ -53,6 +135,7 @@ pub const ALLOWED_SOUNDBOARD_CONTENT_TYPES: &[&str] = &["audio/mpeg", "audio/ogg
 
 pub const MAX_AVATAR_SIZE: usize = 5 * 1024 * 1024; // 5MB
 pub const MAX_DISPLAY_NAME_LENGTH: usize = 64;
+pub const MAX_CUSTOM_STATUS_LENGTH: usize = 128;
 pub const ALLOWED_AVATAR_CONTENT_TYPES: &[&str] =
     &["image/png", "image/gif", "image/webp", "image/jpeg"];
 
@@ -93,19 +176,182 @@ pub fn validate_message_content(content: &str) -> Result<(), AppError> {
     Ok(())
 }
 
+pub fn validate_thread_name(name: &str) -> Result<String, AppError> {
+    let trimmed = name.trim().to_string();
+    if trimmed.is_empty() || trimmed.chars().count() > MAX_THREAD_NAME_LENGTH {
+        return Err(AppError::bad_request(
+            "Thread name must be between 1 and 100 characters",
+        ));
+    }
+    Ok(trimmed)
+}
+
 pub fn validate_message_content_optional(
     content: &Option<String>,
-    has_attachments: bool,
+    has_other_content: bool,
 ) -> Result<(), AppError> {
     match content {
         Some(c) if !c.trim().is_empty() => validate_message_content(c),
-        _ if has_attachments => Ok(()),
+        _ if has_other_content => Ok(()),
         _ => Err(AppError::bad_request(
-            "Message must have content or attachments",
+            "Message must have content, attachments or a poll",
         )),
     }
 }
 
+/// Trims the question and options and checks their lengths and count.
+pub fn validate_poll(mut poll: CreatePollRequest) -> Result<CreatePollRequest, AppError> {
+    poll.question = poll.question.trim().to_string();
+    if poll.question.is_empty() || poll.question.chars().count() > MAX_POLL_QUESTION_LENGTH {
+        return Err(AppError::bad_request(format!(
+            "Poll question must be between 1 and {MAX_POLL_QUESTION_LENGTH} characters"
+        )));
+    }
+
+    if !(MIN_POLL_OPTIONS..=MAX_POLL_OPTIONS).contains(&poll.options.len()) {
+        return Err(AppError::bad_request(format!(
+            "Polls must have between {MIN_POLL_OPTIONS} and {MAX_POLL_OPTIONS} options"
+        )));
+    }
+    for option in &mut poll.options {
+        *option = option.trim().to_string();
+        if option.is_empty() || option.chars().count() > MAX_POLL_OPTION_LENGTH {
+            return Err(AppError::bad_request(format!(
+                "Poll options must be between 1 and {MAX_POLL_OPTION_LENGTH} characters"
+            )));
+        }
+    }
+
+    if let Some(minutes) = poll.duration_minutes
+        && !(1..=MAX_POLL_DURATION_MINUTES).contains(&minutes)
+    {
+        return Err(AppError::bad_request(format!(
+            "Poll duration must be between 1 and {MAX_POLL_DURATION_MINUTES} minutes"
+        )));
+    }
+
+    Ok(poll)
+}
+
+pub fn validate_webhook_name(name: &str) -> Result<String, AppError> {
+    let trimmed = name.trim().to_string();
+    if trimmed.is_empty() || trimmed.chars().count() > MAX_WEBHOOK_NAME_LENGTH {
+        return Err(AppError::bad_request(format!(
+            "Webhook name must be between 1 and {MAX_WEBHOOK_NAME_LENGTH} characters"
+        )));
+    }
+    Ok(trimmed)
+}
+
+/// Only absolute http(s) URLs are accepted for links and images.
+pub fn validate_http_url(url: &str, field_name: &str) -> Result<(), AppError> {
+    let valid = url.len() <= MAX_URL_LENGTH
+        && url::Url::parse(url).is_ok_and(|u| matches!(u.scheme(), "http" | "https"));
+    if !valid {
+        return Err(AppError::bad_request(format!(
+            "{field_name} must be an http or https URL"
+        )));
+    }
+    Ok(())
+}
+
+/// Deduplicates event types and rejects unknown ones.
+pub fn validate_event_types(event_types: &[String]) -> Result<Vec<String>, AppError> {
+    let mut validated: Vec<String> = Vec::new();
+    for event_type in event_types {
+        if !OUTGOING_WEBHOOK_EVENT_TYPES.contains(&event_type.as_str()) {
+            return Err(AppError::bad_request(format!(
+                "Unknown event type: {event_type}"
+            )));
+        }
+        if !validated.contains(event_type) {
+            validated.push(event_type.clone());
+        }
+    }
+    if validated.is_empty() {
+        return Err(AppError::bad_request(
+            "Subscribe to at least one event type",
+        ));
+    }
+    Ok(validated)
+}
+
+/// Checks embed count, text lengths, colors and URLs.
+pub fn validate_embeds(embeds: &[Embed]) -> Result<(), AppError> {
+    if embeds.len() > MAX_EMBEDS_PER_MESSAGE {
+        return Err(AppError::bad_request(format!(
+            "Maximum {MAX_EMBEDS_PER_MESSAGE} embeds per message"
+        )));
+    }
+    for embed in embeds {
+        if embed.title.is_none() && embed.description.is_none() && embed.fields.is_empty() {
+            return Err(AppError::bad_request(
+                "Embeds need a title, description or fields",
+            ));
+        }
+        if embed
+            .title
+            .as_ref()
+            .is_some_and(|t| t.chars().count() > MAX_EMBED_TITLE_LENGTH)
+        {
+            return Err(AppError::bad_request(format!(
+                "Embed titles can be at most {MAX_EMBED_TITLE_LENGTH} characters"
+            )));
+        }
+        if embed
+            .description
+            .as_ref()
+            .is_some_and(|d| d.chars().count() > MAX_EMBED_DESCRIPTION_LENGTH)
+        {
+            return Err(AppError::bad_request(format!(
+                "Embed descriptions can be at most {MAX_EMBED_DESCRIPTION_LENGTH} characters"
+            )));
+        }
+        if embed.fields.len() > MAX_EMBED_FIELDS {
+            return Err(AppError::bad_request(format!(
+                "Maximum {MAX_EMBED_FIELDS} fields per embed"
+            )));
+        }
+        for field in &embed.fields {
+            if field.name.trim().is_empty()
+                || field.name.chars().count() > MAX_EMBED_FIELD_NAME_LENGTH
+                || field.value.trim().is_empty()
+                || field.value.chars().count() > MAX_EMBED_FIELD_VALUE_LENGTH
+            {
+                return Err(AppError::bad_request(format!(
+                    "Embed field names must be 1 to {MAX_EMBED_FIELD_NAME_LENGTH} and values 1 to {MAX_EMBED_FIELD_VALUE_LENGTH} characters"
+                )));
+            }
+        }
+        if embed.color.is_some_and(|c| c > 0xFF_FF_FF) {
+            return Err(AppError::bad_request(
+                "Embed color must be an RGB value between 0 and 0xFFFFFF",
+            ));
+        }
+        if let Some(url) = &embed.url {
+            validate_http_url(url, "Embed url")?;
+        }
+        if let Some(url) = &embed.image_url {
+            validate_http_url(url, "Embed image_url")?;
+        }
+    }
+    Ok(())
+}
+
+pub fn validate_send_at(send_at: DateTime<Utc>, now: DateTime<Utc>) -> Result<(), AppError> {
+    if send_at <= now {
+        return Err(AppError::bad_request(
+            "Scheduled time must be in the future",
+        ));
+    }
+    if send_at > now + chrono::Duration::days(MAX_SCHEDULE_AHEAD_DAYS) {
+        return Err(AppError::bad_request(format!(
+            "Messages can be scheduled at most {MAX_SCHEDULE_AHEAD_DAYS} days ahead"
+        )));
+    }
+    Ok(())
+}
+
 pub fn validate_attachment_content_type(content_type: &str) -> Result<(), AppError> {
     if !ALLOWED_CONTENT_TYPES.contains(&content_type) {
         return Err(AppError::bad_request(format!(
@@ -275,3 +521,166 @@ pub fn validate_channel_name(name: &str) -> Result<String, AppError> {
     }
     Ok(trimmed)
 }
+
+/// Returns None for an empty topic.
+pub fn validate_channel_topic(topic: &str) -> Result<Option<String>, AppError> {
+    let trimmed = topic.trim();
+    if trimmed.chars().count() > MAX_CHANNEL_TOPIC_LENGTH {
+        return Err(AppError::bad_request(format!(
+            "Channel topic must be at most {MAX_CHANNEL_TOPIC_LENGTH} characters"
+        )));
+    }
+    Ok((!trimmed.is_empty()).then(|| trimmed.to_string()))
+}
+
+pub fn validate_slowmode(secs: i32) -> Result<i32, AppError> {
+    if !(0..=MAX_SLOWMODE_SECS).contains(&secs) {
+        return Err(AppError::bad_request(format!(
+            "Slowmode must be between 0 and {MAX_SLOWMODE_SECS} seconds"
+        )));
+    }
+    Ok(secs)
+}
+
+pub fn validate_category_name(name: &str) -> Result<String, AppError> {
+    let trimmed = name.trim().to_string();
+    if trimmed.is_empty() || trimmed.chars().count() > MAX_CATEGORY_NAME_LENGTH {
+        return Err(AppError::bad_request(
+            "Category name must be between 1 and 50 characters",
+        ));
+    }
+    Ok(trimmed)
+}
+
+pub fn validate_custom_status(status: &CustomStatus) -> Result<CustomStatus, AppError> {
+    let text = status
+        .text
+        .as_deref()
+        .map(str::trim)
+        .filter(|t| !t.is_empty())
+        .map(str::to_string);
+    if text
+        .as_ref()
+        .is_some_and(|t| t.chars().count() > MAX_CUSTOM_STATUS_LENGTH)
+    {
+        return Err(AppError::bad_request(format!(
+            "Custom status must be at most {MAX_CUSTOM_STATUS_LENGTH} characters"
+        )));
+    }
+    let emoji = status
+        .emoji
+        .as_deref()
+        .map(str::trim)
+        .filter(|e| !e.is_empty())
+        .map(str::to_string);
+    if emoji.as_ref().is_some_and(|e| e.len() > MAX_EMOJI_LENGTH) {
+        return Err(AppError::bad_request(
+            "Emoji must be between 1 and 32 characters",
+        ));
+    }
+    if text.is_none() && emoji.is_none() {
+        return Err(AppError::bad_request(
+            "Custom status must have text or an emoji",
+        ));
+    }
+    if status.expires_at.is_some_and(|at| at <= chrono::Utc::now()) {
+        return Err(AppError::bad_request(
+            "Custom status expiry must be in the future",
+        ));
+    }
+    Ok(CustomStatus {
+        text,
+        emoji,
+        expires_at: status.expires_at,
+    })
+}
+
+pub fn validate_role_name(name: &str) -> Result<String, AppError> {
+    let trimmed = name.trim().to_string();
+    if trimmed.is_empty() || trimmed.chars().count() > MAX_ROLE_NAME_LENGTH {
+        return Err(AppError::bad_request(
+            "Role name must be between 1 and 32 characters",
+        ));
+    }
+    if matches!(trimmed.to_lowercase().as_str(), "everyone" | "here") {
+        return Err(AppError::bad_request("Role name is reserved"));
+    }
+    Ok(trimmed)
+}
+
+pub fn validate_role_color(color: Option<i32>) -> Result<(), AppError> {
+    if color.is_some_and(|c| !(0..=0xFF_FF_FF).contains(&c)) {
+        return Err(AppError::bad_request(
+            "Role color must be an RGB value between 0 and 0xFFFFFF",
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn event_types_are_deduplicated() {
+        let types = ["message", "message_deleted", "message"].map(String::from);
+        assert_eq!(
+            validate_event_types(&types).unwrap(),
+            ["message", "message_deleted"]
+        );
+    }
+
+    #[test]
+    fn unknown_or_missing_event_types_are_rejected() {
+        assert!(validate_event_types(&["message".into(), "nope".into()]).is_err());
+        assert!(validate_event_types(&[]).is_err());
+    }
+
+    fn embed(title: &str) -> Embed {
+        Embed {
+            title: Some(title.to_string()),
+            description: None,
+            url: None,
+            color: None,
+            image_url: None,
+            fields: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn valid_embeds_pass() {
+        let mut full = embed("Release");
+        full.description = Some("Notes".to_string());
+        full.url = Some("https://example.com/release".to_string());
+        full.color = Some(0xFF_FF_FF);
+        full.fields.push(crate::models::EmbedField {
+            name: "Version".to_string(),
+            value: "1.2".to_string(),
+            inline: true,
+        });
+        assert!(validate_embeds(&[embed("Hello"), full]).is_ok());
+    }
+
+    #[test]
+    fn invalid_embeds_are_rejected() {
+        let mut empty = embed("");
+        empty.title = None;
+        let long_title = embed(&"x".repeat(MAX_EMBED_TITLE_LENGTH + 1));
+        let mut bad_color = embed("Hello");
+        bad_color.color = Some(0x1_00_00_00);
+        let mut bad_url = embed("Hello");
+        bad_url.url = Some("javascript:alert(1)".to_string());
+        let mut blank_field = embed("Hello");
+        blank_field.fields.push(crate::models::EmbedField {
+            name: " ".to_string(),
+            value: "value".to_string(),
+            inline: false,
+        });
+
+        for invalid in [empty, long_title, bad_color, bad_url, blank_field] {
+            assert!(validate_embeds(&[invalid]).is_err());
+        }
+        let too_many = vec![embed("Hello"); MAX_EMBEDS_PER_MESSAGE + 1];
+        assert!(validate_embeds(&too_many).is_err());
+    }
+}
@@ -8,7 +8,9 @@ use uuid::Uuid;
 
 use crate::auth::AuthUser;
 use crate::database;
-use crate::models::{AppState, ChannelType, JoinVoiceRequest, LeaveVoiceRequest, VoiceState};
+use crate::gateway::GatewayEvent;
+use crate::models::{AppState, JoinVoiceRequest, LeaveVoiceRequest, VoiceState};
+use crate::permissions::{self, Permissions};
 use crate::shared::{AppError, AppResult};
 
 pub async fn join_voice_channel(
@@ -18,11 +20,15 @@ pub async fn join_voice_channel(
 ) -> AppResult<Json<VoiceState>> {
     let user_id = auth_user.user_id();
 
-    // Verify the channel exists and is a voice channel
+    // Verify the channel exists and supports voice (voice channels and DMs)
+    permissions::require_channel_access(&state, request.channel_id, user_id)?;
     let channel_type = database::get_channel_type(&state.db, request.channel_id).await?;
-    if channel_type != ChannelType::Voice {
+    if !channel_type.supports_voice() {
         return Err(AppError::bad_request("Cannot join a non-voice channel"));
     }
+    database::get_channel_permissions(&state.db, request.channel_id, user_id)
+        .await?
+        .require(Permissions::CONNECT)?;
 
     // Leave all voice channels the user is currently in (including the target channel for re-joins)
     state.remove_user_from_voice(user_id).await;
@@ -53,13 +59,12 @@ pub async fn join_voice_channel(
         joined_at: now,
     };
 
-    state
-        .voice_states
-        .entry(request.channel_id)
-        .or_default()
-        .insert(user_id, voice_state.clone());
+    state.set_voice_state(voice_state.clone());
 
-    state.broadcast_global("voice_user_joined", serde_json::json!(voice_state));
+    state.broadcast_for_channel(
+        request.channel_id,
+        GatewayEvent::VoiceUserJoined(voice_state.clone()),
+    );
 
     tracing::info!(
         "User {} joined voice channel {}",
@@ -77,28 +82,9 @@ pub async fn leave_voice_channel(
 ) -> AppResult<()> {
     let user_id = auth_user.user_id();
 
-    // Remove from voice states
-    if let Some(channel_users) = state.voice_states.get(&request.channel_id) {
-        channel_users.remove(&user_id);
-        drop(channel_users);
-        state
-            .voice_states
-            .remove_if(&request.channel_id, |_, users| users.is_empty());
-    }
-
-    // Close all SFU transports for this user in this channel
-    state
-        .sfu_service
-        .close_user_connections(request.channel_id, user_id)
-        .await;
-
-    state.broadcast_global(
-        "voice_user_left",
-        serde_json::json!({
-            "user_id": user_id,
-            "channel_id": request.channel_id,
-        }),
-    );
+    // Remove from voice states and close all SFU transports for this user
+    // in this channel
+    state.leave_voice(request.channel_id, user_id).await;
 
     tracing::info!("User {user_id} left voice channel {}", request.channel_id);
 
@@ -108,8 +94,10 @@ pub async fn leave_voice_channel(
 pub async fn get_voice_states(
     State(state): State<Arc<AppState>>,
     Path(channel_id): Path<Uuid>,
-    _auth_user: AuthUser,
+    auth_user: AuthUser,
 ) -> AppResult<Json<Vec<VoiceState>>> {
+    permissions::require_channel_access(&state, channel_id, auth_user.user_id())?;
+
     let users_in_voice = state
         .voice_states
         .get(&channel_id)
@@ -121,7 +109,7 @@ pub async fn get_voice_states(
 
 pub async fn get_all_voice_states(
     State(state): State<Arc<AppState>>,
-    _auth_user: AuthUser,
+    auth_user: AuthUser,
 ) -> AppResult<Json<Vec<VoiceState>>> {
-    Ok(Json(state.all_voice_states()))
+    Ok(Json(state.visible_voice_states(auth_user.user_id())))
 }
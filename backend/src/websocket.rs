@@ -1,74 +1,369 @@
 use axum::{
     extract::{
         State,
-        ws::{Message, WebSocket, WebSocketUpgrade},
+        ws::{Message, WebSocket, WebSocketUpgrade, close_code},
     },
     response::Response,
 };
+use futures_util::stream::SplitSink;
 use futures_util::{SinkExt, StreamExt};
 use serde::Deserialize;
-use std::sync::Arc;
-use tokio::sync::broadcast;
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, OnceLock};
+use tokio::sync::{broadcast, oneshot};
+use tokio::task::JoinHandle;
+use tokio_stream::StreamMap;
+use tokio_stream::wrappers::BroadcastStream;
+use tokio_stream::wrappers::errors::BroadcastStreamRecvError;
 use tracing::{error, info};
 use uuid::Uuid;
 
 use crate::auth;
-use crate::models::AppState;
+use crate::gateway::{
+    AckMessage, CameraUpdate, ChannelTarget, ChatMessage, GATEWAY_PROTOCOL_VERSION, GatewayCommand,
+    GatewayEvent, MIN_GATEWAY_PROTOCOL_VERSION, ScreenShareUpdate, SubscriptionTarget,
+    VoiceSpeakingUpdate, VoiceStateUpdate,
+};
+use crate::models::{AppState, PresenceStatus, VoiceState};
 use crate::permissions;
-use crate::shared::validation;
-
-#[derive(Debug, Deserialize)]
-struct WsEnvelope {
-    message_type: String,
-    #[serde(flatten)]
-    payload: serde_json::Value,
-}
+use crate::shared::{AppError, validation};
 
 /// First message sent by the client after the WebSocket handshake completes.
 /// The token travels in the message body rather than the URL so it does not
 /// appear in server logs, browser history, or proxy/CDN access logs.
+///
+/// Sending `"message_type": "resume"` with the previous `session_id` and the
+/// last `seq` received reattaches to that session and replays missed events.
+/// `protocol_version` is the gateway protocol version the client speaks;
+/// clients that omit it are treated as version 1.
 #[derive(Debug, Deserialize)]
 struct WsAuthMessage {
     token: String,
+    message_type: Option<String>,
+    session_id: Option<Uuid>,
+    seq: Option<u64>,
+    protocol_version: Option<u32>,
 }
 
-#[derive(Debug, Deserialize)]
-struct ChatMessage {
-    channel_id: Uuid,
-    content: Option<String>,
-    reply_to_id: Option<Uuid>,
-    #[serde(default)]
-    attachment_ids: Vec<Uuid>,
+impl WsAuthMessage {
+    fn resume_target(&self) -> Option<(Uuid, u64)> {
+        if self.message_type.as_deref() != Some("resume") {
+            return None;
+        }
+        Some((self.session_id?, self.seq.unwrap_or(0)))
+    }
 }
 
-#[derive(Debug, Deserialize)]
-struct ChannelTarget {
-    channel_id: Uuid,
+static MAX_SUBSCRIPTIONS: OnceLock<usize> = OnceLock::new();
+
+/// Maximum channel/thread subscriptions per connection, from
+/// `WS_MAX_SUBSCRIPTIONS` or the default.
+fn max_subscriptions() -> usize {
+    *MAX_SUBSCRIPTIONS.get_or_init(|| {
+        std::env::var("WS_MAX_SUBSCRIPTIONS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .filter(|&n: &usize| n > 0)
+            .unwrap_or(validation::DEFAULT_WS_MAX_SUBSCRIPTIONS)
+    })
 }
 
-#[derive(Debug, Deserialize)]
-struct VoiceStateUpdate {
-    channel_id: Uuid,
-    is_muted: Option<bool>,
-    is_deafened: Option<bool>,
+/// Channel and thread topics a connection receives events for, multiplexed
+/// into a single stream keyed by topic id.
+struct Subscriptions {
+    streams: StreamMap<Uuid, BroadcastStream<String>>,
+    /// The channel each topic belongs to: itself for channels, the parent
+    /// for threads. Access is always checked against the channel.
+    channels: HashMap<Uuid, Uuid>,
 }
 
-#[derive(Debug, Deserialize)]
-struct VoiceSpeakingUpdate {
-    channel_id: Uuid,
-    is_speaking: bool,
+impl Subscriptions {
+    fn new() -> Self {
+        Self {
+            streams: StreamMap::new(),
+            channels: HashMap::new(),
+        }
+    }
+
+    /// Subscribe to a topic in `channel_id`. Re-subscribing is a no-op.
+    /// Returns false if the per-connection cap has been reached.
+    fn subscribe(&mut self, state: &Arc<AppState>, topic_id: Uuid, channel_id: Uuid) -> bool {
+        if self.streams.contains_key(&topic_id) {
+            return true;
+        }
+        if self.streams.len() >= max_subscriptions() {
+            return false;
+        }
+        let rx = get_or_create_broadcast(state, topic_id).subscribe();
+        self.streams.insert(topic_id, BroadcastStream::new(rx));
+        self.channels.insert(topic_id, channel_id);
+        true
+    }
+
+    fn unsubscribe(&mut self, topic_id: Uuid) {
+        self.streams.remove(&topic_id);
+        self.channels.remove(&topic_id);
+    }
+
+    fn clear(&mut self) {
+        self.streams.clear();
+        self.channels.clear();
+    }
+
+    /// Whether the user can still see the channel a topic belongs to.
+    fn can_access(&self, state: &AppState, topic_id: Uuid, user_id: Uuid) -> bool {
+        let channel_id = self.channels.get(&topic_id).copied().unwrap_or(topic_id);
+        state.can_access_channel(channel_id, user_id)
+    }
 }
 
-#[derive(Debug, Deserialize)]
-struct ScreenShareUpdate {
-    channel_id: Uuid,
-    is_screen_sharing: bool,
+fn subscription_limit_error() -> GatewayEvent {
+    GatewayEvent::error(
+        "subscription_limit",
+        format!(
+            "You can subscribe to at most {} channels",
+            max_subscriptions()
+        ),
+    )
 }
 
-#[derive(Debug, Deserialize)]
-struct CameraUpdate {
-    channel_id: Uuid,
-    is_camera_sharing: bool,
+/// Broadcast receivers feeding one gateway session. They live in the session
+/// rather than the connection so events keep queueing while it is parked.
+struct EventStreams {
+    global_rx: broadcast::Receiver<String>,
+    user_rx: broadcast::Receiver<String>,
+    subscriptions: Subscriptions,
+}
+
+/// A gateway session. Every outbound event is stamped with a monotonically
+/// increasing `seq` and kept in a bounded replay buffer so a client that
+/// reconnects with its last seen seq can pick up where it left off.
+struct Session {
+    id: Uuid,
+    user_id: Uuid,
+    username: String,
+    seq: u64,
+    replay: VecDeque<(u64, String)>,
+    /// Set when broadcast lag dropped events while the session was parked,
+    /// so the replay buffer alone cannot close the gap.
+    gap: bool,
+    streams: EventStreams,
+}
+
+impl Session {
+    fn new(state: &Arc<AppState>, user_id: Uuid, username: String) -> Self {
+        Self {
+            id: Uuid::now_v7(),
+            user_id,
+            username,
+            seq: 0,
+            replay: VecDeque::with_capacity(validation::WS_REPLAY_BUFFER_SIZE),
+            gap: false,
+            streams: EventStreams {
+                global_rx: state.global_broadcast.subscribe(),
+                user_rx: state.subscribe_user(user_id),
+                subscriptions: Subscriptions::new(),
+            },
+        }
+    }
+
+    /// Assign the next seq to an event and record it for replay. Events are
+    /// serialized JSON objects, so the seq is spliced in as the first field
+    /// instead of re-parsing the payload.
+    fn stamp(&mut self, event: &str) -> String {
+        self.seq += 1;
+        let body = event.strip_prefix('{').unwrap_or(event);
+        let stamped = format!("{{\"seq\":{},{body}", self.seq);
+        if self.replay.len() == validation::WS_REPLAY_BUFFER_SIZE {
+            self.replay.pop_front();
+        }
+        self.replay.push_back((self.seq, stamped.clone()));
+        stamped
+    }
+
+    /// Events the client has not seen, given the last seq it received.
+    /// Returns None when the gap cannot be filled from the buffer.
+    fn events_after(&self, last_seq: u64) -> Option<Vec<String>> {
+        let oldest = self.replay.front().map_or(self.seq + 1, |(seq, _)| *seq);
+        if self.gap || last_seq > self.seq || last_seq + 1 < oldest {
+            return None;
+        }
+        Some(
+            self.replay
+                .iter()
+                .filter(|(seq, _)| *seq > last_seq)
+                .map(|(_, event)| event.clone())
+                .collect(),
+        )
+    }
+}
+
+/// A session whose connection dropped. A background task keeps buffering its
+/// events until the client resumes or the resume window expires.
+pub struct ParkedSession {
+    user_id: Uuid,
+    stop: oneshot::Sender<()>,
+    task: JoinHandle<Option<Session>>,
+}
+
+fn park_session(state: &Arc<AppState>, session: Session) {
+    let (stop, stop_rx) = oneshot::channel();
+    let session_id = session.id;
+    let user_id = session.user_id;
+    let task = tokio::spawn(buffer_parked_session(state.clone(), session, stop_rx));
+    state.ws_sessions.insert(
+        session_id,
+        ParkedSession {
+            user_id,
+            stop,
+            task,
+        },
+    );
+}
+
+/// Reclaim a parked session for a resuming connection. Returns None if the
+/// session does not exist, belongs to another user, or has already expired.
+async fn take_parked_session(state: &AppState, session_id: Uuid, user_id: Uuid) -> Option<Session> {
+    let (_, parked) = state
+        .ws_sessions
+        .remove_if(&session_id, |_, parked| parked.user_id == user_id)?;
+    let _ = parked.stop.send(());
+    parked.task.await.ok().flatten()
+}
+
+async fn buffer_parked_session(
+    state: Arc<AppState>,
+    mut session: Session,
+    mut stop_rx: oneshot::Receiver<()>,
+) -> Option<Session> {
+    let expiry = tokio::time::sleep(std::time::Duration::from_secs(
+        validation::WS_SESSION_RESUME_SECS,
+    ));
+    tokio::pin!(expiry);
+
+    loop {
+        tokio::select! {
+            stop = &mut stop_rx => {
+                if stop.is_ok() {
+                    return Some(session);
+                }
+                break;
+            }
+            _ = &mut expiry => break,
+            Some((topic_id, msg)) = session.streams.subscriptions.streams.next(), if !session.streams.subscriptions.streams.is_empty() => {
+                // Same check as a live connection, so revoked access is not
+                // replayed on resume
+                if !session.streams.subscriptions.can_access(&state, topic_id, session.user_id) {
+                    session.streams.subscriptions.unsubscribe(topic_id);
+                    continue;
+                }
+                match msg {
+                    Ok(text) => {
+                        session.stamp(&text);
+                    }
+                    Err(BroadcastStreamRecvError::Lagged(_)) => session.gap = true,
+                }
+            }
+            msg = session.streams.user_rx.recv() => {
+                match msg {
+                    Ok(text) => {
+                        session.stamp(&text);
+                    }
+                    Err(broadcast::error::RecvError::Lagged(_)) => session.gap = true,
+                    Err(broadcast::error::RecvError::Closed) => break,
+                }
+            }
+            msg = session.streams.global_rx.recv() => {
+                match msg {
+                    Ok(text) => {
+                        if is_removal_event(&text, session.user_id) {
+                            break;
+                        }
+                        session.stamp(&text);
+                    }
+                    Err(broadcast::error::RecvError::Lagged(_)) => session.gap = true,
+                    Err(broadcast::error::RecvError::Closed) => break,
+                }
+            }
+        }
+    }
+
+    state.ws_sessions.remove(&session.id);
+    info!(
+        "WebSocket session expired: {} ({})",
+        session.username, session.user_id
+    );
+    end_session(&state, session).await;
+    None
+}
+
+/// Tear down a session for good. Presence and voice state are only cleared
+/// when this was the user's last session, since another connection may
+/// still be live.
+async fn end_session(state: &AppState, session: Session) {
+    let Session {
+        user_id,
+        username,
+        streams,
+        ..
+    } = session;
+
+    // Drop the per-user broadcast channel if this was the last connection
+    drop(streams);
+    state.release_user_broadcast(user_id);
+    if state.user_broadcasts.contains_key(&user_id) {
+        return;
+    }
+
+    // Clean up presence on disconnect. The user may still be connected
+    // through another node.
+    let was_invisible = state
+        .online_users
+        .get(&user_id)
+        .is_some_and(|p| p.status == PresenceStatus::Invisible);
+    if !state.set_offline(user_id) {
+        return;
+    }
+
+    // Clean up voice states on disconnect
+    state.remove_user_from_voice(user_id).await;
+
+    if !was_invisible {
+        state.broadcast_global(GatewayEvent::UserOffline { user_id, username });
+    }
+}
+
+/// Stamp and send an event. Returns false if the connection is gone.
+async fn send_event(
+    sender: &mut SplitSink<WebSocket, Message>,
+    session: &mut Session,
+    event: &str,
+) -> bool {
+    let stamped = session.stamp(event);
+    sender.send(Message::Text(stamped.into())).await.is_ok()
+}
+
+async fn send_gateway_event(
+    sender: &mut SplitSink<WebSocket, Message>,
+    session: &mut Session,
+    event: &GatewayEvent,
+) -> bool {
+    send_event(sender, session, &event.to_json()).await
+}
+
+/// Whether a global event kicks or bans this user.
+fn is_removal_event(text: &str, user_id: Uuid) -> bool {
+    let Ok(parsed) = serde_json::from_str::<serde_json::Value>(text) else {
+        return false;
+    };
+    let event_type = parsed.get("type").and_then(|t| t.as_str());
+    let target_id = parsed
+        .get("data")
+        .and_then(|d| d.get("user_id"))
+        .and_then(|u| u.as_str());
+
+    matches!(event_type, Some("user_kicked") | Some("user_banned"))
+        && target_id == Some(&user_id.to_string())
 }
 
 pub async fn websocket_handler(
@@ -84,7 +379,7 @@ async fn websocket(socket: WebSocket, state: Arc<AppState>) {
     let (mut sender, mut receiver) = socket.split();
 
     // --- Phase 1: authenticate via first message ---
-    let claims = loop {
+    let (claims, auth_msg) = loop {
         match receiver.next().await {
             Some(Ok(Message::Text(text))) => {
                 match serde_json::from_str::<WsAuthMessage>(&text) {
@@ -92,11 +387,13 @@ async fn websocket(socket: WebSocket, state: Arc<AppState>) {
                         Ok(c) => {
                             // Reject banned users immediately
                             match permissions::check_not_banned(&state.db, c.sub).await {
-                                Ok(()) => break c,
+                                Ok(()) => break (c, auth_msg),
                                 Err(_) => {
                                     let _ = sender
                                         .send(Message::Text(
-                                            r#"{"type":"error","data":{"code":"banned","message":"You are banned"}}"#.into(),
+                                            GatewayEvent::error("banned", "You are banned")
+                                                .to_json()
+                                                .into(),
                                         ))
                                         .await;
                                     return;
@@ -106,7 +403,9 @@ async fn websocket(socket: WebSocket, state: Arc<AppState>) {
                         Err(_) => {
                             let _ = sender
                                 .send(Message::Text(
-                                    r#"{"type":"error","data":{"code":"unauthorized","message":"Invalid token"}}"#.into(),
+                                    GatewayEvent::error("unauthorized", "Invalid token")
+                                        .to_json()
+                                        .into(),
                                 ))
                                 .await;
                             return;
@@ -116,7 +415,12 @@ async fn websocket(socket: WebSocket, state: Arc<AppState>) {
                         // Not a valid auth message; reject
                         let _ = sender
                             .send(Message::Text(
-                                r#"{"type":"error","data":{"code":"unauthorized","message":"First message must be an auth message"}}"#.into(),
+                                GatewayEvent::error(
+                                    "unauthorized",
+                                    "First message must be an auth message",
+                                )
+                                .to_json()
+                                .into(),
                             ))
                             .await;
                         return;
@@ -128,11 +432,31 @@ async fn websocket(socket: WebSocket, state: Arc<AppState>) {
         }
     };
 
+    let requested_version = auth_msg
+        .protocol_version
+        .unwrap_or(MIN_GATEWAY_PROTOCOL_VERSION);
+    if requested_version < MIN_GATEWAY_PROTOCOL_VERSION {
+        let _ = sender
+            .send(Message::Text(
+                GatewayEvent::error(
+                    "unsupported_protocol_version",
+                    format!(
+                        "Protocol version {requested_version} is not supported; \
+                         minimum is {MIN_GATEWAY_PROTOCOL_VERSION}"
+                    ),
+                )
+                .to_json()
+                .into(),
+            ))
+            .await;
+        return;
+    }
+    let protocol_version = requested_version.min(GATEWAY_PROTOCOL_VERSION);
+    let resume = auth_msg.resume_target();
+
     let user_id = claims.sub;
     let username = claims.username.clone();
 
-    info!("WebSocket connected: {} ({})", username, user_id);
-
     // Look up avatar and display_name for presence
     let (avatar_url, display_name) = match crate::database::get_user_by_id(&state.db, user_id).await
     {
@@ -142,84 +466,186 @@ async fn websocket(socket: WebSocket, state: Arc<AppState>) {
         ),
         _ => (None, None),
     };
-
-    // Track online presence
-    let presence = crate::models::UserPresence {
-        user_id,
-        username: username.clone(),
-        display_name: display_name.clone(),
-        avatar_url: avatar_url.clone(),
-        connected_at: chrono::Utc::now(),
+    let presence_settings = crate::database::get_presence_settings(&state.db, user_id)
+        .await
+        .ok();
+
+    let resumed = match resume {
+        Some((session_id, last_seq)) => take_parked_session(&state, session_id, user_id)
+            .await
+            .map(|session| (session, last_seq)),
+        None => None,
     };
-    state.online_users.insert(user_id, presence);
-    state.broadcast_global(
-        "user_online",
-        serde_json::json!({ "user_id": user_id, "username": &username, "display_name": display_name, "avatar_url": avatar_url }),
-    );
 
-    let mut global_rx = state.global_broadcast.subscribe();
-    let mut current_channel: Option<Uuid> = None;
-    let mut broadcast_rx: Option<broadcast::Receiver<String>> = None;
+    let mut session = match resumed {
+        Some((mut session, last_seq)) => {
+            info!(
+                "WebSocket resumed: {} ({}) session {}",
+                username, user_id, session.id
+            );
+            match session.events_after(last_seq) {
+                Some(missed) => {
+                    for event in missed {
+                        if sender.send(Message::Text(event.into())).await.is_err() {
+                            park_session(&state, session);
+                            return;
+                        }
+                    }
+                }
+                None => {
+                    session.gap = false;
+                    let sync = GatewayEvent::SyncRequired {
+                        reason: "resume_gap",
+                        channel_id: None,
+                    };
+                    send_gateway_event(&mut sender, &mut session, &sync).await;
+                }
+            }
+            let resumed_event = GatewayEvent::Resumed {
+                session_id: session.id,
+                protocol_version,
+            };
+            send_gateway_event(&mut sender, &mut session, &resumed_event).await;
+            session
+        }
+        None => {
+            info!("WebSocket connected: {} ({})", username, user_id);
+
+            // Track online presence
+            let presence = crate::models::UserPresence {
+                user_id,
+                username: username.clone(),
+                display_name: display_name.clone(),
+                avatar_url,
+                status: presence_settings
+                    .as_ref()
+                    .map_or(PresenceStatus::Online, |s| s.effective_status(false)),
+                custom_status: presence_settings.as_ref().and_then(|s| s.custom_status()),
+                connected_at: chrono::Utc::now(),
+            };
+            state.set_online(presence.clone());
+            // Invisible users appear offline to everyone else
+            if presence.status != PresenceStatus::Invisible {
+                state.broadcast_global(GatewayEvent::UserOnline(presence));
+            }
+
+            let mut session = Session::new(&state, user_id, username.clone());
+            let ready = GatewayEvent::Ready {
+                session_id: session.id,
+                protocol_version,
+                resume_window_secs: validation::WS_SESSION_RESUME_SECS,
+            };
+            send_gateway_event(&mut sender, &mut session, &ready).await;
+            // A resume was requested but the session is gone; the client
+            // has missed events and must refetch.
+            if resume.is_some() {
+                let sync = GatewayEvent::SyncRequired {
+                    reason: "session_expired",
+                    channel_id: None,
+                };
+                send_gateway_event(&mut sender, &mut session, &sync).await;
+            }
+            session
+        }
+    };
 
     // Ping interval to keep ALB from closing idle connections (ALB default timeout = 60s)
     let mut ping_interval = tokio::time::interval(std::time::Duration::from_secs(30));
     ping_interval.tick().await; // consume the immediate first tick
 
+    // Whether the session should be kept for a later resume. False when the
+    // client closed normally or was kicked/banned.
+    let mut resumable = true;
+
     loop {
         tokio::select! {
             msg = receiver.next() => {
                 match msg {
                     Some(Ok(Message::Text(text))) => {
-                        let Ok(envelope) = serde_json::from_str::<WsEnvelope>(&text) else {
-                            continue;
+                        let command = match serde_json::from_str::<GatewayCommand>(&text) {
+                            Ok(command) => command,
+                            Err(e) => {
+                                let error = GatewayEvent::error("invalid_command", e.to_string());
+                                send_gateway_event(&mut sender, &mut session, &error).await;
+                                continue;
+                            }
                         };
 
-                        match envelope.message_type.as_str() {
-                            "message" => {
+                        match command {
+                            GatewayCommand::Message(chat_msg) => {
                                 if !state.check_message_rate_limit(user_id) {
-                                    let _ = sender.send(Message::Text(
-                                        r#"{"type":"error","data":{"code":"rate_limited","message":"You are sending messages too fast"}}"#.into()
-                                    )).await;
+                                    let error = GatewayEvent::error("rate_limited", "You are sending messages too fast");
+                                    send_gateway_event(&mut sender, &mut session, &error).await;
                                     continue;
                                 }
-                                handle_chat_message(
-                                    &state, envelope.payload, user_id,
-                                    &mut current_channel, &mut broadcast_rx,
-                                ).await;
+                                if let Some(error) = handle_chat_message(
+                                    &state, chat_msg, user_id, &mut session.streams.subscriptions,
+                                ).await {
+                                    send_gateway_event(&mut sender, &mut session, &error).await;
+                                }
+                            }
+                            GatewayCommand::Join(target) => {
+                                session.streams.subscriptions.clear();
+                                handle_subscribe(&state, target, user_id, &mut session.streams.subscriptions).await;
+                            }
+                            GatewayCommand::Subscribe(target) => {
+                                let within_cap = handle_subscribe(&state, target, user_id, &mut session.streams.subscriptions).await;
+                                if !within_cap {
+                                    send_gateway_event(&mut sender, &mut session, &subscription_limit_error()).await;
+                                }
+                            }
+                            GatewayCommand::Unsubscribe(target) => {
+                                for topic_id in target.channel_id.into_iter().chain(target.thread_id) {
+                                    session.streams.subscriptions.unsubscribe(topic_id);
+                                }
                             }
-                            "join" => {
-                                handle_join(&state, envelope.payload, &mut current_channel, &mut broadcast_rx).await;
+                            GatewayCommand::Leave(target) => {
+                                match target.channel_id {
+                                    Some(channel_id) => session.streams.subscriptions.unsubscribe(channel_id),
+                                    None => session.streams.subscriptions.clear(),
+                                }
                             }
-                            "leave" => {
-                                current_channel = None;
-                                broadcast_rx = None;
+                            GatewayCommand::Ack(ack) => {
+                                handle_ack(&state, ack, user_id).await;
                             }
-                            "typing" => {
+                            GatewayCommand::Typing(target) => {
                                 let typing_name = display_name.as_deref().unwrap_or(&username);
-                                handle_typing(&state, envelope.payload, user_id, typing_name);
+                                handle_typing(&state, target, user_id, typing_name);
+                            }
+                            GatewayCommand::VoiceStateUpdate(update) => {
+                                handle_voice_state_update(&state, update, user_id);
                             }
-                            "voice_state_update" => {
-                                handle_voice_state_update(&state, envelope.payload, user_id);
+                            GatewayCommand::VoiceSpeaking(update) => {
+                                handle_voice_speaking(&state, update, user_id);
                             }
-                            "voice_speaking" => {
-                                handle_voice_speaking(&state, envelope.payload, user_id);
+                            GatewayCommand::ScreenShareUpdate(update) => {
+                                handle_screen_share_update(&state, update, user_id);
                             }
-                            "screen_share_update" => {
-                                handle_screen_share_update(&state, envelope.payload, user_id);
+                            GatewayCommand::CameraUpdate(update) => {
+                                handle_camera_update(&state, update, user_id);
+                            }
+                            GatewayCommand::IdleUpdate(update) => {
+                                if let Err(e) = crate::services::presence::set_auto_idle(&state, user_id, update.idle).await {
+                                    tracing::debug!("WS idle update failed: {e}");
+                                }
                             }
-                            "camera_update" => {
-                                handle_camera_update(&state, envelope.payload, user_id);
+                            GatewayCommand::Ping => {
+                                send_gateway_event(&mut sender, &mut session, &GatewayEvent::Pong).await;
                             }
-                            "ping" => {
-                                let _ = sender.send(Message::Text("{\"type\":\"pong\"}".into())).await;
+                            GatewayCommand::Unknown => {
+                                let error = GatewayEvent::error("unknown_command", "Unknown message_type");
+                                send_gateway_event(&mut sender, &mut session, &error).await;
                             }
-                            _ => {}
                         }
                     }
                     Some(Ok(Message::Ping(data))) => {
                         let _ = sender.send(Message::Pong(data)).await;
                     }
-                    Some(Ok(Message::Close(_))) | None => break,
+                    Some(Ok(Message::Close(frame))) => {
+                        resumable = frame.is_none_or(|f| f.code != close_code::NORMAL);
+                        break;
+                    }
+                    None => break,
                     _ => {}
                 }
             }
@@ -230,57 +656,64 @@ async fn websocket(socket: WebSocket, state: Arc<AppState>) {
                 }
             }
 
-            msg = async {
-                match &mut broadcast_rx {
-                    Some(rx) => rx.recv().await,
-                    None => std::future::pending().await,
+            // Closed topics (e.g. deleted channels) end their stream and are
+            // dropped from the map automatically.
+            Some((topic_id, msg)) = session.streams.subscriptions.streams.next(), if !session.streams.subscriptions.streams.is_empty() => {
+                // Access may have been revoked by a permission overwrite
+                if !session.streams.subscriptions.can_access(&state, topic_id, user_id) {
+                    session.streams.subscriptions.unsubscribe(topic_id);
+                    continue;
                 }
-            } => {
                 match msg {
                     Ok(text) => {
-                        if sender.send(Message::Text(text.into())).await.is_err() {
+                        if !send_event(&mut sender, &mut session, &text).await {
                             break;
                         }
                     }
-                    Err(broadcast::error::RecvError::Lagged(n)) => {
-                        tracing::warn!("Channel broadcast: client {} lagged by {} messages", user_id, n);
-                        let _ = sender.send(Message::Text(
-                            r#"{"type":"sync_required","data":{"reason":"lagged"}}"#.into()
-                        )).await;
+                    Err(BroadcastStreamRecvError::Lagged(n)) => {
+                        tracing::warn!("Channel broadcast: client {} lagged by {} messages on {}", user_id, n, topic_id);
+                        let sync = GatewayEvent::SyncRequired {
+                            reason: "lagged",
+                            channel_id: Some(topic_id),
+                        };
+                        send_gateway_event(&mut sender, &mut session, &sync).await;
                     }
-                    Err(broadcast::error::RecvError::Closed) => {
-                        broadcast_rx = None;
+                }
+            }
+
+            msg = session.streams.user_rx.recv() => {
+                match msg {
+                    Ok(text) => {
+                        if !send_event(&mut sender, &mut session, &text).await {
+                            break;
+                        }
                     }
+                    Err(broadcast::error::RecvError::Lagged(n)) => {
+                        tracing::warn!("User broadcast: client {} lagged by {} messages", user_id, n);
+                        let sync = GatewayEvent::SyncRequired { reason: "lagged", channel_id: None };
+                        send_gateway_event(&mut sender, &mut session, &sync).await;
+                    }
+                    Err(broadcast::error::RecvError::Closed) => break,
                 }
             }
 
-            msg = global_rx.recv() => {
+            msg = session.streams.global_rx.recv() => {
                 match msg {
-                    Ok(ref text) => {
-                        // Check if this is a kick/ban targeting us
-                        if let Ok(parsed) = serde_json::from_str::<serde_json::Value>(text) {
-                            let event_type = parsed.get("type").and_then(|t| t.as_str());
-                            let target_id = parsed
-                                .get("data")
-                                .and_then(|d| d.get("user_id"))
-                                .and_then(|u| u.as_str());
-
-                            if matches!(event_type, Some("user_kicked") | Some("user_banned"))
-                                && target_id == Some(&user_id.to_string())
-                            {
-                                let _ = sender.send(Message::Text(text.clone().into())).await;
-                                break;
-                            }
+                    Ok(text) => {
+                        // Kicked or banned: deliver the event, then end the session for good
+                        if is_removal_event(&text, user_id) {
+                            send_event(&mut sender, &mut session, &text).await;
+                            resumable = false;
+                            break;
                         }
-                        if sender.send(Message::Text(text.clone().into())).await.is_err() {
+                        if !send_event(&mut sender, &mut session, &text).await {
                             break;
                         }
                     }
                     Err(broadcast::error::RecvError::Lagged(n)) => {
                         tracing::warn!("Global broadcast: client {} lagged by {} messages", user_id, n);
-                        let _ = sender.send(Message::Text(
-                            r#"{"type":"sync_required","data":{"reason":"lagged"}}"#.into()
-                        )).await;
+                        let sync = GatewayEvent::SyncRequired { reason: "lagged", channel_id: None };
+                        send_gateway_event(&mut sender, &mut session, &sync).await;
                     }
                     Err(broadcast::error::RecvError::Closed) => break,
                 }
@@ -288,41 +721,48 @@ async fn websocket(socket: WebSocket, state: Arc<AppState>) {
         }
     }
 
-    // Clean up voice states on disconnect
-    state.remove_user_from_voice(user_id).await;
-
-    // Clean up presence on disconnect
-    state.online_users.remove(&user_id);
-    state.broadcast_global(
-        "user_offline",
-        serde_json::json!({ "user_id": user_id, "username": &username }),
-    );
-
-    info!("WebSocket disconnected: {} ({})", username, user_id);
+    if resumable {
+        info!(
+            "WebSocket dropped: {} ({}), session {} kept for resume",
+            username, user_id, session.id
+        );
+        park_session(&state, session);
+    } else {
+        end_session(&state, session).await;
+        info!("WebSocket disconnected: {} ({})", username, user_id);
+    }
 }
 
+/// Returns an error event for the client when a channel rule (slowmode or
+/// announcement mode) rejects the message.
 async fn handle_chat_message(
     state: &Arc<AppState>,
-    payload: serde_json::Value,
+    chat_msg: ChatMessage,
     user_id: Uuid,
-    current_channel: &mut Option<Uuid>,
-    broadcast_rx: &mut Option<broadcast::Receiver<String>>,
-) {
-    let Ok(chat_msg) = serde_json::from_value::<ChatMessage>(payload) else {
-        return;
-    };
-
+    subscriptions: &mut Subscriptions,
+) -> Option<GatewayEvent> {
     // Use in-memory cache for ban/mute checks to avoid per-message DB queries.
     // The cache is kept in sync by moderation event handlers in AppState.
     if state.is_banned_cached(user_id) || state.is_muted_cached(user_id) {
-        return;
+        return None;
     }
 
-    // Auto-subscribe to channel if not already
-    if *current_channel != Some(chat_msg.channel_id) {
-        *current_channel = Some(chat_msg.channel_id);
-        let tx = get_or_create_broadcast(state, chat_msg.channel_id);
-        *broadcast_rx = Some(tx.subscribe());
+    if !state.can_access_channel(chat_msg.channel_id, user_id) {
+        return None;
+    }
+
+    match crate::services::message::check_channel_rules(state, chat_msg.channel_id, user_id).await {
+        Ok(()) => {}
+        Err(AppError::TooManyRequests(message)) => {
+            return Some(GatewayEvent::error("slowmode", message));
+        }
+        Err(AppError::Forbidden(message)) => {
+            return Some(GatewayEvent::error("forbidden", message));
+        }
+        Err(e) => {
+            error!("Failed to check channel rules: {e}");
+            return None;
+        }
     }
 
     match crate::services::message::create_message(
@@ -330,68 +770,101 @@ async fn handle_chat_message(
         &state.db,
         crate::services::message::CreateMessageParams {
             user_id,
+            author: crate::services::message::MessageAuthor::User,
             channel_id: chat_msg.channel_id,
             content: chat_msg.content,
             reply_to_id: chat_msg.reply_to_id,
+            thread_id: chat_msg.thread_id,
             attachment_ids: chat_msg.attachment_ids,
+            poll: None,
             validate_reply_channel: true,
         },
     )
     .await
     {
         Ok(result) => {
-            state.broadcast_channel(
-                result.channel_id,
-                "message",
-                serde_json::json!(result.message),
-            );
+            // Auto-subscribe to the channel (or thread) the post landed in,
+            // if there is room under the cap. Only once it succeeded, since
+            // only then is the thread known to belong to the channel.
+            subscriptions.subscribe(state, result.message.topic_id(), result.channel_id);
+            crate::services::message::broadcast_new_message(state, &result);
         }
         Err(e) => {
+            state.release_slowmode(chat_msg.channel_id, user_id);
             error!("Failed to create message: {e}");
         }
     }
+    None
 }
 
-async fn handle_join(
+/// Subscribe to a channel and/or thread after verifying it exists and the
+/// user can see it. Returns false only when the subscription cap was hit.
+async fn handle_subscribe(
     state: &Arc<AppState>,
-    payload: serde_json::Value,
-    current_channel: &mut Option<Uuid>,
-    broadcast_rx: &mut Option<broadcast::Receiver<String>>,
-) {
-    let Ok(target) = serde_json::from_value::<ChannelTarget>(payload) else {
-        return;
-    };
+    target: SubscriptionTarget,
+    user_id: Uuid,
+    subscriptions: &mut Subscriptions,
+) -> bool {
+    if let Some(channel_id) = target.channel_id {
+        if !state.can_access_channel(channel_id, user_id) {
+            return true;
+        }
 
-    // Verify the channel actually exists before subscribing
-    match crate::database::get_channel_by_id(&state.db, target.channel_id).await {
-        Ok(Some(_)) => {}
-        Ok(None) => return,
-        Err(e) => {
-            tracing::warn!(
-                "WS join: DB error checking channel {}: {e}",
-                target.channel_id
-            );
-            return;
+        // Verify the channel actually exists before subscribing
+        match crate::database::get_channel_by_id(&state.db, channel_id).await {
+            Ok(Some(_)) => {}
+            Ok(None) => return true,
+            Err(e) => {
+                tracing::warn!("WS subscribe: DB error checking channel {channel_id}: {e}");
+                return true;
+            }
+        }
+
+        if !subscriptions.subscribe(state, channel_id, channel_id) {
+            return false;
+        }
+    }
+
+    if let Some(thread_id) = target.thread_id {
+        let channel_id = match crate::database::get_thread_by_id(&state.db, thread_id).await {
+            Ok(Some(thread)) if state.can_access_channel(thread.channel_id, user_id) => {
+                thread.channel_id
+            }
+            Ok(_) => return true,
+            Err(e) => {
+                tracing::warn!("WS subscribe: DB error checking thread {thread_id}: {e}");
+                return true;
+            }
+        };
+
+        if !subscriptions.subscribe(state, thread_id, channel_id) {
+            return false;
         }
     }
 
-    *current_channel = Some(target.channel_id);
-    let tx = get_or_create_broadcast(state, target.channel_id);
-    *broadcast_rx = Some(tx.subscribe());
+    true
 }
 
-fn handle_typing(state: &Arc<AppState>, payload: serde_json::Value, user_id: Uuid, username: &str) {
-    let Ok(target) = serde_json::from_value::<ChannelTarget>(payload) else {
+async fn handle_ack(state: &Arc<AppState>, ack: AckMessage, user_id: Uuid) {
+    if let Err(e) =
+        crate::services::read_state::ack_channel(state, user_id, ack.channel_id, ack.message_id)
+            .await
+    {
+        tracing::debug!("WS ack failed for channel {}: {e}", ack.channel_id);
+    }
+}
+
+fn handle_typing(state: &Arc<AppState>, target: ChannelTarget, user_id: Uuid, username: &str) {
+    if !state.can_access_channel(target.channel_id, user_id) {
         return;
-    };
+    }
     state.broadcast_channel(
         target.channel_id,
-        "typing",
-        serde_json::json!({
-            "user_id": user_id,
-            "username": username,
-            "channel_id": target.channel_id,
-        }),
+        GatewayEvent::Typing {
+            user_id,
+            username: username.to_string(),
+            channel_id: target.channel_id,
+        },
     );
 }
 
@@ -399,8 +872,8 @@ fn modify_voice_state(
     state: &Arc<AppState>,
     channel_id: Uuid,
     user_id: Uuid,
-    event_type: &str,
-    update_fn: impl FnOnce(&mut crate::models::VoiceState),
+    make_event: fn(VoiceState) -> GatewayEvent,
+    update_fn: impl FnOnce(&mut VoiceState),
 ) {
     let Some(channel_users) = state.voice_states.get(&channel_id) else {
         return;
@@ -414,18 +887,16 @@ fn modify_voice_state(
     drop(voice_state);
     drop(channel_users);
 
-    state.broadcast_global(event_type, serde_json::json!(updated));
+    state.set_voice_state(updated.clone());
+    state.broadcast_for_channel(channel_id, make_event(updated));
 }
 
-fn handle_voice_state_update(state: &Arc<AppState>, payload: serde_json::Value, user_id: Uuid) {
-    let Ok(update) = serde_json::from_value::<VoiceStateUpdate>(payload) else {
-        return;
-    };
+fn handle_voice_state_update(state: &Arc<AppState>, update: VoiceStateUpdate, user_id: Uuid) {
     modify_voice_state(
         state,
         update.channel_id,
         user_id,
-        "voice_state_updated",
+        GatewayEvent::VoiceStateUpdated,
         |vs| {
             if let Some(muted) = update.is_muted {
                 vs.is_muted = muted;
@@ -437,10 +908,7 @@ fn handle_voice_state_update(state: &Arc<AppState>, payload: serde_json::Value,
     );
 }
 
-fn handle_voice_speaking(state: &Arc<AppState>, payload: serde_json::Value, user_id: Uuid) {
-    let Ok(update) = serde_json::from_value::<VoiceSpeakingUpdate>(payload) else {
-        return;
-    };
+fn handle_voice_speaking(state: &Arc<AppState>, update: VoiceSpeakingUpdate, user_id: Uuid) {
     // Verify user is actually in this voice channel before broadcasting
     let in_channel = state
         .voice_states
@@ -449,38 +917,38 @@ fn handle_voice_speaking(state: &Arc<AppState>, payload: serde_json::Value, user
     if !in_channel {
         return;
     }
-    state.broadcast_global(
-        "voice_speaking",
-        serde_json::json!({
-            "user_id": user_id,
-            "channel_id": update.channel_id,
-            "is_speaking": update.is_speaking,
-        }),
+    state.broadcast_for_channel(
+        update.channel_id,
+        GatewayEvent::VoiceSpeaking {
+            user_id,
+            channel_id: update.channel_id,
+            is_speaking: update.is_speaking,
+        },
     );
 }
 
-fn handle_screen_share_update(state: &Arc<AppState>, payload: serde_json::Value, user_id: Uuid) {
-    let Ok(update) = serde_json::from_value::<ScreenShareUpdate>(payload) else {
-        return;
-    };
+fn handle_screen_share_update(state: &Arc<AppState>, update: ScreenShareUpdate, user_id: Uuid) {
     modify_voice_state(
         state,
         update.channel_id,
         user_id,
-        "screen_share_updated",
+        GatewayEvent::ScreenShareUpdated,
         |vs| {
             vs.is_screen_sharing = update.is_screen_sharing;
         },
     );
 }
 
-fn handle_camera_update(state: &Arc<AppState>, payload: serde_json::Value, user_id: Uuid) {
-    let Ok(update) = serde_json::from_value::<CameraUpdate>(payload) else {
-        return;
-    };
-    modify_voice_state(state, update.channel_id, user_id, "camera_updated", |vs| {
-        vs.is_camera_sharing = update.is_camera_sharing;
-    });
+fn handle_camera_update(state: &Arc<AppState>, update: CameraUpdate, user_id: Uuid) {
+    modify_voice_state(
+        state,
+        update.channel_id,
+        user_id,
+        GatewayEvent::CameraUpdated,
+        |vs| {
+            vs.is_camera_sharing = update.is_camera_sharing;
+        },
+    );
 }
 
 fn get_or_create_broadcast(state: &Arc<AppState>, channel_id: Uuid) -> broadcast::Sender<String> {
@@ -5,11 +5,14 @@ import { getWs } from "../ws";
 import { chatState } from "../stores/chatState.svelte";
 import { serverState } from "../stores/serverState.svelte";
 import { authState } from "../stores/authState.svelte";
-import type { Message } from "../api";
+import type { CreatePollRequest, Message, Poll } from "../api";
 
 const TYPING_DEBOUNCE_MS = 3000;
+// Matches the server's undo window, less a little for latency
+const UNDO_DELETE_MS = 14000;
 let lastTypingSent = 0;
 let _sendErrorTimeout: ReturnType<typeof setTimeout> | null = null;
+let _undoDeleteTimeout: ReturnType<typeof setTimeout> | null = null;
 
 export function resetChatActionState() {
   lastTypingSent = 0;
@@ -17,6 +20,10 @@ export function resetChatActionState() {
     clearTimeout(_sendErrorTimeout);
     _sendErrorTimeout = null;
   }
+  if (_undoDeleteTimeout) {
+    clearTimeout(_undoDeleteTimeout);
+    _undoDeleteTimeout = null;
+  }
 }
 
 export function populateAvatarsFromMessages(msgs: Message[]) {
@@ -33,6 +40,8 @@ export async function selectChannel(channelId: string, channelName: string) {
   chatState.selectedChannelName = channelName;
   chatState.messages = [];
   chatState.hasMoreMessages = true;
+  chatState.hasNewerMessages = false;
+  chatState.highlightedMessageId = null;
   chatState.replyingTo = null;
   chatState.typingUsers = {};
 
@@ -46,7 +55,8 @@ export async function selectChannel(channelId: string, channelName: string) {
     });
 
   try {
-    const msgs = await API.getMessages(channelId, 50);
+    const page = await API.getMessages(channelId, 50);
+    const msgs = page.messages;
     populateAvatarsFromMessages(msgs);
     // Guard: channel may have changed during the async fetch
     if (chatState.selectedChannelId !== channelId) return true;
@@ -58,7 +68,7 @@ export async function selectChannel(channelId: string, channelName: string) {
         new Date(a.timestamp).getTime() - new Date(b.timestamp).getTime(),
     );
     chatState.messages = merged;
-    chatState.hasMoreMessages = msgs.length >= 50;
+    chatState.hasMoreMessages = page.has_more_before;
     return true;
   } catch (error) {
     console.error("Failed to load messages:", error);
@@ -78,25 +88,21 @@ export async function loadOlderMessages(scrollToPreserve: () => () => void) {
 
   chatState.loadingMore = true;
   const channelId = chatState.selectedChannelId;
-  const oldestTimestamp = chatState.messages[0]?.timestamp;
+  const oldestId = chatState.messages[0].id;
 
   try {
-    const olderMessages = await API.getMessages(channelId, 50, oldestTimestamp);
-    populateAvatarsFromMessages(olderMessages);
-    if (olderMessages.length > 0) {
+    const page = await API.getMessages(channelId, 50, { before: oldestId });
+    populateAvatarsFromMessages(page.messages);
+    // Guard: channel may have changed during the async fetch
+    if (chatState.selectedChannelId !== channelId) return;
+    if (page.messages.length > 0) {
       const restoreScroll = scrollToPreserve();
-      // Guard: channel may have changed during the async fetch
-      if (chatState.selectedChannelId !== channelId) return;
       const existingIds = new Set(chatState.messages.map((m) => m.id));
-      const uniqueOlder = olderMessages.filter((m) => !existingIds.has(m.id));
+      const uniqueOlder = page.messages.filter((m) => !existingIds.has(m.id));
       chatState.messages = [...uniqueOlder, ...chatState.messages];
-      chatState.hasMoreMessages = olderMessages.length >= 50;
       restoreScroll();
-    } else {
-      if (chatState.selectedChannelId === channelId) {
-        chatState.hasMoreMessages = false;
-      }
     }
+    chatState.hasMoreMessages = page.has_more_before;
   } catch (error) {
     console.error("Failed to load older messages:", error);
   } finally {
@@ -104,6 +110,107 @@ export async function loadOlderMessages(scrollToPreserve: () => () => void) {
   }
 }
 
+export async function loadNewerMessages() {
+  if (
+    chatState.loadingMore ||
+    !chatState.hasNewerMessages ||
+    !chatState.selectedChannelId ||
+    chatState.messages.length === 0
+  )
+    return;
+
+  chatState.loadingMore = true;
+  const channelId = chatState.selectedChannelId;
+  const newestId = chatState.messages[chatState.messages.length - 1].id;
+
+  try {
+    const page = await API.getMessages(channelId, 50, { after: newestId });
+    populateAvatarsFromMessages(page.messages);
+    if (chatState.selectedChannelId !== channelId) return;
+    const existingIds = new Set(chatState.messages.map((m) => m.id));
+    const uniqueNewer = page.messages.filter((m) => !existingIds.has(m.id));
+    chatState.messages = [...chatState.messages, ...uniqueNewer];
+    chatState.hasNewerMessages = page.has_more_after;
+  } catch (error) {
+    console.error("Failed to load newer messages:", error);
+  } finally {
+    chatState.loadingMore = false;
+  }
+}
+
+/** Load the history around a message in the selected channel and highlight it. */
+export async function jumpToMessage(messageId: string) {
+  const channelId = chatState.selectedChannelId;
+  if (!channelId) return;
+  if (chatState.messages.some((m) => m.id === messageId)) {
+    chatState.highlightedMessageId = messageId;
+    return;
+  }
+
+  chatState.loadingMore = true;
+  try {
+    const page = await API.getMessages(channelId, 50, { around: messageId });
+    populateAvatarsFromMessages(page.messages);
+    if (chatState.selectedChannelId !== channelId) return;
+    chatState.messages = page.messages;
+    chatState.hasMoreMessages = page.has_more_before;
+    chatState.hasNewerMessages = page.has_more_after;
+    chatState.highlightedMessageId = messageId;
+  } catch (error) {
+    console.error("Failed to jump to message:", error);
+  } finally {
+    chatState.loadingMore = false;
+  }
+}
+
+/**
+ * Open a permalink: switch to the message's channel and show the history
+ * around it. Thread replies open their channel, as threads have no view of
+ * their own yet. Returns the channel id, or null if the message cannot be
+ * shown.
+ */
+export async function openMessageLink(messageId: string) {
+  try {
+    const { message, context } = await API.getMessageLink(messageId);
+    const channel = serverState.channels.find(
+      (c) => c.id === message.channel_id,
+    );
+    if (!channel) return null;
+    if (chatState.selectedChannelId !== channel.id) {
+      await selectChannel(channel.id, channel.name);
+    }
+    if (message.thread_id || chatState.selectedChannelId !== channel.id) {
+      return channel.id;
+    }
+    populateAvatarsFromMessages(context.messages);
+    chatState.messages = context.messages;
+    chatState.hasMoreMessages = context.has_more_before;
+    chatState.hasNewerMessages = context.has_more_after;
+    chatState.highlightedMessageId = messageId;
+    return channel.id;
+  } catch (error) {
+    console.error("Failed to open message link:", error);
+    return null;
+  }
+}
+
+/** Leave older history and show the newest messages again. */
+export async function jumpToPresent() {
+  const channelId = chatState.selectedChannelId;
+  if (!channelId) return;
+  try {
+    const page = await API.getMessages(channelId, 50);
+    populateAvatarsFromMessages(page.messages);
+    if (chatState.selectedChannelId !== channelId) return;
+    chatState.messages = page.messages;
+    chatState.hasMoreMessages = page.has_more_before;
+    chatState.hasNewerMessages = false;
+    chatState.highlightedMessageId = null;
+  } catch (error) {
+    console.error("Failed to load messages:", error);
+  }
+}
+
 export function sendMessage(text: string, attachmentIds?: string[]) {
   const { selectedChannelId, replyingTo } = chatState;
   const currentUser = authState.user;
@@ -117,6 +224,8 @@ export function sendMessage(text: string, attachmentIds?: string[]) {
     if (sent) {
       chatState.replyingTo = null;
       chatState.sendError = false;
+      // Our message lands at the end of history, so show it
+      if (chatState.hasNewerMessages) jumpToPresent();
     } else {
       chatState.sendError = true;
       if (_sendErrorTimeout) clearTimeout(_sendErrorTimeout);
@@ -128,6 +237,52 @@ export function sendMessage(text: string, attachmentIds?: string[]) {
   }
 }
 
+export async function scheduleMessage(
+  text: string,
+  sendAt: Date,
+  attachmentIds?: string[],
+): Promise<void> {
+  const { selectedChannelId, replyingTo } = chatState;
+  if (!selectedChannelId) return;
+  await API.scheduleMessage(selectedChannelId, {
+    send_at: sendAt.toISOString(),
+    ...(text ? { content: text } : {}),
+    ...(attachmentIds?.length ? { attachment_ids: attachmentIds } : {}),
+    ...(replyingTo ? { reply_to_id: replyingTo.id } : {}),
+  });
+  chatState.replyingTo = null;
+  chatState.scheduledVersion++;
+}
+
+export async function createPoll(poll: CreatePollRequest): Promise<void> {
+  const { selectedChannelId, replyingTo } = chatState;
+  if (!selectedChannelId) return;
+  await API.sendMessage(selectedChannelId, {
+    poll,
+    ...(replyingTo ? { reply_to_id: replyingTo.id } : {}),
+  });
+  chatState.replyingTo = null;
+}
+
+function setMessagePoll(messageId: string, poll: Poll) {
+  chatState.messages = chatState.messages.map((m) =>
+    m.id === messageId ? { ...m, poll } : m,
+  );
+}
+
+export async function togglePollVote(message: Message, optionId: string) {
+  const option = message.poll?.options.find((o) => o.id === optionId);
+  if (!option) return;
+  try {
+    const poll = option.voted
+      ? await API.unvotePoll(message.channel_id, message.id, optionId)
+      : await API.votePoll(message.channel_id, message.id, optionId);
+    setMessagePoll(message.id, poll);
+  } catch (error) {
+    console.error("Failed to update vote:", error);
+  }
+}
+
 export function sendTyping() {
   const now = Date.now();
   const { selectedChannelId } = chatState;
@@ -168,10 +323,49 @@ export async function deleteMessage(messageId: string) {
   if (!confirm("Delete this message?")) return;
   const { selectedChannelId } = chatState;
   if (!selectedChannelId) return;
+  const message = chatState.messages.find((m) => m.id === messageId);
   try {
     await API.deleteMessage(selectedChannelId, messageId);
   } catch (error) {
     console.error("Failed to delete message:", error);
+    return;
+  }
+  if (message && message.author_id === authState.user?.id) {
+    chatState.undoDelete = { channelId: selectedChannelId, messageId };
+    if (_undoDeleteTimeout) clearTimeout(_undoDeleteTimeout);
+    _undoDeleteTimeout = setTimeout(() => {
+      chatState.undoDelete = null;
+      _undoDeleteTimeout = null;
+    }, UNDO_DELETE_MS);
+  }
+}
+
+export async function undoDeleteMessage() {
+  const pending = chatState.undoDelete;
+  if (!pending) return;
+  chatState.undoDelete = null;
+  if (_undoDeleteTimeout) {
+    clearTimeout(_undoDeleteTimeout);
+    _undoDeleteTimeout = null;
+  }
+  try {
+    await API.restoreMessage(pending.channelId, pending.messageId);
+  } catch (error) {
+    console.error("Failed to restore message:", error);
+  }
+}
+
+export async function togglePin(message: Message) {
+  const { selectedChannelId } = chatState;
+  if (!selectedChannelId) return;
+  try {
+    if (message.pinned_at) {
+      await API.unpinMessage(selectedChannelId, message.id);
+    } else {
+      await API.pinMessage(selectedChannelId, message.id);
+    }
+  } catch (error) {
+    console.error("Failed to toggle pin:", error);
   }
 }
 
@@ -199,9 +393,13 @@ export async function toggleReaction(messageId: string, emoji: string) {
   }
 }
 
-export async function createChannel(name: string, type: "text" | "voice") {
+export async function createChannel(
+  name: string,
+  type: "text" | "voice",
+  categoryId: string | null = null,
+) {
   try {
-    await API.createChannel(name, type);
+    await API.createChannel(name, type, categoryId);
   } catch (error) {
     console.error("Failed to create channel:", error);
   }
@@ -224,6 +422,76 @@ export async function deleteChannel(channelId: string) {
   }
 }
 
+export async function createCategory(name: string) {
+  try {
+    await API.createCategory(name);
+  } catch (error) {
+    console.error("Failed to create category:", error);
+  }
+}
+
+export async function renameCategory(categoryId: string, name: string) {
+  try {
+    await API.updateCategory(categoryId, name);
+  } catch (error) {
+    console.error("Failed to rename category:", error);
+  }
+}
+
+/** Delete a category, moving its channels out of it unless `deleteChannels`. */
+export async function deleteCategory(
+  categoryId: string,
+  deleteChannels: boolean,
+) {
+  try {
+    await API.deleteCategory(categoryId, deleteChannels);
+  } catch (error) {
+    console.error("Failed to delete category:", error);
+  }
+}
+
+/** Move a channel into a category (null = uncategorized), before `beforeId` or at the end. */
+export async function moveChannel(
+  channelId: string,
+  categoryId: string | null,
+  beforeId: string | null,
+) {
+  if (channelId === beforeId) return;
+  const ids = serverState.channels
+    .filter((c) => c.category_id === categoryId && c.id !== channelId)
+    .sort((a, b) => a.position - b.position)
+    .map((c) => c.id);
+  const index = beforeId ? ids.indexOf(beforeId) : -1;
+  ids.splice(index < 0 ? ids.length : index, 0, channelId);
+  try {
+    await API.reorderChannels(
+      [],
+      ids.map((id, position) => ({ id, category_id: categoryId, position })),
+    );
+  } catch (error) {
+    console.error("Failed to move channel:", error);
+  }
+}
+
+/** Move a category before `beforeId`. */
+export async function moveCategory(categoryId: string, beforeId: string) {
+  if (categoryId === beforeId) return;
+  const ids = [...serverState.categories]
+    .sort((a, b) => a.position - b.position)
+    .map((c) => c.id)
+    .filter((id) => id !== categoryId);
+  const index = ids.indexOf(beforeId);
+  ids.splice(index < 0 ? ids.length : index, 0, categoryId);
+  try {
+    await API.reorderChannels(
+      ids.map((id, position) => ({ id, position })),
+      [],
+    );
+  } catch (error) {
+    console.error("Failed to move category:", error);
+  }
+}
+
 export function updateEditMessageContent(content: string) {
   chatState.editMessageContent = content;
 }
@@ -6,6 +6,7 @@ import AuthService from "../auth";
 import { voiceManager } from "../voice";
 import { isTauri, activeServer } from "../serverManager";
 import { getWs, resetWs } from "../ws";
+import { startIdleTracking } from "../idle";
 import { voiceStore } from "../stores/voiceStore.svelte";
 import { serverState } from "../stores/serverState.svelte";
 import { chatState } from "../stores/chatState.svelte";
@@ -74,19 +75,26 @@ async function _connectToServer() {
   chatState.selectedChannelId = "";
   chatState.selectedChannelName = "";
   chatState.hasMoreMessages = true;
+  chatState.hasNewerMessages = false;
   chatState.loadingMore = false;
+  chatState.highlightedMessageId = null;
   chatState.editingMessageId = null;
   chatState.editMessageContent = "";
   chatState.replyingTo = null;
   chatState.typingUsers = {};
   chatState.rateLimitWarning = false;
+  chatState.channelRuleWarning = "";
   chatState.sendError = false;
+  chatState.undoDelete = null;
 
   serverState.channels = [];
+  serverState.categories = [];
   serverState.onlineUsers = [];
   serverState.members = [];
   serverState.userAvatars = {};
   serverState.userRolesMap = {};
+  serverState.roles = [];
+  serverState.myPermissions = 0;
   serverState.serverName = "";
   serverState.backendVersion = "";
   serverState.customEmojis = [];
@@ -125,12 +133,15 @@ async function _connectToServer() {
       avatarMap[currentUser.id] = API.getAvatarUrl(currentUser.id);
 
     serverState.channels = init.channels;
+    serverState.categories = init.categories;
     serverState.onlineUsers = init.online_users;
     serverState.members = init.members;
     serverState.userAvatars = avatarMap;
     serverState.userRolesMap = Object.fromEntries(
-      init.members.map((m) => [m.id, m.role]),
+      init.members.map((m) => [m.id, m.role_ids]),
     );
+    serverState.roles = init.roles;
+    serverState.myPermissions = init.permissions;
     serverState.serverName = init.server_name;
     serverState.backendVersion = init.version;
 
@@ -155,6 +166,7 @@ async function _connectToServer() {
     // will be buffered via WS and deduplicated in selectChannel.
     await getWs().connect();
     syncVoiceState();
+    startIdleTracking();
 
     // Ensure current user appears in onlineUsers (they miss their own
     // user_online broadcast because the WS subscribe happens after it).
@@ -169,6 +181,7 @@ async function _connectToServer() {
           username: currentUser.username,
           display_name: currentUser.display_name,
           avatar_url: currentUser.avatar_url,
+          status: "online",
           connected_at: new Date().toISOString(),
         },
       ];
@@ -1,8 +1,12 @@
 import { goto } from "$app/navigation";
-import { API, type MemberInfo, type WsIncomingMessage } from "../api";
+import { API, type WsIncomingMessage } from "../api";
 import { playSound } from "../sounds";
 import { playSoundboardAudio } from "../soundboardAudio";
-import AuthService from "../auth";
+import AuthService, {
+  Permission,
+  computePermissions,
+  hasPermission,
+} from "../auth";
 import { voiceManager } from "../voice";
 import { getWs } from "../ws";
 import { voiceStore } from "../stores/voiceStore.svelte";
@@ -15,6 +19,7 @@ import { selectChannel, populateAvatarsFromMessages } from "./chat";
 const TYPING_DISPLAY_MS = 5000;
 
 let _rateLimitTimeout: ReturnType<typeof setTimeout> | null = null;
+let _channelRuleTimeout: ReturnType<typeof setTimeout> | null = null;
 let _activeHandler: ((data: WsIncomingMessage) => void) | null = null;
 
 export function teardownWsHandlers() {
@@ -22,6 +27,10 @@ export function teardownWsHandlers() {
     clearTimeout(_rateLimitTimeout);
     _rateLimitTimeout = null;
   }
+  if (_channelRuleTimeout) {
+    clearTimeout(_channelRuleTimeout);
+    _channelRuleTimeout = null;
+  }
   if (_activeHandler) {
     getWs().offMessage(_activeHandler);
     _activeHandler = null;
@@ -88,6 +97,28 @@ function updateMessageReaction(
   });
 }
 
+// Moderators keep deleted messages as tombstones
+function markMessagesDeleted(ids: string[], deletedAt: string) {
+  const deleted = new Set(ids);
+  if (hasPermission(serverState.myPermissions, Permission.MANAGE_MESSAGES)) {
+    chatState.messages = chatState.messages.map((m) =>
+      deleted.has(m.id) ? { ...m, deleted_at: deletedAt } : m,
+    );
+  } else {
+    chatState.messages = chatState.messages.filter((m) => !deleted.has(m.id));
+  }
+}
+
+function refreshMyPermissions() {
+  const currentUser = authState.user;
+  if (!currentUser) return;
+  serverState.myPermissions = computePermissions(
+    serverState.roles,
+    serverState.userRolesMap[currentUser.id] ?? [],
+    currentUser.is_owner,
+  );
+}
+
 export function setupWsHandlers() {
   if (_activeHandler) {
     getWs().offMessage(_activeHandler);
@@ -105,13 +136,20 @@ export function setupWsHandlers() {
           const { [authorId]: _, ...typingUsers } = chatState.typingUsers;
           chatState.typingUsers = typingUsers;
         }
-        chatState.messages = [...chatState.messages, msg];
+        // Viewing older history: the new message arrives when we page forward
+        if (!chatState.hasNewerMessages) {
+          chatState.messages = [...chatState.messages, msg];
+        }
       }
     }
 
     if (data.type === "channel_created") {
       const ch = data.data;
-      serverState.channels = [...serverState.channels, ch];
+      // Also sent when an overwrite grants access to an existing channel
+      serverState.channels = [
+        ...serverState.channels.filter((c) => c.id !== ch.id),
+        ch,
+      ];
     }
     if (data.type === "channel_updated") {
       const ch = data.data;
@@ -139,14 +177,39 @@ export function setupWsHandlers() {
       }
     }
 
-    if (data.type === "user_online") {
-      const presence = data.data;
-      const exists = serverState.onlineUsers.find(
-        (u) => u.user_id === presence.user_id,
+    if (data.type === "category_created") {
+      serverState.categories = [...serverState.categories, data.data];
+    }
+    if (data.type === "category_updated") {
+      serverState.categories = serverState.categories.map((c) =>
+        c.id === data.data.id ? data.data : c,
       );
-      if (!exists) {
-        serverState.onlineUsers = [...serverState.onlineUsers, presence];
-      }
+    }
+    if (data.type === "category_deleted") {
+      const { id } = data.data;
+      serverState.categories = serverState.categories.filter(
+        (c) => c.id !== id,
+      );
+    }
+    if (data.type === "channels_reordered") {
+      const positions = new Map(data.data.channels.map((p) => [p.id, p]));
+      serverState.categories = data.data.categories;
+      serverState.channels = serverState.channels.map((c) => {
+        const p = positions.get(c.id);
+        return p
+          ? { ...c, category_id: p.category_id, position: p.position }
+          : c;
+      });
+    }
+
+    if (data.type === "user_online" || data.type === "presence_updated") {
+      const presence = data.data;
+      serverState.onlineUsers = [
+        ...serverState.onlineUsers.filter(
+          (u) => u.user_id !== presence.user_id,
+        ),
+        presence,
+      ];
       if (presence.avatar_url) {
         serverState.userAvatars[presence.user_id] = API.getAvatarUrl(
           presence.user_id,
@@ -206,12 +269,73 @@ export function setupWsHandlers() {
       }
     }
     if (data.type === "message_deleted") {
-      const { id, channel_id } = data.data;
+      const { id, channel_id, deleted_at } = data.data;
+      if (channel_id === chatState.selectedChannelId) {
+        markMessagesDeleted([id], deleted_at);
+      }
+    }
+    if (data.type === "messages_bulk_deleted") {
+      const { ids, channel_id, deleted_at } = data.data;
+      if (channel_id === chatState.selectedChannelId) {
+        markMessagesDeleted(ids, deleted_at);
+      }
+    }
+    if (data.type === "message_restored") {
+      const msg = data.data;
+      if (msg.channel_id === chatState.selectedChannelId) {
+        populateAvatarsFromMessages([msg]);
+        chatState.messages = [
+          ...chatState.messages.filter((m) => m.id !== msg.id),
+          msg,
+        ].sort(
+          (a, b) =>
+            new Date(a.timestamp).getTime() - new Date(b.timestamp).getTime(),
+        );
+      }
+    }
+
+    if (data.type === "message_pinned" || data.type === "message_unpinned") {
+      const { channel_id, message_id } = data.data;
+      if (channel_id === chatState.selectedChannelId) {
+        const pin =
+          data.type === "message_pinned"
+            ? {
+                pinned_at: data.data.pinned_at,
+                pinned_by: data.data.pinned_by,
+              }
+            : { pinned_at: undefined, pinned_by: undefined };
+        chatState.messages = chatState.messages.map((m) =>
+          m.id === message_id ? { ...m, ...pin } : m,
+        );
+        chatState.pinsVersion++;
+      }
+    }
+
+    if (data.type === "poll_updated") {
+      const { channel_id, message_id, poll } = data.data;
       if (channel_id === chatState.selectedChannelId) {
-        chatState.messages = chatState.messages.filter((m) => m.id !== id);
+        chatState.messages = chatState.messages.map((m) => {
+          if (m.id !== message_id || !m.poll) return m;
+          // Broadcasts omit our own votes, so keep the ones we know about
+          const mine = new Set(
+            m.poll.options.filter((o) => o.voted).map((o) => o.id),
+          );
+          const options = poll.options.map((o) => ({
+            ...o,
+            voted: mine.has(o.id),
+          }));
+          return { ...m, poll: { ...poll, options } };
+        });
       }
     }
 
+    if (
+      data.type === "scheduled_message_sent" ||
+      data.type === "scheduled_message_failed"
+    ) {
+      chatState.scheduledVersion++;
+    }
+
     if (data.type === "reaction_added" && chatState.selectedChannelId) {
       const { message_id, emoji, user_id, username } = data.data;
       updateMessageReaction(message_id, emoji, user_id, username, true);
@@ -337,6 +461,13 @@ export function setupWsHandlers() {
       }
     }
 
+    if (data.type === "member_joined") {
+      const member = data.data;
+      if (!serverState.members.some((m) => m.id === member.id)) {
+        serverState.members = [...serverState.members, member];
+      }
+    }
+
     if (data.type === "user_kicked") {
       if (data.data.user_id === currentUser?.id) {
         alert("You have been kicked from the server.");
@@ -354,20 +485,51 @@ export function setupWsHandlers() {
       }
     }
 
-    if (data.type === "user_role_changed") {
-      const { user_id, new_role } = data.data;
-      if (user_id === currentUser?.id && currentUser) {
-        authState.user = { ...currentUser, role: new_role };
-      }
+    if (data.type === "user_roles_updated") {
+      const { user_id, role_ids } = data.data;
       serverState.userRolesMap = {
         ...serverState.userRolesMap,
-        [user_id]: new_role,
+        [user_id]: role_ids,
       };
       serverState.members = serverState.members.map((m) =>
-        m.id === user_id ? { ...m, role: new_role as MemberInfo["role"] } : m,
+        m.id === user_id ? { ...m, role_ids } : m,
+      );
+      if (user_id === currentUser?.id) refreshMyPermissions();
+    }
+
+    if (data.type === "role_created") {
+      serverState.roles = [...serverState.roles, data.data].sort(
+        (a, b) => a.position - b.position,
       );
     }
 
+    if (data.type === "role_updated") {
+      serverState.roles = serverState.roles.map((r) =>
+        r.id === data.data.id ? data.data : r,
+      );
+      refreshMyPermissions();
+    }
+
+    if (data.type === "role_deleted") {
+      const roleId = data.data.id;
+      serverState.roles = serverState.roles.filter((r) => r.id !== roleId);
+      serverState.userRolesMap = Object.fromEntries(
+        Object.entries(serverState.userRolesMap).map(([id, roleIds]) => [
+          id,
+          roleIds.filter((r) => r !== roleId),
+        ]),
+      );
+      serverState.members = serverState.members.map((m) => ({
+        ...m,
+        role_ids: m.role_ids.filter((r) => r !== roleId),
+      }));
+      refreshMyPermissions();
+    }
+
+    if (data.type === "roles_reordered") {
+      serverState.roles = data.data.roles;
+    }
+
     if (data.type === "user_renamed") {
       const { user_id, new_username } = data.data;
       serverState.onlineUsers = serverState.onlineUsers.map((u) =>
@@ -390,6 +552,18 @@ export function setupWsHandlers() {
       }, 3000);
     }
 
+    if (
+      data.type === "error" &&
+      (data.data.code === "slowmode" || data.data.code === "forbidden")
+    ) {
+      chatState.channelRuleWarning = data.data.message ?? "";
+      if (_channelRuleTimeout) clearTimeout(_channelRuleTimeout);
+      _channelRuleTimeout = setTimeout(() => {
+        chatState.channelRuleWarning = "";
+        _channelRuleTimeout = null;
+      }, 3000);
+    }
+
     if (data.type === "sync_required") {
       const { selectedChannelId, selectedChannelName } = chatState;
       if (selectedChannelId) {
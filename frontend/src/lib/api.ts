@@ -16,8 +16,48 @@ export interface Channel {
   id: string;
   name: string;
   channel_type: "text" | "voice";
+  category_id: string | null;
+  /** Order within the channel's category. */
+  position: number;
+  topic: string | null;
+  /** Minimum seconds between messages from the same member. 0 is off. */
+  slowmode_secs: number;
+  /** Only members holding one of `announcement_role_ids` may post. */
+  announcement: boolean;
+  announcement_role_ids: string[];
+  overwrites?: PermissionOverwrite[];
 }
 
+export interface ChannelSettings {
+  topic: string;
+  slowmode_secs: number;
+  announcement: boolean;
+  announcement_role_ids: string[];
+}
+
+export interface ChannelCategory {
+  id: string;
+  name: string;
+  position: number;
+}
+
+export interface ChannelPosition {
+  id: string;
+  category_id: string | null;
+  position: number;
+}
+
+/** Allow/deny channel permissions for one role or user. */
+export interface PermissionOverwrite {
+  channel_id: string;
+  role_id?: string;
+  user_id?: string;
+  allow: number;
+  deny: number;
+}
+
+export type OverwriteTargetType = "role" | "user";
+
 export interface ReplyPreview {
   id: string;
   username: string;
@@ -39,6 +79,8 @@ export interface LinkPreview {
   description?: string;
   image_url?: string;
   site_name?: string;
+  /** Set when the preview quotes a message on this server. */
+  quoted_message_id?: string;
 }
 
 export interface Attachment {
@@ -66,12 +108,146 @@ export interface Message {
   reactions?: ReactionData[];
   link_previews?: LinkPreview[];
   attachments?: Attachment[];
+  /** Set on system messages. A pin notice replies to the pinned message. */
+  kind?: "pin_notice";
+  pinned_at?: string;
+  pinned_by?: string;
+  /** Only set on tombstones, which are shown to moderators. */
+  deleted_at?: string;
+  deleted_by?: string;
+  poll?: Poll;
+  forwarded_from?: ForwardedFrom;
+  /** Set when the message was posted through an incoming webhook. */
+  webhook?: WebhookAuthor;
+  embeds?: Embed[];
+}
+
+/** Where a forwarded message was first posted. Ids are cleared once removed. */
+export interface ForwardedFrom {
+  message_id: string | null;
+  channel_id: string | null;
+  author_id: string | null;
+  username?: string;
+  display_name?: string;
+  timestamp: string;
+}
+
+/** Who a webhook message was posted as. `id` is cleared once deleted. */
+export interface WebhookAuthor {
+  id: string | null;
+  name: string;
+  avatar_url?: string;
+}
+
+export interface Embed {
+  title?: string;
+  description?: string;
+  url?: string;
+  /** RGB color of the accent bar. */
+  color?: number;
+  image_url?: string;
+  fields?: EmbedField[];
+}
+
+export interface EmbedField {
+  name: string;
+  value: string;
+  inline?: boolean;
+}
+
+/** `token` is only returned on creation and when it is rotated. */
+export interface Webhook {
+  id: string;
+  channel_id: string;
+  name: string;
+  avatar_url?: string;
+  created_by: string;
+  created_at: string;
+  token?: string;
+}
+
+export interface PollOption {
+  id: string;
+  text: string;
+  votes: number;
+  /** Whether the current user voted for this option. */
+  voted?: boolean;
+  /** Omitted on anonymous polls. */
+  voters?: string[];
+}
+
+export interface Poll {
+  message_id: string;
+  question: string;
+  options: PollOption[];
+  allow_multiple: boolean;
+  anonymous: boolean;
+  expires_at?: string;
+  closed_at?: string;
+  total_voters: number;
+}
+
+export interface CreatePollRequest {
+  question: string;
+  options: string[];
+  allow_multiple?: boolean;
+  anonymous?: boolean;
+  duration_minutes?: number;
+}
+
+/** Pages history from a message id. Give at most one; none means newest. */
+export interface MessageCursor {
+  before?: string;
+  after?: string;
+  around?: string;
+}
+
+export interface MessagePage {
+  /** Oldest first. */
+  messages: Message[];
+  has_more_before: boolean;
+  has_more_after: boolean;
+}
+
+/** A message resolved from its permalink, with the history around it. */
+export interface MessageLink {
+  message: Message;
+  context: MessagePage;
+}
+
+/** A previous version of an edited message, oldest first. */
+export interface MessageRevision {
+  id: string;
+  message_id: string;
+  content: string;
+  created_at: string;
+  replaced_at: string;
 }
 
 export interface SendMessageRequest {
   content?: string;
   reply_to_id?: string;
   attachment_ids?: string[];
+  poll?: CreatePollRequest;
+}
+
+/** A message queued to post at `send_at`. */
+export interface ScheduledMessage {
+  id: string;
+  author_id: string;
+  channel_id: string;
+  content?: string;
+  reply_to_id?: string;
+  attachment_ids?: string[];
+  poll?: CreatePollRequest;
+  send_at: string;
+  created_at: string;
+  /** Why delivery failed. Reschedule or cancel to clear it. */
+  failure?: string;
+}
+
+export interface ScheduleMessageRequest extends SendMessageRequest {
+  send_at: string;
 }
 
 export interface VoiceState {
@@ -92,19 +268,40 @@ export interface JoinVoiceRequest {
   channel_id: string;
 }
 
+export type PresenceStatus = "online" | "idle" | "dnd" | "invisible";
+
+export interface CustomStatus {
+  text?: string;
+  emoji?: string;
+  expires_at?: string;
+}
+
 export interface UserPresence {
   user_id: string;
   username: string;
   display_name?: string;
   avatar_url?: string;
+  status: PresenceStatus;
+  custom_status?: CustomStatus;
   connected_at: string;
 }
 
+export interface Role {
+  id: string;
+  name: string;
+  color?: number;
+  position: number;
+  permissions: number;
+  is_default: boolean;
+  created_at: string;
+}
+
 export interface UserSummary {
   id: string;
   username: string;
   email: string;
-  role: "owner" | "admin" | "moderator" | "member";
+  is_owner: boolean;
+  role_ids: string[];
   created_at: string;
   avatar_url?: string;
 }
@@ -113,7 +310,8 @@ export interface MemberInfo {
   id: string;
   username: string;
   display_name?: string;
-  role: "owner" | "admin" | "moderator" | "member";
+  is_owner: boolean;
+  role_ids: string[];
   avatar_url?: string;
 }
 
@@ -122,7 +320,8 @@ export interface PublicProfile {
   username: string;
   display_name?: string;
   avatar_url?: string;
-  role: string;
+  is_owner: boolean;
+  role_ids: string[];
   created_at: string;
 }
 
@@ -155,16 +354,55 @@ export interface Invite {
   created_at: string;
 }
 
+/** `secret` is only returned on creation and when it is rotated. */
+export interface OutgoingWebhook {
+  id: string;
+  name: string;
+  url: string;
+  event_types: string[];
+  enabled: boolean;
+  created_by: string | null;
+  created_at: string;
+  secret?: string;
+}
+
+/** `status_code` and `error` describe the most recent attempt. */
+export interface OutgoingWebhookDelivery {
+  id: string;
+  webhook_id: string;
+  event_type: string;
+  status: "pending" | "succeeded" | "failed";
+  attempts: number;
+  status_code: number | null;
+  error: string | null;
+  next_attempt_at: string;
+  created_at: string;
+  completed_at: string | null;
+}
+
 export interface ModLogEntry {
   id: string;
   action: string;
   moderator_id: string;
-  target_user_id: string;
+  /** Absent for actions without a single target, such as a bulk purge. */
+  target_user_id?: string;
   reason?: string;
   details?: string;
   created_at: string;
 }
 
+/** Every filter given must match; `has_link` and `has_attachment` together
+ * match messages with either. */
+export interface PurgeMessagesRequest {
+  limit?: number;
+  author_id?: string;
+  before?: string;
+  after?: string;
+  has_link?: boolean;
+  has_attachment?: boolean;
+  reason?: string;
+}
+
 export interface CustomEmoji {
   id: string;
   name: string;
@@ -255,9 +493,12 @@ export class API {
     server_name: string;
     version: string;
     channels: Channel[];
+    categories: ChannelCategory[];
     online_users: UserPresence[];
     voice_states: VoiceState[];
     members: MemberInfo[];
+    roles: Role[];
+    permissions: number;
     users?: UserSummary[];
   }> {
     return this.request("/init", {}, "Failed to initialize");
@@ -267,14 +508,22 @@ export class API {
     return this.request("/channels", {}, "Failed to fetch channels");
   }
 
+  static async getMessageLink(messageId: string): Promise<MessageLink> {
+    return this.request(
+      `/messages/${messageId}`,
+      {},
+      "Failed to open message link",
+    );
+  }
+
   static async getMessages(
     channelId: string,
     limit = 50,
-    before?: string,
-  ): Promise<Message[]> {
+    cursor: MessageCursor = {},
+  ): Promise<MessagePage> {
     const params = new URLSearchParams({ limit: String(limit) });
-    if (before) {
-      params.set("before", before);
+    for (const [key, id] of Object.entries(cursor)) {
+      if (id) params.set(key, id);
     }
     return this.request(
       `/channels/${channelId}/messages?${params}`,
@@ -298,11 +547,12 @@ export class API {
   static async createChannel(
     name: string,
     channelType: "text" | "voice",
+    categoryId: string | null = null,
   ): Promise<Channel> {
     return this.jsonRequest(
       "/channels",
       "POST",
-      { name, channel_type: channelType },
+      { name, channel_type: channelType, category_id: categoryId },
       "Failed to create channel",
     );
   }
@@ -310,11 +560,12 @@ export class API {
   static async updateChannel(
     channelId: string,
     name: string,
+    settings: Partial<ChannelSettings> = {},
   ): Promise<Channel> {
     return this.jsonRequest(
       `/channels/${channelId}`,
       "PUT",
-      { name },
+      { name, ...settings },
       "Failed to update channel",
     );
   }
@@ -327,6 +578,56 @@ export class API {
     );
   }
 
+  static async reorderChannels(
+    categories: { id: string; position: number }[],
+    channels: ChannelPosition[],
+  ): Promise<void> {
+    return this.jsonRequest(
+      "/channels/positions",
+      "PUT",
+      { categories, channels },
+      "Failed to reorder channels",
+    );
+  }
+
+  static async createCategory(name: string): Promise<ChannelCategory> {
+    return this.jsonRequest(
+      "/categories",
+      "POST",
+      { name },
+      "Failed to create category",
+    );
+  }
+
+  static async updateCategory(
+    categoryId: string,
+    name: string,
+  ): Promise<ChannelCategory> {
+    return this.jsonRequest(
+      `/categories/${categoryId}`,
+      "PUT",
+      { name },
+      "Failed to update category",
+    );
+  }
+
+  /** Channels move to `moveTo` (or out of any category) unless `deleteChannels`. */
+  static async deleteCategory(
+    categoryId: string,
+    deleteChannels: boolean,
+    moveTo: string | null = null,
+  ): Promise<void> {
+    const params = new URLSearchParams();
+    if (deleteChannels) params.set("delete_channels", "true");
+    if (moveTo) params.set("move_to", moveTo);
+    const query = params.toString();
+    return this.request(
+      `/categories/${categoryId}${query ? `?${query}` : ""}`,
+      { method: "DELETE" },
+      "Failed to delete category",
+    );
+  }
+
   static async editMessage(
     channelId: string,
     messageId: string,
@@ -340,6 +641,40 @@ export class API {
     );
   }
 
+  static async purgeMessages(
+    channelId: string,
+    filter: PurgeMessagesRequest,
+  ): Promise<{ deleted: number }> {
+    return this.jsonRequest(
+      `/channels/${channelId}/messages/purge`,
+      "POST",
+      filter,
+      "Failed to purge messages",
+    );
+  }
+
+  static async restoreMessage(
+    channelId: string,
+    messageId: string,
+  ): Promise<Message> {
+    return this.request(
+      `/channels/${channelId}/messages/${messageId}/restore`,
+      { method: "POST" },
+      "Failed to restore message",
+    );
+  }
+
+  static async getMessageHistory(
+    channelId: string,
+    messageId: string,
+  ): Promise<MessageRevision[]> {
+    return this.request(
+      `/channels/${channelId}/messages/${messageId}/history`,
+      {},
+      "Failed to fetch edit history",
+    );
+  }
+
   static async deleteMessage(
     channelId: string,
     messageId: string,
@@ -363,6 +698,147 @@ export class API {
     );
   }
 
+  static async votePoll(
+    channelId: string,
+    messageId: string,
+    optionId: string,
+  ): Promise<Poll> {
+    return this.request(
+      `/channels/${channelId}/messages/${messageId}/poll/votes/${optionId}`,
+      { method: "PUT" },
+      "Failed to vote",
+    );
+  }
+
+  static async unvotePoll(
+    channelId: string,
+    messageId: string,
+    optionId: string,
+  ): Promise<Poll> {
+    return this.request(
+      `/channels/${channelId}/messages/${messageId}/poll/votes/${optionId}`,
+      { method: "DELETE" },
+      "Failed to remove vote",
+    );
+  }
+
+  static async getScheduledMessages(): Promise<ScheduledMessage[]> {
+    return this.request(
+      "/scheduled-messages",
+      {},
+      "Failed to fetch scheduled messages",
+    );
+  }
+
+  static async scheduleMessage(
+    channelId: string,
+    message: ScheduleMessageRequest,
+  ): Promise<ScheduledMessage> {
+    return this.jsonRequest(
+      `/channels/${channelId}/scheduled-messages`,
+      "POST",
+      message,
+      "Failed to schedule message",
+    );
+  }
+
+  static async updateScheduledMessage(
+    scheduledId: string,
+    update: { content?: string; send_at?: string },
+  ): Promise<ScheduledMessage> {
+    return this.jsonRequest(
+      `/scheduled-messages/${scheduledId}`,
+      "PUT",
+      update,
+      "Failed to update scheduled message",
+    );
+  }
+
+  static async cancelScheduledMessage(scheduledId: string): Promise<void> {
+    return this.request(
+      `/scheduled-messages/${scheduledId}`,
+      { method: "DELETE" },
+      "Failed to cancel scheduled message",
+    );
+  }
+
+  static async getPinnedMessages(channelId: string): Promise<Message[]> {
+    return this.request(
+      `/channels/${channelId}/pins`,
+      {},
+      "Failed to fetch pinned messages",
+    );
+  }
+
+  static async forwardMessage(
+    channelId: string,
+    messageId: string,
+    targetChannelId: string,
+  ): Promise<Message> {
+    return this.jsonRequest(
+      `/channels/${channelId}/messages/${messageId}/forward`,
+      "POST",
+      { channel_id: targetChannelId },
+      "Failed to forward message",
+    );
+  }
+
+  static async getWebhooks(channelId: string): Promise<Webhook[]> {
+    return this.request(
+      `/channels/${channelId}/webhooks`,
+      {},
+      "Failed to fetch webhooks",
+    );
+  }
+
+  static async createWebhook(
+    channelId: string,
+    name: string,
+    avatarUrl?: string,
+  ): Promise<Webhook> {
+    return this.jsonRequest(
+      `/channels/${channelId}/webhooks`,
+      "POST",
+      { name, avatar_url: avatarUrl },
+      "Failed to create webhook",
+    );
+  }
+
+  static async rotateWebhookToken(webhookId: string): Promise<Webhook> {
+    return this.request(
+      `/webhooks/${webhookId}/token`,
+      { method: "POST" },
+      "Failed to reset webhook URL",
+    );
+  }
+
+  static async deleteWebhook(webhookId: string): Promise<void> {
+    return this.request(
+      `/webhooks/${webhookId}`,
+      { method: "DELETE" },
+      "Failed to delete webhook",
+    );
+  }
+
+  static async pinMessage(channelId: string, messageId: string): Promise<void> {
+    return this.request(
+      `/channels/${channelId}/messages/${messageId}/pin`,
+      { method: "PUT" },
+      "Failed to pin message",
+    );
+  }
+
+  static async unpinMessage(
+    channelId: string,
+    messageId: string,
+  ): Promise<void> {
+    return this.request(
+      `/channels/${channelId}/messages/${messageId}/pin`,
+      { method: "DELETE" },
+      "Failed to unpin message",
+    );
+  }
+
   static async removeReaction(
     channelId: string,
     messageId: string,
@@ -428,6 +904,18 @@ export class API {
     );
   }
 
+  static async updatePresence(data: {
+    status: PresenceStatus;
+    custom_status: CustomStatus | null;
+  }): Promise<void> {
+    return this.jsonRequest(
+      "/auth/me/presence",
+      "PUT",
+      data,
+      "Failed to update status",
+    );
+  }
+
   static async changePassword(
     currentPassword: string,
     newPassword: string,
@@ -479,12 +967,89 @@ export class API {
     return this.request("/admin/users", {}, "Failed to fetch users");
   }
 
-  static async changeUserRole(userId: string, role: string): Promise<void> {
+  static async setUserRoles(userId: string, roleIds: string[]): Promise<void> {
+    return this.jsonRequest(
+      `/admin/users/${userId}/roles`,
+      "PUT",
+      { role_ids: roleIds },
+      "Failed to update roles",
+    );
+  }
+
+  // --- Roles ---
+
+  static async getRoles(): Promise<Role[]> {
+    return this.request("/roles", {}, "Failed to fetch roles");
+  }
+
+  static async createRole(
+    name: string,
+    color: number | null,
+    permissions: number,
+  ): Promise<Role> {
+    return this.jsonRequest(
+      "/admin/roles",
+      "POST",
+      { name, color, permissions },
+      "Failed to create role",
+    );
+  }
+
+  static async updateRole(
+    roleId: string,
+    name: string,
+    color: number | null,
+    permissions: number,
+  ): Promise<Role> {
+    return this.jsonRequest(
+      `/admin/roles/${roleId}`,
+      "PUT",
+      { name, color, permissions },
+      "Failed to update role",
+    );
+  }
+
+  static async deleteRole(roleId: string): Promise<void> {
+    return this.request(
+      `/admin/roles/${roleId}`,
+      { method: "DELETE" },
+      "Failed to delete role",
+    );
+  }
+
+  static async setChannelOverwrite(
+    channelId: string,
+    targetType: OverwriteTargetType,
+    targetId: string,
+    allow: number,
+    deny: number,
+  ): Promise<PermissionOverwrite> {
+    return this.jsonRequest(
+      `/channels/${channelId}/overwrites/${targetType}/${targetId}`,
+      "PUT",
+      { allow, deny },
+      "Failed to update channel permissions",
+    );
+  }
+
+  static async deleteChannelOverwrite(
+    channelId: string,
+    targetType: OverwriteTargetType,
+    targetId: string,
+  ): Promise<void> {
+    return this.request(
+      `/channels/${channelId}/overwrites/${targetType}/${targetId}`,
+      { method: "DELETE" },
+      "Failed to remove channel permission overwrite",
+    );
+  }
+
+  static async reorderRoles(roleIds: string[]): Promise<Role[]> {
     return this.jsonRequest(
-      `/admin/users/${userId}/role`,
+      "/admin/roles/positions",
       "PUT",
-      { role },
-      "Failed to change role",
+      { role_ids: roleIds },
+      "Failed to reorder roles",
     );
   }
 
@@ -609,6 +1174,81 @@ export class API {
     );
   }
 
+  // --- Outgoing webhooks ---
+
+  static async getOutgoingWebhooks(): Promise<OutgoingWebhook[]> {
+    return this.request("/admin/webhooks", {}, "Failed to fetch webhooks");
+  }
+
+  static async createOutgoingWebhook(
+    name: string,
+    url: string,
+    eventTypes: string[],
+  ): Promise<OutgoingWebhook> {
+    return this.jsonRequest(
+      "/admin/webhooks",
+      "POST",
+      { name, url, event_types: eventTypes },
+      "Failed to create webhook",
+    );
+  }
+
+  static async updateOutgoingWebhook(
+    webhookId: string,
+    update: {
+      name?: string;
+      url?: string;
+      event_types?: string[];
+      enabled?: boolean;
+    },
+  ): Promise<OutgoingWebhook> {
+    return this.jsonRequest(
+      `/admin/webhooks/${webhookId}`,
+      "PUT",
+      update,
+      "Failed to update webhook",
+    );
+  }
+
+  static async rotateOutgoingWebhookSecret(
+    webhookId: string,
+  ): Promise<OutgoingWebhook> {
+    return this.request(
+      `/admin/webhooks/${webhookId}/secret`,
+      { method: "POST" },
+      "Failed to rotate webhook secret",
+    );
+  }
+
+  static async deleteOutgoingWebhook(webhookId: string): Promise<void> {
+    return this.request(
+      `/admin/webhooks/${webhookId}`,
+      { method: "DELETE" },
+      "Failed to delete webhook",
+    );
+  }
+
+  static async getWebhookDeliveries(
+    webhookId: string,
+  ): Promise<OutgoingWebhookDelivery[]> {
+    return this.request(
+      `/admin/webhooks/${webhookId}/deliveries`,
+      {},
+      "Failed to fetch webhook deliveries",
+    );
+  }
+
+  static async retryWebhookDelivery(
+    webhookId: string,
+    deliveryId: string,
+  ): Promise<OutgoingWebhookDelivery> {
+    return this.request(
+      `/admin/webhooks/${webhookId}/deliveries/${deliveryId}/retry`,
+      { method: "POST" },
+      "Failed to retry delivery",
+    );
+  }
+
   // --- Mod Log ---
 
   static async getModLog(): Promise<ModLogEntry[]> {
@@ -768,17 +1408,61 @@ export type WsOutgoingMessage =
       channel_id: string;
       is_camera_sharing: boolean;
     }
+  | { message_type: "idle_update"; idle: boolean }
   | { message_type: "ping"; channel_id: ""; content: "" };
 
 export type WsIncomingMessage =
   | { type: "message"; data: Message }
   | { type: "message_edited"; data: Message }
-  | { type: "message_deleted"; data: { id: string; channel_id: string } }
+  | {
+      type: "message_deleted";
+      data: {
+        id: string;
+        channel_id: string;
+        thread_id?: string;
+        deleted_at: string;
+      };
+    }
+  | { type: "message_restored"; data: Message }
+  | {
+      type: "poll_updated";
+      data: { channel_id: string; message_id: string; poll: Poll };
+    }
+  | {
+      type: "scheduled_message_sent";
+      data: { id: string; channel_id: string; message_id: string };
+    }
+  | { type: "scheduled_message_failed"; data: ScheduledMessage }
+  | {
+      type: "messages_bulk_deleted";
+      data: { channel_id: string; ids: string[]; deleted_at: string };
+    }
+  | {
+      type: "message_pinned";
+      data: {
+        channel_id: string;
+        message_id: string;
+        pinned_by: string;
+        pinned_at: string;
+      };
+    }
+  | {
+      type: "message_unpinned";
+      data: { channel_id: string; message_id: string };
+    }
   | { type: "channel_created"; data: Channel }
   | { type: "channel_updated"; data: Channel }
   | { type: "channel_deleted"; data: { id: string } }
+  | { type: "category_created"; data: ChannelCategory }
+  | { type: "category_updated"; data: ChannelCategory }
+  | { type: "category_deleted"; data: { id: string } }
+  | {
+      type: "channels_reordered";
+      data: { categories: ChannelCategory[]; channels: ChannelPosition[] };
+    }
   | { type: "user_online"; data: UserPresence }
   | { type: "user_offline"; data: { user_id: string } }
+  | { type: "presence_updated"; data: UserPresence }
   | {
       type: "user_avatar_updated";
       data: { user_id: string; avatar_url?: string };
@@ -793,9 +1477,14 @@ export type WsIncomingMessage =
       };
     }
   | { type: "user_renamed"; data: { user_id: string; new_username: string } }
-  | { type: "user_role_changed"; data: { user_id: string; new_role: string } }
+  | { type: "user_roles_updated"; data: { user_id: string; role_ids: string[] } }
+  | { type: "role_created"; data: Role }
+  | { type: "role_updated"; data: Role }
+  | { type: "role_deleted"; data: { id: string } }
+  | { type: "roles_reordered"; data: { roles: Role[] } }
   | { type: "user_kicked"; data: { user_id: string } }
   | { type: "user_banned"; data: { user_id: string } }
+  | { type: "member_joined"; data: MemberInfo }
   | {
       type: "reaction_added";
       data: {
@@ -853,6 +1542,15 @@ export type WsIncomingMessage =
   | { type: "error"; data: { code: string; message?: string } }
   | { type: "pong"; data: Record<string, never> }
   | { type: "sync_required"; data: { reason: string } }
+  | {
+      type: "ready";
+      data: {
+        session_id: string;
+        protocol_version: number;
+        resume_window_secs: number;
+      };
+    }
+  | { type: "resumed"; data: { session_id: string; protocol_version: number } }
   | { type: "soundboard_sound_created"; data: SoundboardSound }
   | { type: "soundboard_sound_updated"; data: SoundboardSound }
   | { type: "soundboard_sound_deleted"; data: { sound_id: string } }
@@ -866,6 +1564,9 @@ export type WsIncomingMessage =
       };
     };
 
+/** Gateway protocol version this client speaks (see backend gateway.rs). */
+const GATEWAY_PROTOCOL_VERSION = 1;
+
 export class WebSocketManager {
   private ws: WebSocket | null = null;
   private messageHandlers: ((data: WsIncomingMessage) => void)[] = [];
@@ -876,6 +1577,9 @@ export class WebSocketManager {
   private reconnectTimer: ReturnType<typeof setTimeout> | null = null;
   private heartbeatTimer: ReturnType<typeof setInterval> | null = null;
   private currentChannelId: string | null = null;
+  private sessionId: string | null = null;
+  private lastSeq = 0;
+  private pendingResync = false;
 
   connect(): Promise<void> {
     this.intentionalClose = false;
@@ -886,23 +1590,46 @@ export class WebSocketManager {
 
     return new Promise<void>((resolve) => {
       this.ws!.onopen = () => {
-        // Send auth message immediately after the handshake
-        this.ws!.send(JSON.stringify({ token }));
+        // Send auth message immediately after the handshake. After a drop,
+        // try to resume the previous session so missed events are replayed.
         const isReconnect = this.reconnectAttempts > 0;
+        this.ws!.send(
+          JSON.stringify(
+            isReconnect && this.sessionId
+              ? {
+                  message_type: "resume",
+                  token,
+                  protocol_version: GATEWAY_PROTOCOL_VERSION,
+                  session_id: this.sessionId,
+                  seq: this.lastSeq,
+                }
+              : { token, protocol_version: GATEWAY_PROTOCOL_VERSION },
+          ),
+        );
         this.reconnectAttempts = 0;
         if (this.currentChannelId) {
           this.joinChannel(this.currentChannelId);
         }
         this.startHeartbeat();
-        if (isReconnect) {
-          this.reconnectCallbacks.forEach((cb) => cb());
-        }
+        // A fresh session (not a resume) means state must be refetched
+        this.pendingResync = isReconnect;
         resolve();
       };
 
       this.ws!.onmessage = (event) => {
         try {
-          const data = JSON.parse(event.data) as WsIncomingMessage;
+          const data = JSON.parse(event.data) as WsIncomingMessage & {
+            seq?: number;
+          };
+          if (typeof data.seq === "number") this.lastSeq = data.seq;
+          if (data.type === "ready") {
+            this.sessionId = data.data.session_id;
+            if (this.pendingResync) {
+              this.reconnectCallbacks.forEach((cb) => cb());
+            }
+            this.pendingResync = false;
+          }
+          if (data.type === "resumed") this.pendingResync = false;
           if (data.type === "pong") return;
           this.messageHandlers.forEach((handler) => handler(data));
         } catch {
@@ -930,13 +1657,15 @@ export class WebSocketManager {
 
   disconnect() {
     this.intentionalClose = true;
+    this.sessionId = null;
+    this.lastSeq = 0;
     this.stopHeartbeat();
     if (this.reconnectTimer) {
       clearTimeout(this.reconnectTimer);
       this.reconnectTimer = null;
     }
     if (this.ws) {
-      this.ws.close();
+      this.ws.close(1000);
       this.ws = null;
     }
   }
@@ -1038,4 +1767,8 @@ export class WebSocketManager {
       is_camera_sharing: isCameraSharing,
     });
   }
+
+  sendIdleUpdate(idle: boolean) {
+    this.send({ message_type: "idle_update", idle });
+  }
 }
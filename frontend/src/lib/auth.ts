@@ -6,7 +6,9 @@ import {
   appFetch,
 } from "./serverManager";
 import { authState } from "./stores/authState.svelte";
+import { serverState } from "./stores/serverState.svelte";
 import type { User } from "./stores/authState.svelte";
+import type { Channel, Role } from "./api";
 export type { User };
 
 export interface AuthResponse {
@@ -154,18 +156,174 @@ class AuthService {
 
 export default AuthService;
 
-export function isModerator(role: string | undefined): boolean {
-  return role === "moderator" || role === "admin" || role === "owner";
+/** Server permission bits; mirrors `Permissions` in the backend. */
+export const Permission = {
+  ADMINISTRATOR: 1 << 0,
+  MANAGE_SERVER: 1 << 1,
+  MANAGE_ROLES: 1 << 2,
+  MANAGE_CHANNELS: 1 << 3,
+  MANAGE_EMOJIS: 1 << 4,
+  MANAGE_SOUNDBOARD: 1 << 5,
+  MANAGE_MESSAGES: 1 << 6,
+  MANAGE_INVITES: 1 << 7,
+  VIEW_MOD_LOG: 1 << 8,
+  KICK_MEMBERS: 1 << 9,
+  BAN_MEMBERS: 1 << 10,
+  MUTE_MEMBERS: 1 << 11,
+  ATTACH_FILES: 1 << 12,
+  USE_SOUNDBOARD: 1 << 13,
+  MENTION_EVERYONE: 1 << 14,
+  VIEW_CHANNEL: 1 << 15,
+  SEND_MESSAGES: 1 << 16,
+  ADD_REACTIONS: 1 << 17,
+  CONNECT: 1 << 18,
+  SPEAK: 1 << 19,
+  STREAM: 1 << 20,
+  PIN_MESSAGES: 1 << 21,
+} as const;
+
+export const ALL_PERMISSIONS = (1 << 22) - 1;
+
+/** Permissions that channel overwrites may allow or deny. */
+export const CHANNEL_PERMISSIONS =
+  Permission.VIEW_CHANNEL |
+  Permission.SEND_MESSAGES |
+  Permission.ADD_REACTIONS |
+  Permission.ATTACH_FILES |
+  Permission.CONNECT |
+  Permission.SPEAK |
+  Permission.STREAM |
+  Permission.PIN_MESSAGES;
+
+/** Permissions that grant access to the admin panel. */
+export const ADMIN_PANEL_PERMISSIONS =
+  Permission.MANAGE_SERVER |
+  Permission.MANAGE_ROLES |
+  Permission.MANAGE_INVITES |
+  Permission.VIEW_MOD_LOG |
+  Permission.KICK_MEMBERS |
+  Permission.BAN_MEMBERS |
+  Permission.MUTE_MEMBERS;
+
+export function hasPermission(permissions: number, permission: number): boolean {
+  return (permissions & permission) === permission;
+}
+
+export function hasAnyPermission(
+  permissions: number,
+  permission: number,
+): boolean {
+  return (permissions & permission) !== 0;
+}
+
+/** Effective permissions from the default role and the given roles. */
+export function computePermissions(
+  roles: Role[],
+  roleIds: string[],
+  isOwner: boolean,
+): number {
+  let permissions = 0;
+  for (const role of roles) {
+    if (role.is_default || roleIds.includes(role.id)) {
+      permissions |= role.permissions;
+    }
+  }
+  if (isOwner || hasPermission(permissions, Permission.ADMINISTRATOR)) {
+    return ALL_PERMISSIONS;
+  }
+  return permissions;
+}
+
+/**
+ * Effective permissions in a channel: overwrites for the default role, then
+ * the user's other roles, then the user. Mirrors `MemberPermissions::in_channel`.
+ */
+export function computeChannelPermissions(
+  base: number,
+  channel: Channel | undefined,
+  roles: Role[],
+  roleIds: string[],
+  userId: string,
+): number {
+  if (!channel?.overwrites || hasPermission(base, Permission.ADMINISTRATOR)) {
+    return base;
+  }
+  const defaultRoleId = roles.find((r) => r.is_default)?.id;
+  const layers = [0, 1, 2].map(() => ({ allow: 0, deny: 0 }));
+  for (const o of channel.overwrites) {
+    let layer: number;
+    if (o.user_id === userId) layer = 2;
+    else if (o.role_id && o.role_id === defaultRoleId) layer = 0;
+    else if (o.role_id && roleIds.includes(o.role_id)) layer = 1;
+    else continue;
+    layers[layer].allow |= o.allow;
+    layers[layer].deny |= o.deny;
+  }
+  let permissions = base;
+  for (const { allow, deny } of layers) {
+    permissions = (permissions & ~deny) | allow;
+  }
+  return permissions;
+}
+
+/** The current user's effective permissions in a channel. */
+export function myChannelPermissions(channelId: string): number {
+  const userId = authState.user?.id ?? "";
+  return computeChannelPermissions(
+    serverState.myPermissions,
+    serverState.channels.find((c) => c.id === channelId),
+    serverState.roles,
+    serverState.userRolesMap[userId] ?? [],
+    userId,
+  );
+}
+
+/** Whether the current user is exempt from slowmode and announcement mode. */
+export function bypassesChannelRules(): boolean {
+  return hasAnyPermission(
+    serverState.myPermissions,
+    Permission.MANAGE_MESSAGES | Permission.MANAGE_CHANNELS,
+  );
+}
+
+/** Whether announcement mode, if enabled, lets the current user post. */
+export function canPostInAnnouncementChannel(channel?: Channel): boolean {
+  if (!channel?.announcement || bypassesChannelRules()) return true;
+  const roleIds = serverState.userRolesMap[authState.user?.id ?? ""] ?? [];
+  return serverState.roles.some(
+    (r) =>
+      channel.announcement_role_ids.includes(r.id) &&
+      (r.is_default || roleIds.includes(r.id)),
+  );
+}
+
+/** Position of the highest role among `roleIds`; the default role is 0. */
+export function topRolePosition(roles: Role[], roleIds: string[]): number {
+  return roles
+    .filter((r) => roleIds.includes(r.id))
+    .reduce((top, r) => Math.max(top, r.position), 0);
+}
+
+/** Highest non-default role among `roleIds`, if any. */
+export function topRole(roles: Role[], roleIds: string[]): Role | undefined {
+  return roles
+    .filter((r) => !r.is_default && roleIds.includes(r.id))
+    .reduce<
+      Role | undefined
+    >((top, r) => (!top || r.position > top.position ? r : top), undefined);
 }
 
-export function isAdminRole(role: string | undefined): boolean {
-  return role === "admin" || role === "owner";
+export function roleColorHex(color: number | undefined | null): string | null {
+  return color == null ? null : `#${color.toString(16).padStart(6, "0")}`;
 }
 
 export function canDeleteMessage(
   authorId: string,
   currentUserId: string,
-  role: string | undefined,
+  permissions: number,
 ): boolean {
-  return authorId === currentUserId || isModerator(role);
+  return (
+    authorId === currentUserId ||
+    hasPermission(permissions, Permission.MANAGE_MESSAGES)
+  );
 }
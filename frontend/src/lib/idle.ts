@@ -0,0 +1,50 @@
+import { getWs } from "./ws";
+
+// Report the user as idle after this long without input. The server only
+// applies it when the chosen status is "online".
+const IDLE_AFTER_MS = 10 * 60 * 1000;
+const CHECK_INTERVAL_MS = 30 * 1000;
+const ACTIVITY_EVENTS = ["mousemove", "keydown", "pointerdown", "wheel"];
+
+let lastActivity = Date.now();
+let idle = false;
+let checkTimer: ReturnType<typeof setInterval> | null = null;
+
+function markActive() {
+  lastActivity = Date.now();
+  if (idle) {
+    idle = false;
+    getWs().sendIdleUpdate(false);
+  }
+}
+
+function checkIdle() {
+  if (!idle && Date.now() - lastActivity >= IDLE_AFTER_MS) {
+    idle = true;
+    getWs().sendIdleUpdate(true);
+  }
+}
+
+export function startIdleTracking(): void {
+  stopIdleTracking();
+  lastActivity = Date.now();
+  idle = false;
+  for (const event of ACTIVITY_EVENTS) {
+    window.addEventListener(event, markActive, { passive: true });
+  }
+  checkTimer = setInterval(checkIdle, CHECK_INTERVAL_MS);
+  // A fresh session starts out active; restate idleness after reconnecting.
+  getWs().onReconnect(() => {
+    if (idle) getWs().sendIdleUpdate(true);
+  });
+}
+
+export function stopIdleTracking(): void {
+  for (const event of ACTIVITY_EVENTS) {
+    window.removeEventListener(event, markActive);
+  }
+  if (checkTimer) {
+    clearInterval(checkTimer);
+    checkTimer = null;
+  }
+}
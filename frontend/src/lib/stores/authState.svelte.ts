@@ -2,7 +2,7 @@ export interface User {
   id: string;
   username: string;
   email: string;
-  role: string;
+  is_owner: boolean;
   avatar_url?: string;
   display_name?: string;
 }
@@ -10,13 +10,25 @@ export interface ChatStateStore {
   selectedChannelId: string;
   selectedChannelName: string;
   hasMoreMessages: boolean;
+  /** Set while viewing older history that does not reach the newest message. */
+  hasNewerMessages: boolean;
   loadingMore: boolean;
+  /** Message jumped to, scrolled into view and briefly highlighted. */
+  highlightedMessageId: string | null;
   editingMessageId: string | null;
   editMessageContent: string;
   replyingTo: Message | null;
   typingUsers: Record<string, TypingUser>;
+  /** Bumped when the selected channel's pins change. */
+  pinsVersion: number;
+  /** Bumped when one of our scheduled messages is added, sent or fails. */
+  scheduledVersion: number;
   rateLimitWarning: boolean;
+  /** Why the server rejected the last message under the channel's rules. */
+  channelRuleWarning: string;
   sendError: boolean;
+  /** Own message just deleted, restorable until the undo window closes. */
+  undoDelete: { channelId: string; messageId: string } | null;
 }
 
 export const chatState = $state<ChatStateStore>({
@@ -24,11 +36,17 @@ export const chatState = $state<ChatStateStore>({
   selectedChannelId: "",
   selectedChannelName: "",
   hasMoreMessages: true,
+  hasNewerMessages: false,
   loadingMore: false,
+  highlightedMessageId: null,
   editingMessageId: null,
   editMessageContent: "",
   replyingTo: null,
   typingUsers: {},
+  pinsVersion: 0,
+  scheduledVersion: 0,
   rateLimitWarning: false,
+  channelRuleWarning: "",
   sendError: false,
+  undoDelete: null,
 });
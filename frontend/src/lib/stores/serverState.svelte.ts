@@ -1,11 +1,22 @@
-import type { Channel, UserPresence, MemberInfo, CustomEmoji } from "../api";
+import type {
+  Channel,
+  ChannelCategory,
+  UserPresence,
+  MemberInfo,
+  CustomEmoji,
+  Role,
+} from "../api";
 
 export interface ServerStateStore {
   channels: Channel[];
+  categories: ChannelCategory[];
   onlineUsers: UserPresence[];
   members: MemberInfo[];
   userAvatars: Record<string, string | undefined>;
-  userRolesMap: Record<string, string>;
+  userRolesMap: Record<string, string[]>;
+  roles: Role[];
+  /** The current user's effective permissions. */
+  myPermissions: number;
   serverName: string;
   backendVersion: string;
   customEmojis: CustomEmoji[];
@@ -13,10 +24,13 @@ export interface ServerStateStore {
 
 export const serverState = $state<ServerStateStore>({
   channels: [],
+  categories: [],
   onlineUsers: [],
   members: [],
   userAvatars: {},
   userRolesMap: {},
+  roles: [],
+  myPermissions: 0,
   serverName: "",
   backendVersion: "",
   customEmojis: [],
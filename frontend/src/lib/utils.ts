@@ -27,3 +27,34 @@ export function resolveUrl(url: string): string {
   }
   return url;
 }
+
+const MESSAGE_LINK_PATH = /^\/messages\/([0-9a-f-]{36})$/i;
+
+function serverOrigin(): string {
+  return new URL(resolveUrl("/"), location.href).origin;
+}
+
+/** Shareable permalink for a message on the current server. */
+export function messageLink(messageId: string): string {
+  return `${serverOrigin()}/messages/${messageId}`;
+}
+
+/** The message id of a permalink to the current server, or null. */
+export function parseMessageLink(href: string): string | null {
+  let url: URL;
+  try {
+    url = new URL(href, location.href);
+  } catch {
+    return null;
+  }
+  if (url.origin !== serverOrigin()) return null;
+  return MESSAGE_LINK_PATH.exec(url.pathname)?.[1] ?? null;
+}
+
+/** URL that posts to a webhook. Only known while its token is. */
+export function webhookUrl(webhookId: string, token: string): string {
+  return new URL(
+    resolveUrl(`/api/webhooks/${webhookId}/${token}`),
+    location.href,
+  ).href;
+}